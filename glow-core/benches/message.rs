@@ -0,0 +1,62 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use glow_core::base::dns::message::Message;
+use glow_core::base::dns::view::MessageRef;
+
+/// response for www.example.com. A: a CNAME chain and three addresses,
+/// names compressed as upstream servers send them
+const RESPONSE: &[u8] = &[
+    0xab, 0xcd, 0x81, 0x80, 0x00, 0x01, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, //
+    // question www.example.com. A IN
+    0x03, b'w', b'w', b'w', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o', b'm',
+    0x00, 0x00, 0x01, 0x00, 0x01, //
+    // www.example.com. CNAME edge.example.com.
+    0xc0, 0x0c, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x01, 0x2c, 0x00, 0x07, 0x04, b'e', b'd', b'g',
+    b'e', 0xc0, 0x10, //
+    // edge.example.com. CNAME cdn.example.com.
+    0xc0, 0x2d, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x01, 0x2c, 0x00, 0x06, 0x03, b'c', b'd', b'n',
+    0xc0, 0x10, //
+    // cdn.example.com. A x3
+    0xc0, 0x40, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04, 93, 184, 216, 34, 0xc0,
+    0x40, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04, 93, 184, 216, 35, 0xc0, 0x40,
+    0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04, 93, 184, 216, 36,
+];
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            let message = Message::from(black_box(RESPONSE));
+            black_box(message.questions[0].name().len())
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let message = MessageRef::parse(black_box(RESPONSE)).unwrap();
+            let question = message.questions().next().unwrap();
+            black_box(question.name().decode().unwrap().as_bytes().len())
+        })
+    });
+    group.bench_function("owned_all_names", |b| {
+        b.iter(|| {
+            let message = Message::from(black_box(RESPONSE));
+            message
+                .answers
+                .iter()
+                .map(|rr| rr.name.len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("borrowed_all_names", |b| {
+        b.iter(|| {
+            let message = MessageRef::parse(black_box(RESPONSE)).unwrap();
+            message
+                .answers()
+                .map(|rr| rr.name().decode().unwrap().as_bytes().len())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+#![no_main]
+use glow_core::base::dns::message::Message;
+use glow_core::base::dns::view::MessageRef;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let owned = Message::parse(data);
+
+    // the borrowed view must accept everything the owned parser accepts
+    let view = match MessageRef::parse(data) {
+        Ok(view) => view,
+        Err(_) => {
+            assert!(owned.is_err());
+            return;
+        }
+    };
+    let names = view.questions().map(|q| q.name()).chain(
+        view.answers()
+            .chain(view.authorities())
+            .chain(view.additionals())
+            .map(|rr| rr.name()),
+    );
+    let names: Vec<_> = names.map(|name| name.decode()).collect();
+
+    if let Ok(owned) = owned {
+        let owned_names = owned.questions.iter().map(|q| q.name()).chain(
+            owned
+                .answers
+                .iter()
+                .chain(owned.authorities.iter())
+                .chain(owned.additionals.iter())
+                .map(|rr| rr.name.as_str()),
+        );
+        // the owned parser rejects dots in labels but keeps backslashes
+        // as they are, which the view escapes
+        for (name, owned) in names.iter().zip(owned_names) {
+            assert_eq!(name.as_ref().unwrap().as_str(), owned.replace('\\', "\\\\"));
+        }
+    }
+});
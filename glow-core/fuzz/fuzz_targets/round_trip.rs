@@ -0,0 +1,36 @@
+#![no_main]
+use bytes::BytesMut;
+use glow_core::base::dns::message::{Message, RData};
+use glow_core::base::dns::view::MessageRef;
+use libfuzzer_sys::fuzz_target;
+
+// parse -> encode -> parse must give the same message,
+// and the uncompressed encoding must survive byte for byte
+fuzz_target!(|data: &[u8]| {
+    if let Ok(message) = Message::parse(data) {
+        // records without a decoder keep their type code and RDATA
+        let view = MessageRef::parse(data).expect("owned parser accepts what the view rejects");
+        let records = message
+            .answers
+            .iter()
+            .chain(&message.authorities)
+            .chain(&message.additionals);
+        let refs = view
+            .answers()
+            .chain(view.authorities())
+            .chain(view.additionals());
+        for (rr, rr_ref) in records.zip(refs) {
+            assert_eq!(rr.type_code(), rr_ref.type_code(), "type code changed");
+            if let RData::Unknown { data, .. } = &rr.r_data {
+                assert!(&data[..] == rr_ref.r_data(), "RDATA changed");
+            }
+        }
+
+        let encoded: BytesMut = message.into();
+        let message = Message::parse(data).unwrap();
+        let reparsed = Message::parse(&encoded).expect("encoded message does not parse");
+        assert!(reparsed == message, "message changed in a round trip");
+        let reencoded: BytesMut = reparsed.into();
+        assert!(reencoded == encoded, "encoding changed in a round trip");
+    }
+});
@@ -0,0 +1,261 @@
+use crate::ip::HostAddr;
+use std::cmp::Ordering;
+use std::net::IpAddr;
+
+/// default policy table (RFC 6724 2.1): prefix, length, precedence, label,
+/// longest prefixes first so the first match is the best one
+const POLICY: [(u128, u32, u8, u8); 9] = [
+    (1, 128, 50, 0),
+    (0xffff << 32, 96, 35, 4),
+    (0, 96, 1, 3),
+    (0x2001 << 112, 32, 5, 5),
+    (0x2002 << 112, 16, 30, 2),
+    (0x3ffe << 112, 16, 1, 12),
+    (0xfec0 << 112, 10, 1, 11),
+    (0xfc00 << 112, 7, 3, 13),
+    (0, 0, 40, 1),
+];
+
+const SCOPE_LINK_LOCAL: u8 = 0x2;
+const SCOPE_SITE_LOCAL: u8 = 0x5;
+const SCOPE_GLOBAL: u8 = 0xe;
+
+/// sort `dests` by destination address selection (RFC 6724 6),
+/// `sources` are the addresses of the host
+pub fn sort_destinations(dests: &mut [IpAddr], sources: &[HostAddr]) {
+    let mut candidates: Vec<_> = dests
+        .iter()
+        .map(|dest| (*dest, select_source(dest, sources)))
+        .collect();
+    // stable, so equal destinations keep their order (rule 10)
+    candidates.sort_by(|a, b| compare_destinations(a, b));
+    for (dest, (addr, _)) in dests.iter_mut().zip(candidates) {
+        *dest = addr;
+    }
+}
+
+/// the source the host would use to reach `dest` (RFC 6724 5)
+fn select_source<'a>(dest: &IpAddr, sources: &'a [HostAddr]) -> Option<&'a HostAddr> {
+    sources
+        .iter()
+        .filter(|source| source.addr.is_ipv4() == dest.is_ipv4())
+        .max_by(|a, b| compare_sources(&a.addr, &b.addr, dest))
+}
+
+/// `Greater` when `a` is the better source for `dest`
+fn compare_sources(a: &IpAddr, b: &IpAddr, dest: &IpAddr) -> Ordering {
+    // rule 1: prefer same address
+    if a != b && (a == dest || b == dest) {
+        return if a == dest {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+    }
+
+    // rule 2: prefer appropriate scope
+    let (scope_a, scope_b, scope_d) = (scope(a), scope(b), scope(dest));
+    if scope_a != scope_b {
+        let smaller_is_enough = scope_a.min(scope_b) >= scope_d;
+        return match (scope_a < scope_b, smaller_is_enough) {
+            (true, true) | (false, false) => Ordering::Greater,
+            _ => Ordering::Less,
+        };
+    }
+
+    // rule 6: prefer matching label
+    let label_d = policy(dest).1;
+    let (match_a, match_b) = (policy(a).1 == label_d, policy(b).1 == label_d);
+    if match_a != match_b {
+        return match_a.cmp(&match_b);
+    }
+
+    // rule 8: use longest matching prefix
+    common_prefix(a, dest).cmp(&common_prefix(b, dest))
+}
+
+/// `Less` when `a` should be tried before `b`
+fn compare_destinations(
+    a: &(IpAddr, Option<&HostAddr>),
+    b: &(IpAddr, Option<&HostAddr>),
+) -> Ordering {
+    let (dest_a, source_a) = match a {
+        (dest, Some(source)) => (dest, source),
+        _ => {
+            return if b.1.is_some() {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        }
+    };
+    let (dest_b, source_b) = match b {
+        (dest, Some(source)) => (dest, source),
+        // rule 1: avoid unusable destinations
+        _ => return Ordering::Less,
+    };
+
+    // rule 2: prefer matching scope
+    let matching = |dest: &IpAddr, source: &HostAddr| scope(dest) == scope(&source.addr);
+    let (match_a, match_b) = (matching(dest_a, source_a), matching(dest_b, source_b));
+    if match_a != match_b {
+        return match_b.cmp(&match_a);
+    }
+
+    // rule 5: prefer matching label
+    let matching = |dest: &IpAddr, source: &HostAddr| policy(dest).1 == policy(&source.addr).1;
+    let (match_a, match_b) = (matching(dest_a, source_a), matching(dest_b, source_b));
+    if match_a != match_b {
+        return match_b.cmp(&match_a);
+    }
+
+    // rule 6: prefer higher precedence
+    let (precedence_a, precedence_b) = (policy(dest_a).0, policy(dest_b).0);
+    if precedence_a != precedence_b {
+        return precedence_b.cmp(&precedence_a);
+    }
+
+    // rule 8: prefer smaller scope
+    let (scope_a, scope_b) = (scope(dest_a), scope(dest_b));
+    if scope_a != scope_b {
+        return scope_a.cmp(&scope_b);
+    }
+
+    // rule 9: use longest matching prefix, as far as the source prefix goes
+    if dest_a.is_ipv4() == dest_b.is_ipv4() {
+        let prefix = |dest: &IpAddr, source: &HostAddr| {
+            common_prefix(dest, &source.addr).min(source.prefix_len as u32)
+        };
+        return prefix(dest_b, source_b).cmp(&prefix(dest_a, source_a));
+    }
+    Ordering::Equal
+}
+
+/// precedence and label of `addr` in the policy table
+fn policy(addr: &IpAddr) -> (u8, u8) {
+    let addr = mapped(addr);
+    POLICY
+        .iter()
+        .find(|(prefix, len, _, _)| *len == 0 || (addr ^ prefix) >> (128 - len) == 0)
+        .map(|(_, _, precedence, label)| (*precedence, *label))
+        .unwrap_or((40, 1))
+}
+
+/// scope of `addr` (RFC 6724 3.1 and 3.2)
+fn scope(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(addr) if addr.is_loopback() || addr.is_link_local() => SCOPE_LINK_LOCAL,
+        IpAddr::V4(_) => SCOPE_GLOBAL,
+        IpAddr::V6(addr) => {
+            let first = addr.segments()[0];
+            if addr.is_multicast() {
+                (first & 0xf) as u8
+            } else if addr.is_loopback() || first & 0xffc0 == 0xfe80 {
+                SCOPE_LINK_LOCAL
+            } else if first & 0xffc0 == 0xfec0 {
+                SCOPE_SITE_LOCAL
+            } else {
+                SCOPE_GLOBAL
+            }
+        }
+    }
+}
+
+/// length of the prefix shared by `a` and `b`
+fn common_prefix(a: &IpAddr, b: &IpAddr) -> u32 {
+    match (a, b) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => (u32::from(*a) ^ u32::from(*b)).leading_zeros(),
+        (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(*a) ^ u128::from(*b)).leading_zeros(),
+        _ => 0,
+    }
+}
+
+/// IPv4 addresses are looked up as IPv4-mapped IPv6 addresses
+fn mapped(addr: &IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u128::from(addr.to_ipv6_mapped()),
+        IpAddr::V6(addr) => u128::from(*addr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(dests: &[&str], sources: &[&str]) -> Vec<IpAddr> {
+        let sources: Vec<_> = sources
+            .iter()
+            .map(|source| HostAddr {
+                addr: source.parse().unwrap(),
+                prefix_len: if source.contains(':') { 64 } else { 24 },
+                index: 1,
+            })
+            .collect();
+        let mut dests: Vec<IpAddr> = dests.iter().map(|dest| dest.parse().unwrap()).collect();
+        sort_destinations(&mut dests, &sources);
+        dests
+    }
+
+    fn first(dests: &[&str], sources: &[&str]) -> String {
+        sorted(dests, sources)[0].to_string()
+    }
+
+    #[test]
+    fn test_policy() {
+        assert_eq!(policy(&"::1".parse().unwrap()), (50, 0));
+        assert_eq!(policy(&"192.0.2.1".parse().unwrap()), (35, 4));
+        assert_eq!(policy(&"2002:c633:6401::1".parse().unwrap()), (30, 2));
+        assert_eq!(policy(&"2001::1".parse().unwrap()), (5, 5));
+        assert_eq!(policy(&"2001:db8::1".parse().unwrap()), (40, 1));
+        assert_eq!(policy(&"fd00::1".parse().unwrap()), (3, 13));
+        assert_eq!(scope(&"169.254.1.1".parse().unwrap()), SCOPE_LINK_LOCAL);
+        assert_eq!(scope(&"ff05::1".parse().unwrap()), SCOPE_SITE_LOCAL);
+    }
+
+    /// examples of RFC 6724 10.2
+    #[test]
+    fn test_sort_destinations() {
+        let dests = ["2001:db8:1::1", "198.51.100.121"];
+        assert_eq!(first(&dests, &["2001:db8:1::2", "169.254.13.78"]), dests[0]);
+        assert_eq!(first(&dests, &["fe80::1", "198.51.100.117"]), dests[1]);
+
+        let dests = ["2001:db8:1::1", "10.1.2.3"];
+        assert_eq!(
+            first(&dests, &["2001:db8:1::2", "fe80::1", "10.1.2.4"]),
+            dests[0]
+        );
+
+        let dests = ["2001:db8:1::1", "fe80::1"];
+        assert_eq!(first(&dests, &["2001:db8:1::2", "fe80::2"]), dests[1]);
+
+        let dests = ["2001:db8:3ffe::1", "2001:db8:1::1"];
+        let sources = ["2001:db8:1::2", "2001:db8:3f44::2", "fe80::2"];
+        assert_eq!(first(&dests, &sources), dests[1]);
+
+        let dests = ["2001:db8:1::1", "2002:c633:6401::1"];
+        assert_eq!(first(&dests, &["2002:c633:6401::2", "fe80::2"]), dests[1]);
+        let sources = ["2002:c633:6401::2", "2001:db8:1::2", "fe80::2"];
+        assert_eq!(first(&dests, &sources), dests[0]);
+
+        let dests = ["2001:db8:1::1", "fe80::1", "10.1.2.3"];
+        let sources = ["fe80::2", "10.1.2.4"];
+        assert_eq!(
+            sorted(&dests, &sources),
+            vec![
+                dests[1].parse::<IpAddr>().unwrap(),
+                dests[2].parse().unwrap(),
+                dests[0].parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unusable_destinations() {
+        let dests = ["2001:db8:1::1", "198.51.100.121", "2001:db8:2::1"];
+        let addrs = sorted(&dests, &["192.168.1.2"]);
+        assert_eq!(addrs[0].to_string(), dests[1]);
+        // the rest keep their order
+        assert_eq!(addrs[1].to_string(), dests[0]);
+        assert_eq!(addrs[2].to_string(), dests[2]);
+    }
+}
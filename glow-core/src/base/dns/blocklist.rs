@@ -0,0 +1,368 @@
+use super::message::{FlagRCode, Message, RData, ResourceRecord};
+use super::types::{QType, Type};
+use glow_utils::fs::read_file;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// TTL of synthesized answers for blocked names
+const BLOCKED_TTL: u32 = 60;
+
+/// Names commonly found in hosts-format lists which must never be blocked
+const HOSTS_IGNORED: [&str; 6] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// How a blocked name is answered
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, PartialEq, Default)]
+pub enum BlockAction {
+    /// answer with NXDOMAIN
+    #[default]
+    NxDomain,
+    /// answer A with 0.0.0.0 and AAAA with ::
+    Null,
+    /// answer A and AAAA with the sinkhole addresses,
+    /// AAAA gets an empty answer if no IPv6 sinkhole is set
+    Sinkhole(Ipv4Addr, Option<Ipv6Addr>),
+}
+
+impl FromStr for BlockAction {
+    type Err = String;
+
+    /// `nxdomain`, `null` or `<ipv4>[,<ipv6>]` for a sinkhole
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nxdomain" => Ok(BlockAction::NxDomain),
+            "null" => Ok(BlockAction::Null),
+            _ => {
+                let mut addrs = s.splitn(2, ',');
+                let v4 = addrs.next().unwrap_or_default().parse();
+                let v6 = addrs.next().map(str::parse).transpose();
+                match (v4, v6) {
+                    (Ok(v4), Ok(v6)) => Ok(BlockAction::Sinkhole(v4, v6)),
+                    _ => Err(format!("invalid block action: {}", s)),
+                }
+            }
+        }
+    }
+}
+
+impl BlockAction {
+    /// build the response for a blocked `query`
+    pub fn respond(&self, query: &Message) -> Message {
+        let (v4, v6) = match *self {
+            BlockAction::NxDomain => return query.reply(FlagRCode::NameError),
+            BlockAction::Null => (Ipv4Addr::UNSPECIFIED, Some(Ipv6Addr::UNSPECIFIED)),
+            BlockAction::Sinkhole(v4, v6) => (v4, v6),
+        };
+
+        let mut response = query.reply(FlagRCode::NoError);
+        for q in query.questions.iter() {
+            match q.q_type() {
+                QType::Type(Type::A) => response.add_answer(ResourceRecord::new(
+                    q.name(),
+                    Type::A,
+                    BLOCKED_TTL,
+                    RData::A(v4),
+                )),
+                QType::Type(Type::AAAA) => {
+                    if let Some(v6) = v6 {
+                        response.add_answer(ResourceRecord::new(
+                            q.name(),
+                            Type::AAAA,
+                            BLOCKED_TTL,
+                            RData::AAAA(v6),
+                        ))
+                    }
+                }
+                _ => {}
+            }
+        }
+        response
+    }
+}
+
+/// A blocked domain, which also blocks all of its subdomains
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Rule {
+    pub domain: String,
+    hits: AtomicU64,
+}
+
+impl Rule {
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+/// Suffix trie keyed by labels from the root, e.g. com -> example -> ads
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Default)]
+struct Node {
+    children: HashMap<String, Node>,
+    rule: Option<usize>,
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Default)]
+pub struct Blocklist {
+    root: Node,
+    rules: Vec<Rule>,
+
+    /// list files and their modification time when last loaded
+    sources: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Blocklist {
+    /// load every list in `paths`, see `Blocklist::add_list` for the formats
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> io::Result<Blocklist> {
+        let mut list = Blocklist::default();
+        for path in paths {
+            let path = path.as_ref();
+            list.add_list(&read_file(path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?);
+            list.sources.push((path.to_owned(), modified(path)));
+        }
+        Ok(list)
+    }
+
+    /// add rules from a list in hosts format (`0.0.0.0 ads.example.com`)
+    /// or domain-list format (`ads.example.com`), which can be mixed.
+    /// Comments start with '#', a leading `*.` is ignored.
+    pub fn add_list(&mut self, content: &str) {
+        for line in content.lines() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+
+            let mut fields = line.split_whitespace().peekable();
+            let is_hosts = match fields.peek() {
+                Some(field) => field.parse::<IpAddr>().is_ok(),
+                None => continue,
+            };
+            if is_hosts {
+                fields.next();
+                for name in fields {
+                    if !HOSTS_IGNORED.contains(&name) && name.parse::<IpAddr>().is_err() {
+                        self.add(name);
+                    }
+                }
+            } else if let Some(name) = fields.next() {
+                self.add(name);
+            }
+        }
+    }
+
+    /// block `domain` and its subdomains
+    pub fn add(&mut self, domain: &str) {
+        let domain = domain
+            .trim_start_matches("*.")
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        if domain.is_empty() {
+            return;
+        }
+
+        let mut node = &mut self.root;
+        for label in domain.rsplit('.') {
+            node = node.children.entry(label.to_owned()).or_default();
+        }
+        if node.rule.is_none() {
+            node.rule = Some(self.rules.len());
+            self.rules.push(Rule {
+                domain,
+                hits: AtomicU64::new(0),
+            });
+        }
+    }
+
+    /// find the most specific rule blocking `name` and count the hit
+    pub fn matches(&self, name: &str) -> Option<&Rule> {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        let mut node = &self.root;
+        let mut found = None;
+        for label in name.rsplit('.') {
+            node = match node.children.get(label) {
+                Some(node) => node,
+                None => break,
+            };
+            found = node.rule.or(found);
+        }
+
+        let rule = &self.rules[found?];
+        rule.hits.fetch_add(1, Ordering::Relaxed);
+        Some(rule)
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// the list files loaded
+    pub fn sources(&self) -> Vec<PathBuf> {
+        self.sources.iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    /// whether any list file changed since it was loaded
+    pub fn is_stale(&self) -> bool {
+        self.sources
+            .iter()
+            .any(|(path, loaded)| modified(path) != *loaded)
+    }
+
+    /// load the same list files again, hit counters of kept rules are preserved
+    pub fn reload(&self) -> io::Result<Blocklist> {
+        let list = Blocklist::load(&self.sources())?;
+        list.keep_hits(self);
+        Ok(list)
+    }
+
+    /// add the hit counters of the rules of `old` to the same rules here
+    pub fn keep_hits(&self, old: &Blocklist) {
+        for rule in old.rules.iter() {
+            if let Some(new) = self.find(&rule.domain) {
+                new.hits.fetch_add(rule.hits(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// find the rule of exactly `domain`
+    fn find(&self, domain: &str) -> Option<&Rule> {
+        let mut node = &self.root;
+        for label in domain.rsplit('.') {
+            node = node.children.get(label)?;
+        }
+        node.rule.map(|i| &self.rules[i])
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::message::Question;
+
+    const LIST: &str = "\
+# hosts format
+0.0.0.0 localhost
+0.0.0.0 ads.example.com tracker.example.net
+127.0.0.1 0.0.0.0
+# domain list
+*.doubleclick.net
+Malware.Example.ORG.
+";
+
+    #[test]
+    fn test_blocklist_match() {
+        let mut list = Blocklist::default();
+        list.add_list(LIST);
+        assert_eq!(list.len(), 4);
+
+        assert!(list.matches("localhost").is_none());
+        assert!(list.matches("example.com").is_none());
+        assert!(list.matches("notads.example.com").is_none());
+        assert_eq!(
+            list.matches("ads.example.com.").map(|r| r.domain.as_str()),
+            Some("ads.example.com")
+        );
+        assert_eq!(
+            list.matches("a.b.AdS.example.com")
+                .map(|r| r.domain.as_str()),
+            Some("ads.example.com")
+        );
+        assert!(list.matches("stats.doubleclick.net").is_some());
+        assert!(list.matches("malware.example.org").is_some());
+        assert_eq!(list.find("ads.example.com").unwrap().hits(), 2);
+    }
+
+    #[test]
+    fn test_most_specific_rule() {
+        let mut list = Blocklist::default();
+        list.add("example.com");
+        list.add("ads.example.com");
+        assert_eq!(
+            list.matches("x.ads.example.com").map(|r| r.domain.as_str()),
+            Some("ads.example.com")
+        );
+        assert_eq!(
+            list.matches("www.example.com").map(|r| r.domain.as_str()),
+            Some("example.com")
+        );
+    }
+
+    #[test]
+    fn test_block_action() {
+        let mut query = Message::new(Default::default(), false);
+        query.add_question(Question::new("ads.example.com", QType::Type(Type::A)));
+        query.add_question(Question::new("ads.example.com", QType::Type(Type::AAAA)));
+
+        let response = BlockAction::NxDomain.respond(&query);
+        assert!(response.header.flag().rcode == FlagRCode::NameError);
+        assert!(response.answers.is_empty());
+
+        let response = BlockAction::Null.respond(&query);
+        assert!(response.header.flag().is_response);
+        assert_eq!(response.header.an_count, 2);
+
+        let sinkhole = BlockAction::Sinkhole(Ipv4Addr::new(10, 0, 0, 1), None);
+        let response = sinkhole.respond(&query);
+        assert_eq!(response.answers.len(), 1);
+        match response.answers[0].r_data {
+            RData::A(addr) => assert_eq!(addr, Ipv4Addr::new(10, 0, 0, 1)),
+            _ => panic!("No A record found!"),
+        }
+
+        assert!(BlockAction::from_str("null") == Ok(BlockAction::Null));
+        assert!(
+            BlockAction::from_str("10.0.0.1,fd00::1")
+                == Ok(BlockAction::Sinkhole(
+                    Ipv4Addr::new(10, 0, 0, 1),
+                    Some("fd00::1".parse().unwrap())
+                ))
+        );
+        assert!(BlockAction::from_str("sinkhole").is_err());
+    }
+
+    #[test]
+    fn test_reload_keeps_hits() {
+        let path = std::env::temp_dir().join(format!("glow_blocklist_{}", std::process::id()));
+        std::fs::write(&path, "ads.example.com\ntracker.example.net\n").unwrap();
+        let list = Blocklist::load(&[&path]).unwrap();
+        list.matches("ads.example.com");
+        list.matches("tracker.example.net");
+
+        std::fs::write(&path, "ads.example.com\n").unwrap();
+        let new = list.reload().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(new.find("ads.example.com").unwrap().hits(), 1);
+
+        // hits counted on the old list after it was reloaded
+        list.matches("ads.example.com");
+        let mut newer = Blocklist::default();
+        newer.add("ads.example.com");
+        newer.keep_hits(&list);
+        assert_eq!(newer.find("ads.example.com").unwrap().hits(), 2);
+    }
+}
@@ -1,6 +1,6 @@
 /// CLASS fields appear in resource records. The following CLASS mnemonics
 /// and values are defined:
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum Class {
     /// 1 the Internet
@@ -12,6 +12,10 @@ pub enum Class {
     CH = 3,
     /// 4 Hesiod [Dyer 87]
     HS = 4,
+    /// 254 none, only used by UPDATE messages (RFC 2136)
+    NONE = 254,
+    /// 255 any class, only used by UPDATE and TSIG records
+    ANY = 255,
 }
 
 impl Into<u16> for Class {
@@ -28,6 +32,8 @@ impl From<u16> for Class {
             2 => Class::CS,
             3 => Class::CH,
             4 => Class::HS,
+            254 => Class::NONE,
+            255 => Class::ANY,
             _ => Class::IN, // FIXME
         }
     }
@@ -36,7 +42,7 @@ impl From<u16> for Class {
 /// QCLASS fields appear in the question section of a query. QCLASS values
 /// are a superset of CLASS values; every CLASS is a valid QCLASS. In
 /// addition to CLASS values, the following QCLASSes are defined:
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum QClass {
     Class(Class),
@@ -0,0 +1,409 @@
+use super::message::{FlagRCode, Header, Message, Question};
+use super::types::{QType, Type};
+use super::view::{MessageRef, RecordRef};
+use bytes::{BufMut, BytesMut};
+use glow_utils::binary::parse_u32;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// The well-known prefix of RFC 6052
+pub const WELL_KNOWN_PREFIX: Ipv6Addr = Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0);
+
+/// Prefix lengths an IPv4 address can be embedded with (RFC 6052 2.2)
+const PREFIX_LENS: [u8; 6] = [32, 40, 48, 56, 64, 96];
+
+/// Synthesizes AAAA records from A records for IPv6-only clients (RFC 6147)
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct Dns64 {
+    prefix: Ipv6Addr,
+    prefix_len: u8,
+
+    /// AAAA records in these networks are treated as absent
+    exclude_v6: Vec<(Ipv6Addr, u8)>,
+    /// A records in these networks are never synthesized
+    exclude_v4: Vec<(Ipv4Addr, u8)>,
+    /// names under these domains are never synthesized
+    exclude_names: Vec<String>,
+}
+
+impl Default for Dns64 {
+    fn default() -> Self {
+        Dns64::new(WELL_KNOWN_PREFIX, 96).unwrap()
+    }
+}
+
+impl FromStr for Dns64 {
+    type Err = String;
+
+    /// `<ipv6 prefix>/<length>`, e.g. `64:ff9b::/96`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_network(s) {
+            Some((IpAddr::V6(prefix), len)) => Dns64::new(prefix, len),
+            _ => Err(format!("invalid NAT64 prefix: {}", s)),
+        }
+    }
+}
+
+impl Dns64 {
+    /// `prefix_len` must be one of 32, 40, 48, 56, 64 and 96
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> Result<Dns64, String> {
+        if !PREFIX_LENS.contains(&prefix_len) {
+            return Err(format!("invalid NAT64 prefix length: {}", prefix_len));
+        }
+        Ok(Dns64 {
+            prefix: Ipv6Addr::from(u128::from(prefix) & mask(prefix_len as u32, 128)),
+            prefix_len,
+            // IPv4-mapped addresses are useless to IPv6-only clients (RFC 6147 5.1.4)
+            exclude_v6: vec![(Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96)],
+            exclude_v4: Vec::new(),
+            exclude_names: Vec::new(),
+        })
+    }
+
+    /// exclude the network `net` in `<addr>/<length>` form:
+    /// IPv6 networks are ignored in AAAA answers, IPv4 networks are not synthesized
+    pub fn exclude(&mut self, net: &str) -> Result<&Dns64, String> {
+        match parse_network(net) {
+            Some((IpAddr::V4(addr), len)) => self.exclude_v4.push((addr, len)),
+            Some((IpAddr::V6(addr), len)) => self.exclude_v6.push((addr, len)),
+            None => return Err(format!("invalid network: {}", net)),
+        }
+        Ok(self)
+    }
+
+    /// never synthesize `domain` and names under it
+    pub fn exclude_name(&mut self, domain: &str) -> &Dns64 {
+        self.exclude_names
+            .push(domain.trim_end_matches('.').to_ascii_lowercase());
+        self
+    }
+
+    /// whether AAAA queries of `name` are synthesized at all
+    pub fn applies_to(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        !self.exclude_names.iter().any(|domain| {
+            name == *domain
+                || (name.ends_with(domain.as_str())
+                    && name[..(name.len() - domain.len())].ends_with('.'))
+        })
+    }
+
+    /// embed `addr` in the prefix (RFC 6052 2.2),
+    /// skipping bits 64 to 71 which must be zero
+    pub fn synthesize(&self, addr: Ipv4Addr) -> Ipv6Addr {
+        let addr = u32::from(addr) as u128;
+        let embedded = if self.prefix_len == 96 {
+            addr
+        } else {
+            // bits of the address before bit 64, the rest follows the zero octet
+            let before = 64 - self.prefix_len as u32;
+            let after = 32 - before;
+            ((addr >> after) << 64) | ((addr & ((1 << after) - 1)) << (24 + before))
+        };
+        Ipv6Addr::from(u128::from(self.prefix) | embedded)
+    }
+
+    /// whether `addr` should not be handed out in a synthesized answer
+    fn excluded_v4(&self, addr: Ipv4Addr) -> bool {
+        // the well-known prefix must not be used with non-global addresses (RFC 6052 3.1)
+        let non_global = addr.is_private()
+            || addr.is_loopback()
+            || addr.is_link_local()
+            || addr.is_unspecified()
+            || addr.is_broadcast();
+        (self.prefix == WELL_KNOWN_PREFIX && self.prefix_len == 96 && non_global)
+            || self.exclude_v4.iter().any(|(net, len)| {
+                in_network(
+                    u32::from(addr) as u128,
+                    u32::from(*net) as u128,
+                    *len as u32,
+                    32,
+                )
+            })
+    }
+
+    fn excluded_v6(&self, addr: Ipv6Addr) -> bool {
+        self.exclude_v6
+            .iter()
+            .any(|(net, len)| in_network(u128::from(addr), u128::from(*net), *len as u32, 128))
+    }
+
+    /// whether the upstream `response` to an AAAA query calls for synthesis:
+    /// it is not NXDOMAIN and holds no usable AAAA record (RFC 6147 5.1.2)
+    pub fn needs_synthesis(&self, response: &MessageRef) -> bool {
+        if response.header().flag().rcode == FlagRCode::NameError {
+            return false;
+        }
+        !response.answers().any(|answer| match aaaa_addr(&answer) {
+            Some(addr) => !self.excluded_v6(addr),
+            None => false,
+        })
+    }
+
+    /// the A query to send upstream in place of the AAAA `query`
+    pub fn a_query(&self, query: &Message) -> Message {
+        let mut header = Header::default();
+        header.id = query.header.id;
+        header.set_flag(query.header.flag());
+        let mut a_query = Message::new(header, false);
+        for q in query.questions.iter() {
+            let mut a = Question::new(q.name(), QType::Type(Type::A));
+            a.set_unicast_response(q.unicast_response());
+            a_query.add_question(a);
+        }
+        a_query
+    }
+
+    /// answer the AAAA `query` from the upstream `aaaa` and `a` responses,
+    /// `None` when nothing could be synthesized and `aaaa` stands.
+    /// Records of `a` other than A records are copied as received.
+    pub fn respond(
+        &self,
+        query: &MessageRef,
+        aaaa: &MessageRef,
+        a: &MessageRef,
+    ) -> io::Result<Option<BytesMut>> {
+        if a.header().flag().rcode != FlagRCode::NoError {
+            return Ok(None);
+        }
+        // synthesized records live no longer than the negative AAAA answer (RFC 6147 5.1.7)
+        let negative_ttl = aaaa
+            .authorities()
+            .find_map(|rr| match rr.r_type() {
+                // MINIMUM ends the RDATA
+                Some(Type::SOA) if rr.r_data().len() >= 22 => {
+                    Some(parse_u32(&rr.r_data()[(rr.r_data().len() - 4)..], true).min(rr.ttl()))
+                }
+                _ => None,
+            })
+            .unwrap_or(u32::MAX);
+
+        let mut answers = BytesMut::new();
+        let mut count = 0;
+        let mut synthesized = false;
+        for answer in a.answers() {
+            match a_addr(&answer) {
+                Some(addr) if self.excluded_v4(addr) => continue,
+                Some(addr) => {
+                    synthesized = true;
+                    answer.name().append_to(&mut answers)?;
+                    answers.put_u16(Type::AAAA.into());
+                    answers.put_u16(answer.class().into());
+                    answers.put_u32(answer.ttl().min(negative_ttl));
+                    answers.put_u16(16);
+                    answers.put(&self.synthesize(addr).octets()[..]);
+                }
+                None => answer.append_to(&mut answers)?,
+            }
+            count += 1;
+        }
+        if !synthesized {
+            return Ok(None);
+        }
+
+        let mut header = query.header().reply(FlagRCode::NoError);
+        header.qd_count = query.header().qd_count;
+        header.an_count = count;
+        let mut response: BytesMut = header.into();
+        // at the same offset, pointers into it stay valid
+        response.put(query.question_section());
+        response.put(&answers[..]);
+        Ok(Some(response))
+    }
+}
+
+fn a_addr(rr: &RecordRef) -> Option<Ipv4Addr> {
+    match (rr.r_type(), rr.r_data()) {
+        (Some(Type::A), &[a, b, c, d]) => Some(Ipv4Addr::new(a, b, c, d)),
+        _ => None,
+    }
+}
+
+fn aaaa_addr(rr: &RecordRef) -> Option<Ipv6Addr> {
+    match rr.r_type() {
+        Some(Type::AAAA) if rr.r_data().len() == 16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(rr.r_data());
+            Some(Ipv6Addr::from(octets))
+        }
+        _ => None,
+    }
+}
+
+/// `<addr>/<length>`
+fn parse_network(s: &str) -> Option<(IpAddr, u8)> {
+    let mut parts = s.splitn(2, '/');
+    let addr: IpAddr = parts.next()?.parse().ok()?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let len = match parts.next() {
+        Some(len) => len.parse().ok().filter(|len| *len <= max)?,
+        None => max,
+    };
+    Some((addr, len))
+}
+
+fn in_network(addr: u128, net: u128, len: u32, bits: u32) -> bool {
+    let mask = mask(len, bits);
+    addr & mask == net & mask
+}
+
+/// `len` leading ones in a `bits` wide mask
+fn mask(len: u32, bits: u32) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        (u128::MAX << (128 - len)) >> (128 - bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::message::{RData, ResourceRecord, SOA};
+
+    fn synthesize(prefix: &str, addr: &str) -> String {
+        let dns64: Dns64 = prefix.parse().unwrap();
+        dns64.synthesize(addr.parse().unwrap()).to_string()
+    }
+
+    /// examples of RFC 6052 2.4
+    #[test]
+    fn test_synthesize() {
+        let addr = "192.0.2.33";
+        assert_eq!(synthesize("2001:db8::/32", addr), "2001:db8:c000:221::");
+        assert_eq!(synthesize("2001:db8:100::/40", addr), "2001:db8:1c0:2:21::");
+        assert_eq!(
+            synthesize("2001:db8:122::/48", addr),
+            "2001:db8:122:c000:2:2100::"
+        );
+        assert_eq!(
+            synthesize("2001:db8:122:300::/56", addr),
+            "2001:db8:122:3c0:0:221::"
+        );
+        assert_eq!(
+            synthesize("2001:db8:122:344::/64", addr),
+            "2001:db8:122:344:c0:2:2100:0"
+        );
+        assert_eq!(
+            synthesize("2001:db8:122:344::/96", addr),
+            "2001:db8:122:344::c000:221"
+        );
+        assert_eq!(
+            Dns64::default()
+                .synthesize(addr.parse().unwrap())
+                .to_string(),
+            "64:ff9b::c000:221"
+        );
+        assert!("2001:db8::/33".parse::<Dns64>().is_err());
+        assert!("192.0.2.0/96".parse::<Dns64>().is_err());
+    }
+
+    fn encode(message: Message) -> BytesMut {
+        message.into()
+    }
+
+    #[test]
+    fn test_respond() {
+        let mut dns64 = Dns64::default();
+        dns64.exclude("198.51.100.0/24").unwrap();
+        let mut query = Message::new(Header::default(), false);
+        query.add_question(Question::new("www.example.com", QType::Type(Type::AAAA)));
+
+        let mut aaaa = query.reply(FlagRCode::NoError);
+        let mapped = RData::AAAA("::ffff:192.0.2.33".parse().unwrap());
+        aaaa.add_answer(ResourceRecord::new(
+            "www.example.com",
+            Type::AAAA,
+            300,
+            mapped,
+        ));
+        let soa = SOA {
+            mname: "ns.example.com.".to_owned(),
+            rname: "hostmaster.example.com.".to_owned(),
+            serial: 1,
+            refresh: 3600,
+            retry: 900,
+            expire: 604800,
+            minimum: 60,
+        };
+        aaaa.add_authority(ResourceRecord::new(
+            "example.com",
+            Type::SOA,
+            300,
+            RData::SOA(soa),
+        ));
+        let aaaa = encode(aaaa);
+        let aaaa = MessageRef::parse(&aaaa).unwrap();
+        assert!(dns64.needs_synthesis(&aaaa));
+
+        let a_query = dns64.a_query(&query);
+        assert_eq!(a_query.header.id, query.header.id);
+        assert!(a_query.questions[0].q_type() == QType::Type(Type::A));
+
+        // names compressed, with a record of a type without a decoder
+        let mut a: Vec<u8> = vec![0, 0, 0x81, 0x80, 0, 1, 0, 6, 0, 0, 0, 0];
+        a.extend_from_slice(b"\x03www\x07example\x03com\x00\x00\x01\x00\x01");
+        // www.example.com. CNAME edge.example.com.
+        a.extend_from_slice(b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x01\x2c\x00\x07\x04edge\xc0\x10");
+        // edge.example.com. TYPE65280 \# 2 dead
+        a.extend_from_slice(b"\xc0\x2d\xff\x00\x00\x01\x00\x00\x01\x2c\x00\x02\xde\xad");
+        for addr in &[[192, 0, 2, 33], [198, 51, 100, 1], [10, 0, 0, 1]] {
+            a.extend_from_slice(b"\xc0\x2d\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04");
+            a.extend_from_slice(addr);
+        }
+        // a malformed A record is copied too
+        a.extend_from_slice(b"\xc0\x2d\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x01\x00");
+
+        let query = encode(query);
+        let query = MessageRef::parse(&query).unwrap();
+        let a_ref = MessageRef::parse(&a).unwrap();
+        let response = dns64.respond(&query, &aaaa, &a_ref).unwrap().unwrap();
+        let view = MessageRef::parse(&response).unwrap();
+        let answers: Vec<_> = view.answers().collect();
+        assert_eq!(answers.len(), 4);
+        assert_eq!(answers[1].type_code(), 65280);
+        assert_eq!(answers[1].r_data(), &[0xde, 0xad]);
+        assert!(answers[1].name().eq_ignore_case("edge.example.com"));
+        assert_eq!(answers[3].r_data(), &[0]);
+
+        let response = Message::parse(&response).unwrap();
+        assert_eq!(response.header.id, query.header().id);
+        assert!(response.questions[0].q_type() == QType::Type(Type::AAAA));
+        assert!(response.answers[0].r_data == RData::CNAME("edge.example.com.".to_owned()));
+        let synthesized = &response.answers[2];
+        assert_eq!(synthesized.name, "edge.example.com.");
+        assert_eq!(synthesized.ttl, 60);
+        assert!(synthesized.r_data == RData::AAAA("64:ff9b::c000:221".parse().unwrap()));
+
+        // only excluded addresses, nothing to synthesize
+        a.truncate(a.len() - 3 * 16 - 13);
+        a.extend_from_slice(b"\xc0\x2d\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04\x0a\x00\x00\x01");
+        a[7] = 3;
+        let a_ref = MessageRef::parse(&a).unwrap();
+        assert!(dns64.respond(&query, &aaaa, &a_ref).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let mut dns64 = Dns64::default();
+        dns64.exclude_name("ipv4only.lan");
+        assert!(!dns64.applies_to("IPv4only.lan."));
+        assert!(!dns64.applies_to("www.ipv4only.lan"));
+        assert!(dns64.applies_to("notipv4only.lan"));
+
+        // other errors are taken as an empty answer
+        let nxdomain = encode(Message::default().reply(FlagRCode::NameError));
+        assert!(!dns64.needs_synthesis(&MessageRef::parse(&nxdomain).unwrap()));
+        let servfail = encode(Message::default().reply(FlagRCode::ServerFailure));
+        assert!(dns64.needs_synthesis(&MessageRef::parse(&servfail).unwrap()));
+
+        assert!(dns64.exclude("2001:db8::/129").is_err());
+        dns64.exclude("2001:db8::/32").unwrap();
+        let mut aaaa = Message::default().reply(FlagRCode::NoError);
+        let addr = RData::AAAA("2001:db8::1".parse().unwrap());
+        aaaa.add_answer(ResourceRecord::new("www.lan", Type::AAAA, 300, addr));
+        assert!(dns64.needs_synthesis(&MessageRef::parse(&encode(aaaa)).unwrap()));
+    }
+}
@@ -0,0 +1,154 @@
+use super::message::{RData, ResourceRecord};
+use super::types::{QType, Type};
+use glow_utils::fs::read_file;
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+pub const HOSTS_PATH: &str = "/etc/hosts";
+
+/// TTL of records answered from hosts file
+const HOSTS_TTL: u32 = 0;
+
+/// Static host table in the format of hosts(5):
+/// `<address> <canonical name> [aliases...]`, comments start with '#'.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Default)]
+pub struct Hosts {
+    /// lowercased name without trailing '.' -> addresses, in file order
+    names: HashMap<String, Vec<IpAddr>>,
+
+    /// address -> canonical name, the first entry wins
+    addrs: HashMap<IpAddr, String>,
+}
+
+impl Hosts {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
+        let content = read_file(path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(Hosts::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Hosts {
+        let mut hosts = Hosts::default();
+        for line in content.lines() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+
+            let mut fields = line.split_whitespace();
+            let addr: IpAddr = match fields.next().map(str::parse) {
+                Some(Ok(addr)) => addr,
+                _ => continue,
+            };
+
+            for (i, name) in fields.enumerate() {
+                let name = normalize(name);
+                if i == 0 {
+                    hosts.addrs.entry(addr).or_insert_with(|| name.clone());
+                }
+                let addrs = hosts.names.entry(name).or_insert_with(Vec::new);
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+        }
+        hosts
+    }
+
+    /// all addresses of `name`
+    pub fn lookup(&self, name: &str) -> Option<&[IpAddr]> {
+        self.names.get(&normalize(name)).map(Vec::as_slice)
+    }
+
+    /// canonical name of `addr`
+    pub fn lookup_addr(&self, addr: &IpAddr) -> Option<&str> {
+        self.addrs.get(addr).map(String::as_str)
+    }
+
+    /// answer `name` locally, None if the name is not in the table
+    pub fn records(&self, name: &str, qtype: QType) -> Option<Vec<ResourceRecord>> {
+        let (want_v4, want_v6) = match qtype {
+            QType::Type(Type::A) => (true, false),
+            QType::Type(Type::AAAA) => (false, true),
+            QType::ANY => (true, true),
+            _ => return None,
+        };
+
+        let addrs = self.lookup(name)?;
+        Some(
+            addrs
+                .iter()
+                .filter_map(|addr| match addr {
+                    IpAddr::V4(addr) if want_v4 => Some(ResourceRecord::new(
+                        name,
+                        Type::A,
+                        HOSTS_TTL,
+                        RData::A(*addr),
+                    )),
+                    IpAddr::V6(addr) if want_v6 => Some(ResourceRecord::new(
+                        name,
+                        Type::AAAA,
+                        HOSTS_TTL,
+                        RData::AAAA(*addr),
+                    )),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTS: &str = "\
+# Static table lookup for hostnames.
+127.0.0.1   localhost
+::1         localhost ip6-localhost
+192.168.1.2 printer.lan printer # office printer
+192.168.1.3 Router.LAN
+not-an-ip   broken
+";
+
+    #[test]
+    fn test_parse_hosts() {
+        let hosts = Hosts::parse(HOSTS);
+        assert_eq!(
+            hosts.lookup("localhost"),
+            Some(&["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()][..])
+        );
+        assert_eq!(
+            hosts.lookup("printer"),
+            Some(&["192.168.1.2".parse().unwrap()][..])
+        );
+        assert_eq!(
+            hosts.lookup("router.lan."),
+            Some(&["192.168.1.3".parse().unwrap()][..])
+        );
+        assert_eq!(hosts.lookup("broken"), None);
+        assert_eq!(
+            hosts.lookup_addr(&"192.168.1.2".parse().unwrap()),
+            Some("printer.lan")
+        );
+    }
+
+    #[test]
+    fn test_hosts_records() {
+        let hosts = Hosts::parse(HOSTS);
+        let records = hosts.records("localhost", QType::Type(Type::AAAA)).unwrap();
+        assert_eq!(records.len(), 1);
+        match records[0].r_data {
+            RData::AAAA(addr) => assert_eq!(addr, "::1".parse::<std::net::Ipv6Addr>().unwrap()),
+            _ => panic!("No AAAA record found!"),
+        }
+        assert!(hosts.records("example.com", QType::Type(Type::A)).is_none());
+        assert!(hosts.records("localhost", QType::Type(Type::MX)).is_none());
+    }
+}
@@ -0,0 +1,628 @@
+use super::message::{
+    FlagOpCode, FlagRCode, Header, HeaderFlag, Message, Question, RData, ResourceRecord,
+};
+use super::types::{QType, Type};
+use bytes::BytesMut;
+use socket2::{Domain, Protocol, SockAddr, Socket};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const MDNS_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const MDNS_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+pub const MDNS_PORT: u16 = 5353;
+
+/// Name listing every advertised service type (RFC 6763 9)
+pub const SERVICES: &str = "_services._dns-sd._udp.local.";
+
+/// TTL of records containing host names (RFC 6762 10)
+const HOST_TTL: u32 = 120;
+/// TTL of other records
+const SERVICE_TTL: u32 = 4500;
+/// TTL cap of responses to legacy unicast queries (RFC 6762 6.7)
+const LEGACY_TTL: u32 = 10;
+
+/// A DNS-SD service instance.
+/// Instance names must not contain dots, escaping is not supported.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct Service {
+    /// instance name, e.g. `Living Room`
+    pub instance: String,
+    /// service type, e.g. `_ipp._tcp`
+    pub service: String,
+    /// host the service runs on, e.g. `glow.local.`
+    pub host: String,
+    pub port: u16,
+    /// `key=value` pairs
+    pub txt: Vec<String>,
+    pub addrs: Vec<IpAddr>,
+}
+
+impl Service {
+    pub fn new(instance: &str, service: &str, port: u16) -> Service {
+        Service {
+            instance: instance.to_owned(),
+            service: service.trim_end_matches('.').to_owned(),
+            host: String::new(),
+            port,
+            txt: Vec::new(),
+            addrs: Vec::new(),
+        }
+    }
+
+    /// `_ipp._tcp.local.`
+    pub fn service_name(&self) -> String {
+        format!("{}.local.", self.service)
+    }
+
+    /// `Living Room._ipp._tcp.local.`
+    pub fn instance_name(&self) -> String {
+        format!("{}.{}", self.instance, self.service_name())
+    }
+}
+
+/// Multicast DNS responder (RFC 6762) advertising a host
+/// and its DNS-SD services (RFC 6763)
+pub struct MdnsResponder {
+    host: String,
+    addrs: Vec<IpAddr>,
+    services: Vec<Service>,
+}
+
+impl MdnsResponder {
+    /// respond for `host`.local
+    pub fn new(host: &str) -> MdnsResponder {
+        let host = host.trim_end_matches('.');
+        let host = host.strip_suffix(".local").unwrap_or(host);
+        MdnsResponder {
+            host: format!("{}.local.", host),
+            addrs: Vec::new(),
+            services: Vec::new(),
+        }
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn address(&mut self, addr: IpAddr) -> &MdnsResponder {
+        self.addrs.push(addr);
+        self
+    }
+
+    pub fn service(&mut self, mut service: Service) -> &MdnsResponder {
+        service.host = self.host.clone();
+        self.services.push(service);
+        self
+    }
+
+    fn address_records(&self) -> Vec<ResourceRecord> {
+        self.addrs
+            .iter()
+            .map(|addr| {
+                let (r_type, r_data) = match addr {
+                    IpAddr::V4(addr) => (Type::A, RData::A(*addr)),
+                    IpAddr::V6(addr) => (Type::AAAA, RData::AAAA(*addr)),
+                };
+                unique(ResourceRecord::new(&self.host, r_type, HOST_TTL, r_data))
+            })
+            .collect()
+    }
+
+    fn service_records(&self, service: &Service) -> Vec<ResourceRecord> {
+        let name = service.instance_name();
+        let txt = if service.txt.is_empty() {
+            // a TXT record holds at least one string (RFC 6763 6.1)
+            vec![String::new()]
+        } else {
+            service.txt.clone()
+        };
+        vec![
+            unique(ResourceRecord::new(
+                &name,
+                Type::SRV,
+                HOST_TTL,
+                RData::SRV(0, 0, service.port, self.host.clone()),
+            )),
+            unique(ResourceRecord::new(
+                &name,
+                Type::TXT,
+                SERVICE_TTL,
+                RData::TXT(txt),
+            )),
+        ]
+    }
+
+    /// answers and additional records of `question`
+    fn records(&self, question: &Question) -> (Vec<ResourceRecord>, Vec<ResourceRecord>) {
+        let name = question.name();
+        let wanted = |rr: &ResourceRecord| match question.q_type() {
+            QType::ANY => true,
+            QType::Type(t) => t == rr.r_type,
+            _ => false,
+        };
+        let mut answers = Vec::new();
+        let mut additionals = Vec::new();
+
+        if same_name(name, &self.host) {
+            answers.extend(self.address_records().into_iter().filter(wanted));
+        }
+        if same_name(name, SERVICES) {
+            for service in self.services.iter() {
+                let rr = ResourceRecord::new(
+                    SERVICES,
+                    Type::PTR,
+                    SERVICE_TTL,
+                    RData::PTR(service.service_name()),
+                );
+                if wanted(&rr)
+                    && !answers
+                        .iter()
+                        .any(|a: &ResourceRecord| a.r_data == rr.r_data)
+                {
+                    answers.push(rr);
+                }
+            }
+        }
+        for service in self.services.iter() {
+            if same_name(name, &service.service_name()) {
+                let rr = ResourceRecord::new(
+                    name,
+                    Type::PTR,
+                    SERVICE_TTL,
+                    RData::PTR(service.instance_name()),
+                );
+                if wanted(&rr) {
+                    answers.push(rr);
+                    additionals.extend(self.service_records(service));
+                    additionals.extend(self.address_records());
+                }
+            } else if same_name(name, &service.instance_name()) {
+                let records: Vec<_> = self
+                    .service_records(service)
+                    .into_iter()
+                    .filter(wanted)
+                    .collect();
+                if !records.is_empty() {
+                    answers.extend(records);
+                    additionals.extend(self.address_records());
+                }
+            }
+        }
+        (answers, additionals)
+    }
+
+    /// response to `query` received from `src` and where to send it,
+    /// `None` if nothing should be sent
+    pub fn respond(&self, query: &Message, src: SocketAddr) -> Option<(Message, SocketAddr)> {
+        let flag = query.header.flag();
+        if flag.is_response || flag.opcode != FlagOpCode::Query {
+            return None;
+        }
+
+        let mut answers: Vec<ResourceRecord> = Vec::new();
+        let mut additionals: Vec<ResourceRecord> = Vec::new();
+        for question in query.questions.iter() {
+            let (a, ar) = self.records(question);
+            answers.extend(a);
+            additionals.extend(ar);
+        }
+
+        // known-answer suppression (RFC 6762 7.1)
+        answers.retain(|rr| {
+            !query
+                .answers
+                .iter()
+                .any(|known| same_record(known, rr) && known.ttl >= rr.ttl / 2)
+        });
+        if answers.is_empty() {
+            return None;
+        }
+        additionals.retain(|rr| !answers.iter().any(|a| same_record(a, rr)));
+        let mut unique_additionals: Vec<ResourceRecord> = Vec::new();
+        for rr in additionals {
+            if !unique_additionals.iter().any(|a| same_record(a, &rr)) {
+                unique_additionals.push(rr);
+            }
+        }
+
+        let legacy = src.port() != MDNS_PORT;
+        let unicast = legacy || query.questions.iter().all(|q| q.unicast_response());
+        let mut response = if legacy {
+            query.reply(FlagRCode::NoError)
+        } else {
+            Message::new(Header::new(0, 0, 0, 0, 0, 0), false)
+        };
+        response.header.set_flag(HeaderFlag {
+            is_response: true,
+            authoritative_answer: true,
+            recursion_desired: false,
+            ..Default::default()
+        });
+
+        let prepare = |mut rr: ResourceRecord| {
+            if legacy {
+                rr.cache_flush = false;
+                rr.ttl = rr.ttl.min(LEGACY_TTL);
+            }
+            rr
+        };
+        for rr in answers {
+            response.add_answer(prepare(rr));
+        }
+        for rr in unique_additionals {
+            response.add_additional(prepare(rr));
+        }
+
+        let dest = match (unicast, src) {
+            (true, _) => src,
+            (false, SocketAddr::V4(_)) => SocketAddr::new(MDNS_V4.into(), MDNS_PORT),
+            (false, SocketAddr::V6(_)) => SocketAddr::new(MDNS_V6.into(), MDNS_PORT),
+        };
+        Some((response, dest))
+    }
+
+    /// unsolicited response announcing every record (RFC 6762 8.3)
+    pub fn announcement(&self) -> Message {
+        let mut response = Message::new(Header::new(0, 0, 0, 0, 0, 0), false);
+        response.header.set_flag(HeaderFlag {
+            is_response: true,
+            authoritative_answer: true,
+            recursion_desired: false,
+            ..Default::default()
+        });
+        for rr in self.address_records() {
+            response.add_answer(rr);
+        }
+        for service in self.services.iter() {
+            response.add_answer(ResourceRecord::new(
+                &service.service_name(),
+                Type::PTR,
+                SERVICE_TTL,
+                RData::PTR(service.instance_name()),
+            ));
+            for rr in self.service_records(service) {
+                response.add_answer(rr);
+            }
+        }
+        response
+    }
+
+    /// announce and answer queries on both multicast groups forever
+    pub fn serve(self) -> io::Result<()> {
+        let responder = Arc::new(self);
+        let v4 = multicast_socket(IpAddr::V4(MDNS_V4))?;
+
+        match multicast_socket(IpAddr::V6(MDNS_V6)) {
+            Ok(v6) => {
+                let responder = responder.clone();
+                thread::spawn(move || {
+                    if let Err(e) = responder.serve_on(&v6) {
+                        eprintln!("mDNS over IPv6 stopped: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("mDNS over IPv6 is unavailable: {}", e),
+        }
+
+        let announcement: BytesMut = responder.announcement().into();
+        let group = SocketAddr::new(MDNS_V4.into(), MDNS_PORT);
+        v4.send_to(&announcement, group)?;
+        thread::sleep(Duration::from_secs(1));
+        v4.send_to(&announcement, group)?;
+
+        responder.serve_on(&v4)
+    }
+
+    /// answer queries received on `socket` forever
+    pub fn serve_on(&self, socket: &UdpSocket) -> io::Result<()> {
+        let mut buffer = [0u8; 9000];
+        loop {
+            let (size, src) = socket.recv_from(&mut buffer)?;
+            let query = match Message::parse(&buffer[..size]).ok() {
+                Some(query) => query,
+                None => continue,
+            };
+            if let Some((response, dest)) = self.respond(&query, src) {
+                let response: BytesMut = response.into();
+                if let Err(e) = socket.send_to(&response, dest) {
+                    eprintln!("failed to answer mDNS query from {}: {}", src, e);
+                }
+            }
+        }
+    }
+}
+
+/// Multicast DNS querier browsing and resolving DNS-SD services
+pub struct MdnsClient {
+    target: SocketAddr,
+    timeout: Duration,
+}
+
+impl Default for MdnsClient {
+    fn default() -> Self {
+        MdnsClient {
+            target: SocketAddr::new(MDNS_V4.into(), MDNS_PORT),
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+impl MdnsClient {
+    /// send queries to `target` instead of the IPv4 multicast group
+    pub fn target(&mut self, target: SocketAddr) -> &MdnsClient {
+        self.target = target;
+        self
+    }
+
+    /// time spent collecting responses of each query
+    pub fn timeout(&mut self, timeout: Duration) -> &MdnsClient {
+        self.timeout = timeout;
+        self
+    }
+
+    /// one-shot query (RFC 6762 5.1), returning every record
+    /// of the responses received before the timeout
+    pub fn query(&self, name: &str, q_type: QType) -> io::Result<Vec<ResourceRecord>> {
+        let bind: SocketAddr = match self.target {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind)?;
+        let mut query = Message::new(Header::new(rand::random(), 0, 0, 0, 0, 0), false);
+        query.add_question(Question::new(name, q_type));
+        let query: BytesMut = query.into();
+        socket.send_to(&query, self.target)?;
+
+        let mut records: Vec<ResourceRecord> = Vec::new();
+        let mut buffer = [0u8; 9000];
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(records);
+            }
+            socket.set_read_timeout(Some(deadline - now))?;
+            let size = match socket.recv_from(&mut buffer) {
+                Ok((size, _)) => size,
+                Err(e) if is_timeout(&e) => return Ok(records),
+                Err(e) => return Err(e),
+            };
+            let response = match Message::parse(&buffer[..size]).ok() {
+                Some(response) if response.header.flag().is_response => response,
+                _ => continue,
+            };
+            for rr in response.answers.into_iter().chain(response.additionals) {
+                if !records.iter().any(|r| same_record(r, &rr)) {
+                    records.push(rr);
+                }
+            }
+        }
+    }
+
+    /// instance names of `service`, e.g. `_http._tcp`
+    pub fn browse(&self, service: &str) -> io::Result<Vec<String>> {
+        let name = format!("{}.local.", service.trim_end_matches('.'));
+        Ok(self
+            .query(&name, QType::Type(Type::PTR))?
+            .into_iter()
+            .filter(|rr| same_name(&rr.name, &name))
+            .filter_map(|rr| match rr.r_data {
+                RData::PTR(instance) => Some(instance),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// service types advertised on the link
+    pub fn services(&self) -> io::Result<Vec<String>> {
+        self.browse("_services._dns-sd._udp")
+    }
+
+    /// host, port, TXT and addresses of an instance name
+    /// like `Living Room._ipp._tcp.local.`
+    pub fn resolve(&self, instance_name: &str) -> io::Result<Option<Service>> {
+        let mut labels = instance_name.splitn(2, '.');
+        let instance = labels.next().unwrap_or_default();
+        let service = labels.next().unwrap_or_default().trim_end_matches('.');
+        let service = service.strip_suffix(".local").unwrap_or(service);
+
+        let mut records = self.query(instance_name, QType::ANY)?;
+        let (port, host) = match records.iter().find_map(|rr| match &rr.r_data {
+            RData::SRV(_, _, port, host) if same_name(&rr.name, instance_name) => {
+                Some((*port, host.clone()))
+            }
+            _ => None,
+        }) {
+            Some(srv) => srv,
+            None => return Ok(None),
+        };
+
+        let mut service = Service::new(instance, service, port);
+        service.host = host;
+        let addrs = |records: &[ResourceRecord], host: &str| -> Vec<IpAddr> {
+            records
+                .iter()
+                .filter(|rr| same_name(&rr.name, host))
+                .filter_map(|rr| match rr.r_data {
+                    RData::A(addr) => Some(IpAddr::V4(addr)),
+                    RData::AAAA(addr) => Some(IpAddr::V6(addr)),
+                    _ => None,
+                })
+                .collect()
+        };
+        service.addrs = addrs(&records, &service.host);
+        if service.addrs.is_empty() {
+            records = self.query(&service.host, QType::ANY)?;
+            service.addrs = addrs(&records, &service.host);
+        }
+        service.txt = records
+            .iter()
+            .find_map(|rr| match &rr.r_data {
+                RData::TXT(txt) if same_name(&rr.name, instance_name) => Some(txt.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect();
+        Ok(Some(service))
+    }
+}
+
+/// socket bound to the mDNS port and joined to `group`
+fn multicast_socket(group: IpAddr) -> io::Result<UdpSocket> {
+    let (domain, bind): (Domain, SocketAddr) = match group {
+        IpAddr::V4(_) => (Domain::ipv4(), (Ipv4Addr::UNSPECIFIED, MDNS_PORT).into()),
+        IpAddr::V6(_) => (Domain::ipv6(), (Ipv6Addr::UNSPECIFIED, MDNS_PORT).into()),
+    };
+    let socket = Socket::new(domain, socket2::Type::dgram(), Some(Protocol::udp()))?;
+    // share the port with other responders like avahi
+    socket.set_reuse_address(true)?;
+    if group.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.bind(&SockAddr::from(bind))?;
+
+    let socket = socket.into_udp_socket();
+    match group {
+        IpAddr::V4(group) => socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?,
+        IpAddr::V6(group) => socket.join_multicast_v6(&group, 0)?,
+    }
+    Ok(socket)
+}
+
+/// mark a record as the only one of its RRset
+fn unique(mut rr: ResourceRecord) -> ResourceRecord {
+    rr.cache_flush = true;
+    rr
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+fn same_record(a: &ResourceRecord, b: &ResourceRecord) -> bool {
+    a.r_type == b.r_type && a.r_data == b.r_data && same_name(&a.name, &b.name)
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::class::Class;
+
+    fn responder() -> MdnsResponder {
+        let mut responder = MdnsResponder::new("glow");
+        responder.address("192.168.1.1".parse().unwrap());
+        let mut printer = Service::new("Living Room", "_ipp._tcp", 631);
+        printer.txt = vec!["rp=ipp/print".to_owned()];
+        responder.service(printer);
+        responder
+    }
+
+    fn query(name: &str, q_type: QType, unicast: bool) -> Message {
+        let mut question = Question::new(name, q_type);
+        question.set_unicast_response(unicast);
+        let mut query = Message::new(Header::new(0, 0, 0, 0, 0, 0), false);
+        query.add_question(question);
+        // round trip through the wire format
+        let raw: BytesMut = query.into();
+        Message::from(&raw[..])
+    }
+
+    #[test]
+    fn test_respond_multicast() {
+        let responder = responder();
+        let src: SocketAddr = "192.168.1.10:5353".parse().unwrap();
+
+        let (response, dest) = responder
+            .respond(
+                &query("_ipp._tcp.local", QType::Type(Type::PTR), false),
+                src,
+            )
+            .unwrap();
+        assert_eq!(dest, "224.0.0.251:5353".parse().unwrap());
+        assert_eq!(response.header.id, 0);
+        assert!(response.questions.is_empty());
+        assert!(response.header.flag().authoritative_answer);
+        assert!(
+            response.answers[0].r_data == RData::PTR("Living Room._ipp._tcp.local.".to_owned())
+        );
+        assert!(!response.answers[0].cache_flush);
+        // SRV, TXT and A as additional records
+        assert_eq!(response.additionals.len(), 3);
+
+        let raw: BytesMut = response.into();
+        let response = Message::from(&raw[..]);
+        let srv = &response.additionals[0];
+        assert!(srv.cache_flush && srv.class == Class::IN);
+        assert!(srv.r_data == RData::SRV(0, 0, 631, "glow.local.".to_owned()));
+
+        // unicast-response bit
+        let (_, dest) = responder
+            .respond(&query("glow.local", QType::Type(Type::A), true), src)
+            .unwrap();
+        assert_eq!(dest, src);
+
+        // not ours
+        assert!(responder
+            .respond(&query("other.local", QType::ANY, false), src)
+            .is_none());
+    }
+
+    #[test]
+    fn test_respond_known_answer() {
+        let responder = responder();
+        let src: SocketAddr = "192.168.1.10:5353".parse().unwrap();
+        let mut known = query("glow.local", QType::Type(Type::A), false);
+        known.add_answer(ResourceRecord::new(
+            "glow.local.",
+            Type::A,
+            HOST_TTL,
+            RData::A("192.168.1.1".parse().unwrap()),
+        ));
+        assert!(responder.respond(&known, src).is_none());
+    }
+
+    #[test]
+    fn test_browse_resolve() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || responder().serve_on(&socket));
+
+        let mut client = MdnsClient::default();
+        client.target(addr);
+        client.timeout(Duration::from_millis(200));
+        assert_eq!(client.services().unwrap(), vec!["_ipp._tcp.local."]);
+
+        let instances = client.browse("_ipp._tcp").unwrap();
+        assert_eq!(instances, vec!["Living Room._ipp._tcp.local."]);
+
+        let service = client.resolve(&instances[0]).unwrap().unwrap();
+        assert_eq!(service.instance, "Living Room");
+        assert_eq!(service.service, "_ipp._tcp");
+        assert_eq!(service.host, "glow.local.");
+        assert_eq!(service.port, 631);
+        assert_eq!(service.txt, vec!["rp=ipp/print"]);
+        assert_eq!(
+            service.addrs,
+            vec!["192.168.1.1".parse::<IpAddr>().unwrap()]
+        );
+
+        assert!(client
+            .resolve("Kitchen._ipp._tcp.local.")
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,10 +1,22 @@
 use super::{class, types};
 use bytes::{BufMut, BytesMut};
+use glow_utils::binary::parse_u32;
 use glow_utils::{get_bit, get_bits, u8_merge};
+use std::io;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
+/// Maximum length of a domain name in presentation format
+pub const MAX_NAME_LEN: usize = 255;
+
+/// Maximum octets of a label, and of a name on the wire (RFC 1035 2.3.4)
+pub const MAX_LABEL_LEN: usize = 63;
+const MAX_WIRE_NAME_LEN: usize = 255;
+
+/// Maximum octets of a <character-string>
+const MAX_STRING_LEN: usize = 255;
+
 #[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Header {
     /// A 16 bit identifier assigned by the program that
     /// generates any kind of query. This identifier is copied
@@ -55,6 +67,26 @@ impl Header {
     pub fn flag(&self) -> HeaderFlag {
         HeaderFlag::from(self.flag)
     }
+
+    pub fn set_flag(&mut self, flag: HeaderFlag) {
+        self.flag = flag.into();
+    }
+
+    /// the header of a reply to this query, with empty sections
+    pub fn reply(&self, rcode: FlagRCode) -> Header {
+        let query = self.flag();
+        let mut header = Header::new(self.id, 0, 0, 0, 0, 0);
+        header.set_flag(HeaderFlag {
+            is_response: true,
+            opcode: query.opcode,
+            authoritative_answer: false,
+            truncated: false,
+            recursion_desired: query.recursion_desired,
+            recursion_available: true,
+            rcode,
+        });
+        header
+    }
 }
 
 impl Default for Header {
@@ -151,6 +183,18 @@ impl From<u16> for HeaderFlag {
     }
 }
 
+impl From<HeaderFlag> for u16 {
+    fn from(flag: HeaderFlag) -> Self {
+        (flag.is_response as u16) << 15
+            | (flag.opcode as u16 & 0b1111) << 11
+            | (flag.authoritative_answer as u16) << 10
+            | (flag.truncated as u16) << 9
+            | (flag.recursion_desired as u16) << 8
+            | (flag.recursion_available as u16) << 7
+            | (flag.rcode as u16 & 0b1111)
+    }
+}
+
 impl Default for HeaderFlag {
     fn default() -> Self {
         HeaderFlag {
@@ -173,6 +217,7 @@ impl HeaderFlag {
 /// message. This value is set by the originator of a query
 /// and copied into the response. The values are:
 #[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
 pub enum FlagOpCode {
     /// 0 a standard query (QUERY)
     Query = 0,
@@ -180,7 +225,9 @@ pub enum FlagOpCode {
     IQuery = 1,
     /// 2 a server status request (STATUS)
     Status = 2,
-    /// 3-15 reserved for future use
+    /// 5 a dynamic update (UPDATE, RFC 2136)
+    Update = 5,
+    /// 3-4, 6-15 reserved for future use
     Reserved = 15,
 }
 
@@ -190,6 +237,7 @@ impl From<u16> for FlagOpCode {
             0 => FlagOpCode::Query,
             1 => FlagOpCode::IQuery,
             2 => FlagOpCode::Status,
+            5 => FlagOpCode::Update,
             _ => FlagOpCode::Reserved,
         }
     }
@@ -199,6 +247,7 @@ impl From<u16> for FlagOpCode {
 /// responses. The values have the following
 /// interpretation:
 #[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
 pub enum FlagRCode {
     /// 0 No error condition
     NoError = 0,
@@ -227,7 +276,23 @@ pub enum FlagRCode {
     /// a particular operation (e.g., zone
     /// transfer) for particular data.s
     Refused = 5,
-    /// 6-15 Reserved for future use.
+    /// 6 Some name that ought not to exist,
+    /// does exist. (RFC 2136)
+    YXDomain = 6,
+    /// 7 Some RRset that ought not to exist,
+    /// does exist. (RFC 2136)
+    YXRRSet = 7,
+    /// 8 Some RRset that ought to exist,
+    /// does not exist. (RFC 2136)
+    NXRRSet = 8,
+    /// 9 The server is not authoritative for
+    /// the zone named in the Zone Section,
+    /// or the request is not authorized. (RFC 2136, RFC 8945)
+    NotAuth = 9,
+    /// 10 A name used in the Prerequisite or
+    /// Update Section is not within the zone. (RFC 2136)
+    NotZone = 10,
+    /// 11-15 Reserved for future use.
     Reserved = 15,
 }
 
@@ -240,6 +305,11 @@ impl From<u16> for FlagRCode {
             3 => FlagRCode::NameError,
             4 => FlagRCode::NotImplemented,
             5 => FlagRCode::Refused,
+            6 => FlagRCode::YXDomain,
+            7 => FlagRCode::YXRRSet,
+            8 => FlagRCode::NXRRSet,
+            9 => FlagRCode::NotAuth,
+            10 => FlagRCode::NotZone,
             _ => FlagRCode::Reserved,
         }
     }
@@ -254,12 +324,18 @@ impl Into<String> for FlagRCode {
             FlagRCode::NameError => "NameError".to_owned(),
             FlagRCode::NotImplemented => "NotImplemented".to_owned(),
             FlagRCode::Refused => "Refused".to_owned(),
+            FlagRCode::YXDomain => "YXDomain".to_owned(),
+            FlagRCode::YXRRSet => "YXRRSet".to_owned(),
+            FlagRCode::NXRRSet => "NXRRSet".to_owned(),
+            FlagRCode::NotAuth => "NotAuth".to_owned(),
+            FlagRCode::NotZone => "NotZone".to_owned(),
             FlagRCode::Reserved => "Reserved".to_owned(),
         }
     }
 }
 
 #[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
 pub struct Question {
     /// a domain name represented as a sequence of labels, where
     /// each label consists of a length octet followed by that
@@ -278,6 +354,10 @@ pub struct Question {
     /// a two octet code that specifies the class of the query.
     /// For example, the QCLASS field is IN for the Internet.
     q_class: class::QClass,
+
+    /// the top bit of QCLASS in multicast DNS, asking
+    /// for a unicast response (RFC 6762 5.4)
+    unicast_response: bool,
 }
 
 impl Question {
@@ -286,18 +366,35 @@ impl Question {
             q_name: host.to_owned(),
             q_type,
             q_class: class::QClass::Class(class::Class::IN),
+            unicast_response: false,
         }
     }
 
+    pub fn unicast_response(&self) -> bool {
+        self.unicast_response
+    }
+
+    pub fn set_unicast_response(&mut self, unicast_response: bool) {
+        self.unicast_response = unicast_response;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.q_name
+    }
+
+    pub fn q_type(&self) -> types::QType {
+        self.q_type
+    }
+
+    pub fn q_class(&self) -> class::QClass {
+        self.q_class
+    }
+
     /// append Question to BytesMut for query
     pub fn append_to<'a>(&self, m: &'a mut BytesMut) -> &'a mut BytesMut {
-        for part in self.q_name.split('.') {
-            m.put_u8(part.len() as u8);
-            m.put(part.as_bytes());
-        }
-        m.put_u8(0);
+        write_name(m, &self.q_name);
         m.put_u16(self.q_type.into());
-        m.put_u16(self.q_class.into());
+        m.put_u16(Into::<u16>::into(self.q_class) | (self.unicast_response as u16) << 15);
         m
     }
 
@@ -312,17 +409,22 @@ impl Question {
         m
     }
 
-    pub fn parse(raw: &[u8], base_offset: usize) -> (Question, usize) {
-        let (name, size) = read_name(raw, base_offset);
+    pub fn parse(raw: &[u8], base_offset: usize) -> io::Result<(Question, usize)> {
+        let (name, size) = read_name(raw, base_offset)?;
         let offset = base_offset + size;
-        (
+        if offset + 4 > raw.len() {
+            return Err(invalid("question is truncated"));
+        }
+        let q_class = u8_merge!(raw[offset + 2], raw[offset + 3]);
+        Ok((
             Question {
                 q_name: name,
                 q_type: types::QType::from(u8_merge!(raw[offset], raw[offset + 1])),
-                q_class: class::QClass::from(u8_merge!(raw[offset + 2], raw[offset + 3])),
+                q_class: class::QClass::from(q_class & 0x7fff),
+                unicast_response: q_class & 0x8000 != 0,
             },
             size + 4,
-        )
+        ))
     }
 }
 
@@ -335,6 +437,7 @@ impl Into<BytesMut> for Question {
 }
 
 #[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
 pub struct ResourceRecord {
     /// a domain name to which this resource record pertains.
     pub name: String,
@@ -348,6 +451,10 @@ pub struct ResourceRecord {
     /// RDATA field.
     pub class: class::Class,
 
+    /// the top bit of CLASS in multicast DNS, telling caches
+    /// to replace the RRset instead of adding to it (RFC 6762 10.2)
+    pub cache_flush: bool,
+
     /// a 32 bit unsigned integer that specifies the time
     /// interval (in seconds) that the resource record may be
     /// cached before it should be discarded. Zero values are
@@ -368,30 +475,135 @@ pub struct ResourceRecord {
 }
 
 impl ResourceRecord {
-    pub fn parse(raw: &[u8], base_offset: usize) -> (ResourceRecord, usize) {
-        let (name, size) = read_name(raw, base_offset);
+    pub fn new(name: &str, r_type: types::Type, ttl: u32, r_data: RData) -> ResourceRecord {
+        ResourceRecord {
+            name: name.to_owned(),
+            r_type,
+            class: class::Class::IN,
+            cache_flush: false,
+            ttl,
+            rd_length: r_data.encode().len() as u16,
+            r_data,
+        }
+    }
+
+    /// the TYPE code on the wire, `r_type` stands in for unknown codes
+    pub fn type_code(&self) -> u16 {
+        match &self.r_data {
+            RData::Unknown { r_type, .. } => *r_type,
+            _ => self.r_type.into(),
+        }
+    }
+
+    /// append ResourceRecord to BytesMut, names are not compressed
+    pub fn append_to<'a>(&self, m: &'a mut BytesMut) -> &'a mut BytesMut {
+        let r_data = self.r_data.encode();
+        write_name(m, &self.name);
+        m.put_u16(self.type_code());
+        m.put_u16(Into::<u16>::into(self.class) | (self.cache_flush as u16) << 15);
+        m.put_u32(self.ttl);
+        m.put_u16(r_data.len() as u16);
+        m.put(r_data);
+        m
+    }
+
+    pub fn parse(raw: &[u8], base_offset: usize) -> io::Result<(ResourceRecord, usize)> {
+        let (name, size) = read_name(raw, base_offset)?;
         let offset = base_offset + size;
-        let r_type = types::Type::from(u8_merge!(raw[offset], raw[offset + 1]));
-        (
+        if offset + 10 > raw.len() {
+            return Err(invalid("record is truncated"));
+        }
+        let raw_type = u8_merge!(raw[offset], raw[offset + 1]);
+        let r_type = types::Type::from(raw_type);
+        let class = u8_merge!(raw[offset + 2], raw[offset + 3]);
+        let rd_length = u8_merge!(raw[offset + 8], raw[offset + 9]);
+        if offset + 10 + rd_length as usize > raw.len() {
+            return Err(invalid("record data is truncated"));
+        }
+        let r_data = RData::decode(raw_type, raw, offset + 10, rd_length as usize)?;
+        Ok((
             ResourceRecord {
                 name,
                 r_type,
-                class: class::Class::from(u8_merge!(raw[offset + 2], raw[offset + 3])),
-                ttl: 0,
-                rd_length: u8_merge!(raw[offset + 8], raw[offset + 9]),
-                r_data: RData::decode(r_type, raw, offset + 10),
+                class: class::Class::from(class & 0x7fff),
+                cache_flush: class & 0x8000 != 0,
+                ttl: parse_u32(&raw[(offset + 4)..(offset + 8)], true),
+                rd_length,
+                r_data,
             },
-            size + 4,
-        )
+            size + 10 + rd_length as usize,
+        ))
+    }
+}
+
+/// `rd_length` follows from `r_data` and is not compared
+impl PartialEq for ResourceRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.r_type == other.r_type
+            && self.class == other.class
+            && self.cache_flush == other.cache_flush
+            && self.ttl == other.ttl
+            && self.r_data == other.r_data
     }
 }
 
 // TODO: more RDATA types
 #[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
 pub enum RData {
     A(Ipv4Addr),
     AAAA(Ipv6Addr),
-    Unknown,
+    NS(String),
+    CNAME(String),
+    SOA(SOA),
+    PTR(String),
+    /// preference, exchange
+    MX(u16, String),
+    TXT(Vec<String>),
+    /// priority, weight, port, target
+    SRV(u16, u16, u16, String),
+    TSIG(TSIG),
+    /// RDATA of a type without a decoder kept as is (RFC 3597), also
+    /// the empty RDATA of UPDATE messages
+    Unknown {
+        r_type: u16,
+        data: Vec<u8>,
+    },
+}
+
+/// RDATA of SOA records (RFC 1035 3.3.13)
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct SOA {
+    /// name server that was the original or primary source of data for this zone
+    pub mname: String,
+    /// mailbox of the person responsible for this zone
+    pub rname: String,
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    /// TTL of negative responses (RFC 2308)
+    pub minimum: u32,
+}
+
+/// RDATA of TSIG records (RFC 8945 4.2)
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct TSIG {
+    /// name of the MAC algorithm, e.g. `hmac-sha256.`
+    pub algorithm: String,
+    /// seconds since unix epoch, 48 bits on the wire
+    pub time_signed: u64,
+    /// seconds of error permitted in `time_signed`
+    pub fudge: u16,
+    pub mac: Vec<u8>,
+    /// id of the message before any forwarding
+    pub original_id: u16,
+    /// extended rcode covering TSIG processing
+    pub error: u16,
+    pub other: Vec<u8>,
 }
 
 impl RData {
@@ -410,126 +622,307 @@ impl RData {
                     buf.put_u8(*octet);
                 }
             }
-            _ => {}
+            RData::NS(name) | RData::CNAME(name) | RData::PTR(name) => {
+                write_name(&mut buf, name);
+            }
+            RData::SOA(soa) => {
+                write_name(&mut buf, &soa.mname);
+                write_name(&mut buf, &soa.rname);
+                buf.put_u32(soa.serial);
+                buf.put_u32(soa.refresh);
+                buf.put_u32(soa.retry);
+                buf.put_u32(soa.expire);
+                buf.put_u32(soa.minimum);
+            }
+            RData::MX(preference, exchange) => {
+                buf.put_u16(*preference);
+                write_name(&mut buf, exchange);
+            }
+            RData::TXT(strings) => {
+                // longer strings are split, as zone file parsers do
+                for string in strings.iter() {
+                    if string.is_empty() {
+                        buf.put_u8(0);
+                    }
+                    for chunk in string.as_bytes().chunks(MAX_STRING_LEN) {
+                        buf.put_u8(chunk.len() as u8);
+                        buf.put(chunk);
+                    }
+                }
+            }
+            RData::SRV(priority, weight, port, target) => {
+                buf.put_u16(*priority);
+                buf.put_u16(*weight);
+                buf.put_u16(*port);
+                write_name(&mut buf, target);
+            }
+            RData::TSIG(tsig) => {
+                write_name(&mut buf, &tsig.algorithm);
+                buf.put_u16((tsig.time_signed >> 32) as u16);
+                buf.put_u32(tsig.time_signed as u32);
+                buf.put_u16(tsig.fudge);
+                buf.put_u16(tsig.mac.len() as u16);
+                buf.put(&tsig.mac[..]);
+                buf.put_u16(tsig.original_id);
+                buf.put_u16(tsig.error);
+                buf.put_u16(tsig.other.len() as u16);
+                buf.put(&tsig.other[..]);
+            }
+            RData::Unknown { data, .. } => buf.put(&data[..]),
         };
 
         buf
     }
 
-    fn decode(r_type: types::Type, raw: &[u8], offset: usize) -> RData {
-        match r_type {
+    /// `raw_type` is the TYPE code of the record
+    fn decode(raw_type: u16, raw: &[u8], offset: usize, length: usize) -> io::Result<RData> {
+        // names in RDATA may only point backwards, never past its end
+        let raw = &raw[..(offset + length)];
+        let end = offset + length;
+        let unknown = |data: &[u8]| RData::Unknown {
+            r_type: raw_type,
+            data: data.to_vec(),
+        };
+        // Type::from falls back to A for unknown codes, never decode those
+        let r_type = types::Type::from(raw_type);
+        if Into::<u16>::into(r_type) != raw_type {
+            return Ok(unknown(&raw[offset..end]));
+        }
+        let fits = |size: usize| {
+            if offset + size == end {
+                Ok(())
+            } else {
+                Err(invalid("record data has a wrong length"))
+            }
+        };
+        // empty RDATA appears in UPDATE messages
+        if length == 0 {
+            return Ok(unknown(&[]));
+        }
+
+        #[allow(deprecated)]
+        Ok(match r_type {
+            types::Type::A if length != 4 => unknown(&raw[offset..end]),
+            types::Type::AAAA if length != 16 => unknown(&raw[offset..end]),
             types::Type::A => RData::A(Ipv4Addr::new(
                 raw[offset],
                 raw[offset + 1],
                 raw[offset + 2],
                 raw[offset + 3],
             )),
-            types::Type::AAAA => RData::AAAA(Ipv6Addr::new(
-                u8_merge!(raw[offset], raw[offset + 1]),
-                u8_merge!(raw[offset + 2], raw[offset + 3]),
-                u8_merge!(raw[offset + 4], raw[offset + 5]),
-                u8_merge!(raw[offset + 6], raw[offset + 7]),
-                u8_merge!(raw[offset + 8], raw[offset + 9]),
-                u8_merge!(raw[offset + 10], raw[offset + 11]),
-                u8_merge!(raw[offset + 12], raw[offset + 13]),
-                u8_merge!(raw[offset + 14], raw[offset + 15]),
-            )),
-            _ => RData::Unknown,
+            types::Type::AAAA => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&raw[offset..end]);
+                RData::AAAA(Ipv6Addr::from(octets))
+            }
+            types::Type::NS | types::Type::CNAME | types::Type::PTR => {
+                let (name, size) = read_name(raw, offset)?;
+                fits(size)?;
+                match r_type {
+                    types::Type::NS => RData::NS(name),
+                    types::Type::CNAME => RData::CNAME(name),
+                    _ => RData::PTR(name),
+                }
+            }
+            types::Type::SOA => {
+                let (mname, size) = read_name(raw, offset)?;
+                let (rname, rsize) = read_name(raw, offset + size)?;
+                fits(size + rsize + 20)?;
+                let offset = offset + size + rsize;
+                let u32_at =
+                    |i: usize| parse_u32(&raw[(offset + i * 4)..(offset + i * 4 + 4)], true);
+                RData::SOA(SOA {
+                    mname,
+                    rname,
+                    serial: u32_at(0),
+                    refresh: u32_at(1),
+                    retry: u32_at(2),
+                    expire: u32_at(3),
+                    minimum: u32_at(4),
+                })
+            }
+            types::Type::MX if length < 3 => return Err(invalid("MX record is truncated")),
+            types::Type::MX => {
+                let (exchange, size) = read_name(raw, offset + 2)?;
+                fits(size + 2)?;
+                RData::MX(u8_merge!(raw[offset], raw[offset + 1]), exchange)
+            }
+            types::Type::TXT => {
+                let mut strings = Vec::new();
+                let mut i = offset;
+                while i < end {
+                    let len = raw[i] as usize;
+                    let string = raw
+                        .get((i + 1)..(i + 1 + len))
+                        .ok_or_else(|| invalid("TXT record is truncated"))?;
+                    // binary strings are legal, only the UTF-8 parts are kept
+                    strings.push(String::from_utf8_lossy(string).into_owned());
+                    i += len + 1;
+                }
+                RData::TXT(strings)
+            }
+            types::Type::SRV if length < 7 => return Err(invalid("SRV record is truncated")),
+            types::Type::SRV => {
+                let (target, size) = read_name(raw, offset + 6)?;
+                fits(size + 6)?;
+                RData::SRV(
+                    u8_merge!(raw[offset], raw[offset + 1]),
+                    u8_merge!(raw[offset + 2], raw[offset + 3]),
+                    u8_merge!(raw[offset + 4], raw[offset + 5]),
+                    target,
+                )
+            }
+            types::Type::TSIG => {
+                RData::TSIG(decode_tsig(raw, offset).ok_or_else(|| invalid("bad TSIG record"))?)
+            }
+            // the other types of RFC 1035 may compress their names, which
+            // are expanded as the pointers are only valid in this message
+            // (RFC 3597 4)
+            types::Type::MD
+            | types::Type::MF
+            | types::Type::MB
+            | types::Type::MG
+            | types::Type::MR => {
+                let (name, size) = read_name(raw, offset)?;
+                fits(size)?;
+                let mut data = BytesMut::new();
+                write_name(&mut data, &name);
+                unknown(&data)
+            }
+            types::Type::MINFO => {
+                let (rmailbx, size) = read_name(raw, offset)?;
+                let (emailbx, esize) = read_name(raw, offset + size)?;
+                fits(size + esize)?;
+                let mut data = BytesMut::new();
+                write_name(&mut data, &rmailbx);
+                write_name(&mut data, &emailbx);
+                unknown(&data)
+            }
+            _ => unknown(&raw[offset..end]),
+        })
+    }
+}
+
+fn decode_tsig(raw: &[u8], offset: usize) -> Option<TSIG> {
+    let (algorithm, size) = read_name(raw, offset).ok()?;
+    let fixed = raw.get((offset + size)..(offset + size + 10))?;
+    let mac_size = u8_merge!(fixed[8], fixed[9]) as usize;
+    let mac_end = offset + size + 10 + mac_size;
+    let mac = raw.get((offset + size + 10)..mac_end)?.to_vec();
+    let tail = raw.get(mac_end..(mac_end + 6))?;
+    let other_len = u8_merge!(tail[4], tail[5]) as usize;
+    let other = raw.get((mac_end + 6)..(mac_end + 6 + other_len))?.to_vec();
+    Some(TSIG {
+        algorithm,
+        time_signed: (u8_merge!(fixed[0], fixed[1]) as u64) << 32
+            | parse_u32(&fixed[2..6], true) as u64,
+        fudge: u8_merge!(fixed[6], fixed[7]),
+        mac,
+        original_id: u8_merge!(tail[0], tail[1]),
+        error: u8_merge!(tail[2], tail[3]),
+        other,
+    })
+}
+
+/// check that `name` can be written on the wire,
+/// with labels and the whole name short enough
+pub fn check_name(name: &str) -> io::Result<()> {
+    let mut len = 1;
+    for part in name.split('.').filter(|part| !part.is_empty()) {
+        if part.len() > MAX_LABEL_LEN {
+            return Err(invalid(&format!(
+                "label {} is longer than {} octets",
+                part, MAX_LABEL_LEN
+            )));
         }
+        len += part.len() + 1;
+    }
+    if len > MAX_WIRE_NAME_LEN {
+        return Err(invalid(&format!("name {} is too long", name)));
     }
+    Ok(())
 }
 
-fn read_name_part(raw: &[u8], offset: usize) -> (String, usize) {
-    let len = raw[offset] as usize;
-    (
-        std::str::from_utf8(&raw[(offset + 1)..(offset + len + 1)])
-            .unwrap()
-            .to_owned(),
-        len,
-    )
+/// write an uncompressed domain name, a trailing '.' is optional.
+/// Names from text must have passed `check_name`, a longer label
+/// would be taken for a pointer.
+pub(crate) fn write_name(m: &mut BytesMut, name: &str) {
+    for part in name.split('.').filter(|part| !part.is_empty()) {
+        m.put_u8(part.len() as u8);
+        m.put(part.as_bytes());
+    }
+    m.put_u8(0);
 }
 
-fn read_name(raw: &[u8], base_offset: usize) -> (String, usize) {
+/// read a possibly compressed name, returning it with a trailing dot
+/// and the octets it takes at `base_offset`
+fn read_name(raw: &[u8], base_offset: usize) -> io::Result<(String, usize)> {
     let mut name = String::new();
     let mut offset = base_offset;
-    let mut len = raw[offset];
-    while len != 0 {
-        let is_ptr = len & 0b11000000 > 0;
-        let (part, size) = if is_ptr {
-            let offset = (len as usize & 0b00111111 << 8) + raw[offset + 1] as usize;
-            read_name(raw, offset)
-        } else {
-            read_name_part(raw, offset)
-        };
-        name.push_str(&part);
-
-        if is_ptr {
-            offset += 1;
-            break;
-        } else {
-            offset += size + 1;
-            len = raw[offset];
-            name.push('.');
+    // octets taken at base_offset, known once the first pointer is met
+    let mut size = None;
+    loop {
+        let len = *raw
+            .get(offset)
+            .ok_or_else(|| invalid("name is truncated"))? as usize;
+        match len & 0b11000000 {
+            0 if len == 0 => break,
+            0 => {
+                let part = raw
+                    .get((offset + 1)..(offset + len + 1))
+                    .ok_or_else(|| invalid("label is truncated"))?;
+                let part = std::str::from_utf8(part).map_err(|_| invalid("label is not UTF-8"))?;
+                if part.contains('.') {
+                    return Err(invalid("label contains a dot"));
+                }
+                if name.len() + len + 1 > MAX_NAME_LEN {
+                    return Err(invalid("name is too long"));
+                }
+                name.push_str(part);
+                name.push('.');
+                offset += len + 1;
+            }
+            0b11000000 => {
+                let low = *raw
+                    .get(offset + 1)
+                    .ok_or_else(|| invalid("pointer is truncated"))?;
+                let target = ((len & 0b00111111) << 8) | low as usize;
+                // pointing backwards only guarantees termination
+                if target >= offset {
+                    return Err(invalid("pointer does not point backwards"));
+                }
+                size.get_or_insert_with(|| offset + 2 - base_offset);
+                offset = target;
+            }
+            _ => return Err(invalid("unsupported label type")),
         }
     }
-    offset += 1; // final '\0'
-    (name, offset - base_offset)
+    let size = size.unwrap_or_else(|| offset + 1 - base_offset);
+    Ok((name, size))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
 }
 
 #[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(PartialEq)]
 pub struct Message {
     /// sections
     pub header: Header,
     pub questions: Vec<Question>,
     pub answers: Vec<ResourceRecord>,
+    pub authorities: Vec<ResourceRecord>,
+    pub additionals: Vec<ResourceRecord>,
 
     /// control flags
     pub bypass_gfw: bool,
 }
 
+/// Panics on malformed messages, use `Message::parse` for untrusted input
 impl From<&[u8]> for Message {
     fn from(buffer: &[u8]) -> Self {
-        // parse header
-        let header = Header::from(buffer);
-
-        match header.flag().rcode {
-            FlagRCode::NoError => {
-                // parse question
-                let mut offset: usize = 12;
-                let mut questions: Vec<Question> = Vec::new();
-                for _ in 0..header.qd_count {
-                    let (question, size) = Question::parse(&buffer, offset);
-                    questions.push(question);
-                    offset += size;
-                }
-
-                // parse resource record
-                let mut answers: Vec<ResourceRecord> = Vec::new();
-                for _ in 0..header.an_count {
-                    let (answer, size) = ResourceRecord::parse(buffer, offset);
-                    answers.push(answer);
-                    offset += size;
-                }
-
-                Message {
-                    header,
-                    questions,
-                    answers,
-
-                    bypass_gfw: false,
-                }
-            }
-            _ => {
-                // on error
-                Message {
-                    header,
-                    questions: Vec::new(),
-                    answers: Vec::new(),
-
-                    bypass_gfw: false,
-                }
-            }
-        }
+        Message::parse(buffer).expect("malformed DNS message")
     }
 }
 
@@ -553,30 +946,105 @@ impl Into<BytesMut> for Message {
             q.append_to(&mut message);
         }
 
-        // TODO: append a to message
-        // for a in self.answers.iter() {}
+        // append answer, authority and additional
+        for rr in self
+            .answers
+            .iter()
+            .chain(self.authorities.iter())
+            .chain(self.additionals.iter())
+        {
+            rr.append_to(&mut message);
+        }
 
         message
     }
 }
 
 impl Message {
+    pub fn parse(buffer: &[u8]) -> io::Result<Message> {
+        if buffer.len() < 12 {
+            return Err(invalid("message too short"));
+        }
+        // parse header
+        let header = Header::from(buffer);
+
+        // parse question
+        let mut offset: usize = 12;
+        let mut questions: Vec<Question> = Vec::new();
+        for _ in 0..header.qd_count {
+            let (question, size) = Question::parse(buffer, offset)?;
+            questions.push(question);
+            offset += size;
+        }
+
+        // parse resource record
+        let mut parse_section = |count: u16| -> io::Result<Vec<ResourceRecord>> {
+            let mut records: Vec<ResourceRecord> = Vec::new();
+            for _ in 0..count {
+                let (record, size) = ResourceRecord::parse(buffer, offset)?;
+                records.push(record);
+                offset += size;
+            }
+            Ok(records)
+        };
+        let answers = parse_section(header.an_count)?;
+        let authorities = parse_section(header.ns_count)?;
+        let additionals = parse_section(header.ar_count)?;
+
+        Ok(Message {
+            header,
+            questions,
+            answers,
+            authorities,
+            additionals,
+
+            bypass_gfw: false,
+        })
+    }
+
     pub fn new(header: Header, bypass_gfw: bool) -> Message {
         let mut message = Message {
             header,
             questions: Vec::new(),
             answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
             bypass_gfw,
         };
         message.header.qd_count = if bypass_gfw { 1 } else { 0 };
         message
     }
 
+    /// build an empty response to this query with `rcode`,
+    /// the id, opcode, RD bit and questions are copied from the query
+    pub fn reply(&self, rcode: FlagRCode) -> Message {
+        let mut message = Message::new(self.header.reply(rcode), false);
+        for q in self.questions.iter() {
+            message.add_question(q.clone());
+        }
+        message
+    }
+
     pub fn add_question(&mut self, q: Question) {
         self.questions.push(q);
         self.header.qd_count += 1;
     }
 
+    pub fn add_answer(&mut self, a: ResourceRecord) {
+        self.answers.push(a);
+        self.header.an_count += 1;
+    }
+
+    pub fn add_authority(&mut self, ns: ResourceRecord) {
+        self.authorities.push(ns);
+        self.header.ns_count += 1;
+    }
+
+    pub fn add_additional(&mut self, ar: ResourceRecord) {
+        self.additionals.push(ar);
+        self.header.ar_count += 1;
+    }
+
     pub fn bypass_gfw(&mut self, bypass_gfw: bool) {
         if self.bypass_gfw && !bypass_gfw {
             self.header.qd_count -= 1;
@@ -586,3 +1054,202 @@ impl Message {
         self.bypass_gfw = bypass_gfw;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::view::MessageRef;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use types::{QType, Type};
+
+    fn name() -> impl Strategy<Value = String> {
+        vec("[a-zA-Z0-9_-]{1,20}", 0..5).prop_map(|labels| {
+            labels
+                .iter()
+                .map(|label| format!("{}.", label))
+                .collect::<String>()
+        })
+    }
+
+    fn r_data() -> impl Strategy<Value = (Type, RData)> {
+        prop_oneof![
+            any::<u32>().prop_map(|a| (Type::A, RData::A(a.into()))),
+            any::<u128>().prop_map(|a| (Type::AAAA, RData::AAAA(a.into()))),
+            name().prop_map(|n| (Type::NS, RData::NS(n))),
+            name().prop_map(|n| (Type::CNAME, RData::CNAME(n))),
+            name().prop_map(|n| (Type::PTR, RData::PTR(n))),
+            (any::<u16>(), name()).prop_map(|(p, n)| (Type::MX, RData::MX(p, n))),
+            vec("[ -~]{0,40}", 1..4).prop_map(|t| (Type::TXT, RData::TXT(t))),
+            (any::<u16>(), any::<u16>(), any::<u16>(), name())
+                .prop_map(|(p, w, port, n)| (Type::SRV, RData::SRV(p, w, port, n))),
+            (name(), name(), any::<[u32; 5]>()).prop_map(|(mname, rname, v)| (
+                Type::SOA,
+                RData::SOA(SOA {
+                    mname,
+                    rname,
+                    serial: v[0],
+                    refresh: v[1],
+                    retry: v[2],
+                    expire: v[3],
+                    minimum: v[4],
+                })
+            )),
+            // kept as received, a type known but without a decoder or not known
+            vec(any::<u8>(), 1..20)
+                .prop_map(|data| (Type::HINFO, RData::Unknown { r_type: 13, data })),
+            vec(any::<u8>(), 0..20).prop_map(|data| (
+                Type::from(65280),
+                RData::Unknown {
+                    r_type: 65280,
+                    data
+                }
+            )),
+        ]
+    }
+
+    fn record() -> impl Strategy<Value = ResourceRecord> {
+        (name(), r_data(), any::<u32>(), any::<bool>()).prop_map(
+            |(name, (r_type, r_data), ttl, cache_flush)| {
+                let mut rr = ResourceRecord::new(&name, r_type, ttl, r_data);
+                rr.cache_flush = cache_flush;
+                rr
+            },
+        )
+    }
+
+    fn question() -> impl Strategy<Value = Question> {
+        let q_type = prop_oneof![
+            Just(QType::Type(Type::A)),
+            Just(QType::Type(Type::AAAA)),
+            Just(QType::Type(Type::SRV)),
+            Just(QType::AXFR),
+            Just(QType::ANY),
+        ];
+        (name(), q_type, any::<bool>()).prop_map(|(name, q_type, unicast)| {
+            let mut question = Question::new(&name, q_type);
+            question.set_unicast_response(unicast);
+            question
+        })
+    }
+
+    /// messages with arbitrary header flags and every section filled
+    fn message() -> impl Strategy<Value = Message> {
+        (
+            any::<u16>(),
+            any::<u16>(),
+            vec(question(), 0..3),
+            vec(record(), 0..4),
+            vec(record(), 0..3),
+            vec(record(), 0..3),
+        )
+            .prop_map(|(id, flag, qd, an, ns, ar)| {
+                let mut message = Message::new(Header::new(id, flag, 0, 0, 0, 0), false);
+                qd.into_iter().for_each(|q| message.add_question(q));
+                an.into_iter().for_each(|rr| message.add_answer(rr));
+                ns.into_iter().for_each(|rr| message.add_authority(rr));
+                ar.into_iter().for_each(|rr| message.add_additional(rr));
+                message
+            })
+    }
+
+    fn encode(message: Message) -> Vec<u8> {
+        let raw: BytesMut = message.into();
+        raw.to_vec()
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(message in message()) {
+            let raw = encode(message);
+            let parsed = Message::parse(&raw).unwrap();
+            // records without a decoder keep their type code and RDATA
+            let view = MessageRef::parse(&raw).unwrap();
+            let records = parsed.answers.iter().chain(&parsed.authorities).chain(&parsed.additionals);
+            for (rr, rr_ref) in records.zip(view.answers().chain(view.authorities()).chain(view.additionals())) {
+                prop_assert_eq!(rr.type_code(), rr_ref.type_code());
+                if let RData::Unknown { data, .. } = &rr.r_data {
+                    prop_assert_eq!(&data[..], rr_ref.r_data());
+                }
+            }
+            prop_assert_eq!(encode(parsed), raw);
+        }
+
+        #[test]
+        fn test_mutated(message in message(), flips in vec((any::<prop::sample::Index>(), any::<u8>()), 1..8)) {
+            let mut raw = encode(message);
+            for (i, byte) in flips {
+                let i = i.index(raw.len());
+                raw[i] ^= byte;
+            }
+            // never panics, and whatever is accepted survives a round trip
+            if let Ok(parsed) = Message::parse(&raw) {
+                let encoded = encode(parsed);
+                let reparsed = Message::parse(&encoded).unwrap();
+                prop_assert_eq!(encode(reparsed), encoded);
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_names() {
+        let header = [0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0];
+        let question = |name: &[u8]| {
+            let mut raw = header.to_vec();
+            raw.extend_from_slice(name);
+            raw.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+            raw
+        };
+        assert!(Message::parse(&question(&[0x01, b'a', 0x00])).is_ok());
+        // pointer to itself and forwards
+        assert!(Message::parse(&question(&[0xc0, 0x0c])).is_err());
+        assert!(Message::parse(&question(&[0xc0, 0x20])).is_err());
+        // reserved label types
+        assert!(Message::parse(&question(&[0x40, 0x00])).is_err());
+        // not UTF-8, or containing a dot
+        assert!(Message::parse(&question(&[0x01, 0xff, 0x00])).is_err());
+        assert!(Message::parse(&question(&[0x03, b'a', b'.', b'b', 0x00])).is_err());
+        // truncated
+        assert!(Message::parse(&question(&[0x05, b'a'])[..15]).is_err());
+        assert!(Message::parse(&header[..11]).is_err());
+
+        // found by fuzzing: chained pointers into the header
+        let raw = [14, 0, 0, 0, 0xc0, 0, 0, 0xff, 0, 0, 0xff, 0xc0, 0xc0, 0x04];
+        assert!(Message::parse(&raw).is_err());
+        // and RDATA of an unknown type running past the end
+        let mut raw = [0u8; 29];
+        raw[7] = 1;
+        raw[13..23].copy_from_slice(&[0xfa, 0x8e, 0x08, 0, 0, 0x80, 0x3b, 0, 0, 0x08]);
+        assert!(Message::parse(&raw).is_err());
+    }
+
+    #[test]
+    fn test_long_strings() {
+        let long = "a".repeat(300);
+        let r_data = RData::TXT(vec![long.clone(), String::new()]);
+        let raw = r_data.encode();
+        assert_eq!(raw.len(), 1 + 255 + 1 + 45 + 1);
+        assert!(
+            RData::decode(Type::TXT.into(), &raw, 0, raw.len()).unwrap()
+                == RData::TXT(vec![
+                    long[..255].to_owned(),
+                    long[255..].to_owned(),
+                    String::new()
+                ])
+        );
+
+        assert!(check_name(&format!("{}.lan.", "a".repeat(63))).is_ok());
+        assert!(check_name(&format!("{}.lan.", "a".repeat(64))).is_err());
+        assert!(check_name(&"a.".repeat(127)).is_ok());
+        assert!(check_name(&"a.".repeat(128)).is_err());
+    }
+
+    #[test]
+    fn test_binary_txt() {
+        let raw = [3, b'k', 0xff, b'=', 0];
+        assert!(
+            RData::decode(Type::TXT.into(), &raw, 0, raw.len()).unwrap()
+                == RData::TXT(vec!["k\u{fffd}=".to_owned(), String::new()])
+        );
+    }
+}
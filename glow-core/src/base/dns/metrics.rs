@@ -0,0 +1,213 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Upper bounds of latency buckets in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Response codes counted separately, the last one collects the rest
+const RCODES: [&str; 7] = [
+    "NoError",
+    "FormatError",
+    "ServerFailure",
+    "NameError",
+    "NotImplemented",
+    "Refused",
+    "Reserved",
+];
+
+/// Counters and histograms of the DNS server
+#[derive(Default)]
+pub struct Metrics {
+    responses: [AtomicU64; RCODES.len()],
+    blocked: AtomicU64,
+    local: AtomicU64,
+    upstream_errors: AtomicU64,
+    dropped: AtomicU64,
+
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_sum_us: AtomicU64,
+    latency_count: AtomicU64,
+}
+
+impl Metrics {
+    /// count a response with `rcode` answered in `latency`
+    pub fn observe(&self, rcode: &str, latency: Duration) {
+        let i = RCODES
+            .iter()
+            .position(|r| *r == rcode)
+            .unwrap_or(RCODES.len() - 1);
+        self.responses[i].fetch_add(1, Ordering::Relaxed);
+
+        let seconds = latency.as_secs_f64();
+        for (bucket, le) in self.latency_buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+            if seconds <= *le {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.latency_sum_us
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn blocked(&self) {
+        self.blocked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn local(&self) {
+        self.local.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn upstream_error(&self) {
+        self.upstream_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// metrics in Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let load = |c: &AtomicU64| c.load(Ordering::Relaxed);
+        let mut out = String::new();
+
+        out.push_str("# HELP glow_dns_responses_total DNS responses sent by rcode.\n");
+        out.push_str("# TYPE glow_dns_responses_total counter\n");
+        for (rcode, count) in RCODES.iter().zip(self.responses.iter()) {
+            let _ = writeln!(
+                out,
+                "glow_dns_responses_total{{rcode=\"{}\"}} {}",
+                rcode,
+                load(count)
+            );
+        }
+
+        for (name, help, counter) in [
+            (
+                "blocked",
+                "Queries answered by the blocklist.",
+                &self.blocked,
+            ),
+            ("local", "Queries answered without upstream.", &self.local),
+            (
+                "upstream_errors",
+                "Failed upstream queries.",
+                &self.upstream_errors,
+            ),
+            (
+                "dropped",
+                "Queries dropped while all workers were busy.",
+                &self.dropped,
+            ),
+        ]
+        .iter()
+        {
+            let _ = writeln!(out, "# HELP glow_dns_{}_total {}", name, help);
+            let _ = writeln!(out, "# TYPE glow_dns_{}_total counter", name);
+            let _ = writeln!(out, "glow_dns_{}_total {}", name, load(counter));
+        }
+
+        out.push_str("# HELP glow_dns_latency_seconds Time to answer a query.\n");
+        out.push_str("# TYPE glow_dns_latency_seconds histogram\n");
+        for (le, bucket) in LATENCY_BUCKETS.iter().zip(self.latency_buckets.iter()) {
+            let _ = writeln!(
+                out,
+                "glow_dns_latency_seconds_bucket{{le=\"{}\"}} {}",
+                le,
+                load(bucket)
+            );
+        }
+        let count = load(&self.latency_count);
+        let _ = writeln!(
+            out,
+            "glow_dns_latency_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(
+            out,
+            "glow_dns_latency_seconds_sum {}",
+            load(&self.latency_sum_us) as f64 / 1e6
+        );
+        let _ = writeln!(out, "glow_dns_latency_seconds_count {}", count);
+        out
+    }
+
+    /// serve `GET /metrics` on `addr` in a background thread
+    pub fn serve(self: Arc<Self>, addr: &str) -> io::Result<thread::JoinHandle<()>> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = self.respond(stream) {
+                    eprintln!("failed to serve metrics: {}", e);
+                }
+            }
+        }))
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+
+        let mut parts = request.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => ("200 OK", self.render()),
+            _ => ("404 Not Found", String::new()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.observe("NoError", Duration::from_millis(3));
+        metrics.observe("NameError", Duration::from_millis(300));
+        metrics.blocked();
+
+        let text = metrics.render();
+        assert!(text.contains("glow_dns_responses_total{rcode=\"NoError\"} 1\n"));
+        assert!(text.contains("glow_dns_responses_total{rcode=\"NameError\"} 1\n"));
+        assert!(text.contains("glow_dns_blocked_total 1\n"));
+        assert!(text.contains("glow_dns_latency_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(text.contains("glow_dns_latency_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("glow_dns_latency_seconds_bucket{le=\"0.5\"} 2\n"));
+        assert!(text.contains("glow_dns_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("glow_dns_latency_seconds_sum 0.303\n"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let metrics = Arc::new(Metrics::default());
+        metrics.local();
+        metrics.clone().serve(&addr).unwrap();
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(&metrics.render()));
+    }
+}
@@ -1,6 +1,24 @@
 pub mod message;
+pub mod view;
 
 pub mod class;
 pub mod types;
 
+pub mod hosts;
+pub mod resolv;
+
+pub mod addrsel;
 pub mod sync;
+
+pub mod blocklist;
+pub mod dns64;
+pub mod mdns;
+pub mod metrics;
+pub mod querylog;
+pub mod server;
+pub mod transfer;
+pub mod tsig;
+pub mod update;
+pub mod zone;
+
+pub mod utils;
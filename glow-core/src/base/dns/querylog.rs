@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One answered query, written as a single JSON line
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Serialize)]
+pub struct QueryLogEntry {
+    /// milliseconds since unix epoch
+    pub timestamp: u128,
+    pub client: SocketAddr,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub q_type: String,
+    /// upstream server, or the local source which answered the query
+    pub upstream: String,
+    pub rcode: String,
+    pub latency_us: u64,
+    pub cache_hit: bool,
+    /// GFW bypass strategy applied to the upstream query
+    pub bypass: String,
+}
+
+impl QueryLogEntry {
+    pub fn now() -> u128 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default()
+    }
+}
+
+/// File rotated to `<path>.1`, `<path>.2`, ... once it grows over `max_size`
+struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_size: u64, keep: usize) -> io::Result<RotatingFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_owned(),
+            max_size,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                if self.rotated(n).exists() {
+                    fs::rename(self.rotated(n), self.rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+enum Sink {
+    Stdout,
+    File(Mutex<RotatingFile>),
+}
+
+/// Structured query log in JSON lines
+pub struct QueryLog {
+    sink: Sink,
+}
+
+impl QueryLog {
+    pub fn stdout() -> QueryLog {
+        QueryLog { sink: Sink::Stdout }
+    }
+
+    /// log to `path`, keeping at most `keep` rotated files of `max_size` bytes
+    pub fn file<P: AsRef<Path>>(path: P, max_size: u64, keep: usize) -> io::Result<QueryLog> {
+        let file = RotatingFile::open(path.as_ref(), max_size, keep)?;
+        Ok(QueryLog {
+            sink: Sink::File(Mutex::new(file)),
+        })
+    }
+
+    pub fn log(&self, entry: &QueryLogEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        match &self.sink {
+            Sink::Stdout => io::stdout().lock().write_all(&line),
+            Sink::File(file) => file.lock().unwrap().write_line(&line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> QueryLogEntry {
+        QueryLogEntry {
+            timestamp: 1600000000000,
+            client: "127.0.0.1:5353".parse().unwrap(),
+            name: name.to_owned(),
+            q_type: "AAAA".to_owned(),
+            upstream: "1.0.0.1:53".to_owned(),
+            rcode: "NoError".to_owned(),
+            latency_us: 1200,
+            cache_hit: false,
+            bypass: "none".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_entry_json() {
+        assert_eq!(
+            serde_json::to_string(&entry("glow.mmf.moe.")).unwrap(),
+            r#"{"timestamp":1600000000000,"client":"127.0.0.1:5353","name":"glow.mmf.moe.","type":"AAAA","upstream":"1.0.0.1:53","rcode":"NoError","latency_us":1200,"cache_hit":false,"bypass":"none"}"#
+        );
+    }
+
+    #[test]
+    fn test_rotate() {
+        let dir = std::env::temp_dir().join(format!("glow-querylog-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("query.log");
+
+        let line_len = serde_json::to_vec(&entry("a.")).unwrap().len() as u64 + 1;
+        let log = QueryLog::file(&path, line_len * 2, 2).unwrap();
+        for name in ["a.", "b.", "c.", "d.", "e.", "f.", "g."].iter() {
+            log.log(&entry(name)).unwrap();
+        }
+
+        let read = |p: &Path| fs::read_to_string(p).unwrap();
+        assert!(read(&path).contains("\"g.\""));
+        assert!(read(&dir.join("query.log.1")).contains("\"f.\""));
+        assert!(read(&dir.join("query.log.2")).contains("\"c.\""));
+        assert!(!dir.join("query.log.3").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
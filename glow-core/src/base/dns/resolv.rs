@@ -0,0 +1,219 @@
+use glow_utils::fs::{read_file, write_file};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+
+pub const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// Maximum values accepted by glibc, see resolv.conf(5)
+const MAX_NDOTS: u8 = 15;
+const MAX_TIMEOUT: u32 = 30;
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Resolver configuration in the format of resolv.conf(5).
+/// Only `nameserver`, `search`, `domain` and `options ndots/timeout/attempts`
+/// are understood, other lines are ignored.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct ResolvConf {
+    /// name servers in the order they should be queried
+    pub nameservers: Vec<IpAddr>,
+
+    /// search list for host-name lookup
+    pub search: Vec<String>,
+
+    /// a name with at least `ndots` dots is tried as absolute first
+    pub ndots: u8,
+
+    /// seconds to wait for a response from a name server
+    pub timeout: u32,
+
+    /// number of times to query name servers before giving up
+    pub attempts: u32,
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        ResolvConf {
+            nameservers: Vec::new(),
+            search: Vec::new(),
+            ndots: 1,
+            timeout: 5,
+            attempts: 2,
+        }
+    }
+}
+
+impl ResolvConf {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ResolvConf> {
+        let content = read_file(path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(ResolvConf::parse(&content))
+    }
+
+    /// write the configuration to `path`, replacing its content
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        write_file(path, &self.to_string()).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    pub fn parse(content: &str) -> ResolvConf {
+        let mut conf = ResolvConf::default();
+        for line in content.lines() {
+            let line = match line.find(['#', ';']) {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("nameserver") => {
+                    if let Some(Ok(addr)) = fields.next().map(IpAddr::from_str) {
+                        conf.nameservers.push(addr);
+                    }
+                }
+                // domain and search are mutually exclusive, the last one wins
+                Some("domain") => {
+                    conf.search = fields.next().map(str::to_owned).into_iter().collect();
+                }
+                Some("search") => {
+                    conf.search = fields.map(str::to_owned).collect();
+                }
+                Some("options") => {
+                    for option in fields {
+                        let mut kv = option.splitn(2, ':');
+                        match (kv.next(), kv.next().map(u32::from_str)) {
+                            (Some("ndots"), Some(Ok(n))) => {
+                                conf.ndots = n.min(MAX_NDOTS as u32) as u8
+                            }
+                            (Some("timeout"), Some(Ok(n))) => conf.timeout = n.min(MAX_TIMEOUT),
+                            (Some("attempts"), Some(Ok(n))) => conf.attempts = n.min(MAX_ATTEMPTS),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        conf
+    }
+
+    /// upstream addresses for `DNSClient::upstream`
+    pub fn upstreams(&self) -> Vec<SocketAddr> {
+        self.nameservers
+            .iter()
+            .map(|addr| SocketAddr::new(*addr, 53))
+            .collect()
+    }
+
+    /// candidate names to query for `name`, in order, applying the search list
+    pub fn search_names(&self, name: &str) -> Vec<String> {
+        // an absolute name is never expanded
+        if name.ends_with('.') {
+            return vec![name.trim_end_matches('.').to_owned()];
+        }
+
+        let expanded = self
+            .search
+            .iter()
+            .map(|domain| format!("{}.{}", name, domain.trim_end_matches('.')));
+        if name.matches('.').count() >= self.ndots as usize {
+            std::iter::once(name.to_owned()).chain(expanded).collect()
+        } else {
+            expanded.chain(std::iter::once(name.to_owned())).collect()
+        }
+    }
+}
+
+impl fmt::Display for ResolvConf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Generated by glow, do not edit")?;
+        for addr in self.nameservers.iter() {
+            writeln!(f, "nameserver {}", addr)?;
+        }
+        if !self.search.is_empty() {
+            writeln!(f, "search {}", self.search.join(" "))?;
+        }
+
+        let default = ResolvConf::default();
+        let mut options = Vec::new();
+        if self.ndots != default.ndots {
+            options.push(format!("ndots:{}", self.ndots));
+        }
+        if self.timeout != default.timeout {
+            options.push(format!("timeout:{}", self.timeout));
+        }
+        if self.attempts != default.attempts {
+            options.push(format!("attempts:{}", self.attempts));
+        }
+        if !options.is_empty() {
+            writeln!(f, "options {}", options.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESOLV_CONF: &str = "\
+# Generated by NetworkManager
+domain example.org
+search lan corp.example.com
+nameserver 192.168.1.1
+nameserver 2001:4860:4860::8888 ; google
+nameserver not-an-ip
+options rotate ndots:2 timeout:3 attempts:9
+";
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let conf = ResolvConf::parse(RESOLV_CONF);
+        assert_eq!(
+            conf.nameservers,
+            vec![
+                "192.168.1.1".parse::<IpAddr>().unwrap(),
+                "2001:4860:4860::8888".parse().unwrap()
+            ]
+        );
+        assert_eq!(conf.search, vec!["lan", "corp.example.com"]);
+        assert_eq!(conf.ndots, 2);
+        assert_eq!(conf.timeout, 3);
+        assert_eq!(conf.attempts, MAX_ATTEMPTS);
+        assert_eq!(conf.upstreams()[1].to_string(), "[2001:4860:4860::8888]:53");
+    }
+
+    #[test]
+    fn test_write_resolv_conf() {
+        let conf = ResolvConf::parse(RESOLV_CONF);
+        let written = conf.to_string();
+        assert_eq!(
+            written,
+            "# Generated by glow, do not edit\n\
+             nameserver 192.168.1.1\n\
+             nameserver 2001:4860:4860::8888\n\
+             search lan corp.example.com\n\
+             options ndots:2 timeout:3 attempts:5\n"
+        );
+        assert_eq!(ResolvConf::parse(&written), conf);
+    }
+
+    #[test]
+    fn test_search_names() {
+        let conf = ResolvConf::parse(RESOLV_CONF);
+        assert_eq!(
+            conf.search_names("printer"),
+            vec!["printer.lan", "printer.corp.example.com", "printer"]
+        );
+        assert_eq!(
+            conf.search_names("glow.mmf.moe"),
+            vec![
+                "glow.mmf.moe",
+                "glow.mmf.moe.lan",
+                "glow.mmf.moe.corp.example.com"
+            ]
+        );
+        assert_eq!(conf.search_names("printer.lan."), vec!["printer.lan"]);
+    }
+}
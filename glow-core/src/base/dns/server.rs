@@ -0,0 +1,578 @@
+use super::blocklist::{BlockAction, Blocklist};
+use super::dns64::Dns64;
+use super::message::{FlagOpCode, FlagRCode, Header, Message};
+use super::metrics::Metrics;
+use super::querylog::{QueryLog, QueryLogEntry};
+use super::sync::DNSClient;
+use super::types::{QType, Type};
+use super::view::MessageRef;
+use super::zone::Zone;
+use bytes::BytesMut;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often list files are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Threads answering queries
+const WORKERS: usize = 16;
+
+/// Queries waiting for a worker, more are dropped
+const QUEUE_LEN: usize = 256;
+
+/// Where the answer of a query came from
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub enum Source {
+    /// the name server which answered, empty if none did
+    Upstream(String),
+    Blocklist,
+    Zone,
+    /// AAAA synthesized from the upstream A answer
+    Dns64,
+}
+
+/// A forwarding DNS server.
+/// Queries are answered locally when possible and relayed to the
+/// upstream of `client` otherwise, by a fixed pool of worker threads.
+pub struct DNSServer {
+    listen: String,
+    client: DNSClient,
+
+    zones: Vec<Zone>,
+
+    blocklist: RwLock<Blocklist>,
+    block_action: BlockAction,
+
+    dns64: Option<Dns64>,
+
+    querylog: Option<QueryLog>,
+    metrics: Arc<Metrics>,
+}
+
+impl DNSServer {
+    pub fn new(listen: &str, client: DNSClient) -> DNSServer {
+        DNSServer {
+            listen: listen.to_owned(),
+            client,
+            zones: Vec::new(),
+            blocklist: RwLock::new(Blocklist::default()),
+            block_action: BlockAction::default(),
+            dns64: None,
+            querylog: None,
+            metrics: Arc::new(Metrics::default()),
+        }
+    }
+
+    /// log every answered query to `log`
+    pub fn querylog(&mut self, log: QueryLog) -> &DNSServer {
+        self.querylog = Some(log);
+        self
+    }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// answer names in `zone` authoritatively
+    pub fn zone(&mut self, zone: Zone) -> &DNSServer {
+        self.zones.push(zone);
+        self
+    }
+
+    /// the most specific zone containing `name`
+    fn find_zone(&self, name: &str) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| zone.contains(name))
+            .max_by_key(|zone| zone.origin().len())
+    }
+
+    /// answer names matching `list` with `action`
+    pub fn blocklist(&mut self, list: Blocklist, action: BlockAction) -> &DNSServer {
+        self.blocklist = RwLock::new(list);
+        self.block_action = action;
+        self
+    }
+
+    /// load the list files again if any of them changed
+    pub fn reload_blocklist(&self) -> io::Result<bool> {
+        if !self.blocklist.read().unwrap().is_stale() {
+            return Ok(false);
+        }
+        let paths = self.blocklist.read().unwrap().sources();
+        let list = Blocklist::load(&paths)?;
+        // hits counted while loading are carried over too
+        let mut current = self.blocklist.write().unwrap();
+        list.keep_hits(&current);
+        *current = list;
+        Ok(true)
+    }
+
+    /// synthesize AAAA answers from A answers of upstream with `dns64`
+    pub fn dns64(&mut self, dns64: Dns64) -> &DNSServer {
+        self.dns64 = Some(dns64);
+        self
+    }
+
+    /// (domain, hits) of every blocklist rule which has been hit
+    pub fn blocklist_hits(&self) -> Vec<(String, u64)> {
+        self.blocklist
+            .read()
+            .unwrap()
+            .rules()
+            .iter()
+            .filter(|rule| rule.hits() > 0)
+            .map(|rule| (rule.domain.clone(), rule.hits()))
+            .collect()
+    }
+
+    /// answer a raw query, returning the raw response
+    pub fn handle(&self, raw: &[u8]) -> io::Result<Vec<u8>> {
+        self.answer(raw).map(|(response, _)| response)
+    }
+
+    fn answer(&self, raw: &[u8]) -> io::Result<(Vec<u8>, Source)> {
+        // only decode the full message when answering locally
+        let view = MessageRef::parse(raw)?;
+        let question = match view.questions().next() {
+            Some(question)
+                if view.header().flag().opcode == FlagOpCode::Query && bypass(raw).is_none() =>
+            {
+                question
+            }
+            _ => return self.forward(raw),
+        };
+
+        let name = question.name().decode()?;
+        if let Some(zone) = self.find_zone(name.as_str()) {
+            let response: BytesMut = zone.respond(&Message::parse(raw)?).into();
+            return Ok((response.to_vec(), Source::Zone));
+        }
+
+        let blocked = self
+            .blocklist
+            .read()
+            .unwrap()
+            .matches(name.as_str())
+            .is_some();
+        if blocked {
+            self.metrics.blocked();
+            let response: BytesMut = self.block_action.respond(&Message::parse(raw)?).into();
+            return Ok((response.to_vec(), Source::Blocklist));
+        }
+
+        match &self.dns64 {
+            Some(dns64)
+                if question.q_type() == QType::Type(Type::AAAA)
+                    && dns64.applies_to(name.as_str()) =>
+            {
+                self.forward_dns64(dns64, raw)
+            }
+            _ => self.forward(raw),
+        }
+    }
+
+    /// forward the AAAA query `raw`, and ask upstream for A records
+    /// to synthesize the answer from when it has no AAAA records
+    fn forward_dns64(&self, dns64: &Dns64, raw: &[u8]) -> io::Result<(Vec<u8>, Source)> {
+        let (response, source) = self.forward(raw)?;
+        let aaaa = MessageRef::parse(&response)?;
+        if !dns64.needs_synthesis(&aaaa) {
+            return Ok((response, source));
+        }
+
+        let a_query: BytesMut = dns64.a_query(&Message::parse(raw)?).into();
+        let (a, _) = self.forward(&a_query)?;
+        let query = MessageRef::parse(raw)?;
+        match dns64.respond(&query, &aaaa, &MessageRef::parse(&a)?)? {
+            Some(synthesized) => Ok((synthesized.to_vec(), Source::Dns64)),
+            None => Ok((response, source)),
+        }
+    }
+
+    fn forward(&self, raw: &[u8]) -> io::Result<(Vec<u8>, Source)> {
+        let response = self.client.forward(raw).and_then(|(response, upstream)| {
+            if response.len() < 12 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "upstream response too short",
+                ));
+            }
+            Ok((response, upstream))
+        });
+        match response {
+            Ok((response, upstream)) => Ok((response, Source::Upstream(upstream.to_owned()))),
+            Err(e) => {
+                self.metrics.upstream_error();
+                Err(e)
+            }
+        }
+    }
+
+    /// answer a query from `client`, recording it in query log and metrics,
+    /// with SERVFAIL if it could not be answered
+    fn process(&self, raw: &[u8], client: SocketAddr) -> io::Result<Vec<u8>> {
+        let start = Instant::now();
+        let (response, source) = match self.answer(raw) {
+            Ok(answer) => answer,
+            Err(e) => {
+                eprintln!("failed to answer {}: {}", client, e);
+                let response: BytesMut =
+                    Message::parse(raw)?.reply(FlagRCode::ServerFailure).into();
+                (response.to_vec(), Source::Upstream(String::new()))
+            }
+        };
+        let latency = start.elapsed();
+
+        let rcode: String = Header::from(&response[..]).flag().rcode.into();
+        self.metrics.observe(&rcode, latency);
+        if let Source::Zone | Source::Blocklist = source {
+            self.metrics.local();
+        }
+
+        if let Some(log) = &self.querylog {
+            // the question of a bypass query follows the one for GFW
+            let question = MessageRef::parse(raw).ok().and_then(|query| {
+                query
+                    .questions()
+                    .find(|question| question.name().decode().is_ok())
+            });
+            let (name, q_type) = match question {
+                Some(q) => (
+                    q.name().decode().map(|n| n.to_string()).unwrap_or_default(),
+                    q.q_type().to_string(),
+                ),
+                None => (String::new(), String::new()),
+            };
+            let entry = QueryLogEntry {
+                timestamp: QueryLogEntry::now(),
+                client,
+                name,
+                q_type,
+                upstream: match &source {
+                    Source::Upstream(upstream) => upstream.clone(),
+                    _ => source.to_string(),
+                },
+                rcode,
+                latency_us: latency.as_micros() as u64,
+                cache_hit: false,
+                bypass: match source {
+                    Source::Upstream(_) | Source::Dns64 => bypass(raw).unwrap_or("none"),
+                    _ => "none",
+                }
+                .to_owned(),
+            };
+            if let Err(e) = log.log(&entry) {
+                eprintln!("failed to write query log: {}", e);
+            }
+        }
+        Ok(response)
+    }
+
+    /// serve forever
+    pub fn serve(self) -> io::Result<()> {
+        let server = Arc::new(self);
+        let socket = UdpSocket::bind(&server.listen)?;
+        socket.set_read_timeout(Some(RELOAD_INTERVAL))?;
+
+        let (sender, receiver) = mpsc::sync_channel::<(Vec<u8>, SocketAddr)>(QUEUE_LEN);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..WORKERS {
+            let server = server.clone();
+            let socket = socket.try_clone()?;
+            let receiver = receiver.clone();
+            thread::spawn(move || loop {
+                // the lock is released before answering
+                let next = receiver.lock().unwrap().recv();
+                let (query, src) = match next {
+                    Ok(next) => next,
+                    Err(_) => return,
+                };
+                match server.process(&query, src) {
+                    Ok(response) => {
+                        let _ = socket.send_to(&response, src);
+                    }
+                    Err(e) => eprintln!("failed to answer {}: {}", src, e),
+                }
+            });
+        }
+
+        let mut buffer = [0u8; 1500];
+        let mut last_reload = Instant::now();
+        loop {
+            match socket.recv_from(&mut buffer) {
+                Ok((size, src)) => match sender.try_send((buffer[..size].to_vec(), src)) {
+                    Ok(()) => {}
+                    // the client retries, as after a lost datagram
+                    Err(TrySendError::Full(_)) => server.metrics.dropped(),
+                    Err(TrySendError::Disconnected(_)) => {
+                        return Err(io::Error::new(io::ErrorKind::Other, "workers exited"))
+                    }
+                },
+                Err(e) if is_timeout(&e) => {}
+                Err(e) => return Err(e),
+            }
+
+            if last_reload.elapsed() >= RELOAD_INTERVAL {
+                last_reload = Instant::now();
+                if let Err(e) = server.reload_blocklist() {
+                    eprintln!("failed to reload blocklist: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Upstream(_) => "upstream",
+            Source::Blocklist => "blocklist",
+            Source::Zone => "zone",
+            Source::Dns64 => "dns64",
+        })
+    }
+}
+
+/// the GFW bypass strategy of a query, a first question made of a
+/// pointer to the name of the next one, see `Question::append_gfw`
+fn bypass(raw: &[u8]) -> Option<&'static str> {
+    if raw.len() <= 12 {
+        return None;
+    }
+    match Header::from(raw).qd_count > 1 && raw[12] & 0b11000000 == 0b11000000 {
+        true => Some("gfw"),
+        false => None,
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::message::{FlagRCode, Question, RData, ResourceRecord};
+
+    fn blocking_server(action: BlockAction) -> DNSServer {
+        let mut list = Blocklist::default();
+        list.add("ads.example.com");
+        let mut server = DNSServer::new("127.0.0.1:0", DNSClient::new("127.0.0.1:0"));
+        server.blocklist(list, action);
+        server
+    }
+
+    fn query(name: &str, qtype: QType) -> BytesMut {
+        let mut message = Message::new(Default::default(), false);
+        message.add_question(Question::new(name, qtype));
+        message.into()
+    }
+
+    #[test]
+    fn test_handle_zone() {
+        let zone = Zone::parse(
+            "@ SOA ns hostmaster 1 3600 900 604800 300\nprinter A 192.168.1.2",
+            "lan",
+        )
+        .unwrap();
+        let mut server = blocking_server(BlockAction::NxDomain);
+        server.zone(zone);
+
+        let raw = query("PRINTER.lan", QType::Type(Type::A));
+        let response = Message::from(&server.handle(&raw).unwrap()[..]);
+        assert!(response.header.flag().authoritative_answer);
+        assert!(response.answers[0].r_data == RData::A("192.168.1.2".parse().unwrap()));
+
+        let raw = query("printer.lan", QType::Type(Type::AAAA));
+        let response = Message::from(&server.handle(&raw).unwrap()[..]);
+        assert!(response.header.flag().rcode == FlagRCode::NoError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities.len(), 1);
+    }
+
+    #[test]
+    fn test_handle_blocked() {
+        let server = blocking_server(BlockAction::NxDomain);
+        let raw = query("www.ads.example.com", QType::Type(Type::A));
+        let response = Message::from(&server.handle(&raw).unwrap()[..]);
+        let flag = response.header.flag();
+        assert_eq!(response.header.id, Message::from(&raw[..]).header.id);
+        assert!(flag.is_response && flag.recursion_desired && flag.recursion_available);
+        assert!(flag.rcode == FlagRCode::NameError);
+        assert_eq!(
+            server.blocklist_hits(),
+            vec![("ads.example.com".to_owned(), 1)]
+        );
+
+        let server = blocking_server(BlockAction::Null);
+        let raw = query("ads.example.com", QType::Type(Type::AAAA));
+        let response = Message::from(&server.handle(&raw).unwrap()[..]);
+        assert_eq!(response.questions[0].name(), "ads.example.com.");
+        match response.answers[0].r_data {
+            RData::AAAA(addr) => assert!(addr.is_unspecified()),
+            _ => panic!("No AAAA record found!"),
+        }
+    }
+
+    #[test]
+    fn test_handle_dns64() {
+        let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = upstream.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            loop {
+                let (size, src) = upstream.recv_from(&mut buffer).unwrap();
+                let query = Message::from(&buffer[..size]);
+                let question = &query.questions[0];
+                let mut response = query.reply(FlagRCode::NoError);
+                match (question.name(), question.q_type()) {
+                    ("v6.example.com.", QType::Type(Type::AAAA)) => {
+                        let addr = RData::AAAA("2001:db8::1".parse().unwrap());
+                        response.add_answer(ResourceRecord::new(
+                            question.name(),
+                            Type::AAAA,
+                            60,
+                            addr,
+                        ))
+                    }
+                    (_, QType::Type(Type::A)) => {
+                        let addr = RData::A("192.0.2.33".parse().unwrap());
+                        response.add_answer(ResourceRecord::new(question.name(), Type::A, 60, addr))
+                    }
+                    _ => {}
+                }
+                let response: BytesMut = response.into();
+                upstream.send_to(&response, src).unwrap();
+            }
+        });
+
+        let mut client = DNSClient::new("127.0.0.1:0");
+        client.upstream(&addr);
+        let mut server = DNSServer::new("127.0.0.1:0", client);
+        let mut dns64 = Dns64::default();
+        dns64.exclude_name("v4.example.com");
+        server.dns64(dns64);
+
+        let raw = query("www.example.com", QType::Type(Type::AAAA));
+        let (response, source) = server.answer(&raw).unwrap();
+        let response = Message::from(&response[..]);
+        assert!(source == Source::Dns64);
+        assert_eq!(response.header.id, Message::from(&raw[..]).header.id);
+        assert!(response.answers[0].r_data == RData::AAAA("64:ff9b::c000:221".parse().unwrap()));
+
+        // native AAAA records, and excluded names, are passed through
+        let raw = query("v6.example.com", QType::Type(Type::AAAA));
+        let (response, source) = server.answer(&raw).unwrap();
+        assert!(source == Source::Upstream(addr.clone()));
+        let response = Message::from(&response[..]);
+        assert!(response.answers[0].r_data == RData::AAAA("2001:db8::1".parse().unwrap()));
+
+        let raw = query("v4.example.com", QType::Type(Type::AAAA));
+        let (response, source) = server.answer(&raw).unwrap();
+        assert!(source == Source::Upstream(addr.clone()));
+        assert!(Message::from(&response[..]).answers.is_empty());
+    }
+
+    #[test]
+    fn test_serve() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        drop(socket);
+
+        let mut server = blocking_server(BlockAction::NxDomain);
+        server.listen = addr.clone();
+        thread::spawn(move || server.serve());
+        thread::sleep(Duration::from_millis(100));
+
+        // more queries at once than workers
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.connect(&addr).unwrap();
+        let n = WORKERS * 2;
+        for _ in 0..n {
+            client
+                .send(&query("ads.example.com", QType::Type(Type::A)))
+                .unwrap();
+        }
+        let mut buffer = [0u8; 1500];
+        for _ in 0..n {
+            let size = client.recv(&mut buffer).unwrap();
+            let response = Message::from(&buffer[..size]);
+            assert!(response.header.flag().rcode == FlagRCode::NameError);
+        }
+    }
+
+    #[test]
+    fn test_process() {
+        let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = upstream.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            // a broken response, then an answer
+            let (_, src) = upstream.recv_from(&mut buffer).unwrap();
+            upstream.send_to(&[0, 1, 2, 3], src).unwrap();
+            let (size, src) = upstream.recv_from(&mut buffer).unwrap();
+            let query = Message::from(&buffer[..size]);
+            let mut response = query.reply(FlagRCode::NoError);
+            let addr = RData::A("192.0.2.1".parse().unwrap());
+            response.add_answer(ResourceRecord::new("www.example.com", Type::A, 60, addr));
+            let response: BytesMut = response.into();
+            upstream.send_to(&response, src).unwrap();
+        });
+
+        let path = std::env::temp_dir().join(format!("glow-server-{}.log", std::process::id()));
+        let mut client = DNSClient::new("127.0.0.1:0");
+        client.upstream(&addr);
+        let mut server = DNSServer::new("127.0.0.1:0", client);
+        server.querylog(QueryLog::file(&path, 1 << 20, 0).unwrap());
+        let src = "127.0.0.1:5353".parse().unwrap();
+
+        let raw = query("fail.example.com", QType::Type(Type::A));
+        let response = Message::from(&server.process(&raw, src).unwrap()[..]);
+        assert!(response.header.flag().rcode == FlagRCode::ServerFailure);
+
+        let raw = query("www.example.com", QType::Type(Type::A));
+        let response = Message::from(&server.process(&raw, src).unwrap()[..]);
+        assert_eq!(response.answers.len(), 1);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["rcode"], "ServerFailure");
+        assert_eq!(lines[1]["upstream"], addr.as_str());
+        assert_eq!(lines[1]["cache_hit"], false);
+        assert_eq!(lines[1]["bypass"], "none");
+        assert_eq!(
+            server
+                .metrics()
+                .render()
+                .matches("upstream_errors_total 1")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_bypass() {
+        let mut message = Message::new(Default::default(), true);
+        message.add_question(Question::new("glow.mmf.moe", QType::Type(Type::A)));
+        let raw: BytesMut = message.into();
+        assert_eq!(bypass(&raw), Some("gfw"));
+        assert_eq!(bypass(&query("glow.mmf.moe", QType::Type(Type::A))), None);
+    }
+}
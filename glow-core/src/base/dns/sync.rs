@@ -1,23 +1,72 @@
+use super::addrsel::sort_destinations;
+use super::hosts::{Hosts, HOSTS_PATH};
 use super::message::*;
-use super::types::QType;
-use std::net::UdpSocket;
+use super::resolv::{ResolvConf, RESOLV_CONF_PATH};
+use super::tsig::TsigKey;
+use super::types::{QType, Type};
+use super::update::Update;
+use super::utils::reverse_name;
+use crate::ip::host_addrs;
+use std::net::{IpAddr, UdpSocket};
 use std::str;
+use std::time::Duration;
+
+/// CNAME targets queried again when the answer stops short of an address
+const MAX_CNAME_QUERIES: usize = 8;
 
 pub struct DNSClient {
     addr: String,
-    upstream: String,
+    /// name servers tried in turn, the next one on timeout
+    upstreams: Vec<String>,
+
+    hosts: Option<Hosts>,
+    conf: ResolvConf,
 }
 
 impl DNSClient {
     pub fn new(addr: &str) -> DNSClient {
         DNSClient {
             addr: addr.to_owned(),
-            upstream: String::new(),
+            upstreams: Vec::new(),
+            hosts: None,
+            conf: ResolvConf::default(),
+        }
+    }
+
+    /// client configured from /etc/hosts and /etc/resolv.conf
+    pub fn system(addr: &str) -> std::io::Result<DNSClient> {
+        let mut client = DNSClient::new(addr);
+        client.resolv_conf(ResolvConf::load(RESOLV_CONF_PATH)?);
+        // a missing hosts file is not an error
+        if let Ok(hosts) = Hosts::load(HOSTS_PATH) {
+            client.hosts(hosts);
         }
+        Ok(client)
     }
 
     pub fn upstream(&mut self, addr: &str) -> &DNSClient {
-        self.upstream = addr.to_owned();
+        self.upstreams = vec![addr.to_owned()];
+        self
+    }
+
+    /// the first name server, queried before the others
+    pub fn upstream_addr(&self) -> &str {
+        self.upstreams.first().map_or("", String::as_str)
+    }
+
+    /// answer names in `hosts` locally instead of querying upstream
+    pub fn hosts(&mut self, hosts: Hosts) -> &DNSClient {
+        self.hosts = Some(hosts);
+        self
+    }
+
+    /// apply search list, ndots, timeout and attempts of `conf`,
+    /// and use its name servers if no upstream is set
+    pub fn resolv_conf(&mut self, conf: ResolvConf) -> &DNSClient {
+        if self.upstreams.is_empty() {
+            self.upstreams = conf.upstreams().iter().map(|u| u.to_string()).collect();
+        }
+        self.conf = conf;
         self
     }
 
@@ -31,20 +80,248 @@ impl DNSClient {
         message.bypass_gfw(bypass_gfw);
         message.add_question(Question::new(domain, qtype));
 
-        // send query
-        let socket = UdpSocket::bind(&self.addr)?;
-        socket.connect(&self.upstream)?;
         let message: bytes::BytesMut = message.into();
-        socket.send(&message[..])?;
-
-        // receive response
-        let mut buffer = [0u8; 1500];
-        socket.recv_from(&mut buffer)?;
+        let (response, _) = self.forward(&message[..])?;
 
         // parse message
-        let response = Message::from(&buffer[..]);
+        let response = Message::parse(&response[..])?;
         Ok((response.header, response.questions, response.answers))
     }
+
+    /// send a raw message to upstream and return the raw response,
+    /// with the name server which answered
+    pub fn forward(&self, message: &[u8]) -> std::io::Result<(Vec<u8>, &str)> {
+        self.exchange(|socket| {
+            socket.send(message)?;
+
+            // receive response
+            let mut buffer = [0u8; 1500];
+            let (size, _) = socket.recv_from(&mut buffer)?;
+            Ok(buffer[..size].to_vec())
+        })
+    }
+
+    /// run `f` on a socket connected to each name server in turn,
+    /// until one of them does not time out, and return the result
+    /// with that name server
+    fn exchange<T, F>(&self, f: F) -> std::io::Result<(T, &str)>
+    where
+        F: Fn(&UdpSocket) -> std::io::Result<T>,
+    {
+        let mut result = Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no name server",
+        ));
+        for upstream in self.upstreams.iter() {
+            let socket = UdpSocket::bind(&self.addr)?;
+            socket.set_read_timeout(Some(Duration::from_secs(self.conf.timeout.max(1) as u64)))?;
+            socket.connect(upstream)?;
+            result = f(&socket).map(|value| (value, upstream.as_str()));
+            match &result {
+                Err(e) if is_timeout(e) => continue,
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// send a dynamic update to upstream, signed with `key` if given,
+    /// and return the response code
+    pub fn update(&self, update: Update, key: Option<&TsigKey>) -> std::io::Result<FlagRCode> {
+        let (message, mac) = match key {
+            Some(key) => key.sign(update.message(), None),
+            None => (update.message().into(), Vec::new()),
+        };
+        let (response, _) = self.forward(&message[..])?;
+        if response.len() < 12 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "message too short",
+            ));
+        }
+
+        let rcode = Header::from(&response[..]).flag().rcode;
+        if let Some(key) = key {
+            // servers may reject a request without signing the response
+            if let Err(e) = key.verify(&response, Some(&mac)) {
+                if rcode == FlagRCode::NoError {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(rcode)
+    }
+
+    /// resolve `name` like the system resolver does:
+    /// hosts table first, then every name of the search list in turn,
+    /// each retried up to `attempts` times on timeout
+    pub fn resolve(&self, name: &str, qtype: QType) -> std::io::Result<Vec<ResourceRecord>> {
+        if let Some(records) = self.hosts.as_ref().and_then(|h| h.records(name, qtype)) {
+            return Ok(records);
+        }
+
+        for candidate in self.conf.search_names(name) {
+            let mut attempt = 0;
+            let (header, _, answers) = loop {
+                attempt += 1;
+                match self.query(&candidate, qtype, false) {
+                    Err(e) if attempt < self.conf.attempts && is_timeout(&e) => continue,
+                    result => break result?,
+                }
+            };
+            if let FlagRCode::NoError = header.flag().rcode {
+                if !answers.is_empty() {
+                    return Ok(answers);
+                }
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// resolve A and AAAA records of `name` at once, following CNAME chains,
+    /// and sort the addresses by RFC 6724 for the current host addresses
+    pub fn resolve_host(&self, name: &str) -> std::io::Result<Vec<IpAddr>> {
+        let mut addrs = self.lookup_host(name)?;
+        // without the host addresses the answers are kept in their order
+        if let Ok(sources) = host_addrs() {
+            sort_destinations(&mut addrs, &sources);
+        }
+        Ok(addrs)
+    }
+
+    /// addresses of `name` in the order they were answered
+    fn lookup_host(&self, name: &str) -> std::io::Result<Vec<IpAddr>> {
+        if let Some(addrs) = self.hosts.as_ref().and_then(|h| h.lookup(name)) {
+            return Ok(addrs.to_vec());
+        }
+
+        for candidate in self.conf.search_names(name) {
+            let mut target = candidate;
+            for _ in 0..MAX_CNAME_QUERIES {
+                let mut attempt = 0;
+                let responses = loop {
+                    attempt += 1;
+                    match self.query_host(&target) {
+                        Err(e) if attempt < self.conf.attempts && is_timeout(&e) => continue,
+                        result => break result?,
+                    }
+                };
+
+                let mut addrs = Vec::new();
+                let mut next = None;
+                for response in responses.iter() {
+                    if response.header.flag().rcode != FlagRCode::NoError {
+                        continue;
+                    }
+                    let (found, end) = chase(&target, &response.answers);
+                    addrs.extend(found);
+                    if !same_name(end, &target) {
+                        next = Some(end.to_owned());
+                    }
+                }
+                if !addrs.is_empty() {
+                    return Ok(addrs);
+                }
+                match next {
+                    Some(next) => target = next,
+                    None => break,
+                }
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// send the A and AAAA queries of `name` on one socket
+    /// without waiting in between, and receive both responses,
+    /// or the one which arrived before the other timed out
+    fn query_host(&self, name: &str) -> std::io::Result<Vec<Message>> {
+        self.exchange(|socket| {
+            let id = rand::random::<u16>();
+            let ids = [id, id.wrapping_add(1)];
+            for (id, r_type) in ids.iter().zip(&[Type::A, Type::AAAA]) {
+                let mut query = Message::new(Header::default(), false);
+                query.header.id = *id;
+                query.add_question(Question::new(name, QType::Type(*r_type)));
+                let query: bytes::BytesMut = query.into();
+                socket.send(&query)?;
+            }
+
+            let mut responses = vec![None, None];
+            let mut buffer = [0u8; 1500];
+            while responses.iter().any(Option::is_none) {
+                let size = match socket.recv(&mut buffer) {
+                    Ok(size) => size,
+                    Err(e) if is_timeout(&e) && responses.iter().any(Option::is_some) => break,
+                    Err(e) => return Err(e),
+                };
+                // stray datagrams are dropped, like late responses of a previous attempt
+                let response = match Message::parse(&buffer[..size]) {
+                    Ok(response) => response,
+                    Err(_) => continue,
+                };
+                if let Some(i) = ids.iter().position(|id| *id == response.header.id) {
+                    responses[i] = Some(response);
+                }
+            }
+            Ok(responses.into_iter().flatten().collect())
+        })
+        .map(|(responses, _)| responses)
+    }
+
+    /// query PTR records of `addr` and return the target names
+    pub fn reverse(&self, addr: IpAddr) -> std::io::Result<Vec<String>> {
+        if let Some(name) = self.hosts.as_ref().and_then(|h| h.lookup_addr(&addr)) {
+            return Ok(vec![name.to_owned()]);
+        }
+
+        let (_, _, answers) = self.query(&reverse_name(&addr), QType::Type(Type::PTR), false)?;
+        Ok(answers
+            .into_iter()
+            .filter_map(|answer| match answer.r_data {
+                RData::PTR(name) => Some(name),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// addresses of `name` in `answers` after following its CNAME chain,
+/// and the name the chain ends at
+fn chase<'a>(name: &'a str, answers: &'a [ResourceRecord]) -> (Vec<IpAddr>, &'a str) {
+    let mut name = name;
+    for _ in 0..MAX_CNAME_QUERIES {
+        let target = answers.iter().find_map(|answer| match &answer.r_data {
+            RData::CNAME(target) if same_name(&answer.name, name) => Some(target),
+            _ => None,
+        });
+        match target {
+            Some(target) => name = target,
+            None => break,
+        }
+    }
+
+    let addrs = answers
+        .iter()
+        .filter(|answer| same_name(&answer.name, name))
+        .filter_map(|answer| match answer.r_data {
+            RData::A(addr) => Some(IpAddr::V4(addr)),
+            RData::AAAA(addr) => Some(IpAddr::V6(addr)),
+            _ => None,
+        })
+        .collect();
+    (addrs, name)
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
 }
 
 #[cfg(test)]
@@ -88,4 +365,187 @@ mod tests {
         }
         panic!("No AAAA record found!");
     }
+
+    #[test]
+    fn test_reverse_name() {
+        use super::super::utils::reverse_name;
+        assert_eq!(
+            reverse_name(&"1.0.0.127".parse().unwrap()),
+            "127.0.0.1.in-addr.arpa"
+        );
+        assert_eq!(
+            reverse_name(&"2001:db8::567:89ab".parse().unwrap()),
+            "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+
+    #[test]
+    fn test_parse_ptr() {
+        #[rustfmt::skip]
+        let raw: [u8; 64] = [
+            // header: id, response, 1 question, 1 answer
+            0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+            // question: 1.1.1.1.in-addr.arpa PTR IN
+            0x01, b'1', 0x01, b'1', 0x01, b'1', 0x01, b'1',
+            0x07, b'i', b'n', b'-', b'a', b'd', b'd', b'r', 0x04, b'a', b'r', b'p', b'a', 0x00,
+            0x00, 0x0c, 0x00, 0x01,
+            // answer: name pointer to question, PTR IN, ttl 1800, rdlength 14
+            0xc0, 0x0c, 0x00, 0x0c, 0x00, 0x01, 0x00, 0x00, 0x07, 0x08, 0x00, 0x0e,
+            // one.one.lan + pointer to "arpa." inside the question (0x1c)
+            0x03, b'o', b'n', b'e', 0x03, b'o', b'n', b'e', 0x03, b'l', b'a', b'n', 0xc0, 0x1c,
+        ];
+        let message = Message::from(&raw[..]);
+        assert_eq!(message.answers.len(), 1);
+        assert_eq!(message.answers[0].ttl, 1800);
+        match &message.answers[0].r_data {
+            RData::PTR(name) => assert_eq!(name, "one.one.lan.arpa."),
+            _ => panic!("No PTR record found!"),
+        }
+    }
+
+    #[test]
+    fn test_chase() {
+        let answers = vec![
+            ResourceRecord::new(
+                "www.glow.lan",
+                Type::CNAME,
+                60,
+                RData::CNAME("cdn.glow.lan.".to_owned()),
+            ),
+            ResourceRecord::new(
+                "cdn.glow.lan",
+                Type::CNAME,
+                60,
+                RData::CNAME("edge.glow.lan.".to_owned()),
+            ),
+            ResourceRecord::new(
+                "edge.glow.lan",
+                Type::A,
+                60,
+                RData::A("192.0.2.1".parse().unwrap()),
+            ),
+            ResourceRecord::new(
+                "other.glow.lan",
+                Type::A,
+                60,
+                RData::A("192.0.2.2".parse().unwrap()),
+            ),
+        ];
+        let (addrs, end) = chase("WWW.glow.lan", &answers);
+        assert_eq!(addrs, vec!["192.0.2.1".parse::<IpAddr>().unwrap()]);
+        assert_eq!(end, "edge.glow.lan.");
+
+        // a chain leaving the answer section
+        let (addrs, end) = chase("www.glow.lan", &answers[..1]);
+        assert!(addrs.is_empty());
+        assert_eq!(end, "cdn.glow.lan.");
+    }
+
+    #[test]
+    fn test_lookup_host() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            // two queries for www, then two for the CNAME target
+            for _ in 0..4 {
+                let (size, src) = server.recv_from(&mut buffer).unwrap();
+                let query = Message::from(&buffer[..size]);
+                let question = &query.questions[0];
+                let mut response = query.reply(FlagRCode::NoError);
+                if question.name() == "www.glow.lan." {
+                    let target = RData::CNAME("edge.glow.lan.".to_owned());
+                    response.add_answer(ResourceRecord::new(
+                        question.name(),
+                        Type::CNAME,
+                        60,
+                        target,
+                    ));
+                } else if question.q_type() == QType::Type(Type::A) {
+                    let addr = RData::A("192.0.2.1".parse().unwrap());
+                    response.add_answer(ResourceRecord::new(question.name(), Type::A, 60, addr));
+                } else {
+                    let addr = RData::AAAA("2001:db8::1".parse().unwrap());
+                    response.add_answer(ResourceRecord::new(question.name(), Type::AAAA, 60, addr));
+                }
+                let response: bytes::BytesMut = response.into();
+                server.send_to(&response, src).unwrap();
+            }
+        });
+
+        let mut client = DNSClient::new("127.0.0.1:0");
+        client.upstream(&addr);
+        let addrs = client.lookup_host("www.glow.lan").unwrap();
+        assert_eq!(
+            addrs,
+            vec![
+                "192.0.2.1".parse::<IpAddr>().unwrap(),
+                "2001:db8::1".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lookup_host_partial() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            // the AAAA query is never answered
+            loop {
+                let (size, src) = server.recv_from(&mut buffer).unwrap();
+                let query = Message::from(&buffer[..size]);
+                let question = &query.questions[0];
+                if question.q_type() != QType::Type(Type::A) {
+                    continue;
+                }
+                let mut response = query.reply(FlagRCode::NoError);
+                let addr = RData::A("192.0.2.1".parse().unwrap());
+                response.add_answer(ResourceRecord::new(question.name(), Type::A, 60, addr));
+                let response: bytes::BytesMut = response.into();
+                server.send_to(&response, src).unwrap();
+            }
+        });
+
+        let mut client = DNSClient::new("127.0.0.1:0");
+        client.resolv_conf(ResolvConf {
+            timeout: 1,
+            ..ResolvConf::default()
+        });
+        client.upstream(&addr);
+        let addrs = client.lookup_host("www.glow.lan").unwrap();
+        assert_eq!(addrs, vec!["192.0.2.1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_failover() {
+        // the first name server never answers
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            let (size, src) = server.recv_from(&mut buffer).unwrap();
+            let query = Message::from(&buffer[..size]);
+            let response: bytes::BytesMut = query.reply(FlagRCode::NameError).into();
+            server.send_to(&response, src).unwrap();
+        });
+
+        let mut client = DNSClient::new("127.0.0.1:0");
+        client.resolv_conf(ResolvConf {
+            timeout: 1,
+            ..ResolvConf::default()
+        });
+        client.upstreams = vec![silent.local_addr().unwrap().to_string(), addr.clone()];
+        let mut query = Message::new(Header::default(), false);
+        query.add_question(Question::new("glow.lan", QType::Type(Type::A)));
+        let query: bytes::BytesMut = query.into();
+        let (response, upstream) = client.forward(&query).unwrap();
+        assert_eq!(
+            Header::from(&response[..]).flag().rcode,
+            FlagRCode::NameError
+        );
+        // the name server which answered, not the first one
+        assert_eq!(upstream, addr);
+    }
 }
@@ -0,0 +1,375 @@
+use super::message::{FlagRCode, Header, Message, Question, RData, ResourceRecord};
+use super::types::{QType, Type};
+use super::zone::Zone;
+use bytes::BytesMut;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Result of an incremental zone transfer
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum Transfer {
+    /// the primary has no newer serial
+    UpToDate,
+    /// the primary sent the whole zone
+    Full(Zone),
+    /// the primary sent differences which have been applied
+    Incremental(Zone),
+}
+
+/// Zone transfer client over TCP (RFC 5936, RFC 1995)
+pub struct TransferClient {
+    primary: String,
+    timeout: Duration,
+}
+
+impl TransferClient {
+    pub fn new(primary: &str) -> TransferClient {
+        TransferClient {
+            primary: primary.to_owned(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn timeout(&mut self, timeout: Duration) -> &TransferClient {
+        self.timeout = timeout;
+        self
+    }
+
+    /// transfer the whole zone at `origin`
+    pub fn axfr(&self, origin: &str) -> io::Result<Zone> {
+        let mut message = Message::new(Header::default(), false);
+        message.add_question(Question::new(origin, QType::AXFR));
+
+        let records = self.transfer(message, true)?;
+        Zone::from_records(strip_soa(records)?)
+    }
+
+    /// transfer changes of `zone` since its serial,
+    /// the primary may fall back to sending the whole zone
+    pub fn ixfr(&self, zone: &Zone) -> io::Result<Transfer> {
+        let mut message = Message::new(Header::default(), false);
+        message.add_question(Question::new(zone.origin(), QType::IXFR));
+        message.add_authority(zone.soa().clone());
+
+        let records = self.transfer(message, false)?;
+        let new_serial = serial(&records[0]).unwrap();
+        if records.len() == 1 || !serial_gt(new_serial, zone.serial()) {
+            return Ok(Transfer::UpToDate);
+        }
+
+        // an incremental response has the old SOA right after the new one
+        match records.get(1).and_then(serial) {
+            Some(old) if old != new_serial => Ok(Transfer::Incremental(apply_diffs(
+                zone,
+                strip_soa(records)?,
+            )?)),
+            _ => Ok(Transfer::Full(Zone::from_records(strip_soa(records)?)?)),
+        }
+    }
+
+    /// send `query` and collect answers until the closing SOA
+    fn transfer(&self, query: Message, axfr: bool) -> io::Result<Vec<ResourceRecord>> {
+        let id = query.header.id;
+        let mut stream = TcpStream::connect(&self.primary)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        let query: BytesMut = query.into();
+        write_frame(&mut stream, &query)?;
+
+        let mut records: Vec<ResourceRecord> = Vec::new();
+        // SOAs after the first one, in an incremental transfer they start
+        // deletions and additions in turn, and the last one closes it
+        let mut soas = 0;
+        loop {
+            let raw = read_frame(&mut stream)?;
+            let response = Message::parse(&raw)?;
+            if response.header.id != id {
+                return Err(invalid("unexpected message id"));
+            }
+            let rcode = response.header.flag().rcode;
+            if rcode != FlagRCode::NoError {
+                let rcode: String = rcode.into();
+                return Err(invalid(&format!("transfer refused: {}", rcode)));
+            }
+
+            for rr in response.answers {
+                records.push(rr);
+                let first = match records.first().and_then(serial) {
+                    Some(serial) => serial,
+                    None => return Err(invalid("transfer does not start with SOA")),
+                };
+                if records.len() == 1 {
+                    continue;
+                }
+                if let Some(serial) = serial(records.last().unwrap()) {
+                    soas += 1;
+                    if soas % 2 == 1 && serial == first {
+                        return Ok(records);
+                    }
+                }
+            }
+
+            // a lone SOA answers an IXFR which is up to date
+            match records.len() {
+                0 => return Err(invalid("transfer does not start with SOA")),
+                1 if !axfr => return Ok(records),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// drop the leading and closing SOA of a transfer, keeping the first
+fn strip_soa(mut records: Vec<ResourceRecord>) -> io::Result<Vec<ResourceRecord>> {
+    if records.len() < 2 {
+        return Err(invalid("transfer is incomplete"));
+    }
+    records.pop();
+    Ok(records)
+}
+
+/// apply IXFR difference sequences, `diffs` is
+/// `SOA(new) [SOA(old) deleted... SOA(next) added...]...`
+fn apply_diffs(zone: &Zone, diffs: Vec<ResourceRecord>) -> io::Result<Zone> {
+    let mut records = zone.records();
+    let mut diffs = diffs.into_iter();
+    let new_soa = diffs.next().unwrap();
+
+    let mut deleting = false;
+    for rr in diffs {
+        if rr.r_type == Type::SOA {
+            // SOAs switch between deletions and additions
+            deleting = !deleting;
+            continue;
+        }
+        if deleting {
+            records.retain(|r| !same_record(r, &rr));
+        } else if !records.iter().any(|r| same_record(r, &rr)) {
+            records.push(rr);
+        }
+    }
+
+    records.retain(|r| r.r_type != Type::SOA);
+    records.push(new_soa);
+    Zone::from_records(records)
+}
+
+fn same_record(a: &ResourceRecord, b: &ResourceRecord) -> bool {
+    a.type_code() == b.type_code()
+        && a.r_data == b.r_data
+        && a.name
+            .trim_end_matches('.')
+            .eq_ignore_ascii_case(b.name.trim_end_matches('.'))
+}
+
+fn serial(rr: &ResourceRecord) -> Option<u32> {
+    match &rr.r_data {
+        RData::SOA(soa) => Some(soa.serial),
+        _ => None,
+    }
+}
+
+/// serial number arithmetic, whether `a` is newer than `b` (RFC 1982)
+pub fn serial_gt(a: u32, b: u32) -> bool {
+    a != b && a.wrapping_sub(b) < (1 << 31)
+}
+
+fn write_frame(stream: &mut TcpStream, message: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(message.len() + 2);
+    frame.extend_from_slice(&(message.len() as u16).to_be_bytes());
+    frame.extend_from_slice(message);
+    stream.write_all(&frame)
+}
+
+fn read_frame(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut message = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::zone::Lookup;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const ZONE_V1: &str = "\
+@ SOA ns hostmaster 1 3600 900 604800 300
+@ NS ns
+ns A 192.168.1.1
+printer A 192.168.1.2
+";
+
+    fn soa(serial: u32) -> ResourceRecord {
+        let zone = Zone::parse(ZONE_V1, "lan").unwrap();
+        let mut soa = zone.soa().clone();
+        if let RData::SOA(data) = &mut soa.r_data {
+            data.serial = serial;
+        }
+        soa
+    }
+
+    fn a(name: &str, addr: &str) -> ResourceRecord {
+        ResourceRecord::new(name, Type::A, 3600, RData::A(addr.parse().unwrap()))
+    }
+
+    /// a record of a type without a decoder
+    fn unknown(name: &str, r_type: u16, data: &[u8]) -> ResourceRecord {
+        let r_data = RData::Unknown {
+            r_type,
+            data: data.to_vec(),
+        };
+        ResourceRecord::new(name, Type::from(r_type), 3600, r_data)
+    }
+
+    /// fake primary answering one query with `responses`, each a message
+    fn primary(responses: Vec<Vec<ResourceRecord>>) -> (String, thread::JoinHandle<Message>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = Message::from(&read_frame(&mut stream).unwrap()[..]);
+            for answers in responses {
+                let mut response = query.reply(FlagRCode::NoError);
+                for rr in answers {
+                    response.add_answer(rr);
+                }
+                let raw: BytesMut = response.into();
+                write_frame(&mut stream, &raw).unwrap();
+            }
+            query
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn test_axfr() {
+        let (addr, handle) = primary(vec![
+            vec![
+                soa(1),
+                ResourceRecord::new("lan.", Type::NS, 3600, RData::NS("ns.lan.".to_owned())),
+            ],
+            vec![
+                a("ns.lan.", "192.168.1.1"),
+                a("printer.lan.", "192.168.1.2"),
+                // types without a decoder, known and unknown
+                unknown("printer.lan.", 13, b"\x03ARM\x05Linux"),
+                unknown("printer.lan.", 65280, &[0xde, 0xad]),
+            ],
+            vec![soa(1)],
+        ]);
+        let zone = TransferClient::new(&addr).axfr("lan").unwrap();
+        let query = handle.join().unwrap();
+        assert!(query.questions[0].q_type() == QType::AXFR);
+
+        assert_eq!(zone.origin(), "lan.");
+        assert_eq!(zone.serial(), 1);
+        assert_eq!(zone.records().len(), 6);
+        match zone.lookup("printer.lan", QType::Type(Type::A)) {
+            Lookup::Answer(rrs) => {
+                assert_eq!(rrs.len(), 1);
+                assert!(rrs[0].r_data == RData::A("192.168.1.2".parse().unwrap()));
+            }
+            _ => panic!("printer.lan should exist"),
+        }
+        match zone.lookup("printer.lan", QType::Type(Type::HINFO)) {
+            Lookup::Answer(rrs) => {
+                assert!(rrs == vec![unknown("printer.lan.", 13, b"\x03ARM\x05Linux")])
+            }
+            _ => panic!("printer.lan should exist"),
+        }
+        // served with its type code and RDATA as received
+        let rr = zone
+            .records()
+            .into_iter()
+            .find(|rr| rr.type_code() == 65280)
+            .unwrap();
+        let mut raw = BytesMut::new();
+        rr.append_to(&mut raw);
+        assert_eq!(
+            &raw[13..],
+            &[0xff, 0x00, 0, 1, 0, 0, 0x0e, 0x10, 0, 2, 0xde, 0xad]
+        );
+    }
+
+    #[test]
+    fn test_ixfr_incremental() {
+        let mut records = Zone::parse(ZONE_V1, "lan").unwrap().records();
+        records.push(unknown("printer.lan.", 65280, &[1]));
+        records.push(unknown("printer.lan.", 65280, &[2]));
+        let zone = Zone::from_records(records).unwrap();
+        let (addr, handle) = primary(vec![
+            vec![
+                soa(3),
+                // 1 -> 2: printer moved, one of its unknown records deleted
+                soa(1),
+                a("printer.lan.", "192.168.1.2"),
+                unknown("printer.lan.", 65280, &[1]),
+                soa(2),
+                a("printer.lan.", "192.168.1.20"),
+                // 2 -> 3: router added, in the next message
+                soa(2),
+                soa(3),
+            ],
+            vec![a("router.lan.", "192.168.1.254"), soa(3)],
+        ]);
+
+        let zone = match TransferClient::new(&addr).ixfr(&zone).unwrap() {
+            Transfer::Incremental(zone) => zone,
+            _ => panic!("expected an incremental transfer"),
+        };
+        let query = handle.join().unwrap();
+        assert!(query.questions[0].q_type() == QType::IXFR);
+        assert_eq!(serial(&query.authorities[0]), Some(1));
+
+        assert_eq!(zone.serial(), 3);
+        match zone.lookup("printer.lan", QType::Type(Type::A)) {
+            Lookup::Answer(rrs) => {
+                assert_eq!(rrs.len(), 1);
+                assert!(rrs[0].r_data == RData::A("192.168.1.20".parse().unwrap()));
+            }
+            _ => panic!("printer.lan should exist"),
+        }
+        assert!(matches!(
+            zone.lookup("router.lan", QType::Type(Type::A)),
+            Lookup::Answer(_)
+        ));
+        let unknown: Vec<_> = zone
+            .records()
+            .into_iter()
+            .filter(|rr| rr.type_code() == 65280)
+            .collect();
+        assert!(unknown == vec![self::unknown("printer.lan.", 65280, &[2])]);
+    }
+
+    #[test]
+    fn test_ixfr_up_to_date_and_full() {
+        let zone = Zone::parse(ZONE_V1, "lan").unwrap();
+        let (addr, _) = primary(vec![vec![soa(1)]]);
+        assert!(matches!(
+            TransferClient::new(&addr).ixfr(&zone).unwrap(),
+            Transfer::UpToDate
+        ));
+
+        let (addr, _) = primary(vec![vec![soa(2), a("ns.lan.", "192.168.1.1"), soa(2)]]);
+        match TransferClient::new(&addr).ixfr(&zone).unwrap() {
+            Transfer::Full(zone) => assert_eq!(zone.records().len(), 2),
+            _ => panic!("expected a full transfer"),
+        }
+    }
+
+    #[test]
+    fn test_serial_gt() {
+        assert!(serial_gt(2, 1));
+        assert!(!serial_gt(1, 1));
+        assert!(!serial_gt(1, 2));
+        assert!(serial_gt(1, u32::MAX));
+    }
+}
@@ -0,0 +1,252 @@
+use super::class::Class;
+use super::message::{write_name, Header, Message, Question, RData, ResourceRecord, TSIG};
+use super::types::Type;
+use bytes::{BufMut, BytesMut};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The only supported MAC algorithm
+pub const HMAC_SHA256: &str = "hmac-sha256.";
+
+/// TSIG error codes (RFC 8945 5.2)
+pub const BADSIG: u16 = 16;
+pub const BADKEY: u16 = 17;
+pub const BADTIME: u16 = 18;
+
+/// Shared secret used to sign and verify messages with TSIG (RFC 8945)
+pub struct TsigKey {
+    name: String,
+    secret: Vec<u8>,
+    fudge: u16,
+}
+
+impl TsigKey {
+    pub fn new(name: &str, secret: &[u8]) -> TsigKey {
+        TsigKey {
+            name: canonical(name),
+            secret: secret.to_vec(),
+            fudge: 300,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// seconds of clock skew permitted between signer and verifier
+    pub fn fudge(&mut self, fudge: u16) -> &TsigKey {
+        self.fudge = fudge;
+        self
+    }
+
+    /// sign `message`, returning the raw message and its MAC.
+    /// `request_mac` is the MAC of the request when signing a response
+    pub fn sign(&self, message: Message, request_mac: Option<&[u8]>) -> (BytesMut, Vec<u8>) {
+        self.sign_at(message, request_mac, now())
+    }
+
+    fn sign_at(
+        &self,
+        message: Message,
+        request_mac: Option<&[u8]>,
+        time_signed: u64,
+    ) -> (BytesMut, Vec<u8>) {
+        let mut tsig = TSIG {
+            algorithm: HMAC_SHA256.to_owned(),
+            time_signed,
+            fudge: self.fudge,
+            mac: Vec::new(),
+            original_id: message.header.id,
+            error: 0,
+            other: Vec::new(),
+        };
+
+        let mut raw: BytesMut = message.into();
+        tsig.mac = self.digest(request_mac, &raw, &tsig);
+        let mac = tsig.mac.clone();
+
+        let mut rr = ResourceRecord::new(&self.name, Type::TSIG, 0, RData::TSIG(tsig));
+        rr.class = Class::ANY;
+        rr.append_to(&mut raw);
+        let ar_count = u16::from_be_bytes([raw[10], raw[11]]) + 1;
+        raw[10..12].copy_from_slice(&ar_count.to_be_bytes());
+        (raw, mac)
+    }
+
+    /// verify the TSIG record closing `raw`, returning the message and its MAC.
+    /// `request_mac` is the MAC of the request when verifying a response
+    pub fn verify(&self, raw: &[u8], request_mac: Option<&[u8]>) -> io::Result<(Message, Vec<u8>)> {
+        let (start, rr) = last_record(raw)?;
+        let tsig = match rr.r_data {
+            RData::TSIG(tsig) if rr.r_type == Type::TSIG => tsig,
+            _ => return Err(invalid("message is not signed")),
+        };
+        if canonical(&rr.name) != self.name || canonical(&tsig.algorithm) != HMAC_SHA256 {
+            return Err(tsig_error(BADKEY));
+        }
+
+        // the MAC covers the message as it was before signing
+        let mut header = Header::from(raw);
+        header.id = tsig.original_id;
+        header.ar_count -= 1;
+        let mut unsigned: BytesMut = header.into();
+        unsigned.put(&raw[12..start]);
+
+        let mac = self.hmac(request_mac, &unsigned, &tsig);
+        mac.verify(&tsig.mac).map_err(|_| tsig_error(BADSIG))?;
+
+        let skew = (now() as i64 - tsig.time_signed as i64).abs();
+        if skew > tsig.fudge as i64 {
+            return Err(tsig_error(BADTIME));
+        }
+
+        let mut message = Message::parse(raw)?;
+        message.additionals.pop();
+        message.header.ar_count -= 1;
+        Ok((message, tsig.mac))
+    }
+
+    fn digest(&self, request_mac: Option<&[u8]>, message: &[u8], tsig: &TSIG) -> Vec<u8> {
+        self.hmac(request_mac, message, tsig)
+            .finalize()
+            .into_bytes()
+            .to_vec()
+    }
+
+    /// HMAC over the request MAC, message and TSIG variables (RFC 8945 4.3)
+    fn hmac(&self, request_mac: Option<&[u8]>, message: &[u8], tsig: &TSIG) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.secret).unwrap();
+        if let Some(request_mac) = request_mac {
+            mac.update(&(request_mac.len() as u16).to_be_bytes());
+            mac.update(request_mac);
+        }
+        mac.update(message);
+
+        let mut variables = BytesMut::new();
+        write_name(&mut variables, &self.name);
+        variables.put_u16(Class::ANY.into());
+        variables.put_u32(0);
+        write_name(&mut variables, &canonical(&tsig.algorithm));
+        variables.put_u16((tsig.time_signed >> 32) as u16);
+        variables.put_u32(tsig.time_signed as u32);
+        variables.put_u16(tsig.fudge);
+        variables.put_u16(tsig.error);
+        variables.put_u16(tsig.other.len() as u16);
+        variables.put(&tsig.other[..]);
+        mac.update(&variables);
+        mac
+    }
+}
+
+/// offset and content of the last resource record in `raw`
+fn last_record(raw: &[u8]) -> io::Result<(usize, ResourceRecord)> {
+    if raw.len() < 12 {
+        return Err(invalid("message too short"));
+    }
+    let header = Header::from(raw);
+    if header.ar_count == 0 {
+        return Err(invalid("message is not signed"));
+    }
+
+    let mut offset = 12;
+    for _ in 0..header.qd_count {
+        offset += Question::parse(raw, offset)?.1;
+    }
+    let records = header.an_count as usize + header.ns_count as usize + header.ar_count as usize;
+    for _ in 1..records {
+        offset += ResourceRecord::parse(raw, offset)?.1;
+    }
+    Ok((offset, ResourceRecord::parse(raw, offset)?.0))
+}
+
+/// lowercase name with a trailing dot
+fn canonical(name: &str) -> String {
+    format!("{}.", name.trim_end_matches('.').to_ascii_lowercase())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn tsig_error(code: u16) -> io::Error {
+    invalid(match code {
+        BADSIG => "TSIG signature does not match",
+        BADKEY => "TSIG key is not recognized",
+        _ => "TSIG time is out of range",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::message::FlagRCode;
+    use crate::base::dns::types::QType;
+
+    fn query() -> Message {
+        let mut message = Message::new(Header::default(), false);
+        message.add_question(Question::new("glow.lan", QType::Type(Type::A)));
+        message
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let key = TsigKey::new("Glow-Key", b"secret");
+        let (raw, mac) = key.sign(query(), None);
+        assert_eq!(mac.len(), 32);
+
+        let (message, verified) = key.verify(&raw, None).unwrap();
+        assert_eq!(verified, mac);
+        assert_eq!(message.questions[0].name(), "glow.lan.");
+        assert!(message.additionals.is_empty());
+
+        let parsed = Message::from(&raw[..]);
+        let rr = &parsed.additionals[0];
+        assert_eq!(rr.name, "glow-key.");
+        assert!(rr.class == Class::ANY);
+        match &rr.r_data {
+            RData::TSIG(tsig) => {
+                assert_eq!(tsig.algorithm, HMAC_SHA256);
+                assert_eq!(tsig.original_id, parsed.header.id);
+            }
+            _ => panic!("TSIG record expected"),
+        }
+
+        // responses are chained to the request MAC
+        let (response, _) = key.sign(message.reply(FlagRCode::NoError), Some(&mac));
+        assert!(key.verify(&response, Some(&mac)).is_ok());
+        assert!(key.verify(&response, None).is_err());
+    }
+
+    #[test]
+    fn test_verify_errors() {
+        let key = TsigKey::new("glow-key", b"secret");
+        let (raw, _) = key.sign(query(), None);
+
+        let mut tampered = raw.clone();
+        tampered[13] ^= 1;
+        let err = key.verify(&tampered, None).unwrap_err();
+        assert_eq!(err.to_string(), tsig_error(BADSIG).to_string());
+
+        let other = TsigKey::new("glow-key", b"another secret");
+        assert!(other.verify(&raw, None).is_err());
+        let other = TsigKey::new("other-key", b"secret");
+        let err = other.verify(&raw, None).unwrap_err();
+        assert_eq!(err.to_string(), tsig_error(BADKEY).to_string());
+
+        let (old, _) = key.sign_at(query(), None, now() - 3600);
+        let err = key.verify(&old, None).unwrap_err();
+        assert_eq!(err.to_string(), tsig_error(BADTIME).to_string());
+
+        let unsigned: BytesMut = query().into();
+        assert!(key.verify(&unsigned, None).is_err());
+    }
+}
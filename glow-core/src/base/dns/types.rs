@@ -1,8 +1,10 @@
+use std::fmt;
+
 /// References: https://en.wikipedia.org/wiki/List_of_DNS_record_types and RFCs
 
 /// TYPE fields are used in resource records.
 /// Note that these types are a subset of QTYPEs.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum Type {
     /// 1 a host address
@@ -67,6 +69,12 @@ pub enum Type {
     AAAA = 28,
     /// 29 location record
     LOC = 29,
+    /// 33 service locator (RFC 2782)
+    SRV = 33,
+    /// 250 transaction signature (RFC 8945)
+    TSIG = 250,
+    /// 255 all records, only used by UPDATE messages (RFC 2136)
+    ANY = 255,
 }
 
 impl Into<u16> for Type {
@@ -110,18 +118,65 @@ impl From<u16> for Type {
             27 => Type::GPOS,
             28 => Type::AAAA,
             29 => Type::LOC,
+            33 => Type::SRV,
+            250 => Type::TSIG,
+            255 => Type::ANY,
             _ => Type::A, // FIXME
         }
     }
 }
 
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::A => "A",
+            Type::NS => "NS",
+            #[allow(deprecated)]
+            Type::MD => "MD",
+            #[allow(deprecated)]
+            Type::MF => "MF",
+            Type::CNAME => "CNAME",
+            Type::SOA => "SOA",
+            Type::MB => "MB",
+            Type::MG => "MG",
+            Type::MR => "MR",
+            Type::NULL => "NULL",
+            #[allow(deprecated)]
+            Type::WKS => "WKS",
+            Type::PTR => "PTR",
+            Type::HINFO => "HINFO",
+            Type::MINFO => "MINFO",
+            Type::MX => "MX",
+            Type::TXT => "TXT",
+            Type::RP => "RP",
+            Type::AFSDB => "AFSDB",
+            Type::X25 => "X25",
+            Type::ISDN => "ISDN",
+            Type::RT => "RT",
+            Type::NSAP => "NSAP",
+            Type::NSAP_PTR => "NSAP-PTR",
+            Type::SIG => "SIG",
+            Type::KEY => "KEY",
+            Type::PX => "PX",
+            Type::GPOS => "GPOS",
+            Type::AAAA => "AAAA",
+            Type::LOC => "LOC",
+            Type::SRV => "SRV",
+            Type::TSIG => "TSIG",
+            Type::ANY => "ANY",
+        })
+    }
+}
+
 /// QTYPE fields appear in the question part of a query. QTYPES are a
 /// superset of TYPEs, hence all TYPEs are valid QTYPEs. In addition, the
 /// following QTYPEs are defined:
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum QType {
     Type(Type),
+    /// 251 A request for an incremental transfer of a zone
+    IXFR,
     /// 252 A request for a transfer of an entire zone
     AXFR,
     /// 253 A request for mailbox-related records (MB, MG or MR)
@@ -137,6 +192,7 @@ impl Into<u16> for QType {
     fn into(self) -> u16 {
         match self {
             QType::Type(t) => t.into(),
+            QType::IXFR => 251,
             QType::AXFR => 252,
             QType::MAILB => 253,
             #[allow(deprecated)]
@@ -149,6 +205,7 @@ impl Into<u16> for QType {
 impl From<u16> for QType {
     fn from(raw: u16) -> Self {
         match raw {
+            251 => QType::IXFR,
             252 => QType::AXFR,
             253 => QType::MAILB,
             #[allow(deprecated)]
@@ -158,3 +215,17 @@ impl From<u16> for QType {
         }
     }
 }
+
+impl fmt::Display for QType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QType::Type(t) => t.fmt(f),
+            QType::IXFR => f.write_str("IXFR"),
+            QType::AXFR => f.write_str("AXFR"),
+            QType::MAILB => f.write_str("MAILB"),
+            #[allow(deprecated)]
+            QType::MAILA => f.write_str("MAILA"),
+            QType::ANY => f.write_str("ANY"),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use super::class::Class;
+use super::message::{FlagOpCode, Header, HeaderFlag, Message, Question, RData, ResourceRecord};
+use super::types::{QType, Type};
+
+/// A dynamic update message (RFC 2136).
+/// The zone, prerequisite and update sections are carried in the
+/// question, answer and authority sections of a `Message`.
+pub struct Update {
+    message: Message,
+}
+
+impl Update {
+    pub fn new(zone: &str) -> Update {
+        let mut header = Header::default();
+        header.set_flag(HeaderFlag {
+            opcode: FlagOpCode::Update,
+            recursion_desired: false,
+            ..Default::default()
+        });
+        let mut message = Message::new(header, false);
+        message.add_question(Question::new(zone, QType::Type(Type::SOA)));
+        Update { message }
+    }
+
+    /// require an RRset of `r_type` to exist at `name`
+    pub fn rrset_exists(&mut self, name: &str, r_type: Type) -> &Update {
+        self.prerequisite(meta(name, r_type, Class::ANY))
+    }
+
+    /// require the RRset of `rr` to exist with exactly this value
+    pub fn rrset_exists_value(&mut self, rr: &ResourceRecord) -> &Update {
+        let mut rr = rr.clone();
+        rr.ttl = 0;
+        self.prerequisite(rr)
+    }
+
+    /// require no RRset of `r_type` at `name`
+    pub fn rrset_absent(&mut self, name: &str, r_type: Type) -> &Update {
+        self.prerequisite(meta(name, r_type, Class::NONE))
+    }
+
+    /// require `name` to own at least one record
+    pub fn name_in_use(&mut self, name: &str) -> &Update {
+        self.prerequisite(meta(name, Type::ANY, Class::ANY))
+    }
+
+    /// require `name` to own no records
+    pub fn name_absent(&mut self, name: &str) -> &Update {
+        self.prerequisite(meta(name, Type::ANY, Class::NONE))
+    }
+
+    fn prerequisite(&mut self, rr: ResourceRecord) -> &Update {
+        self.message.add_answer(rr);
+        self
+    }
+
+    /// add `rr` to its RRset
+    pub fn add(&mut self, rr: ResourceRecord) -> &Update {
+        self.message.add_authority(rr);
+        self
+    }
+
+    /// delete the RRset of `r_type` at `name`
+    pub fn delete_rrset(&mut self, name: &str, r_type: Type) -> &Update {
+        self.add(meta(name, r_type, Class::ANY))
+    }
+
+    /// delete all RRsets at `name`
+    pub fn delete_name(&mut self, name: &str) -> &Update {
+        self.add(meta(name, Type::ANY, Class::ANY))
+    }
+
+    /// delete `rr` from its RRset
+    pub fn delete(&mut self, rr: &ResourceRecord) -> &Update {
+        let mut rr = rr.clone();
+        rr.class = Class::NONE;
+        rr.ttl = 0;
+        self.add(rr)
+    }
+
+    pub fn message(self) -> Message {
+        self.message
+    }
+}
+
+/// record without RDATA, its class carries the meaning
+fn meta(name: &str, r_type: Type, class: Class) -> ResourceRecord {
+    let r_data = RData::Unknown {
+        r_type: r_type.into(),
+        data: Vec::new(),
+    };
+    let mut rr = ResourceRecord::new(name, r_type, 0, r_data);
+    rr.class = class;
+    rr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::message::FlagRCode;
+    use crate::base::dns::sync::DNSClient;
+    use crate::base::dns::tsig::TsigKey;
+    use bytes::BytesMut;
+    use std::net::UdpSocket;
+    use std::thread;
+
+    fn register() -> Update {
+        let addr = RData::A("192.168.1.20".parse().unwrap());
+        let mut update = Update::new("lan");
+        update.name_absent("laptop.lan");
+        update.delete_rrset("laptop.lan", Type::A);
+        update.add(ResourceRecord::new("laptop.lan", Type::A, 300, addr));
+        update
+    }
+
+    #[test]
+    fn test_encode() {
+        let raw: BytesMut = register().message().into();
+        let message = Message::from(&raw[..]);
+        assert!(message.header.flag().opcode == FlagOpCode::Update);
+        assert!(message.questions[0].q_type() == QType::Type(Type::SOA));
+
+        let prerequisite = &message.answers[0];
+        assert!(prerequisite.r_type == Type::ANY && prerequisite.class == Class::NONE);
+        assert!(
+            prerequisite.r_data
+                == RData::Unknown {
+                    r_type: 255,
+                    data: Vec::new()
+                }
+        );
+
+        let delete = &message.authorities[0];
+        assert!(delete.r_type == Type::A && delete.class == Class::ANY);
+        assert_eq!(delete.ttl, 0);
+        let add = &message.authorities[1];
+        assert!(add.class == Class::IN && add.ttl == 300);
+        assert!(add.r_data == RData::A("192.168.1.20".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_signed_update() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let key = TsigKey::new("dhcp-key", b"0123456789abcdef");
+            let mut buffer = [0u8; 1500];
+            let (size, src) = server.recv_from(&mut buffer).unwrap();
+            let (update, mac) = key.verify(&buffer[..size], None).unwrap();
+
+            let (response, _) = key.sign(update.reply(FlagRCode::NoError), Some(&mac));
+            server.send_to(&response, src).unwrap();
+            update
+        });
+
+        let mut client = DNSClient::new("127.0.0.1:0");
+        client.upstream(&addr);
+        let key = TsigKey::new("dhcp-key", b"0123456789abcdef");
+        let rcode = client.update(register(), Some(&key)).unwrap();
+        assert!(rcode == FlagRCode::NoError);
+
+        let update = handle.join().unwrap();
+        assert_eq!(update.questions[0].name(), "lan.");
+        assert_eq!(update.answers.len(), 1);
+        assert_eq!(update.authorities.len(), 2);
+    }
+
+    #[test]
+    fn test_forged_response() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let key = TsigKey::new("dhcp-key", b"not the shared secret");
+            let mut buffer = [0u8; 1500];
+            let (size, src) = server.recv_from(&mut buffer).unwrap();
+            let update = Message::from(&buffer[..size]);
+            let (response, _) = key.sign(update.reply(FlagRCode::NoError), None);
+            server.send_to(&response, src).unwrap();
+        });
+
+        let mut client = DNSClient::new("127.0.0.1:0");
+        client.upstream(&addr);
+        let key = TsigKey::new("dhcp-key", b"0123456789abcdef");
+        assert!(client.update(register(), Some(&key)).is_err());
+    }
+}
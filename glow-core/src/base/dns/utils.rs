@@ -0,0 +1,21 @@
+use std::net::IpAddr;
+
+/// build the name used for reverse lookup of `addr`
+/// IPv4 addresses map to in-addr.arpa (RFC 1035 3.5),
+/// IPv6 addresses map to ip6.arpa nibbles (RFC 3596 2.5)
+pub fn reverse_name(addr: &IpAddr) -> String {
+    match addr {
+        IpAddr::V4(addr) => {
+            let o = addr.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(addr) => {
+            let mut name = String::with_capacity(72);
+            for octet in addr.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", octet & 0x0f, octet >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use super::class::{Class, QClass};
+use super::message::{Header, ResourceRecord, MAX_NAME_LEN};
+use super::types::{QType, Type};
+use bytes::{BufMut, BytesMut};
+use glow_utils::binary::parse_u32;
+use glow_utils::u8_merge;
+use std::fmt;
+use std::io;
+use std::str;
+
+/// A message borrowed from its wire format.
+/// The section layout is validated once by `parse`, names and
+/// records are only decoded when they are iterated over.
+#[derive(Copy, Clone)]
+pub struct MessageRef<'a> {
+    raw: &'a [u8],
+    header: Header,
+    /// offsets of the question, answer, authority and additional sections
+    sections: [usize; 4],
+}
+
+impl<'a> MessageRef<'a> {
+    pub fn parse(raw: &'a [u8]) -> io::Result<MessageRef<'a>> {
+        if raw.len() < 12 {
+            return Err(invalid("message too short"));
+        }
+        let header = Header::from(raw);
+
+        let mut offset = 12;
+        for _ in 0..header.qd_count {
+            offset = skip_name(raw, offset)? + 4;
+        }
+        if offset > raw.len() {
+            return Err(invalid("question is truncated"));
+        }
+
+        let mut sections = [12, offset, 0, 0];
+        let counts = [header.an_count, header.ns_count, header.ar_count];
+        for (i, count) in counts.iter().enumerate() {
+            for _ in 0..*count {
+                offset = skip_name(raw, offset)?;
+                let fixed = raw
+                    .get(offset..(offset + 10))
+                    .ok_or_else(|| invalid("record is truncated"))?;
+                offset += 10 + u8_merge!(fixed[8], fixed[9]) as usize;
+                if offset > raw.len() {
+                    return Err(invalid("record data is truncated"));
+                }
+            }
+            if i < 2 {
+                sections[i + 2] = offset;
+            }
+        }
+
+        Ok(MessageRef {
+            raw,
+            header,
+            sections,
+        })
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// the question section as on the wire
+    pub fn question_section(&self) -> &'a [u8] {
+        &self.raw[self.sections[0]..self.sections[1]]
+    }
+
+    pub fn questions(&self) -> Questions<'a> {
+        Questions {
+            raw: self.raw,
+            offset: self.sections[0],
+            remaining: self.header.qd_count,
+        }
+    }
+
+    pub fn answers(&self) -> Records<'a> {
+        self.records(1, self.header.an_count)
+    }
+
+    pub fn authorities(&self) -> Records<'a> {
+        self.records(2, self.header.ns_count)
+    }
+
+    pub fn additionals(&self) -> Records<'a> {
+        self.records(3, self.header.ar_count)
+    }
+
+    fn records(&self, section: usize, count: u16) -> Records<'a> {
+        Records {
+            raw: self.raw,
+            offset: self.sections[section],
+            remaining: count,
+        }
+    }
+}
+
+/// Iterator over the question section
+pub struct Questions<'a> {
+    raw: &'a [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> Iterator for Questions<'a> {
+    type Item = QuestionRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        // the layout has been validated by MessageRef::parse
+        let name = NameRef {
+            raw: self.raw,
+            offset: self.offset,
+        };
+        let offset = skip_name(self.raw, self.offset).ok()?;
+        self.offset = offset + 4;
+        Some(QuestionRef {
+            name,
+            q_type: u8_merge!(self.raw[offset], self.raw[offset + 1]),
+            q_class: u8_merge!(self.raw[offset + 2], self.raw[offset + 3]),
+        })
+    }
+}
+
+/// Iterator over the records of a section
+pub struct Records<'a> {
+    raw: &'a [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = RecordRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        // the layout has been validated by MessageRef::parse
+        let start = self.offset;
+        let offset = skip_name(self.raw, start).ok()?;
+        let fixed = &self.raw[offset..(offset + 10)];
+        let rd_length = u8_merge!(fixed[8], fixed[9]) as usize;
+        self.offset = offset + 10 + rd_length;
+        Some(RecordRef {
+            raw: self.raw,
+            start,
+            name: NameRef {
+                raw: self.raw,
+                offset: start,
+            },
+            r_type: u8_merge!(fixed[0], fixed[1]),
+            class: u8_merge!(fixed[2], fixed[3]),
+            ttl: parse_u32(&fixed[4..8], true),
+            r_data: &self.raw[(offset + 10)..self.offset],
+        })
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct QuestionRef<'a> {
+    name: NameRef<'a>,
+    q_type: u16,
+    q_class: u16,
+}
+
+impl<'a> QuestionRef<'a> {
+    pub fn name(&self) -> NameRef<'a> {
+        self.name
+    }
+
+    pub fn q_type(&self) -> QType {
+        QType::from(self.q_type)
+    }
+
+    pub fn q_class(&self) -> QClass {
+        QClass::from(self.q_class & 0x7fff)
+    }
+
+    pub fn unicast_response(&self) -> bool {
+        self.q_class & 0x8000 != 0
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RecordRef<'a> {
+    raw: &'a [u8],
+    start: usize,
+    name: NameRef<'a>,
+    r_type: u16,
+    class: u16,
+    ttl: u32,
+    r_data: &'a [u8],
+}
+
+impl<'a> RecordRef<'a> {
+    pub fn name(&self) -> NameRef<'a> {
+        self.name
+    }
+
+    /// the type, `None` if it is not known
+    pub fn r_type(&self) -> Option<Type> {
+        let r_type = Type::from(self.r_type);
+        if Into::<u16>::into(r_type) == self.r_type {
+            Some(r_type)
+        } else {
+            None
+        }
+    }
+
+    pub fn type_code(&self) -> u16 {
+        self.r_type
+    }
+
+    pub fn class(&self) -> Class {
+        Class::from(self.class & 0x7fff)
+    }
+
+    pub fn cache_flush(&self) -> bool {
+        self.class & 0x8000 != 0
+    }
+
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// RDATA as on the wire, names in it may be compressed
+    pub fn r_data(&self) -> &'a [u8] {
+        self.r_data
+    }
+
+    /// append this record to `m` uncompressed. Names in the RDATA of
+    /// types that may compress them (RFC 3597 4) are expanded, other
+    /// RDATA is copied as is.
+    pub fn append_to(&self, m: &mut BytesMut) -> io::Result<()> {
+        self.name.append_to(m)?;
+        m.put_u16(self.r_type);
+        m.put_u16(self.class);
+        m.put_u32(self.ttl);
+
+        // octets before the names and the number of names
+        let (fixed, names) = match self.r_type() {
+            #[allow(deprecated)]
+            Some(Type::NS) | Some(Type::CNAME) | Some(Type::PTR) | Some(Type::MD)
+            | Some(Type::MF) | Some(Type::MB) | Some(Type::MG) | Some(Type::MR) => (0, 1),
+            Some(Type::SOA) | Some(Type::MINFO) => (0, 2),
+            Some(Type::MX) => (2, 1),
+            // the decoder accepts compressed SRV targets too
+            Some(Type::SRV) => (6, 1),
+            _ => (0, 0),
+        };
+        let start = self.r_data.as_ptr() as usize - self.raw.as_ptr() as usize;
+        let end = start + self.r_data.len();
+        let mut offset = start + fixed;
+        if offset > end {
+            return Err(invalid("record data is truncated"));
+        }
+
+        let length_offset = m.len();
+        m.put_u16(0);
+        m.put(&self.r_data[..fixed]);
+        for _ in 0..names {
+            NameRef {
+                raw: self.raw,
+                offset,
+            }
+            .append_to(m)?;
+            offset = skip_name(self.raw, offset)?;
+        }
+        if offset > end {
+            return Err(invalid("name exceeds record data"));
+        }
+        m.put(&self.raw[offset..end]);
+
+        let length = m.len() - length_offset - 2;
+        if length > u16::MAX as usize {
+            return Err(invalid("record data is too long"));
+        }
+        m[length_offset..(length_offset + 2)].copy_from_slice(&(length as u16).to_be_bytes());
+        Ok(())
+    }
+
+    /// decode this record with the owned parser
+    pub fn to_owned(&self) -> io::Result<ResourceRecord> {
+        ResourceRecord::parse(self.raw, self.start).map(|(rr, _)| rr)
+    }
+}
+
+/// A possibly compressed name inside a message
+#[derive(Copy, Clone)]
+pub struct NameRef<'a> {
+    raw: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NameRef<'a> {
+    /// decode into a stack buffer, following compression pointers.
+    /// '.' and '\\' inside labels are escaped as `\.` and `\\`, so that
+    /// the label `www.bank` followed by `com` is not `www.bank.com.`
+    pub fn decode(&self) -> io::Result<Name> {
+        let mut name = Name {
+            buf: [0; 2 * MAX_NAME_LEN],
+            len: 0,
+        };
+        self.labels(|label| {
+            for &octet in label.iter() {
+                if octet == b'.' || octet == b'\\' {
+                    name.push(b'\\');
+                }
+                name.push(octet);
+            }
+            name.push(b'.');
+        })?;
+        str::from_utf8(name.as_bytes()).map_err(|_| invalid("name is not UTF-8"))?;
+        Ok(name)
+    }
+
+    /// append the name to `m` uncompressed, labels as they are
+    pub fn append_to(&self, m: &mut BytesMut) -> io::Result<()> {
+        self.labels(|label| {
+            m.put_u8(label.len() as u8);
+            m.put(label);
+        })?;
+        m.put_u8(0);
+        Ok(())
+    }
+
+    /// call `f` with each label, following compression pointers
+    fn labels<F: FnMut(&[u8])>(&self, mut f: F) -> io::Result<()> {
+        // octets of the name on the wire
+        let mut length = 0;
+        let mut offset = self.offset;
+        loop {
+            let len = *self
+                .raw
+                .get(offset)
+                .ok_or_else(|| invalid("name is truncated"))? as usize;
+            match len & 0b11000000 {
+                0 if len == 0 => return Ok(()),
+                0 => {
+                    let label = self
+                        .raw
+                        .get((offset + 1)..(offset + 1 + len))
+                        .ok_or_else(|| invalid("label is truncated"))?;
+                    length += len + 1;
+                    if length > MAX_NAME_LEN {
+                        return Err(invalid("name is too long"));
+                    }
+                    f(label);
+                    offset += len + 1;
+                }
+                0b11000000 => {
+                    let low = *self
+                        .raw
+                        .get(offset + 1)
+                        .ok_or_else(|| invalid("pointer is truncated"))?;
+                    let target = ((len & 0b00111111) << 8) | low as usize;
+                    // only pointing backwards guarantees termination
+                    if target >= offset {
+                        return Err(invalid("pointer does not point backwards"));
+                    }
+                    offset = target;
+                }
+                _ => return Err(invalid("unsupported label type")),
+            }
+        }
+    }
+
+    /// compare with `other` ignoring case and the trailing dot
+    pub fn eq_ignore_case(&self, other: &str) -> bool {
+        match self.decode() {
+            Ok(name) => name
+                .as_str()
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(other.trim_end_matches('.')),
+            Err(_) => false,
+        }
+    }
+}
+
+/// A decoded name with a trailing dot, the root is empty
+pub struct Name {
+    /// room for every octet escaped
+    buf: [u8; 2 * MAX_NAME_LEN],
+    len: usize,
+}
+
+impl Name {
+    fn push(&mut self, octet: u8) {
+        self.buf[self.len] = octet;
+        self.len += 1;
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn as_str(&self) -> &str {
+        // validated by NameRef::decode
+        str::from_utf8(self.as_bytes()).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// offset right after the name at `offset`, without following pointers
+fn skip_name(raw: &[u8], mut offset: usize) -> io::Result<usize> {
+    loop {
+        let len = *raw
+            .get(offset)
+            .ok_or_else(|| invalid("name is truncated"))? as usize;
+        match len & 0b11000000 {
+            0 if len == 0 => return Ok(offset + 1),
+            0 => offset += len + 1,
+            0b11000000 if offset + 1 < raw.len() => return Ok(offset + 2),
+            0b11000000 => return Err(invalid("pointer is truncated")),
+            _ => return Err(invalid("unsupported label type")),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::message::{Message, Question, RData};
+
+    /// response for glow.mmf.moe. A with a CNAME, names compressed
+    const RESPONSE: [u8; 75] = [
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, //
+        // question glow.mmf.moe. A IN
+        0x04, b'g', b'l', b'o', b'w', 0x03, b'm', b'm', b'f', 0x03, b'm', b'o', b'e', 0x00, 0x00,
+        0x01, 0x00, 0x01, //
+        // glow.mmf.moe. CNAME cdn.mmf.moe.
+        0xc0, 0x0c, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x06, 0x03, b'c', b'd',
+        b'n', 0xc0, 0x11, //
+        // cdn.mmf.moe. A 1.2.3.4
+        0xc0, 0x2a, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04, 1, 2, 3, 4,
+        // padding which is not part of any section
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn test_iterate() {
+        let message = MessageRef::parse(&RESPONSE).unwrap();
+        assert_eq!(message.header().id, 0x1234);
+
+        let question = message.questions().next().unwrap();
+        assert_eq!(question.name().decode().unwrap().as_str(), "glow.mmf.moe.");
+        assert!(question.q_type() == QType::Type(Type::A));
+        assert!(question.name().eq_ignore_case("GLOW.mmf.moe"));
+
+        let answers: Vec<_> = message.answers().collect();
+        assert_eq!(answers.len(), 2);
+        assert!(answers[0].r_type() == Some(Type::CNAME));
+        assert_eq!(answers[0].ttl(), 3600);
+        assert_eq!(answers[1].name().decode().unwrap().as_str(), "cdn.mmf.moe.");
+        assert_eq!(answers[1].r_data(), &[1, 2, 3, 4]);
+        assert!(answers[0].to_owned().unwrap().r_data == RData::CNAME("cdn.mmf.moe.".to_owned()));
+        assert_eq!(message.authorities().count(), 0);
+        assert_eq!(message.additionals().count(), 0);
+    }
+
+    #[test]
+    fn test_same_as_owned() {
+        let mut owned = Message::new(Header::default(), false);
+        owned.add_question(Question::new("glow.lan", QType::Type(Type::AAAA)));
+        owned.add_answer(ResourceRecord::new(
+            "glow.lan",
+            Type::AAAA,
+            60,
+            RData::AAAA("fd00::1".parse().unwrap()),
+        ));
+        owned.add_additional(ResourceRecord::new(
+            "glow.lan",
+            Type::TXT,
+            60,
+            RData::TXT(vec!["v=1".to_owned()]),
+        ));
+        let raw: BytesMut = owned.into();
+        let owned = Message::from(&raw[..]);
+        let borrowed = MessageRef::parse(&raw).unwrap();
+
+        let question = borrowed.questions().next().unwrap();
+        assert_eq!(
+            question.name().decode().unwrap().as_str(),
+            owned.questions[0].name()
+        );
+        let records = borrowed.answers().chain(borrowed.additionals());
+        for (borrowed, owned) in records.zip(owned.answers.iter().chain(owned.additionals.iter())) {
+            assert_eq!(borrowed.name().decode().unwrap().as_str(), owned.name);
+            assert!(borrowed.r_type() == Some(owned.r_type));
+            assert!(borrowed.to_owned().unwrap().r_data == owned.r_data);
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        // truncated anywhere inside the sections
+        for len in 0..64 {
+            assert!(MessageRef::parse(&RESPONSE[..len]).is_err());
+        }
+
+        // pointer to itself
+        let mut looped = RESPONSE;
+        looped[48] = 0xc0;
+        looped[49] = 48;
+        let message = MessageRef::parse(&looped).unwrap();
+        assert!(message.answers().nth(1).unwrap().name().decode().is_err());
+
+        // name longer than 255 octets
+        let mut long = vec![0u8; 12];
+        long[5] = 1;
+        for _ in 0..5 {
+            long.push(63);
+            long.extend_from_slice(&[b'a'; 63]);
+        }
+        long.extend_from_slice(&[0, 0, 1, 0, 1]);
+        let message = MessageRef::parse(&long).unwrap();
+        assert!(message.questions().next().unwrap().name().decode().is_err());
+    }
+
+    #[test]
+    fn test_escaped_labels() {
+        let question = |name: &[u8]| {
+            let mut raw = vec![0u8; 12];
+            raw[5] = 1;
+            raw.extend_from_slice(name);
+            raw.extend_from_slice(&[0, 1, 0, 1]);
+            raw
+        };
+        let decode = |raw: &[u8]| {
+            let message = MessageRef::parse(raw).unwrap();
+            let name = message.questions().next().unwrap().name().decode();
+            name.unwrap().to_string()
+        };
+        // one label with a dot is not two labels
+        let dotted = question(b"\x08www.bank\x03com\x00");
+        assert_eq!(decode(&dotted), "www\\.bank.com.");
+        assert_ne!(
+            decode(&dotted),
+            decode(&question(b"\x03www\x04bank\x03com\x00"))
+        );
+        assert_eq!(decode(&question(b"\x03a\\b\x00")), "a\\\\b.");
+
+        // escapes do not count against the length of the name
+        let mut long = Vec::new();
+        for _ in 0..3 {
+            long.push(63);
+            long.extend_from_slice(&[b'.'; 63]);
+        }
+        long.extend_from_slice(&[61]);
+        long.extend_from_slice(&[b'.'; 61]);
+        long.push(0);
+        assert_eq!(decode(&question(&long)).len(), 2 * 250 + 4);
+    }
+}
@@ -0,0 +1,637 @@
+use super::message::{check_name, FlagRCode, HeaderFlag, Message, RData, ResourceRecord, SOA};
+use super::types::{QType, Type};
+use glow_utils::fs::read_file;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+/// TTL used when neither $TTL nor a previous record gives one
+const DEFAULT_TTL: u32 = 3600;
+
+/// Maximum number of CNAMEs followed inside a zone
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// Result of looking up a name in a zone.
+/// Every variant carries the CNAME chain which led to the final name.
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum Lookup {
+    Answer(Vec<ResourceRecord>),
+    NoData(Vec<ResourceRecord>),
+    NxDomain(Vec<ResourceRecord>),
+}
+
+/// An in-memory authoritative zone
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Zone {
+    /// absolute lowercased name with trailing '.'
+    origin: String,
+    soa: ResourceRecord,
+
+    /// owner name -> records, owner names are absolute and lowercased
+    records: HashMap<String, Vec<ResourceRecord>>,
+
+    /// owner names and empty non-terminals between them and origin
+    names: HashSet<String>,
+}
+
+impl Zone {
+    /// load a zone file, relative names in it are relative to `origin`
+    /// until a $ORIGIN directive
+    pub fn load<P: AsRef<Path>>(path: P, origin: &str) -> io::Result<Zone> {
+        let content = read_file(path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Zone::parse(&content, origin)
+    }
+
+    /// parse a zone in presentation format (RFC 1035 5.1),
+    /// the zone origin is the owner of its only SOA record
+    pub fn parse(content: &str, origin: &str) -> io::Result<Zone> {
+        Zone::from_records(parse_records(content, origin)?)
+    }
+
+    /// build a zone from its records, which must include exactly one SOA
+    pub fn from_records(records: Vec<ResourceRecord>) -> io::Result<Zone> {
+        let records: Vec<ResourceRecord> = records
+            .into_iter()
+            .map(|mut rr| {
+                rr.name = normalize(&rr.name);
+                rr
+            })
+            .collect();
+
+        let mut soa = records.iter().filter(|rr| rr.r_type == Type::SOA);
+        let soa = match (soa.next(), soa.next()) {
+            (Some(soa), None) => soa.clone(),
+            _ => return Err(invalid(0, "a zone must have exactly one SOA record")),
+        };
+
+        let mut zone = Zone {
+            origin: soa.name.clone(),
+            soa,
+            records: HashMap::new(),
+            names: HashSet::new(),
+        };
+        zone.names.insert(zone.origin.clone());
+        for rr in records {
+            if !zone.contains(&rr.name) {
+                return Err(invalid(0, &format!("{} is out of zone", rr.name)));
+            }
+
+            // register empty non-terminals up to origin
+            let mut name = rr.name.as_str();
+            while name != zone.origin && zone.names.insert(name.to_owned()) {
+                name = &name[(name.find('.').unwrap() + 1)..];
+            }
+            zone.records.entry(rr.name.clone()).or_default().push(rr);
+        }
+        Ok(zone)
+    }
+
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    pub fn soa(&self) -> &ResourceRecord {
+        &self.soa
+    }
+
+    /// serial number of the zone SOA
+    pub fn serial(&self) -> u32 {
+        match &self.soa.r_data {
+            RData::SOA(soa) => soa.serial,
+            _ => 0,
+        }
+    }
+
+    /// all records of the zone, including SOA
+    pub fn records(&self) -> Vec<ResourceRecord> {
+        self.records.values().flatten().cloned().collect()
+    }
+
+    /// whether `name` is at or below the zone origin
+    pub fn contains(&self, name: &str) -> bool {
+        let name = normalize(name);
+        self.origin == "." || name == self.origin || name.ends_with(&format!(".{}", self.origin))
+    }
+
+    pub fn lookup(&self, name: &str, qtype: QType) -> Lookup {
+        let mut chain = Vec::new();
+        let mut name = normalize(name);
+        loop {
+            let rrs = match self.find(&name) {
+                Some(rrs) => rrs,
+                None if self.names.contains(&name) => return Lookup::NoData(chain),
+                None => return Lookup::NxDomain(chain),
+            };
+
+            let answers: Vec<ResourceRecord> = rrs
+                .iter()
+                .filter(|rr| matches_type(rr, qtype))
+                .cloned()
+                .collect();
+            if !answers.is_empty() {
+                chain.extend(answers);
+                return Lookup::Answer(chain);
+            }
+
+            // follow CNAME while it stays in this zone
+            let target = rrs.iter().find_map(|rr| match &rr.r_data {
+                RData::CNAME(target) => Some((rr.clone(), normalize(target))),
+                _ => None,
+            });
+            match target {
+                Some((cname, target)) => {
+                    chain.push(cname);
+                    if !self.contains(&target) || chain.len() >= MAX_CNAME_CHAIN {
+                        return Lookup::Answer(chain);
+                    }
+                    name = target;
+                }
+                None => return Lookup::NoData(chain),
+            }
+        }
+    }
+
+    /// records of `name`, synthesized from a wildcard if needed (RFC 4592)
+    fn find(&self, name: &str) -> Option<Vec<ResourceRecord>> {
+        if let Some(rrs) = self.records.get(name) {
+            return Some(rrs.clone());
+        }
+        if self.names.contains(name) || !self.contains(name) {
+            return None;
+        }
+
+        // the closest encloser is the longest existing ancestor
+        let mut encloser = name;
+        while !self.names.contains(encloser) {
+            encloser = &encloser[(encloser.find('.')? + 1)..];
+        }
+        let wildcard = format!("*.{}", encloser);
+        self.records.get(&wildcard).map(|rrs| {
+            rrs.iter()
+                .map(|rr| {
+                    let mut rr = rr.clone();
+                    rr.name = name.to_owned();
+                    rr
+                })
+                .collect()
+        })
+    }
+
+    /// build the authoritative response to `query`
+    pub fn respond(&self, query: &Message) -> Message {
+        let q = &query.questions[0];
+        let (rcode, answers, negative) = match self.lookup(q.name(), q.q_type()) {
+            Lookup::Answer(answers) => (FlagRCode::NoError, answers, false),
+            Lookup::NoData(answers) => (FlagRCode::NoError, answers, true),
+            Lookup::NxDomain(answers) => (FlagRCode::NameError, answers, true),
+        };
+
+        let mut response = query.reply(rcode);
+        let mut flag: HeaderFlag = response.header.flag();
+        flag.authoritative_answer = true;
+        response.header.set_flag(flag);
+        for rr in answers {
+            response.add_answer(rr);
+        }
+
+        // negative responses carry SOA for caching (RFC 2308 3)
+        if negative {
+            let mut soa = self.soa.clone();
+            if let RData::SOA(data) = &self.soa.r_data {
+                soa.ttl = soa.ttl.min(data.minimum);
+            }
+            response.add_authority(soa);
+        }
+        response
+    }
+}
+
+fn matches_type(rr: &ResourceRecord, qtype: QType) -> bool {
+    match qtype {
+        QType::ANY => true,
+        QType::Type(t) => Into::<u16>::into(t) == rr.type_code(),
+        _ => false,
+    }
+}
+
+fn normalize(name: &str) -> String {
+    let mut name = name.to_ascii_lowercase();
+    if !name.ends_with('.') {
+        name.push('.');
+    }
+    name
+}
+
+fn invalid(line: usize, msg: &str) -> io::Error {
+    let msg = if line > 0 {
+        format!("zone line {}: {}", line, msg)
+    } else {
+        msg.to_owned()
+    };
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A logical line of a zone file, with parentheses joined
+struct Entry {
+    line: usize,
+    /// a blank owner field means the previous owner
+    blank_owner: bool,
+    tokens: Vec<String>,
+}
+
+/// split zone file content into entries, removing comments and quotes
+fn tokenize(content: &str) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut current: Option<Entry> = None;
+    let mut depth = 0;
+
+    for (i, line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        let entry = current.get_or_insert_with(|| Entry {
+            line: line_no,
+            blank_owner: line.starts_with(|c: char| c.is_whitespace()),
+            tokens: Vec::new(),
+        });
+
+        let mut chars = line.chars().peekable();
+        let mut token = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                ';' => break,
+                '(' => depth += 1,
+                ')' if depth == 0 => return Err(invalid(line_no, "unbalanced ')'")),
+                ')' => depth -= 1,
+                '"' => {
+                    let mut quoted = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => quoted.extend(chars.next()),
+                            Some(c) => quoted.push(c),
+                            None => return Err(invalid(line_no, "unterminated string")),
+                        }
+                    }
+                    entry.tokens.push(quoted);
+                    continue;
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    token.push(c);
+                    if chars.peek().map_or(true, |n| {
+                        n.is_whitespace() || *n == ';' || *n == '(' || *n == ')'
+                    }) {
+                        entry.tokens.push(std::mem::take(&mut token));
+                    }
+                }
+            }
+        }
+
+        if depth == 0 {
+            let entry = current.take().unwrap();
+            if !entry.tokens.is_empty() {
+                entries.push(entry);
+            }
+        }
+    }
+
+    if depth != 0 {
+        return Err(invalid(0, "unbalanced '('"));
+    }
+    Ok(entries)
+}
+
+/// parse all records of a zone file
+fn parse_records(content: &str, origin: &str) -> io::Result<Vec<ResourceRecord>> {
+    let mut origin = normalize(origin);
+    let mut default_ttl: Option<u32> = None;
+    let mut last_ttl: Option<u32> = None;
+    let mut last_owner: Option<String> = None;
+    let mut records = Vec::new();
+
+    for entry in tokenize(content)? {
+        let line = entry.line;
+        let mut tokens = entry.tokens.iter().map(String::as_str).peekable();
+
+        let owner = if entry.blank_owner {
+            match &last_owner {
+                Some(owner) => owner.clone(),
+                None => return Err(invalid(line, "no previous owner name")),
+            }
+        } else {
+            match tokens.next().unwrap() {
+                "$ORIGIN" => {
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| invalid(line, "missing origin"))?;
+                    origin = absolute(name, &origin);
+                    continue;
+                }
+                "$TTL" => {
+                    let ttl = tokens.next().ok_or_else(|| invalid(line, "missing ttl"))?;
+                    default_ttl = Some(parse_ttl(ttl).ok_or_else(|| invalid(line, "bad ttl"))?);
+                    continue;
+                }
+                directive if directive.starts_with('$') => {
+                    return Err(invalid(line, &format!("unsupported {}", directive)));
+                }
+                owner => absolute(owner, &origin).to_ascii_lowercase(),
+            }
+        };
+        last_owner = Some(owner.clone());
+
+        // [ttl] [class] or [class] [ttl]
+        let mut ttl = None;
+        while let Some(token) = tokens.peek() {
+            if let Some(t) = parse_ttl(token) {
+                ttl = Some(t);
+            } else if token.eq_ignore_ascii_case("IN") {
+            } else {
+                break;
+            }
+            tokens.next();
+        }
+        let ttl = ttl.or(default_ttl).or(last_ttl).unwrap_or(DEFAULT_TTL);
+        last_ttl = Some(ttl);
+
+        let r_type = tokens.next().ok_or_else(|| invalid(line, "missing type"))?;
+        let rdata: Vec<&str> = tokens.collect();
+        let field = |i: usize| -> io::Result<&str> {
+            rdata
+                .get(i)
+                .copied()
+                .ok_or_else(|| invalid(line, &format!("missing {} rdata", r_type)))
+        };
+        let number = |s: &str| -> io::Result<u32> {
+            s.parse()
+                .map_err(|_| invalid(line, &format!("bad number {}", s)))
+        };
+
+        let (r_type, r_data) = match r_type.to_ascii_uppercase().as_str() {
+            "A" => (
+                Type::A,
+                RData::A(
+                    field(0)?
+                        .parse()
+                        .map_err(|_| invalid(line, "bad address"))?,
+                ),
+            ),
+            "AAAA" => (
+                Type::AAAA,
+                RData::AAAA(
+                    field(0)?
+                        .parse()
+                        .map_err(|_| invalid(line, "bad address"))?,
+                ),
+            ),
+            "NS" => (Type::NS, RData::NS(absolute(field(0)?, &origin))),
+            "CNAME" => (Type::CNAME, RData::CNAME(absolute(field(0)?, &origin))),
+            "PTR" => (Type::PTR, RData::PTR(absolute(field(0)?, &origin))),
+            "MX" => (
+                Type::MX,
+                RData::MX(number(field(0)?)? as u16, absolute(field(1)?, &origin)),
+            ),
+            "SRV" => (
+                Type::SRV,
+                RData::SRV(
+                    number(field(0)?)? as u16,
+                    number(field(1)?)? as u16,
+                    number(field(2)?)? as u16,
+                    absolute(field(3)?, &origin),
+                ),
+            ),
+            "TXT" => (
+                Type::TXT,
+                RData::TXT(rdata.iter().map(|s| (*s).to_owned()).collect()),
+            ),
+            "SOA" => (
+                Type::SOA,
+                RData::SOA(SOA {
+                    mname: absolute(field(0)?, &origin),
+                    rname: absolute(field(1)?, &origin),
+                    serial: number(field(2)?)?,
+                    refresh: parse_ttl(field(3)?).ok_or_else(|| invalid(line, "bad refresh"))?,
+                    retry: parse_ttl(field(4)?).ok_or_else(|| invalid(line, "bad retry"))?,
+                    expire: parse_ttl(field(5)?).ok_or_else(|| invalid(line, "bad expire"))?,
+                    minimum: parse_ttl(field(6)?).ok_or_else(|| invalid(line, "bad minimum"))?,
+                }),
+            ),
+            other => return Err(invalid(line, &format!("unsupported type {}", other))),
+        };
+        for name in std::iter::once(owner.as_str()).chain(names(&r_data)) {
+            check_name(name).map_err(|e| invalid(line, &e.to_string()))?;
+        }
+        records.push(ResourceRecord::new(&owner, r_type, ttl, r_data));
+    }
+    Ok(records)
+}
+
+/// the domain names in `r_data`
+fn names(r_data: &RData) -> Vec<&str> {
+    match r_data {
+        RData::NS(name) | RData::CNAME(name) | RData::PTR(name) => vec![name],
+        RData::MX(_, exchange) => vec![exchange],
+        RData::SRV(_, _, _, target) => vec![target],
+        RData::SOA(soa) => vec![&soa.mname, &soa.rname],
+        _ => Vec::new(),
+    }
+}
+
+/// resolve `@` and relative names against `origin`
+fn absolute(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_owned()
+    } else if name.ends_with('.') {
+        name.to_owned()
+    } else if origin == "." {
+        format!("{}.", name)
+    } else {
+        format!("{}.{}", name, origin)
+    }
+}
+
+/// TTL in seconds or with units like `1h30m` (BIND style)
+fn parse_ttl(s: &str) -> Option<u32> {
+    if let Ok(ttl) = s.parse() {
+        return Some(ttl);
+    }
+
+    let mut ttl: u32 = 0;
+    let mut value: Option<u32> = None;
+    for c in s.chars() {
+        if let Some(d) = c.to_digit(10) {
+            value = Some(value.unwrap_or(0).checked_mul(10)?.checked_add(d)?);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return None,
+        };
+        ttl = ttl.checked_add(value.take()?.checked_mul(unit)?)?;
+    }
+    match value {
+        None => Some(ttl),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::dns::message::Question;
+
+    const ZONE: &str = r#"
+$TTL 1h
+@       IN  SOA ns.lan. hostmaster.lan. (
+                2020100101 ; serial
+                3600       ; refresh
+                15m        ; retry
+                1w         ; expire
+                300 )      ; minimum
+        IN  NS  ns
+ns          A   192.168.1.1
+router  60  A   192.168.1.1
+            AAAA fd00::1
+printer     CNAME printer.office
+printer.office IN A 192.168.1.2
+www         CNAME glow.mmf.moe.
+*.dev       A   192.168.1.100
+@           TXT "v=spf1 -all" "second string"
+@           MX  10 mail
+"#;
+
+    fn zone() -> Zone {
+        Zone::parse(ZONE, "lan").unwrap()
+    }
+
+    fn names(rrs: &[ResourceRecord]) -> Vec<&str> {
+        rrs.iter().map(|rr| rr.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_zone() {
+        let zone = zone();
+        assert_eq!(zone.origin(), "lan.");
+        match &zone.soa().r_data {
+            RData::SOA(soa) => {
+                assert_eq!(soa.mname, "ns.lan.");
+                assert_eq!(soa.serial, 2020100101);
+                assert_eq!(soa.retry, 900);
+                assert_eq!(soa.expire, 604800);
+                assert_eq!(soa.minimum, 300);
+            }
+            _ => panic!("No SOA record found!"),
+        }
+        assert_eq!(zone.soa().ttl, 3600);
+
+        let router = &zone.records["router.lan."];
+        assert_eq!(router.len(), 2);
+        assert_eq!((router[0].ttl, router[1].ttl), (60, 3600));
+        assert!(router[1].r_data == RData::AAAA("fd00::1".parse().unwrap()));
+
+        let apex = &zone.records["lan."];
+        assert!(apex
+            .iter()
+            .any(|rr| rr.r_data == RData::MX(10, "mail.lan.".to_owned())));
+        assert!(apex.iter().any(|rr| rr.r_data
+            == RData::TXT(vec!["v=spf1 -all".to_owned(), "second string".to_owned()])));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Zone::parse("@ A 1.2.3.4", "lan").is_err());
+        assert!(Zone::parse(&format!("{}\nwww.example.com. A 1.2.3.4", ZONE), "lan").is_err());
+        assert!(Zone::parse(&format!("{}\nx HINFO pc linux", ZONE), "lan").is_err());
+        assert!(Zone::parse("@ SOA ns hm ( 1 2 3 4 5", "lan").is_err());
+        let label = "a".repeat(64);
+        assert!(Zone::parse(&format!("{}\n{} A 1.2.3.4", ZONE, label), "lan").is_err());
+        assert!(Zone::parse(&format!("{}\nwww CNAME {}", ZONE, label), "lan").is_err());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let zone = zone();
+        match zone.lookup("Router.LAN", QType::Type(Type::A)) {
+            Lookup::Answer(rrs) => assert_eq!(names(&rrs), vec!["router.lan."]),
+            _ => panic!("router.lan should exist"),
+        }
+        match zone.lookup("printer.lan.", QType::Type(Type::A)) {
+            Lookup::Answer(rrs) => {
+                assert_eq!(names(&rrs), vec!["printer.lan.", "printer.office.lan."]);
+                assert!(rrs[1].r_data == RData::A("192.168.1.2".parse().unwrap()));
+            }
+            _ => panic!("printer.lan should exist"),
+        }
+        match zone.lookup("www.lan.", QType::Type(Type::A)) {
+            Lookup::Answer(rrs) => assert_eq!(rrs.len(), 1),
+            _ => panic!("www.lan should be a CNAME"),
+        }
+        match zone.lookup("ns.lan.", QType::Type(Type::AAAA)) {
+            Lookup::NoData(rrs) => assert!(rrs.is_empty()),
+            _ => panic!("ns.lan has no AAAA"),
+        }
+        // empty non-terminal
+        assert!(matches!(
+            zone.lookup("office.lan.", QType::Type(Type::A)),
+            Lookup::NoData(_)
+        ));
+        assert!(matches!(
+            zone.lookup("nas.lan.", QType::Type(Type::A)),
+            Lookup::NxDomain(_)
+        ));
+        assert!(matches!(
+            zone.lookup("lan.", QType::ANY),
+            Lookup::Answer(rrs) if rrs.len() == 4
+        ));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let zone = zone();
+        match zone.lookup("a.b.dev.lan.", QType::Type(Type::A)) {
+            Lookup::Answer(rrs) => {
+                assert_eq!(names(&rrs), vec!["a.b.dev.lan."]);
+                assert!(rrs[0].r_data == RData::A("192.168.1.100".parse().unwrap()));
+            }
+            _ => panic!("wildcard should match"),
+        }
+        assert!(matches!(
+            zone.lookup("x.dev.lan.", QType::Type(Type::MX)),
+            Lookup::NoData(_)
+        ));
+        // wildcard does not match the encloser itself
+        assert!(matches!(
+            zone.lookup("dev.lan.", QType::Type(Type::A)),
+            Lookup::NoData(_)
+        ));
+    }
+
+    #[test]
+    fn test_respond() {
+        let zone = zone();
+        let mut query = Message::new(Default::default(), false);
+        query.add_question(Question::new("nas.lan", QType::Type(Type::A)));
+
+        let raw: bytes::BytesMut = zone.respond(&query).into();
+        let response = Message::from(&raw[..]);
+        let flag = response.header.flag();
+        assert!(flag.authoritative_answer);
+        assert!(flag.rcode == FlagRCode::NameError);
+        assert_eq!(response.authorities.len(), 1);
+        assert_eq!(response.authorities[0].ttl, 300);
+        assert!(response.authorities[0].r_data == zone.soa().r_data);
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("300"), Some(300));
+        assert_eq!(parse_ttl("1h30m"), Some(5400));
+        assert_eq!(parse_ttl("1W"), Some(604800));
+        assert_eq!(parse_ttl("IN"), None);
+        assert_eq!(parse_ttl("10x"), None);
+        assert_eq!(parse_ttl("h"), None);
+    }
+}
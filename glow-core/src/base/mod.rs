@@ -1,2 +1,3 @@
 pub mod dns;
 pub mod nl80211;
+pub mod wpa;
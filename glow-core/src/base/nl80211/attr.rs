@@ -256,9 +256,32 @@ impl_var!(
     AttrNanDual                      => 239,
     AttrNanFunc                      => 240,
     AttrNanMatch                     => 241,
-    AttrAfterLast                    => 242,
-    NumAttr                          => 242,//__AttrAfterLast,
-    AttrMax                          => 241//__AttrAfterLast - 1
+    AttrFilsKek                      => 242,
+    AttrFilsNonces                   => 243,
+    AttrMulticastToUnicastEnabled    => 244,
+    AttrBssid                        => 245,
+    AttrSchedScanRelativeRssi        => 246,
+    AttrSchedScanRssiAdjust          => 247,
+    AttrTimeoutReason                => 248,
+    AttrFilsErpUsername              => 249,
+    AttrFilsErpRealm                 => 250,
+    AttrFilsErpNextSeqNum            => 251,
+    AttrFilsErpRrk                   => 252,
+    AttrFilsCacheId                  => 253,
+    AttrPmk                          => 254,
+    AttrSchedScanMulti               => 255,
+    AttrSchedScanMaxReqs             => 256,
+    AttrWant1x4wayHs                 => 257,
+    AttrPmkr0Name                    => 258,
+    AttrPortAuthorized               => 259,
+    AttrExternalAuthAction           => 260,
+    AttrExternalAuthSupport          => 261,
+    AttrNss                          => 262,
+    AttrAckSignal                    => 263,
+    AttrControlPortOverNl80211       => 264,
+    AttrAfterLast                    => 265,
+    NumAttr                          => 265,//__AttrAfterLast,
+    AttrMax                          => 264//__AttrAfterLast - 1
 );
 
 impl NlAttrType for Nl80211Attr {}
@@ -291,6 +314,8 @@ impl_var!(
     IftypeMax         => 12
 );
 
+impl NlAttrType for Nl80211Iftype {}
+
 impl_var!(
     // nl80211StaFlags as declared in nl80211/nl80211.h:2428
     pub Nl80211StaFlags, u16,
@@ -334,10 +359,17 @@ impl_var!(
     RateInfo160MhzWidth   => 10,
     RateInfo10MhzWidth    => 11,
     RateInfo5MhzWidth     => 12,
-    RateInfoAfterLast     => 13,
-    RateInfoMax           => 12
+    RateInfoHeMcs         => 13,
+    RateInfoHeNss         => 14,
+    RateInfoHeGi          => 15,
+    RateInfoHeDcm         => 16,
+    RateInfoHeRuAlloc     => 17,
+    RateInfoAfterLast     => 18,
+    RateInfoMax           => 17
 );
 
+impl NlAttrType for Nl80211RateInfo {}
+
 impl_var!(
     /// nl80211StaBssParam
     ///
@@ -353,6 +385,8 @@ impl_var!(
     StaBssParamMax            => 5
 );
 
+impl NlAttrType for Nl80211StaBssParam {}
+
 impl_var!(
     /// nl80211StaInfo
     ///
@@ -396,6 +430,8 @@ impl_var!(
     StaInfoMax                => 33
 );
 
+impl NlAttrType for Nl80211StaInfo {}
+
 impl_var!(
     /// nl80211TidStats
     ///
@@ -411,6 +447,8 @@ impl_var!(
     TidStatsMax           => 5
 );
 
+impl NlAttrType for Nl80211TidStats {}
+
 impl_var!(
     /// nl80211MpathFlags
     ///
@@ -454,10 +492,32 @@ impl_var!(
     BandAttrHtAmpduDensity => 6,
     BandAttrVhtMcsSet      => 7,
     BandAttrVhtCapa        => 8,
-    BandAttrAfterLast      => 9,
-    BandAttrMax            => 8
+    BandAttrIftypeData     => 9,
+    BandAttrEdmgChannels   => 10,
+    BandAttrEdmgBwConfig   => 11,
+    BandAttrAfterLast      => 12,
+    BandAttrMax            => 11
+);
+
+impl NlAttrType for Nl80211BandAttr {}
+
+impl_var!(
+    /// nl80211BandIftypeAttr
+    ///
+    /// Enumeration from nl80211/nl80211.h:3594
+    pub Nl80211BandIftypeAttr, u16,
+    BandIftypeAttrInvalid     => 0,
+    BandIftypeAttrIftypes     => 1,
+    BandIftypeAttrHeCapMac    => 2,
+    BandIftypeAttrHeCapPhy    => 3,
+    BandIftypeAttrHeCapMcsSet => 4,
+    BandIftypeAttrHeCapPpe    => 5,
+    BandIftypeAttrAfterLast   => 6,
+    BandIftypeAttrMax         => 5
 );
 
+impl NlAttrType for Nl80211BandIftypeAttr {}
+
 impl_var!(
     /// nl80211FrequencyAttr
     ///
@@ -485,6 +545,8 @@ impl_var!(
     FrequencyAttrMax          => 17
 );
 
+impl NlAttrType for Nl80211FrequencyAttr {}
+
 impl_var!(
     /// nl80211BitrateAttr
     ///
@@ -497,6 +559,8 @@ impl_var!(
     BitrateAttrMax               => 2
 );
 
+impl NlAttrType for Nl80211BitrateAttr {}
+
 impl_var!(
     /// nl80211RegInitiator
     ///
@@ -613,6 +677,8 @@ impl_var!(
     SurveyInfoMax         => 10
 );
 
+impl NlAttrType for Nl80211SurveyInfo {}
+
 impl_var!(
     /// nl80211MntrFlags
     ///
@@ -629,6 +695,8 @@ impl_var!(
     MntrFlagMax        => 6
 );
 
+impl NlAttrType for Nl80211MntrFlags {}
+
 impl_var!(
     /// nl80211MeshPowerMode
     ///
@@ -789,6 +857,8 @@ impl_var!(
     BssMax => 18
 );
 
+impl NlAttrType for Nl80211Bss {}
+
 impl_var!(
     /// nl80211BssStatus
     ///
@@ -831,7 +901,8 @@ impl_var!(
     /// Enumeration from nl80211/nl80211.h:3656
     pub Nl80211Mfp, u16,
     MfpNo       => 0,
-    MfpRequired => 1
+    MfpRequired => 1,
+    MfpOptional => 2
 );
 
 impl_var!(
@@ -872,6 +943,8 @@ impl_var!(
     KeyMax          => 8
 );
 
+impl NlAttrType for Nl80211KeyAttributes {}
+
 impl_var!(
     /// nl80211TxRateAttributes
     ///
@@ -904,7 +977,9 @@ impl_var!(
     Band2ghz  => 0,
     Band5ghz  => 1,
     Band60ghz => 2,
-    NumBands  => 3
+    Band6ghz  => 3,
+    BandS1ghz => 4,
+    NumBands  => 5
 );
 
 impl_var!(
@@ -930,10 +1005,13 @@ impl_var!(
     AttrCqmTxePkts            => 6,
     AttrCqmTxeIntvl           => 7,
     AttrCqmBeaconLossEvent    => 8,
-    AttrCqmAfterLast          => 9,
-    AttrCqmMax                => 8
+    AttrCqmRssiLevel          => 9,
+    AttrCqmAfterLast          => 10,
+    AttrCqmMax                => 9
 );
 
+impl NlAttrType for Nl80211AttrCqm {}
+
 impl_var!(
     /// nl80211CqmRssiThresholdEvent
     ///
@@ -1051,6 +1129,8 @@ impl_var!(
     MaxIfaceLimit    => 2
 );
 
+impl NlAttrType for Nl80211IfaceLimitAttrs {}
+
 impl_var!(
     /// nl80211IfCombinationAttrs
     ///
@@ -1067,6 +1147,8 @@ impl_var!(
     MaxIfaceComb                => 6
 );
 
+impl NlAttrType for Nl80211IfCombinationAttrs {}
+
 impl_var!(
     /// nl80211PlinkState
     ///
@@ -1,36 +1,100 @@
-use super::{attr::Nl80211Attr, constant::*, utils::phy_lookup};
+use super::{
+    attr::Nl80211Attr,
+    cmd::Nl80211Cmd,
+    constant::*,
+    transport::{NlTransport, Transport},
+    utils::{netdev_lookup, phy_lookup},
+};
 use neli::{
+    attr::Attribute,
+    consts::alignto,
     consts::nl::*,
-    consts::genl::*,
     consts::socket::*,
     err::NlError,
     genl::{Genlmsghdr, Nlattr},
     nl::{NlPayload, Nlmsghdr},
     socket::*,
     types::*,
+    Nl,
 };
+use std::collections::VecDeque;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Length of the netlink message header
+const NLMSG_HDRLEN: usize = 16;
+
+/// Time nl80211 has to answer a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub enum CommandIdBy {
     Phy(u32),
     NetDev(u32),
-    WDev(u32),
+    WDev(u64),
     None,
 }
 
-pub struct NL80211Client {
+pub struct NL80211Client<T = NlTransport> {
+    transport: T,
     family_id: u16,
+    groups: Vec<(&'static str, u32)>,
     devidx: CommandIdBy,
+    /// sequence number of the last request
+    seq: u32,
+    /// multicast events received while waiting for responses
+    events: VecDeque<Genlmsghdr<Nl80211Cmd, Nl80211Attr>>,
+}
+
+/// A netlink message of a received chunk
+enum Received {
+    Payload(Genlmsghdr<Nl80211Cmd, Nl80211Attr>, bool),
+    Done,
+    Ack,
+    Err(NlError),
 }
 
 impl NL80211Client {
     pub fn new() -> Result<NL80211Client, NlError> {
         let mut socket = NlSocketHandle::connect(NlFamily::Generic, None, &[])?;
         let family_id = socket.resolve_genl_family(NL80211_FAMILY_NAME)?;
-        Ok(NL80211Client {
+        let mut groups = Vec::new();
+        for name in NL80211_MULTICAST_GROUPS.iter() {
+            // older kernels lack some of the groups
+            if let Ok(id) = socket.resolve_nl_mcast_group(NL80211_FAMILY_NAME, name) {
+                groups.push((*name, id));
+            }
+        }
+        let mut transport = NlTransport::from(socket);
+        transport.set_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut client = NL80211Client::with_transport(transport, family_id);
+        client.groups = groups;
+        Ok(client)
+    }
+}
+
+impl<T: Transport> NL80211Client<T> {
+    /// client talking to nl80211 over `transport`, `family_id` is the
+    /// generic netlink family id of nl80211
+    pub fn with_transport(transport: T, family_id: u16) -> NL80211Client<T> {
+        NL80211Client {
+            transport,
             family_id,
+            groups: Vec::new(),
             devidx: CommandIdBy::None,
-        })
+            seq: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// id of the nl80211 multicast group `name`, resolved by `new`
+    pub fn multicast_group(&mut self, name: &'static str, id: u32) -> &Self {
+        self.groups.retain(|(n, _)| *n != name);
+        self.groups.push((name, id));
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
     }
 
     pub fn set_phy(&mut self, phy: String) -> Result<(), NlError> {
@@ -40,24 +104,67 @@ impl NL80211Client {
     }
 
     pub fn set_netdev(&mut self, dev: String) -> Result<(), NlError> {
-        unimplemented!();
+        let ifindex = netdev_lookup(dev)?;
+        self.devidx = CommandIdBy::NetDev(ifindex);
+        Ok(())
     }
 
     pub fn set_wdev(&mut self, wdev: String) -> Result<(), NlError> {
-        let wdev_id = u32::from_str(&wdev).map_err(NlError::new)?;
+        let wdev_id = u64::from_str(&wdev).map_err(NlError::new)?;
         self.devidx = CommandIdBy::WDev(wdev_id);
         Ok(())
     }
 
-    pub(crate) fn send<T>(
+    /// join the multicast group `name`, returning its id
+    pub(crate) fn subscribe(&mut self, name: &str) -> Result<u32, NlError> {
+        let id = self.group_id(name)?;
+        self.transport.add_membership(id)?;
+        Ok(id)
+    }
+
+    pub(crate) fn unsubscribe(&mut self, name: &str) -> Result<(), NlError> {
+        let id = self.group_id(name)?;
+        self.transport.drop_membership(id)
+    }
+
+    /// id of the multicast group `name`, unknown without resolving it
+    pub(crate) fn group_id(&self, name: &str) -> Result<u32, NlError> {
+        match self.groups.iter().find(|(n, _)| *n == name) {
+            Some((_, id)) => Ok(*id),
+            None => Err(NlError::new(format!(
+                "unknown nl80211 multicast group {}",
+                name
+            ))),
+        }
+    }
+
+    /// whether an event is about the device selected with `set_*`
+    pub(crate) fn targets(&self, msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>) -> bool {
+        let handle = msg.get_attr_handle();
+        match self.devidx {
+            CommandIdBy::Phy(id) => match handle.get_attribute(Nl80211Attr::AttrWiphy) {
+                Some(attr) => attr.get_payload_as::<u32>().ok() == Some(id),
+                None => false,
+            },
+            CommandIdBy::NetDev(id) => match handle.get_attribute(Nl80211Attr::AttrIfindex) {
+                Some(attr) => attr.get_payload_as::<u32>().ok() == Some(id),
+                None => false,
+            },
+            CommandIdBy::WDev(id) => match handle.get_attribute(Nl80211Attr::AttrWdev) {
+                Some(attr) => attr.get_payload_as::<u64>().ok() == Some(id),
+                None => false,
+            },
+            CommandIdBy::None => true,
+        }
+    }
+
+    /// send a request, returning the sequence number of its responses
+    pub(crate) fn send(
         &mut self,
-        cmd: T,
+        cmd: Nl80211Cmd,
         flags: Option<NlmFFlags>,
         attrs: Option<GenlBuffer<Nl80211Attr, Buffer>>,
-    ) -> Result<NlSocketHandle, NlError>
-        where
-            T: Cmd + std::fmt::Debug,
-    {
+    ) -> Result<u32, NlError> {
         let mut attrs = GenlBuffer::from(match attrs {
             Some(a) => a,
             None => GenlBuffer::new(),
@@ -95,6 +202,8 @@ impl NL80211Client {
             }
             _ => (),
         }
+        // multicast events carry 0
+        self.seq = self.seq.wrapping_add(1).max(1);
         let genlhdr = Genlmsghdr::new(cmd, 1, attrs);
         let msg = Nlmsghdr::new(
             None,
@@ -103,13 +212,238 @@ impl NL80211Client {
                 Some(flags) => flags,
                 None => NlmFFlags::new(&[NlmF::Request, NlmF::Ack]),
             },
-            None,
+            Some(self.seq),
             None,
             NlPayload::Payload(genlhdr),
         );
 
-        let mut socket = NlSocketHandle::connect(NlFamily::Generic, None, &[])?;
-        socket.send(msg)?;
-        Ok(socket)
+        let mut buffer = vec![0; msg.asize()];
+        msg.serialize(buffer.as_mut_slice()).map_err(NlError::new)?;
+        self.transport.send(&buffer)?;
+        Ok(self.seq)
+    }
+
+    /// receive the responses to the request `seq` sent with `ack`,
+    /// until the end of a dump, the ACK or the only response
+    ///
+    /// Multicast events received meanwhile are kept for `recv_events`,
+    /// late responses to earlier requests are dropped. Receiving from
+    /// the kernel fails after `REQUEST_TIMEOUT` without a message.
+    pub(crate) fn recv(
+        &mut self,
+        seq: u32,
+        ack: bool,
+    ) -> Result<Vec<Genlmsghdr<Nl80211Cmd, Nl80211Attr>>, NlError> {
+        let mut responses = Vec::new();
+        loop {
+            let mut done = false;
+            let mut error = None;
+            for (msg_seq, received) in self.read()? {
+                if msg_seq != seq {
+                    if let (0, Received::Payload(event, _)) = (msg_seq, received) {
+                        self.events.push_back(event);
+                    }
+                    continue;
+                }
+                // the rest of the chunk is kept as well
+                if done || error.is_some() {
+                    continue;
+                }
+                match received {
+                    Received::Payload(payload, multi) => {
+                        responses.push(payload);
+                        done = !ack && !multi;
+                    }
+                    Received::Done | Received::Ack => done = true,
+                    Received::Err(e) => error = Some(e),
+                }
+            }
+            if let Some(e) = error {
+                return Err(e);
+            }
+            if done {
+                return Ok(responses);
+            }
+        }
+    }
+
+    /// receive the next multicast events of the joined groups, first those
+    /// received while waiting for responses, failing after `deadline`
+    /// and waiting for ever without one
+    pub(crate) fn recv_events(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<Genlmsghdr<Nl80211Cmd, Nl80211Attr>>, NlError> {
+        while self.events.is_empty() {
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(NlError::new("timed out waiting for nl80211 events"));
+                    }
+                    // no timeout at all below the resolution of SO_RCVTIMEO
+                    Some((deadline - now).max(Duration::from_millis(1)))
+                }
+                None => None,
+            };
+            self.transport.set_timeout(timeout)?;
+            let messages = self.read();
+            self.transport.set_timeout(Some(REQUEST_TIMEOUT))?;
+            for (seq, received) in messages? {
+                if let (0, Received::Payload(event, _)) = (seq, received) {
+                    self.events.push_back(event);
+                }
+            }
+        }
+        Ok(self.events.drain(..).collect())
+    }
+
+    /// drop the events kept for `recv_events` that are `stale`, e.g. those
+    /// of a group left
+    pub(crate) fn discard_events<F>(&mut self, stale: F)
+    where
+        F: Fn(&Genlmsghdr<Nl80211Cmd, Nl80211Attr>) -> bool,
+    {
+        self.events.retain(|event| !stale(event));
+    }
+
+    /// receive the next chunk, the messages with their sequence numbers
+    fn read(&mut self) -> Result<Vec<(u32, Received)>, NlError> {
+        let chunk = self.transport.recv()?;
+        if chunk.is_empty() {
+            return Err(NlError::new("netlink socket closed"));
+        }
+
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while offset + NLMSG_HDRLEN <= chunk.len() {
+            let len = u32::from_ne_bytes([
+                chunk[offset],
+                chunk[offset + 1],
+                chunk[offset + 2],
+                chunk[offset + 3],
+            ]) as usize;
+            if len < NLMSG_HDRLEN || offset + len > chunk.len() {
+                return Err(NlError::new("truncated netlink message"));
+            }
+            let raw = &chunk[offset..(offset + len)];
+            offset += alignto(len);
+
+            // the payload of NLMSG_DONE is no generic netlink message
+            let nl_type = u16::from_ne_bytes([raw[4], raw[5]]);
+            let seq = u32::from_ne_bytes([raw[8], raw[9], raw[10], raw[11]]);
+            if nl_type == u16::from(Nlmsg::Done) {
+                messages.push((seq, Received::Done));
+                continue;
+            }
+            let msg = Nlmsghdr::<u16, Genlmsghdr<Nl80211Cmd, Nl80211Attr>>::deserialize(raw)
+                .map_err(NlError::new)?;
+            let received = match msg.nl_payload {
+                NlPayload::Err(e) => Received::Err(NlError::from(e)),
+                NlPayload::Ack(_) => Received::Ack,
+                NlPayload::Payload(payload) => {
+                    Received::Payload(payload, msg.nl_flags.contains(&NlmF::Multi))
+                }
+                NlPayload::Empty => continue,
+            };
+            messages.push((seq, received));
+        }
+        Ok(messages)
+    }
+
+    /// send a request and receive its responses
+    pub(crate) fn request(
+        &mut self,
+        cmd: Nl80211Cmd,
+        flags: Option<NlmFFlags>,
+        attrs: Option<GenlBuffer<Nl80211Attr, Buffer>>,
+    ) -> Result<Vec<Genlmsghdr<Nl80211Cmd, Nl80211Attr>>, NlError> {
+        // requests are acknowledged unless other flags are given
+        let ack = match &flags {
+            Some(flags) => flags.contains(&NlmF::Ack),
+            None => true,
+        };
+        let seq = self.send(cmd, flags, attrs)?;
+        self.recv(seq, ack)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::base::nl80211::transport::Replay;
+
+    pub const FAMILY_ID: u16 = 0x1c;
+
+    /// client replaying `fixtures`, one chunk each
+    pub fn client(fixtures: &[&[u8]]) -> NL80211Client<Replay> {
+        let mut replay = Replay::new();
+        for fixture in fixtures {
+            replay.respond(fixture);
+        }
+        NL80211Client::with_transport(replay, FAMILY_ID)
+    }
+
+    /// NL80211_ATTR_IFINDEX of lo, the netdev the tests select with `set_netdev`
+    pub fn lo_ifindex() -> [u8; 8] {
+        let ifindex = std::fs::read_to_string("/sys/class/net/lo/ifindex").unwrap();
+        let ifindex = ifindex.trim().parse::<u32>().unwrap();
+        let mut attr = [8, 0, 3, 0, 0, 0, 0, 0];
+        attr[4..].copy_from_slice(&ifindex.to_ne_bytes());
+        attr
+    }
+
+    /// select the netdev `ifindex` of recorded events without looking it up
+    pub fn select_netdev(client: &mut NL80211Client<Replay>, ifindex: u32) {
+        client.devidx = CommandIdBy::NetDev(ifindex);
+    }
+
+    /// `fixture` with its responses renumbered to answer the request `seq`
+    pub fn reply(fixture: &[u8], seq: u32) -> Vec<u8> {
+        let mut chunk = fixture.to_vec();
+        let mut offset = 0;
+        while offset < chunk.len() {
+            let len = u32::from_ne_bytes([
+                chunk[offset],
+                chunk[offset + 1],
+                chunk[offset + 2],
+                chunk[offset + 3],
+            ]) as usize;
+            // multicast events keep 0
+            if chunk[(offset + 8)..(offset + 12)] != [0; 4] {
+                chunk[(offset + 8)..(offset + 12)].copy_from_slice(&seq.to_ne_bytes());
+            }
+            offset += alignto(len);
+        }
+        chunk
+    }
+
+    /// the messages of `fixture`, received one at a time like multicast
+    pub fn datagrams(fixture: &[u8]) -> Vec<&[u8]> {
+        let mut datagrams = Vec::new();
+        let mut rest = fixture;
+        while !rest.is_empty() {
+            let len = u32::from_ne_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            datagrams.push(&rest[..len]);
+            rest = &rest[len..];
+        }
+        datagrams
+    }
+
+    #[test]
+    fn test_recv_matches_seq() {
+        let ack = include_bytes!("fixtures/ack.bin");
+        let events = datagrams(include_bytes!("fixtures/events.bin"));
+        let mut client = client(&[events[0], &reply(ack, 7), events[1], &reply(ack, 2)]);
+        client.seq = 1;
+        let seq = client.send(Nl80211Cmd::CmdGetReg, None, None).unwrap();
+        assert_eq!(seq, 2);
+        assert_eq!(&client.transport().sent()[0][8..12], &2u32.to_ne_bytes());
+        // the stale ACK is dropped, the events are kept
+        assert!(client.recv(seq, true).unwrap().is_empty());
+        let kept = client.recv_events(None).unwrap();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].cmd, Nl80211Cmd::CmdNewScanResults);
+        assert!(client.recv_events(None).is_err());
     }
 }
@@ -0,0 +1,412 @@
+use super::{
+    attr::*,
+    client::NL80211Client,
+    cmd::Nl80211Cmd,
+    eapol::ETH_P_PAE,
+    ie::{self, AkmSuite, CipherSuite, Element, Rsn, OUI_IEEE80211},
+    owe::OweKey,
+    status::ReasonCode,
+    transport::Transport,
+};
+use neli::err::NlError;
+use neli::genl::Nlattr;
+use neli::types::{Buffer, GenlBuffer};
+
+/// RSN capabilities with management frame protection capable
+const RSN_CAPABILITIES_MFPC: u16 = 0x0080;
+
+/// RSN capabilities with management frame protection required
+const RSN_CAPABILITIES_MFPR: u16 = 0x0040;
+
+/// A network to connect to in station mode
+#[derive(Debug)]
+pub struct ConnectParams {
+    pub ssid: Vec<u8>,
+    /// BSS preferred, the driver may still pick another one of the SSID
+    pub bssid: Option<[u8; 6]>,
+    /// frequency preferred in MHz
+    pub freq: Option<u32>,
+    /// automatic lets the kernel retry with other algorithms
+    pub auth_type: Nl80211AuthType,
+    pub mfp: Nl80211Mfp,
+    /// RSN element to associate with, selecting the ciphers and AKM
+    pub rsn: Option<Rsn>,
+    /// more elements of the association request
+    pub ies: Vec<u8>,
+    /// exchange EAPOL frames over nl80211 with this socket, for `handshake`
+    pub control_port: bool,
+    /// passphrase of PSK and SAE networks for a `Connector` running the
+    /// handshake itself, `handshake` takes the PMK instead
+    pub passphrase: Option<String>,
+}
+
+/// Connecting a netdev in station mode, natively with nl80211 or through a
+/// supplicant daemon
+pub trait Connector {
+    type Error;
+
+    /// Start connecting to `params`, the result is reported asynchronously
+    fn connect(&mut self, params: &ConnectParams) -> Result<(), Self::Error>;
+
+    fn disconnect(&mut self, reason: ReasonCode) -> Result<(), Self::Error>;
+}
+
+impl ConnectParams {
+    /// an open network
+    pub fn new(ssid: &[u8]) -> ConnectParams {
+        ConnectParams {
+            ssid: ssid.to_vec(),
+            bssid: None,
+            freq: None,
+            auth_type: Nl80211AuthType::AuthtypeAutomatic,
+            mfp: Nl80211Mfp::MfpNo,
+            rsn: None,
+            ies: Vec::new(),
+            control_port: false,
+            passphrase: None,
+        }
+    }
+
+    /// A WPA2-Personal network, `crypto::psk` derives the PMK for the
+    /// 4-way handshake
+    pub fn psk(ssid: &[u8]) -> ConnectParams {
+        let mut params = ConnectParams::new(ssid);
+        params.auth_type = Nl80211AuthType::AuthtypeOpenSystem;
+        params.mfp = Nl80211Mfp::MfpOptional;
+        params.rsn = Some(rsn(AkmSuite::Psk, RSN_CAPABILITIES_MFPC));
+        params.control_port = true;
+        params
+    }
+
+    /// A WPA3-Personal network to `associate` with after `authenticate_sae`
+    /// derived the PMK for the 4-way handshake
+    pub fn sae(ssid: &[u8]) -> ConnectParams {
+        let mut params = ConnectParams::new(ssid);
+        params.auth_type = Nl80211AuthType::AuthtypeSae;
+        params.mfp = Nl80211Mfp::MfpRequired;
+        params.rsn = Some(rsn(
+            AkmSuite::Sae,
+            RSN_CAPABILITIES_MFPC | RSN_CAPABILITIES_MFPR,
+        ));
+        params.control_port = true;
+        params
+    }
+
+    /// An OWE network, `key` derives the PMK from the IEs of the successful
+    /// `Nl80211Event::Connect` for the 4-way handshake
+    pub fn owe(ssid: &[u8], key: &OweKey) -> ConnectParams {
+        let mut params = ConnectParams::new(ssid);
+        params.auth_type = Nl80211AuthType::AuthtypeOpenSystem;
+        params.mfp = Nl80211Mfp::MfpRequired;
+        params.rsn = Some(OweKey::rsn());
+        params.ies = ie::write(&[key.element()]);
+        params.control_port = true;
+        params
+    }
+}
+
+impl<T: Transport> NL80211Client<T> {
+    // COMMAND(connect, NULL,
+    //   "[-w] <SSID> [<freq in MHz>] [<bssid>] [auth open|shared] [key 0:abcde d:1:6162636465] [mfp:req/opt/no]",
+    //   NL80211_CMD_CONNECT, 0, CIB_NETDEV, iw_connect,
+    //   "Join the network with the given SSID (and frequency, BSSID).\n"
+    //   "With -w, wait for the connect to finish or fail.");
+    /// Connect the selected netdev to a network, the result is reported by
+    /// `Nl80211Event::Connect` on the mlme multicast group
+    pub fn connect(&mut self, params: &ConnectParams) -> Result<(), NlError> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrSsid,
+            params.ssid.clone(),
+        )?);
+        if let Some(bssid) = params.bssid {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrMacHint,
+                bssid.to_vec(),
+            )?);
+        }
+        if let Some(freq) = params.freq {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrWiphyFreqHint,
+                freq,
+            )?);
+        }
+        // the kernel picks the algorithm without one
+        if params.auth_type != Nl80211AuthType::AuthtypeAutomatic {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrAuthType,
+                u16::from(params.auth_type) as u32,
+            )?);
+        }
+        security_attrs(params, &mut attrs)?;
+
+        // the result and a disconnect end the handshake
+        if params.control_port && self.group_id("mlme").is_ok() {
+            self.subscribe("mlme")?;
+        }
+        self.request(Nl80211Cmd::CmdConnect, None, Some(attrs))?;
+        Ok(())
+    }
+
+    /// Associate the selected netdev with the BSS and frequency of `params`
+    /// once authenticated, e.g. by `authenticate_sae`
+    pub fn associate(&mut self, params: &ConnectParams) -> Result<(), NlError> {
+        let (bssid, freq) = match (params.bssid, params.freq) {
+            (Some(bssid), Some(freq)) => (bssid, freq),
+            _ => return Err(NlError::new("associating needs the BSSID and frequency")),
+        };
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrSsid,
+            params.ssid.clone(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrMac,
+            bssid.to_vec(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrWiphyFreq,
+            freq,
+        )?);
+        security_attrs(params, &mut attrs)?;
+
+        if params.control_port && self.group_id("mlme").is_ok() {
+            self.subscribe("mlme")?;
+        }
+        self.request(Nl80211Cmd::CmdAssociate, None, Some(attrs))?;
+        Ok(())
+    }
+
+    // COMMAND(disconnect, NULL, NULL,
+    //   NL80211_CMD_DISCONNECT, 0, CIB_NETDEV, iw_disconnect,
+    //   "Disconnect from the current network.");
+    pub fn disconnect(&mut self, reason: ReasonCode) -> Result<(), NlError> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrReasonCode,
+            u16::from(reason),
+        )?);
+        self.request(Nl80211Cmd::CmdDisconnect, None, Some(attrs))?;
+        Ok(())
+    }
+}
+
+impl<T: Transport> Connector for NL80211Client<T> {
+    type Error = NlError;
+
+    fn connect(&mut self, params: &ConnectParams) -> Result<(), NlError> {
+        NL80211Client::connect(self, params)
+    }
+
+    fn disconnect(&mut self, reason: ReasonCode) -> Result<(), NlError> {
+        NL80211Client::disconnect(self, reason)
+    }
+}
+
+/// attributes of the MFP, RSN, IEs and control port of `params`, shared by
+/// connect and associate
+fn security_attrs(
+    params: &ConnectParams,
+    attrs: &mut GenlBuffer<Nl80211Attr, Buffer>,
+) -> Result<(), NlError> {
+    if params.mfp != Nl80211Mfp::MfpNo {
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrUseMfp,
+            u16::from(params.mfp) as u32,
+        )?);
+    }
+
+    let mut ies = Vec::new();
+    if let Some(rsn) = &params.rsn {
+        Element::Rsn(rsn.clone()).write(&mut ies);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrPrivacy,
+            Vec::<u8>::new(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrWpaVersions,
+            u16::from(Nl80211WpaVersions::WpaVersion2) as u32,
+        )?);
+        if let Some(group) = rsn.group_cipher {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrCipherSuiteGroup,
+                group.selector(OUI_IEEE80211),
+            )?);
+        }
+        let pairwise = rsn.pairwise().into_iter();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrCipherSuitesPairwise,
+            suites(pairwise.map(|cipher| cipher.selector(OUI_IEEE80211))),
+        )?);
+        let akms = rsn.akms().into_iter();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrAkmSuites,
+            suites(akms.map(|akm| akm.selector(OUI_IEEE80211))),
+        )?);
+    }
+    ies.extend_from_slice(&params.ies);
+    if !ies.is_empty() {
+        attrs.push(Nlattr::new(None, false, false, Nl80211Attr::AttrIe, ies)?);
+    }
+
+    if params.control_port {
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrControlPort,
+            Vec::<u8>::new(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrControlPortEthertype,
+            ETH_P_PAE,
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrControlPortOverNl80211,
+            Vec::<u8>::new(),
+        )?);
+        // the kernel only sends the EAPOL frames to the owner
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrSocketOwner,
+            Vec::<u8>::new(),
+        )?);
+    }
+    Ok(())
+}
+
+/// RSN element with CCMP-128 and `akm`
+fn rsn(akm: AkmSuite, capabilities: u16) -> Rsn {
+    Rsn {
+        version: 1,
+        group_cipher: Some(CipherSuite::Ccmp128),
+        pairwise_ciphers: Some(vec![CipherSuite::Ccmp128]),
+        akm_suites: Some(vec![akm]),
+        capabilities: Some(capabilities),
+        pmkids: None,
+        group_mgmt_cipher: None,
+    }
+}
+
+/// suite selectors as an array of u32
+fn suites(selectors: impl Iterator<Item = u32>) -> Vec<u8> {
+    selectors
+        .flat_map(|selector| selector.to_ne_bytes().to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::{client, lo_ifindex};
+
+    #[test]
+    fn test_connect_open() {
+        let mut client = client(&[include_bytes!("fixtures/ack.bin")]);
+        client.set_netdev("lo".to_string()).unwrap();
+        let mut params = ConnectParams::new(b"glow");
+        params.bssid = Some([2, 0, 0, 0, 2, 0]);
+        params.freq = Some(5180);
+        client.connect(&params).unwrap();
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdConnect));
+        // NL80211_ATTR_SSID
+        assert_eq!(&sent[20..28], &[8, 0, 52, 0, b'g', b'l', b'o', b'w']);
+        // NL80211_ATTR_MAC_HINT
+        assert_eq!(&sent[28..38], &[10, 0, 200, 0, 2, 0, 0, 0, 2, 0]);
+        // NL80211_ATTR_WIPHY_FREQ_HINT
+        assert_eq!(&sent[40..48], &[8, 0, 201, 0, 0x3c, 0x14, 0, 0]);
+        // no auth type, MFP, privacy or IEs
+        assert_eq!(sent[48..], lo_ifindex());
+    }
+
+    #[test]
+    fn test_connect_owe() {
+        let mut client = client(&[include_bytes!("fixtures/ack.bin")]);
+        let key = OweKey::from_secret(&[0x11; 32]).unwrap();
+        client.connect(&ConnectParams::owe(b"glow", &key)).unwrap();
+
+        let sent = &client.transport().sent()[0];
+        // NL80211_ATTR_AUTH_TYPE, open system
+        assert_eq!(&sent[28..36], &[8, 0, 53, 0, 0, 0, 0, 0]);
+        // NL80211_ATTR_USE_MFP, required
+        assert_eq!(&sent[36..44], &[8, 0, 66, 0, 1, 0, 0, 0]);
+        // NL80211_ATTR_PRIVACY
+        assert_eq!(&sent[44..48], &[4, 0, 70, 0]);
+        // NL80211_ATTR_WPA_VERSIONS
+        assert_eq!(&sent[48..56], &[8, 0, 75, 0, 2, 0, 0, 0]);
+        // NL80211_ATTR_CIPHER_SUITE_GROUP, CCMP-128
+        assert_eq!(&sent[56..64], &[8, 0, 74, 0, 4, 0xac, 0x0f, 0]);
+        // NL80211_ATTR_CIPHER_SUITES_PAIRWISE
+        assert_eq!(&sent[64..72], &[8, 0, 73, 0, 4, 0xac, 0x0f, 0]);
+        // NL80211_ATTR_AKM_SUITES, OWE
+        assert_eq!(&sent[72..80], &[8, 0, 76, 0, 18, 0xac, 0x0f, 0]);
+
+        // NL80211_ATTR_IE with the RSN and Diffie-Hellman Parameter elements
+        assert_eq!(&sent[80..84], &[4 + 22 + 37, 0, 42, 0]);
+        let elements = ie::parse(&sent[84..(84 + 22 + 37)]).unwrap();
+        assert_eq!(elements, vec![Element::Rsn(OweKey::rsn()), key.element()]);
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let mut client = client(&[include_bytes!("fixtures/ack.bin")]);
+        client.disconnect(ReasonCode::DeauthLeaving).unwrap();
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdDisconnect));
+        // NL80211_ATTR_REASON_CODE
+        assert_eq!(&sent[20..26], &[6, 0, 54, 0, 3, 0]);
+    }
+}
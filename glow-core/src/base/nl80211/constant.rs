@@ -1 +1,17 @@
 pub const NL80211_FAMILY_NAME: &str = "nl80211";
+
+pub const NL80211_MULTICAST_GROUP_CONFIG: &str = "config";
+pub const NL80211_MULTICAST_GROUP_SCAN: &str = "scan";
+pub const NL80211_MULTICAST_GROUP_REG: &str = "regulatory";
+pub const NL80211_MULTICAST_GROUP_MLME: &str = "mlme";
+pub const NL80211_MULTICAST_GROUP_VENDOR: &str = "vendor";
+pub const NL80211_MULTICAST_GROUP_NAN: &str = "nan";
+
+pub const NL80211_MULTICAST_GROUPS: [&str; 6] = [
+    NL80211_MULTICAST_GROUP_CONFIG,
+    NL80211_MULTICAST_GROUP_SCAN,
+    NL80211_MULTICAST_GROUP_REG,
+    NL80211_MULTICAST_GROUP_MLME,
+    NL80211_MULTICAST_GROUP_VENDOR,
+    NL80211_MULTICAST_GROUP_NAN,
+];
@@ -0,0 +1,289 @@
+use aes::cipher::generic_array::GenericArray;
+use aes::{Aes128, BlockDecrypt, BlockEncrypt, NewBlockCipher};
+use hmac::{Hmac, Mac, NewMac};
+use sha1::Sha1;
+use sha2::Sha256;
+
+// References: IEEE Std 802.11-2020, 12.7.1 Key hierarchy
+//             RFC 3394, Advanced Encryption Standard (AES) Key Wrap Algorithm
+//             RFC 4493, The AES-CMAC Algorithm
+
+/// Initial value of RFC 3394, checked by the key unwrap
+const KEY_WRAP_IV: u64 = 0xa6a6_a6a6_a6a6_a6a6;
+
+/// A PMK and its PMKID
+#[derive(Debug, PartialEq)]
+pub struct Pmksa {
+    pub pmk: [u8; 32],
+    pub pmkid: [u8; 16],
+}
+
+/// PMK of a WPA2/WPA3-Personal network from its passphrase of 8 to 63
+/// ASCII characters
+pub fn psk(passphrase: &[u8], ssid: &[u8]) -> [u8; 32] {
+    let mut pmk = [0; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha1>>(passphrase, ssid, 4096, &mut pmk);
+    pmk
+}
+
+/// HMAC-SHA-256 of the concatenation of `data`
+pub fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC takes keys of any length");
+    for part in data {
+        mac.update(part);
+    }
+    let mut out = [0; 32];
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    out
+}
+
+/// HMAC-SHA-1 of `data`, truncated to 128 bits as the MIC of EAPOL-Key
+/// frames with descriptor version 2
+pub fn hmac_sha1_128(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut mac = Hmac::<Sha1>::new_varkey(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    let mut out = [0; 16];
+    out.copy_from_slice(&mac.finalize().into_bytes()[..16]);
+    out
+}
+
+/// PRF-n of 802.11i, `len` bytes derived from `key` with HMAC-SHA-1
+pub fn prf_sha1(key: &[u8], label: &[u8], data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 20);
+    let mut i = 0u8;
+    while out.len() < len {
+        let mut mac = Hmac::<Sha1>::new_varkey(key).expect("HMAC takes keys of any length");
+        mac.update(label);
+        mac.update(&[0]);
+        mac.update(data);
+        mac.update(&[i]);
+        out.extend_from_slice(&mac.finalize().into_bytes());
+        i += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+/// KDF-SHA-256-n, `bits` derived from `key` in counter mode
+pub fn kdf_sha256(key: &[u8], label: &[u8], context: &[u8], bits: u16) -> Vec<u8> {
+    let len = bits as usize / 8;
+    let mut out = Vec::with_capacity(len + 32);
+    let mut i = 1u16;
+    while out.len() < len {
+        out.extend_from_slice(&hmac_sha256(
+            key,
+            &[&i.to_le_bytes(), label, context, &bits.to_le_bytes()],
+        ));
+        i += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+/// AES-128-CMAC of `data`
+pub fn aes_cmac(key: &[u8; 16], data: &[u8]) -> [u8; 16] {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let mut l = GenericArray::default();
+    cipher.encrypt_block(&mut l);
+    let k1 = dbl(l.into());
+    let k2 = dbl(k1);
+
+    // the last block is xored with K1 if complete, padded and xored with K2 otherwise
+    let rest = data.len() % 16;
+    let complete = !data.is_empty() && rest == 0;
+    let split = if complete {
+        data.len() - 16
+    } else {
+        data.len() - rest
+    };
+    let mut last = [0; 16];
+    if complete {
+        last.copy_from_slice(&data[split..]);
+        xor(&mut last, &k1);
+    } else {
+        let rest = &data[split..];
+        last[..rest.len()].copy_from_slice(rest);
+        last[rest.len()] = 0x80;
+        xor(&mut last, &k2);
+    }
+
+    let mut x = GenericArray::default();
+    for block in data[..split].chunks(16).chain(std::iter::once(&last[..])) {
+        xor(&mut x, block);
+        cipher.encrypt_block(&mut x);
+    }
+    x.into()
+}
+
+/// Wrap `key`, a multiple of 8 bytes long and at least 16, with `kek`
+pub fn key_wrap(kek: &[u8; 16], key: &[u8]) -> Result<Vec<u8>, String> {
+    let blocks = key.chunks_exact(8);
+    if key.len() < 16 || !blocks.remainder().is_empty() {
+        return Err("invalid length of the key to wrap".to_owned());
+    }
+    let cipher = Aes128::new(GenericArray::from_slice(kek));
+    let mut r: Vec<[u8; 8]> = blocks.map(block64).collect();
+    let n = r.len() as u64;
+    let mut a = KEY_WRAP_IV;
+    for j in 0..6 {
+        for (i, r) in r.iter_mut().enumerate() {
+            let mut b = GenericArray::default();
+            b[..8].copy_from_slice(&a.to_be_bytes());
+            b[8..].copy_from_slice(r);
+            cipher.encrypt_block(&mut b);
+            a = u64::from_be_bytes(block64(&b[..8])) ^ (n * j + i as u64 + 1);
+            r.copy_from_slice(&b[8..]);
+        }
+    }
+
+    let mut out = a.to_be_bytes().to_vec();
+    for block in r {
+        out.extend_from_slice(&block);
+    }
+    Ok(out)
+}
+
+/// Unwrap `wrapped` with `kek`, verifying its integrity
+pub fn key_unwrap(kek: &[u8; 16], wrapped: &[u8]) -> Result<Vec<u8>, String> {
+    let mut blocks = wrapped.chunks_exact(8);
+    if wrapped.len() < 24 || !blocks.remainder().is_empty() {
+        return Err("invalid length of the wrapped key".to_owned());
+    }
+    let cipher = Aes128::new(GenericArray::from_slice(kek));
+    let mut a = u64::from_be_bytes(block64(blocks.next().unwrap()));
+    let mut r: Vec<[u8; 8]> = blocks.map(block64).collect();
+    let n = r.len() as u64;
+    for j in (0..6).rev() {
+        for (i, r) in r.iter_mut().enumerate().rev() {
+            let mut b = GenericArray::default();
+            b[..8].copy_from_slice(&(a ^ (n * j + i as u64 + 1)).to_be_bytes());
+            b[8..].copy_from_slice(r);
+            cipher.decrypt_block(&mut b);
+            a = u64::from_be_bytes(block64(&b[..8]));
+            r.copy_from_slice(&b[8..]);
+        }
+    }
+    if a != KEY_WRAP_IV {
+        return Err("integrity check of the wrapped key failed".to_owned());
+    }
+    Ok(r.concat())
+}
+
+fn block64(bytes: &[u8]) -> [u8; 8] {
+    let mut block = [0; 8];
+    block.copy_from_slice(bytes);
+    block
+}
+
+/// doubling in GF(2^128), the subkey generation of CMAC
+fn dbl(block: [u8; 16]) -> [u8; 16] {
+    let value = u128::from_be_bytes(block);
+    let carry = if value >> 127 == 1 { 0x87 } else { 0 };
+    ((value << 1) ^ carry).to_be_bytes()
+}
+
+fn xor(block: &mut [u8], other: &[u8]) {
+    for (b, o) in block.iter_mut().zip(other) {
+        *b ^= o;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_psk() {
+        // IEEE Std 802.11-2020, J.4.2
+        assert_eq!(
+            psk(b"password", b"IEEE").to_vec(),
+            unhex("f42c6fc52df0ebef9ebb4b90b38a5f902e83fe1b135a70e23aed762e9710a12e")
+        );
+        assert_eq!(
+            psk(b"ThisIsAPassword", b"ThisIsASSID").to_vec(),
+            unhex("0dc0d6eb90555ed6419756b9a15ec3e3209b63df707dd508d14581f8982721af")
+        );
+    }
+
+    #[test]
+    fn test_prf_sha1() {
+        // IEEE Std 802.11-2020, J.3.2
+        assert_eq!(
+            prf_sha1(&[0x0b; 20], b"prefix", b"Hi There", 64),
+            unhex(
+                "bcd4c650b30b9684951829e0d75f9d54b862175ed9f00606e17d8da35402ffee\
+                 75df78c3d31e0f889f012120c0862beb67753e7439ae242edb8373698356cf5a"
+            )
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, 4.3 Test Case 2
+        assert_eq!(
+            hmac_sha256(b"Jefe", &[b"what do ya want ", b"for nothing?"]).to_vec(),
+            unhex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+    }
+
+    #[test]
+    fn test_kdf_sha256() {
+        // KCK and PMK of the SAE group 19 vector,
+        // IEEE Std 802.11-2020, J.10
+        let keyseed = unhex("7457a00754dcc4e3dc2850c124d6bb8fa1699d7fa33bb0667d9c34eeb513deb9");
+        let context = unhex("40a09b6017cebf0072843b5352aa2b4fb1a84a84c0fe5c235896466a2d3418b1");
+        assert_eq!(
+            kdf_sha256(&keyseed, b"SAE KCK and PMK", &context, 512),
+            unhex(
+                "599d6f1e27548be8499dceed2feccf94818ce1c79f1b4eb3d6a53228a09bf3ed\
+                 7aead86fba4c3221fc437f5f14d70d854ea5d5aac1690116793081eda4d557c5"
+            )
+        );
+    }
+
+    #[test]
+    fn test_aes_cmac() {
+        // RFC 4493, 4. Test Vectors
+        let mut key = [0; 16];
+        key.copy_from_slice(&unhex("2b7e151628aed2a6abf7158809cf4f3c"));
+        let message = unhex(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411",
+        );
+        assert_eq!(
+            aes_cmac(&key, &[]).to_vec(),
+            unhex("bb1d6929e95937287fa37d129b756746")
+        );
+        assert_eq!(
+            aes_cmac(&key, &message[..16]).to_vec(),
+            unhex("070a16b46b4d4144f79bdd9dd04a287c")
+        );
+        assert_eq!(
+            aes_cmac(&key, &message).to_vec(),
+            unhex("dfa66747de9ae63030ca32611497c827")
+        );
+    }
+
+    #[test]
+    fn test_key_wrap() {
+        // RFC 3394, 4.1 Wrap 128 bits of Key Data with a 128-bit KEK
+        let mut kek = [0; 16];
+        kek.copy_from_slice(&(0..16).collect::<Vec<u8>>());
+        let key = unhex("00112233445566778899aabbccddeeff");
+        let wrapped = unhex("1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5");
+        assert_eq!(key_wrap(&kek, &key), Ok(wrapped.clone()));
+        assert_eq!(key_unwrap(&kek, &wrapped), Ok(key));
+
+        let mut corrupted = wrapped;
+        corrupted[23] ^= 1;
+        assert!(key_unwrap(&kek, &corrupted).is_err());
+        assert!(key_unwrap(&kek, &[0; 16]).is_err());
+    }
+}
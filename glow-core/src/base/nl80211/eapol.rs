@@ -0,0 +1,297 @@
+use super::ie::{Element, OUI_IEEE80211};
+
+// References: IEEE Std 802.11-2020, 12.7.2 EAPOL-Key frames
+//             IEEE Std 802.1X-2010, 11.3 EAPOL PDU field and parameter definitions
+
+/// EtherType of EAPOL frames on the control port
+pub const ETH_P_PAE: u16 = 0x888e;
+
+/// Packet type of EAPOL-Key frames
+const EAPOL_KEY: u8 = 3;
+
+/// Descriptor type of RSN EAPOL-Key frames
+const DESCRIPTOR_RSN: u8 = 2;
+
+/// Length of an EAPOL-Key frame without key data, with a MIC of 16 octets
+const EAPOL_KEY_LEN: usize = 4 + 95;
+
+/// Offset of the MIC in an EAPOL-Key frame
+const MIC_OFFSET: usize = 81;
+
+/// Element ID of KDEs, shared with vendor specific elements
+const KDE_ID: u8 = 221;
+
+// Key Information, bits 0-2 are the Key Descriptor Version
+pub const KEY_INFO_VERSION: u16 = 0x0007;
+pub const KEY_INFO_PAIRWISE: u16 = 1 << 3;
+pub const KEY_INFO_INSTALL: u16 = 1 << 6;
+pub const KEY_INFO_ACK: u16 = 1 << 7;
+pub const KEY_INFO_MIC: u16 = 1 << 8;
+pub const KEY_INFO_SECURE: u16 = 1 << 9;
+pub const KEY_INFO_ERROR: u16 = 1 << 10;
+pub const KEY_INFO_REQUEST: u16 = 1 << 11;
+pub const KEY_INFO_ENCRYPTED: u16 = 1 << 12;
+
+/// An EAPOL-Key frame with the RSN key descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub struct EapolKey {
+    /// protocol version of the EAPOL header
+    pub version: u8,
+    pub info: u16,
+    pub key_len: u16,
+    pub replay_counter: u64,
+    pub nonce: [u8; 32],
+    pub iv: [u8; 16],
+    pub rsc: [u8; 8],
+    pub mic: [u8; 16],
+    pub data: Vec<u8>,
+}
+
+impl EapolKey {
+    /// a frame of the supplicant replying to `request` of the authenticator,
+    /// without MIC and key data
+    pub fn reply(request: &EapolKey, info: u16) -> EapolKey {
+        EapolKey {
+            version: request.version,
+            info: request.info & KEY_INFO_VERSION | info,
+            key_len: 0,
+            replay_counter: request.replay_counter,
+            nonce: [0; 32],
+            iv: [0; 16],
+            rsc: [0; 8],
+            mic: [0; 16],
+            data: Vec::new(),
+        }
+    }
+
+    pub fn parse(frame: &[u8]) -> Result<EapolKey, String> {
+        if frame.len() < EAPOL_KEY_LEN {
+            return Err("truncated EAPOL-Key frame".to_owned());
+        }
+        if frame[1] != EAPOL_KEY || frame[4] != DESCRIPTOR_RSN {
+            return Err("not an RSN EAPOL-Key frame".to_owned());
+        }
+        let body_len = u16::from_be_bytes([frame[2], frame[3]]) as usize;
+        let data_len = u16::from_be_bytes([frame[97], frame[98]]) as usize;
+        if 4 + body_len > frame.len() || EAPOL_KEY_LEN + data_len > 4 + body_len {
+            return Err("truncated EAPOL-Key frame".to_owned());
+        }
+
+        let mut key = EapolKey {
+            version: frame[0],
+            info: u16::from_be_bytes([frame[5], frame[6]]),
+            key_len: u16::from_be_bytes([frame[7], frame[8]]),
+            replay_counter: 0,
+            nonce: [0; 32],
+            iv: [0; 16],
+            rsc: [0; 8],
+            mic: [0; 16],
+            data: frame[EAPOL_KEY_LEN..(EAPOL_KEY_LEN + data_len)].to_vec(),
+        };
+        let mut replay_counter = [0; 8];
+        replay_counter.copy_from_slice(&frame[9..17]);
+        key.replay_counter = u64::from_be_bytes(replay_counter);
+        key.nonce.copy_from_slice(&frame[17..49]);
+        key.iv.copy_from_slice(&frame[49..65]);
+        key.rsc.copy_from_slice(&frame[65..73]);
+        key.mic
+            .copy_from_slice(&frame[MIC_OFFSET..(MIC_OFFSET + 16)]);
+        Ok(key)
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(EAPOL_KEY_LEN + self.data.len());
+        frame.push(self.version);
+        frame.push(EAPOL_KEY);
+        frame.extend_from_slice(&((EAPOL_KEY_LEN - 4 + self.data.len()) as u16).to_be_bytes());
+        frame.push(DESCRIPTOR_RSN);
+        frame.extend_from_slice(&self.info.to_be_bytes());
+        frame.extend_from_slice(&self.key_len.to_be_bytes());
+        frame.extend_from_slice(&self.replay_counter.to_be_bytes());
+        frame.extend_from_slice(&self.nonce);
+        frame.extend_from_slice(&self.iv);
+        frame.extend_from_slice(&self.rsc);
+        // Key Identifier, reserved
+        frame.extend_from_slice(&[0; 8]);
+        frame.extend_from_slice(&self.mic);
+        frame.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&self.data);
+        frame
+    }
+
+    /// whether all Key Information bits of `bits` are set
+    pub fn has(&self, bits: u16) -> bool {
+        self.info & bits == bits
+    }
+
+    pub fn descriptor_version(&self) -> u16 {
+        self.info & KEY_INFO_VERSION
+    }
+}
+
+/// The EAPOL PDU of a frame accepted by `EapolKey::parse` with a zero MIC,
+/// as the MIC is calculated over
+pub fn without_mic(frame: &[u8]) -> Vec<u8> {
+    let len = 4 + u16::from_be_bytes([frame[2], frame[3]]) as usize;
+    let mut pdu = frame[..len].to_vec();
+    pdu[MIC_OFFSET..(MIC_OFFSET + 16)].copy_from_slice(&[0; 16]);
+    pdu
+}
+
+/// Key Data Encapsulation of the IEEE 802.11 OUI
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kde {
+    Gtk {
+        index: u8,
+        tx: bool,
+        key: Vec<u8>,
+    },
+    Pmkid([u8; 16]),
+    Igtk {
+        index: u16,
+        ipn: [u8; 6],
+        key: Vec<u8>,
+    },
+    Unknown {
+        data_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl Kde {
+    fn parse(data_type: u8, data: &[u8]) -> Option<Kde> {
+        match data_type {
+            1 if data.len() > 2 => Some(Kde::Gtk {
+                index: data[0] & 0x03,
+                tx: data[0] & 0x04 != 0,
+                key: data[2..].to_vec(),
+            }),
+            4 if data.len() == 16 => {
+                let mut pmkid = [0; 16];
+                pmkid.copy_from_slice(data);
+                Some(Kde::Pmkid(pmkid))
+            }
+            9 if data.len() > 8 => {
+                let mut ipn = [0; 6];
+                ipn.copy_from_slice(&data[2..8]);
+                Some(Kde::Igtk {
+                    index: u16::from_le_bytes([data[0], data[1]]),
+                    ipn,
+                    key: data[8..].to_vec(),
+                })
+            }
+            1 | 4 | 9 => None,
+            _ => Some(Kde::Unknown {
+                data_type,
+                data: data.to_vec(),
+            }),
+        }
+    }
+}
+
+/// Split the key data into its elements and KDEs, up to the padding
+pub fn parse_key_data(data: &[u8]) -> Result<(Vec<Element>, Vec<Kde>), String> {
+    let mut elements = Vec::new();
+    let mut kdes = Vec::new();
+    let mut offset = 0;
+    while offset + 2 <= data.len() {
+        let id = data[offset];
+        let len = data[offset + 1] as usize;
+        // the padding starts like an empty KDE
+        if id == KDE_ID && len == 0 {
+            break;
+        }
+        let body = match data.get((offset + 2)..(offset + 2 + len)) {
+            Some(body) => body,
+            None => return Err(format!("key data at {} is truncated", offset)),
+        };
+        if id == KDE_ID && len >= 4 && body[..3] == OUI_IEEE80211 {
+            match Kde::parse(body[3], &body[4..]) {
+                Some(kde) => kdes.push(kde),
+                None => return Err(format!("invalid KDE {} at {}", body[3], offset)),
+            }
+        } else {
+            elements.push(Element::parse(id, body));
+        }
+        offset += 2 + len;
+    }
+    Ok((elements, kdes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::ie::CipherSuite;
+
+    #[test]
+    fn test_eapol_key() {
+        let mut key = EapolKey {
+            version: 2,
+            info: 2 | KEY_INFO_PAIRWISE | KEY_INFO_ACK,
+            key_len: 16,
+            replay_counter: 1,
+            nonce: [0xaa; 32],
+            iv: [0; 16],
+            rsc: [0; 8],
+            mic: [0; 16],
+            data: vec![0xdd, 0x14, 0x00, 0x0f, 0xac, 0x04],
+        };
+        key.data.extend_from_slice(&[0x55; 16]);
+        let frame = key.write();
+        assert_eq!(frame.len(), 99 + 22);
+        assert_eq!(&frame[..9], &[2, 3, 0, 117, 2, 0, 0x8a, 0, 16]);
+        assert_eq!(&frame[9..17], &[0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(&frame[97..99], &[0, 22]);
+        assert_eq!(EapolKey::parse(&frame), Ok(key.clone()));
+        assert!(key.has(KEY_INFO_PAIRWISE | KEY_INFO_ACK));
+        assert!(!key.has(KEY_INFO_PAIRWISE | KEY_INFO_MIC));
+        assert_eq!(key.descriptor_version(), 2);
+
+        let (elements, kdes) = parse_key_data(&key.data).unwrap();
+        assert!(elements.is_empty());
+        assert_eq!(kdes, vec![Kde::Pmkid([0x55; 16])]);
+
+        // trailing bytes after the body are ignored
+        let mut padded = frame.clone();
+        padded.extend_from_slice(&[0; 4]);
+        assert_eq!(EapolKey::parse(&padded), Ok(key));
+        assert!(EapolKey::parse(&frame[..110]).is_err());
+    }
+
+    #[test]
+    fn test_parse_key_data() {
+        let mut data = vec![48, 20, 1, 0, 0, 0x0f, 0xac, 4, 1, 0, 0, 0x0f, 0xac, 4];
+        data.extend_from_slice(&[1, 0, 0, 0x0f, 0xac, 2, 0, 0]);
+        // GTK KDE with key 1, transmit
+        data.extend_from_slice(&[0xdd, 22, 0x00, 0x0f, 0xac, 1, 0x05, 0]);
+        data.extend_from_slice(&[0x11; 16]);
+        // IGTK KDE with key 4 and IPN 1
+        data.extend_from_slice(&[0xdd, 28, 0x00, 0x0f, 0xac, 9, 4, 0, 1, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0x22; 16]);
+        data.extend_from_slice(&[0xdd, 0, 0, 0]);
+
+        let (elements, kdes) = parse_key_data(&data).unwrap();
+        match &elements[..] {
+            [Element::Rsn(rsn)] => assert_eq!(rsn.group_cipher, Some(CipherSuite::Ccmp128)),
+            _ => panic!("unexpected elements {:?}", elements),
+        }
+        assert_eq!(
+            kdes,
+            vec![
+                Kde::Gtk {
+                    index: 1,
+                    tx: true,
+                    key: vec![0x11; 16],
+                },
+                Kde::Igtk {
+                    index: 4,
+                    ipn: [1, 0, 0, 0, 0, 0],
+                    key: vec![0x22; 16],
+                },
+            ]
+        );
+
+        assert!(parse_key_data(&[0xdd, 6, 0x00, 0x0f, 0xac, 1, 0, 0]).is_err());
+        assert!(parse_key_data(&[48, 20, 1, 0]).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+use super::{
+    attr::*,
+    client::NL80211Client,
+    cmd::Nl80211Cmd,
+    constant::*,
+    interface::{parse_interface, Channel, Interface},
+    status::{ReasonCode, StatusCode},
+    transport::Transport,
+    utils::{attr_string, mac_addr},
+};
+use futures::stream::Stream;
+use neli::attr::Attribute;
+use neli::err::NlError;
+use neli::genl::{Genlmsghdr, Nlattr};
+use neli::types::Buffer;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// Multicast groups of the events
+const EVENT_GROUPS: [&str; 5] = [
+    NL80211_MULTICAST_GROUP_CONFIG,
+    NL80211_MULTICAST_GROUP_SCAN,
+    NL80211_MULTICAST_GROUP_REG,
+    NL80211_MULTICAST_GROUP_MLME,
+    NL80211_MULTICAST_GROUP_VENDOR,
+];
+
+/// Events buffered for the stream, more are dropped until it is read
+const EVENT_BUFFER: usize = 256;
+
+/// How often the receiving thread looks whether the stream was dropped
+const CLOSED_POLL: Duration = Duration::from_millis(500);
+
+/// Something nl80211 notified about, `ifindex` is 0 for wdevs without netdev
+#[derive(Debug)]
+pub enum Nl80211Event {
+    NewWiphy {
+        wiphy: u32,
+    },
+    DelWiphy {
+        wiphy: u32,
+    },
+    NewInterface(Interface),
+    DelInterface(Interface),
+    ScanStarted {
+        ifindex: u32,
+    },
+    /// the results can be fetched with `scan_results`
+    ScanDone {
+        ifindex: u32,
+    },
+    ScanAborted {
+        ifindex: u32,
+    },
+    /// result of connecting
+    Connect {
+        ifindex: u32,
+        bssid: Option<[u8; 6]>,
+        status: StatusCode,
+        /// no response from the AP
+        timed_out: bool,
+        /// elements of the association request and response
+        req_ies: Vec<u8>,
+        resp_ies: Vec<u8>,
+    },
+    Roam {
+        ifindex: u32,
+        bssid: [u8; 6],
+    },
+    Disconnect {
+        ifindex: u32,
+        reason: ReasonCode,
+        /// deauthenticated or disassociated by the AP, not by us
+        by_ap: bool,
+    },
+    /// the regulatory domain changed, of `wiphy` if it is self-managed
+    RegChange {
+        wiphy: Option<u32>,
+        initiator: Option<Nl80211RegInitiator>,
+        alpha2: Option<String>,
+    },
+    NewStation {
+        ifindex: u32,
+        mac: [u8; 6],
+    },
+    DelStation {
+        ifindex: u32,
+        mac: [u8; 6],
+    },
+    /// connection quality monitor configured with NL80211_CMD_SET_CQM
+    Cqm {
+        ifindex: u32,
+        bssid: Option<[u8; 6]>,
+        event: CqmEvent,
+    },
+    ChannelSwitch {
+        ifindex: u32,
+        channel: Channel,
+    },
+    Vendor {
+        wiphy: Option<u32>,
+        ifindex: Option<u32>,
+        oui: u32,
+        subcmd: u32,
+        data: Vec<u8>,
+    },
+    /// a command without typed event
+    Other(Nl80211Cmd),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CqmEvent {
+    /// the RSSI fell below the threshold
+    RssiLow {
+        rssi_dbm: Option<i32>,
+    },
+    /// the RSSI rose above the threshold
+    RssiHigh {
+        rssi_dbm: Option<i32>,
+    },
+    BeaconLoss,
+    /// number of packets lost in a row
+    PacketLoss(u32),
+    /// the tx error rate exceeded `rate` percent over `interval` seconds
+    TxError {
+        rate: u32,
+        packets: u32,
+        interval: u32,
+    },
+    Unknown,
+}
+
+/// Events received by a background thread, see `NL80211Client::events`
+pub struct EventStream {
+    events: mpsc::Receiver<Nl80211Event>,
+    closed: Arc<AtomicBool>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventStream {
+    /// number of events dropped as the stream was not read in time
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Nl80211Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        // ends the thread and closes the socket
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<T: Transport + Send + 'static> NL80211Client<T> {
+    /// Join the config, scan, regulatory, mlme and vendor multicast groups
+    /// and stream their events of all devices.
+    ///
+    /// The client is dedicated to the events, use another one for requests.
+    /// Events not read in time are dropped once 256 are waiting,
+    /// counted by `EventStream::dropped`. The stream ends when receiving
+    /// fails, e.g. with ENOBUFS after the socket overflowed as well, so state
+    /// derived from it has to be fetched again.
+    pub fn events(mut self) -> Result<EventStream, NlError> {
+        let mut joined = false;
+        for name in EVENT_GROUPS.iter() {
+            // older kernels lack some of the groups
+            if self.group_id(name).is_ok() {
+                self.subscribe(name)?;
+                joined = true;
+            }
+        }
+        if !joined {
+            return Err(NlError::new("no nl80211 multicast group resolved"));
+        }
+        Ok(self.stream(EVENT_BUFFER))
+    }
+
+    fn stream(mut self, buffer: usize) -> EventStream {
+        let (mut sender, events) = mpsc::channel(buffer);
+        let closed = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let stream = EventStream {
+            events,
+            closed: closed.clone(),
+            dropped: dropped.clone(),
+        };
+        thread::spawn(move || loop {
+            // wakes up in time to notice the stream was dropped
+            let deadline = Instant::now() + CLOSED_POLL;
+            let msgs = match self.recv_events(Some(deadline)) {
+                Ok(msgs) => msgs,
+                Err(_) if Instant::now() >= deadline && !closed.load(Ordering::Relaxed) => continue,
+                Err(_) => return,
+            };
+            for msg in msgs.iter() {
+                let event = match parse_event(msg) {
+                    Ok(event) => event,
+                    Err(_) => continue,
+                };
+                match sender.try_send(event) {
+                    Ok(()) => (),
+                    Err(TrySendError::Full(_)) => {
+                        dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(TrySendError::Closed(_)) => return,
+                }
+            }
+        });
+        stream
+    }
+}
+
+pub(crate) fn parse_event(
+    msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>,
+) -> Result<Nl80211Event, NlError> {
+    let handle = msg.get_attr_handle();
+    let u32_attr = |nla_type| match handle.get_attribute(nla_type) {
+        Some(attr) => attr.get_payload_as::<u32>().map(Some),
+        None => Ok(None),
+    };
+    let mac_attr = || match handle.get_attribute(Nl80211Attr::AttrMac) {
+        Some(attr) => mac_addr(attr.payload().as_ref()).map(Some),
+        None => Ok(None),
+    };
+    let required = |mac: Option<[u8; 6]>| match mac {
+        Some(mac) => Ok(mac),
+        None => Err(NlError::new("event without NL80211_ATTR_MAC")),
+    };
+    let ifindex = u32_attr(Nl80211Attr::AttrIfindex)?.unwrap_or(0);
+    let flag = |nla_type| handle.get_attribute(nla_type).is_some();
+    let bytes = |nla_type| match handle.get_attribute(nla_type) {
+        Some(attr) => attr.payload().as_ref().to_vec(),
+        None => Vec::new(),
+    };
+
+    let event = match &msg.cmd {
+        Nl80211Cmd::CmdNewWiphy => Nl80211Event::NewWiphy {
+            wiphy: u32_attr(Nl80211Attr::AttrWiphy)?.unwrap_or(0),
+        },
+        Nl80211Cmd::CmdDelWiphy => Nl80211Event::DelWiphy {
+            wiphy: u32_attr(Nl80211Attr::AttrWiphy)?.unwrap_or(0),
+        },
+        Nl80211Cmd::CmdNewInterface => Nl80211Event::NewInterface(parse_interface(msg)?),
+        Nl80211Cmd::CmdDelInterface => Nl80211Event::DelInterface(parse_interface(msg)?),
+        Nl80211Cmd::CmdTriggerScan => Nl80211Event::ScanStarted { ifindex },
+        Nl80211Cmd::CmdNewScanResults => Nl80211Event::ScanDone { ifindex },
+        Nl80211Cmd::CmdScanAborted => Nl80211Event::ScanAborted { ifindex },
+        Nl80211Cmd::CmdConnect => Nl80211Event::Connect {
+            ifindex,
+            bssid: mac_attr()?,
+            status: match handle.get_attribute(Nl80211Attr::AttrStatusCode) {
+                Some(attr) => StatusCode::from(attr.get_payload_as::<u16>()?),
+                None => StatusCode::Unspecified,
+            },
+            timed_out: flag(Nl80211Attr::AttrTimedOut),
+            req_ies: bytes(Nl80211Attr::AttrReqIe),
+            resp_ies: bytes(Nl80211Attr::AttrRespIe),
+        },
+        Nl80211Cmd::CmdRoam => Nl80211Event::Roam {
+            ifindex,
+            bssid: required(mac_attr()?)?,
+        },
+        Nl80211Cmd::CmdDisconnect => Nl80211Event::Disconnect {
+            ifindex,
+            reason: match handle.get_attribute(Nl80211Attr::AttrReasonCode) {
+                Some(attr) => ReasonCode::from(attr.get_payload_as::<u16>()?),
+                None => ReasonCode::Unspecified,
+            },
+            by_ap: flag(Nl80211Attr::AttrDisconnectedByAp),
+        },
+        Nl80211Cmd::CmdRegChange | Nl80211Cmd::CmdWiphyRegChange => Nl80211Event::RegChange {
+            wiphy: u32_attr(Nl80211Attr::AttrWiphy)?,
+            initiator: match handle.get_attribute(Nl80211Attr::AttrRegInitiator) {
+                Some(attr) => Some(Nl80211RegInitiator::from(
+                    attr.get_payload_as::<u8>()? as u16
+                )),
+                None => None,
+            },
+            alpha2: handle
+                .get_attribute(Nl80211Attr::AttrRegAlpha2)
+                .map(|attr| attr_string(attr.payload().as_ref())),
+        },
+        Nl80211Cmd::CmdNewStation => Nl80211Event::NewStation {
+            ifindex,
+            mac: required(mac_attr()?)?,
+        },
+        Nl80211Cmd::CmdDelStation => Nl80211Event::DelStation {
+            ifindex,
+            mac: required(mac_attr()?)?,
+        },
+        Nl80211Cmd::CmdNotifyCqm => Nl80211Event::Cqm {
+            ifindex,
+            bssid: mac_attr()?,
+            event: match handle.get_attribute(Nl80211Attr::AttrCqm) {
+                Some(attr) => parse_cqm(attr)?,
+                None => CqmEvent::Unknown,
+            },
+        },
+        Nl80211Cmd::CmdChSwitchNotify => Nl80211Event::ChannelSwitch {
+            ifindex,
+            channel: Channel {
+                freq: u32_attr(Nl80211Attr::AttrWiphyFreq)?.unwrap_or(0),
+                width: Nl80211ChanWidth::from(
+                    u32_attr(Nl80211Attr::AttrChannelWidth)?.unwrap_or(0) as u16,
+                ),
+                center_freq1: u32_attr(Nl80211Attr::AttrCenterFreq1)?.unwrap_or(0),
+                center_freq2: u32_attr(Nl80211Attr::AttrCenterFreq2)?.unwrap_or(0),
+            },
+        },
+        Nl80211Cmd::CmdVendor => Nl80211Event::Vendor {
+            wiphy: u32_attr(Nl80211Attr::AttrWiphy)?,
+            ifindex: u32_attr(Nl80211Attr::AttrIfindex)?,
+            oui: u32_attr(Nl80211Attr::AttrVendorId)?.unwrap_or(0),
+            subcmd: u32_attr(Nl80211Attr::AttrVendorSubcmd)?.unwrap_or(0),
+            data: bytes(Nl80211Attr::AttrVendorData),
+        },
+        cmd => Nl80211Event::Other(*cmd),
+    };
+    Ok(event)
+}
+
+fn parse_cqm(cqm: &Nlattr<Nl80211Attr, Buffer>) -> Result<CqmEvent, NlError> {
+    let handle = cqm.get_attr_handle::<Nl80211AttrCqm>()?;
+    let get = |nla_type| match handle.get_attribute(nla_type) {
+        Some(attr) => attr.get_payload_as::<u32>().map(Some),
+        None => Ok(None),
+    };
+    let rssi_dbm = get(Nl80211AttrCqm::AttrCqmRssiLevel)?.map(|rssi| rssi as i32);
+
+    if let Some(threshold) = get(Nl80211AttrCqm::AttrCqmRssiThresholdEvent)? {
+        return Ok(match Nl80211CqmRssiThresholdEvent::from(threshold as u16) {
+            Nl80211CqmRssiThresholdEvent::CqmRssiThresholdEventLow => {
+                CqmEvent::RssiLow { rssi_dbm }
+            }
+            Nl80211CqmRssiThresholdEvent::CqmRssiThresholdEventHigh => {
+                CqmEvent::RssiHigh { rssi_dbm }
+            }
+            Nl80211CqmRssiThresholdEvent::CqmRssiBeaconLossEvent => CqmEvent::BeaconLoss,
+            _ => CqmEvent::Unknown,
+        });
+    }
+    if let Some(packets) = get(Nl80211AttrCqm::AttrCqmPktLossEvent)? {
+        return Ok(CqmEvent::PacketLoss(packets));
+    }
+    if handle
+        .get_attribute(Nl80211AttrCqm::AttrCqmBeaconLossEvent)
+        .is_some()
+    {
+        return Ok(CqmEvent::BeaconLoss);
+    }
+    if let Some(rate) = get(Nl80211AttrCqm::AttrCqmTxeRate)? {
+        return Ok(CqmEvent::TxError {
+            rate,
+            packets: get(Nl80211AttrCqm::AttrCqmTxePkts)?.unwrap_or(0),
+            interval: get(Nl80211AttrCqm::AttrCqmTxeIntvl)?.unwrap_or(0),
+        });
+    }
+    Ok(CqmEvent::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::{client, datagrams};
+    use crate::base::nl80211::utils::format_mac;
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn test_events() {
+        let mut client = client(&datagrams(include_bytes!("fixtures/events.bin")));
+        for (id, name) in EVENT_GROUPS.iter().enumerate() {
+            client.multicast_group(name, id as u32 + 3);
+        }
+        // ends when the replay runs out of events
+        let events: Vec<Nl80211Event> = block_on(client.events().unwrap().collect());
+        assert_eq!(events.len(), 11);
+
+        assert!(matches!(events[0], Nl80211Event::ScanDone { ifindex: 3 }));
+        match &events[1] {
+            Nl80211Event::Connect {
+                ifindex,
+                bssid,
+                status,
+                timed_out,
+                req_ies,
+                resp_ies,
+            } => {
+                assert_eq!(*ifindex, 3);
+                assert_eq!(format_mac(bssid.as_ref().unwrap()), "02:00:00:00:02:00");
+                assert!(status.is_success());
+                assert!(!timed_out);
+                assert_eq!(req_ies, b"\0\x04glow");
+                assert_eq!(resp_ies, &[1, 1, 0x8c]);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        match &events[2] {
+            Nl80211Event::Cqm { event, .. } => {
+                assert_eq!(
+                    *event,
+                    CqmEvent::RssiLow {
+                        rssi_dbm: Some(-80)
+                    }
+                )
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        match &events[3] {
+            Nl80211Event::ChannelSwitch { channel, .. } => {
+                assert_eq!(channel.freq, 5200);
+                assert_eq!(channel.width, Nl80211ChanWidth::ChanWidth80);
+                assert_eq!(channel.center_freq1, 5210);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        match &events[4] {
+            Nl80211Event::Roam { bssid, .. } => {
+                assert_eq!(format_mac(bssid), "02:00:00:00:03:00")
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        assert!(matches!(
+            events[5],
+            Nl80211Event::Disconnect {
+                ifindex: 3,
+                reason: ReasonCode::DeauthLeaving,
+                by_ap: true
+            }
+        ));
+        match &events[6] {
+            Nl80211Event::RegChange {
+                wiphy,
+                initiator,
+                alpha2,
+            } => {
+                assert_eq!(*wiphy, None);
+                assert_eq!(*initiator, Some(Nl80211RegInitiator::RegdomSetByCountryIe));
+                assert_eq!(alpha2.as_deref(), Some("DE"));
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        assert!(matches!(
+            events[7],
+            Nl80211Event::NewStation { ifindex: 4, .. }
+        ));
+        match &events[8] {
+            Nl80211Event::Vendor {
+                wiphy,
+                oui,
+                subcmd,
+                data,
+                ..
+            } => {
+                assert_eq!(*wiphy, Some(0));
+                assert_eq!(*oui, 0x001374);
+                assert_eq!(*subcmd, 1);
+                assert_eq!(data, &[1, 2, 3]);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        match &events[9] {
+            Nl80211Event::DelInterface(interface) => {
+                assert_eq!(interface.name, "wlan1");
+                assert_eq!(interface.iftype, Nl80211Iftype::IftypeAp);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        assert!(matches!(
+            events[10],
+            Nl80211Event::Other(Nl80211Cmd::CmdFrameTxStatus)
+        ));
+    }
+
+    #[test]
+    fn test_events_unresolved() {
+        assert!(client(&[]).events().is_err());
+    }
+
+    #[test]
+    fn test_events_overflow() {
+        let client = client(&datagrams(include_bytes!("fixtures/events.bin")));
+        let stream = client.stream(4);
+        let start = Instant::now();
+        while stream.dropped() < 7 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+        // the first events are kept
+        let events: Vec<Nl80211Event> = block_on(stream.collect());
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0], Nl80211Event::ScanDone { ifindex: 3 }));
+    }
+}
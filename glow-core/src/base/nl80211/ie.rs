@@ -0,0 +1,1111 @@
+//! 802.11 information elements, as found in beacons and probe responses
+
+use std::convert::TryInto;
+
+/// Element IDs, IEEE Std 802.11-2020 9.4.2.1
+pub const EID_SSID: u8 = 0;
+pub const EID_SUPP_RATES: u8 = 1;
+pub const EID_DS_PARAMS: u8 = 3;
+pub const EID_COUNTRY: u8 = 7;
+pub const EID_HT_CAP: u8 = 45;
+pub const EID_RSN: u8 = 48;
+pub const EID_EXT_SUPP_RATES: u8 = 50;
+pub const EID_MOBILITY_DOMAIN: u8 = 54;
+pub const EID_HT_OPERATION: u8 = 61;
+pub const EID_RM_ENABLED_CAP: u8 = 70;
+pub const EID_EXT_CAP: u8 = 127;
+pub const EID_VHT_CAP: u8 = 191;
+pub const EID_VHT_OPERATION: u8 = 192;
+pub const EID_VENDOR_SPECIFIC: u8 = 221;
+pub const EID_EXTENSION: u8 = 255;
+
+/// Element ID extensions of `EID_EXTENSION`
+pub const EID_EXT_OWE_DH_PARAM: u8 = 32;
+pub const EID_EXT_HE_CAP: u8 = 35;
+pub const EID_EXT_HE_OPERATION: u8 = 36;
+
+/// OUI of the suites in the RSN element
+pub const OUI_IEEE80211: [u8; 3] = [0x00, 0x0f, 0xac];
+/// OUI of Microsoft, used by WPA and WMM
+pub const OUI_MICROSOFT: [u8; 3] = [0x00, 0x50, 0xf2];
+/// vendor specific type of the WPA element
+const WPA_OUI_TYPE: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Ssid(Vec<u8>),
+    /// rates in 500 kbit/s, the top bit marks basic rates
+    SupportedRates(Vec<u8>),
+    /// current channel
+    DsParameter(u8),
+    Country(Country),
+    HtCapabilities(HtCapabilities),
+    Rsn(Rsn),
+    ExtendedSupportedRates(Vec<u8>),
+    MobilityDomain(MobilityDomain),
+    HtOperation(HtOperation),
+    RmEnabledCapabilities([u8; 5]),
+    /// bitmap of extended capabilities, see `has_ext_capability`
+    ExtendedCapabilities(Vec<u8>),
+    VhtCapabilities(VhtCapabilities),
+    VhtOperation(VhtOperation),
+    /// WPA, the pre-RSN vendor element with suites of `OUI_MICROSOFT`
+    Wpa(Rsn),
+    HeCapabilities(HeCapabilities),
+    HeOperation(HeOperation),
+    Vendor {
+        oui: [u8; 3],
+        data: Vec<u8>,
+    },
+    Extension {
+        id: u8,
+        data: Vec<u8>,
+    },
+    /// elements not decoded, or malformed ones
+    Unknown {
+        id: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Country {
+    pub code: [u8; 2],
+    /// ' ' for all environments, 'O' outdoor, 'I' indoor
+    pub environment: u8,
+    pub triplets: Vec<CountryTriplet>,
+    /// padded to an even length
+    pub padded: bool,
+}
+
+/// A subband, or an operating extension if `first_channel` is 201 or more
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountryTriplet {
+    pub first_channel: u8,
+    pub num_channels: u8,
+    pub max_tx_power_dbm: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtCapabilities {
+    pub info: u16,
+    pub ampdu_params: u8,
+    pub mcs_set: [u8; 16],
+    pub extended: u16,
+    pub txbf: u32,
+    pub asel: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtOperation {
+    pub primary_channel: u8,
+    pub info: [u8; 5],
+    pub basic_mcs_set: [u8; 16],
+}
+
+impl HtOperation {
+    /// secondary channel above (1), below (3) or none (0)
+    pub fn secondary_channel_offset(&self) -> u8 {
+        self.info[0] & 0x03
+    }
+
+    /// whether any channel width is allowed, not only 20 MHz
+    pub fn any_channel_width(&self) -> bool {
+        self.info[0] & 0x04 != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VhtCapabilities {
+    pub info: u32,
+    /// Rx and Tx MCS maps and highest data rates
+    pub mcs_set: [u8; 8],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VhtOperation {
+    /// 0 for 20 or 40 MHz, 1 for 80, 160 or 80+80 MHz
+    pub channel_width: u8,
+    pub center_seg0: u8,
+    pub center_seg1: u8,
+    pub basic_mcs_set: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeCapabilities {
+    pub mac: [u8; 6],
+    pub phy: [u8; 11],
+    /// Rx and Tx HE-MCS maps for 80, 160 and 80+80 MHz as supported
+    pub mcs_nss: Vec<u8>,
+    /// PPE thresholds
+    pub ppe: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeOperation {
+    pub params: [u8; 3],
+    pub bss_color: u8,
+    pub basic_mcs_nss: u16,
+    /// VHT operation information, max co-hosted BSSID indicator
+    /// and 6 GHz operation information, as present
+    pub optional: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MobilityDomain {
+    pub mdid: u16,
+    pub ft_capability: u8,
+}
+
+/// The RSN element, trailing fields are omitted from the end
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rsn {
+    pub version: u16,
+    pub group_cipher: Option<CipherSuite>,
+    pub pairwise_ciphers: Option<Vec<CipherSuite>>,
+    pub akm_suites: Option<Vec<AkmSuite>>,
+    pub capabilities: Option<u16>,
+    pub pmkids: Option<Vec<[u8; 16]>>,
+    pub group_mgmt_cipher: Option<CipherSuite>,
+}
+
+impl Rsn {
+    /// management frame protection capable
+    pub fn mfp_capable(&self) -> bool {
+        self.capabilities.unwrap_or(0) & 0x0080 != 0
+    }
+
+    /// management frame protection required
+    pub fn mfp_required(&self) -> bool {
+        self.capabilities.unwrap_or(0) & 0x0040 != 0
+    }
+
+    /// pairwise ciphers, CCMP-128 if omitted
+    pub fn pairwise(&self) -> Vec<CipherSuite> {
+        match &self.pairwise_ciphers {
+            Some(ciphers) => ciphers.clone(),
+            None => vec![CipherSuite::Ccmp128],
+        }
+    }
+
+    /// AKM suites, PSK if omitted
+    pub fn akms(&self) -> Vec<AkmSuite> {
+        match &self.akm_suites {
+            Some(akms) => akms.clone(),
+            None => vec![AkmSuite::Psk],
+        }
+    }
+
+    fn parse(data: &[u8], oui: [u8; 3]) -> Option<Rsn> {
+        let mut reader = Reader(data);
+        let mut rsn = Rsn {
+            version: u16::from_le_bytes(reader.take(2)?.try_into().ok()?),
+            group_cipher: None,
+            pairwise_ciphers: None,
+            akm_suites: None,
+            capabilities: None,
+            pmkids: None,
+            group_mgmt_cipher: None,
+        };
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        rsn.group_cipher = Some(CipherSuite::from_selector(reader.suite()?, oui));
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        let count = reader.u16()?;
+        let mut ciphers = Vec::new();
+        for _ in 0..count {
+            ciphers.push(CipherSuite::from_selector(reader.suite()?, oui));
+        }
+        rsn.pairwise_ciphers = Some(ciphers);
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        let count = reader.u16()?;
+        let mut akms = Vec::new();
+        for _ in 0..count {
+            akms.push(AkmSuite::from_selector(reader.suite()?, oui));
+        }
+        rsn.akm_suites = Some(akms);
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        rsn.capabilities = Some(reader.u16()?);
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        let count = reader.u16()?;
+        let mut pmkids = Vec::new();
+        for _ in 0..count {
+            pmkids.push(reader.take(16)?.try_into().ok()?);
+        }
+        rsn.pmkids = Some(pmkids);
+        if reader.is_empty() {
+            return Some(rsn);
+        }
+        rsn.group_mgmt_cipher = Some(CipherSuite::from_selector(reader.suite()?, oui));
+        if reader.is_empty() {
+            Some(rsn)
+        } else {
+            None
+        }
+    }
+
+    fn write(&self, buf: &mut Vec<u8>, oui: [u8; 3]) {
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        let group = match self.group_cipher {
+            Some(group) => group,
+            None => return,
+        };
+        buf.extend_from_slice(&group.selector(oui).to_be_bytes());
+        let ciphers = match &self.pairwise_ciphers {
+            Some(ciphers) => ciphers,
+            None => return,
+        };
+        buf.extend_from_slice(&(ciphers.len() as u16).to_le_bytes());
+        for cipher in ciphers.iter() {
+            buf.extend_from_slice(&cipher.selector(oui).to_be_bytes());
+        }
+        let akms = match &self.akm_suites {
+            Some(akms) => akms,
+            None => return,
+        };
+        buf.extend_from_slice(&(akms.len() as u16).to_le_bytes());
+        for akm in akms.iter() {
+            buf.extend_from_slice(&akm.selector(oui).to_be_bytes());
+        }
+        let capabilities = match self.capabilities {
+            Some(capabilities) => capabilities,
+            None => return,
+        };
+        buf.extend_from_slice(&capabilities.to_le_bytes());
+        let pmkids = match &self.pmkids {
+            Some(pmkids) => pmkids,
+            None => return,
+        };
+        buf.extend_from_slice(&(pmkids.len() as u16).to_le_bytes());
+        for pmkid in pmkids.iter() {
+            buf.extend_from_slice(pmkid);
+        }
+        if let Some(cipher) = self.group_mgmt_cipher {
+            buf.extend_from_slice(&cipher.selector(oui).to_be_bytes());
+        }
+    }
+}
+
+/// Cipher suite types of the element's OUI, IEEE Std 802.11-2020 Table 9-149
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CipherSuite {
+    UseGroup,
+    Wep40,
+    Tkip,
+    Ccmp128,
+    Wep104,
+    BipCmac128,
+    GroupNotAllowed,
+    Gcmp128,
+    Gcmp256,
+    Ccmp256,
+    BipGmac128,
+    BipGmac256,
+    BipCmac256,
+    /// suite selector, OUI and type
+    Other(u32),
+}
+
+impl CipherSuite {
+    fn from_selector(selector: u32, oui: [u8; 3]) -> CipherSuite {
+        if selector >> 8 != oui_value(oui) {
+            return CipherSuite::Other(selector);
+        }
+        match selector & 0xff {
+            0 => CipherSuite::UseGroup,
+            1 => CipherSuite::Wep40,
+            2 => CipherSuite::Tkip,
+            4 => CipherSuite::Ccmp128,
+            5 => CipherSuite::Wep104,
+            6 => CipherSuite::BipCmac128,
+            7 => CipherSuite::GroupNotAllowed,
+            8 => CipherSuite::Gcmp128,
+            9 => CipherSuite::Gcmp256,
+            10 => CipherSuite::Ccmp256,
+            11 => CipherSuite::BipGmac128,
+            12 => CipherSuite::BipGmac256,
+            13 => CipherSuite::BipCmac256,
+            _ => CipherSuite::Other(selector),
+        }
+    }
+
+    /// suite selector with `oui`, as in `Wiphy::cipher_suites`
+    pub fn selector(self, oui: [u8; 3]) -> u32 {
+        let kind = match self {
+            CipherSuite::UseGroup => 0,
+            CipherSuite::Wep40 => 1,
+            CipherSuite::Tkip => 2,
+            CipherSuite::Ccmp128 => 4,
+            CipherSuite::Wep104 => 5,
+            CipherSuite::BipCmac128 => 6,
+            CipherSuite::GroupNotAllowed => 7,
+            CipherSuite::Gcmp128 => 8,
+            CipherSuite::Gcmp256 => 9,
+            CipherSuite::Ccmp256 => 10,
+            CipherSuite::BipGmac128 => 11,
+            CipherSuite::BipGmac256 => 12,
+            CipherSuite::BipCmac256 => 13,
+            CipherSuite::Other(selector) => return selector,
+        };
+        oui_value(oui) << 8 | kind
+    }
+
+    /// length of the key in bytes, none for suites without keys
+    pub fn key_len(self) -> Option<usize> {
+        match self {
+            CipherSuite::Wep40 => Some(5),
+            CipherSuite::Wep104 => Some(13),
+            CipherSuite::Ccmp128
+            | CipherSuite::BipCmac128
+            | CipherSuite::Gcmp128
+            | CipherSuite::BipGmac128 => Some(16),
+            CipherSuite::Tkip
+            | CipherSuite::Gcmp256
+            | CipherSuite::Ccmp256
+            | CipherSuite::BipGmac256
+            | CipherSuite::BipCmac256 => Some(32),
+            _ => None,
+        }
+    }
+}
+
+/// AKM suite types of the element's OUI, IEEE Std 802.11-2020 Table 9-151
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AkmSuite {
+    Ieee8021x,
+    Psk,
+    FtIeee8021x,
+    FtPsk,
+    Ieee8021xSha256,
+    PskSha256,
+    Tdls,
+    Sae,
+    FtSae,
+    ApPeerKey,
+    SuiteB,
+    SuiteB192,
+    FtIeee8021xSha384,
+    FilsSha256,
+    FilsSha384,
+    FtFilsSha256,
+    FtFilsSha384,
+    Owe,
+    FtPskSha384,
+    PskSha384,
+    /// suite selector, OUI and type
+    Other(u32),
+}
+
+impl AkmSuite {
+    fn from_selector(selector: u32, oui: [u8; 3]) -> AkmSuite {
+        if selector >> 8 != oui_value(oui) {
+            return AkmSuite::Other(selector);
+        }
+        match selector & 0xff {
+            1 => AkmSuite::Ieee8021x,
+            2 => AkmSuite::Psk,
+            3 => AkmSuite::FtIeee8021x,
+            4 => AkmSuite::FtPsk,
+            5 => AkmSuite::Ieee8021xSha256,
+            6 => AkmSuite::PskSha256,
+            7 => AkmSuite::Tdls,
+            8 => AkmSuite::Sae,
+            9 => AkmSuite::FtSae,
+            10 => AkmSuite::ApPeerKey,
+            11 => AkmSuite::SuiteB,
+            12 => AkmSuite::SuiteB192,
+            13 => AkmSuite::FtIeee8021xSha384,
+            14 => AkmSuite::FilsSha256,
+            15 => AkmSuite::FilsSha384,
+            16 => AkmSuite::FtFilsSha256,
+            17 => AkmSuite::FtFilsSha384,
+            18 => AkmSuite::Owe,
+            19 => AkmSuite::FtPskSha384,
+            20 => AkmSuite::PskSha384,
+            _ => AkmSuite::Other(selector),
+        }
+    }
+
+    /// suite selector with `oui`
+    pub fn selector(self, oui: [u8; 3]) -> u32 {
+        let kind = match self {
+            AkmSuite::Ieee8021x => 1,
+            AkmSuite::Psk => 2,
+            AkmSuite::FtIeee8021x => 3,
+            AkmSuite::FtPsk => 4,
+            AkmSuite::Ieee8021xSha256 => 5,
+            AkmSuite::PskSha256 => 6,
+            AkmSuite::Tdls => 7,
+            AkmSuite::Sae => 8,
+            AkmSuite::FtSae => 9,
+            AkmSuite::ApPeerKey => 10,
+            AkmSuite::SuiteB => 11,
+            AkmSuite::SuiteB192 => 12,
+            AkmSuite::FtIeee8021xSha384 => 13,
+            AkmSuite::FilsSha256 => 14,
+            AkmSuite::FilsSha384 => 15,
+            AkmSuite::FtFilsSha256 => 16,
+            AkmSuite::FtFilsSha384 => 17,
+            AkmSuite::Owe => 18,
+            AkmSuite::FtPskSha384 => 19,
+            AkmSuite::PskSha384 => 20,
+            AkmSuite::Other(selector) => return selector,
+        };
+        oui_value(oui) << 8 | kind
+    }
+}
+
+fn oui_value(oui: [u8; 3]) -> u32 {
+    u32::from_be_bytes([0, oui[0], oui[1], oui[2]])
+}
+
+/// Cursor over the fields of an element
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn suite(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Parse a sequence of elements, like `Bss::ies`
+pub fn parse(ies: &[u8]) -> Result<Vec<Element>, String> {
+    let mut elements = Vec::new();
+    parse_into(ies, &mut elements)?;
+    Ok(elements)
+}
+
+/// Parse the elements before a truncated one, as in frames some APs and
+/// drivers cut short
+pub fn parse_lenient(ies: &[u8]) -> Vec<Element> {
+    let mut elements = Vec::new();
+    let _ = parse_into(ies, &mut elements);
+    elements
+}
+
+fn parse_into(ies: &[u8], elements: &mut Vec<Element>) -> Result<(), String> {
+    let mut offset = 0;
+    while offset < ies.len() {
+        if offset + 2 > ies.len() {
+            return Err(format!("element header at {} is truncated", offset));
+        }
+        let id = ies[offset];
+        let len = ies[offset + 1] as usize;
+        let data = match ies.get((offset + 2)..(offset + 2 + len)) {
+            Some(data) => data,
+            None => return Err(format!("element {} at {} is truncated", id, offset)),
+        };
+        elements.push(Element::parse(id, data));
+        offset += 2 + len;
+    }
+    Ok(())
+}
+
+/// Serialize elements, the inverse of `parse`
+pub fn write(elements: &[Element]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for element in elements.iter() {
+        element.write(&mut buf);
+    }
+    buf
+}
+
+/// whether bit `bit` of the Extended Capabilities is set
+pub fn has_ext_capability(capabilities: &[u8], bit: usize) -> bool {
+    match capabilities.get(bit / 8) {
+        Some(byte) => byte & (1 << (bit % 8)) != 0,
+        None => false,
+    }
+}
+
+impl Element {
+    /// decode the body of element `id`, keeping malformed ones as `Unknown`
+    pub fn parse(id: u8, data: &[u8]) -> Element {
+        match Element::decode(id, data) {
+            Some(element) => element,
+            None => Element::Unknown {
+                id,
+                data: data.to_vec(),
+            },
+        }
+    }
+
+    fn decode(id: u8, data: &[u8]) -> Option<Element> {
+        let mut reader = Reader(data);
+        let element = match id {
+            EID_SSID => Element::Ssid(data.to_vec()),
+            EID_SUPP_RATES => Element::SupportedRates(data.to_vec()),
+            EID_EXT_SUPP_RATES => Element::ExtendedSupportedRates(data.to_vec()),
+            EID_DS_PARAMS if data.len() == 1 => Element::DsParameter(data[0]),
+            EID_COUNTRY if data.len() >= 3 && data.len() % 3 <= 1 => {
+                let mut triplets = Vec::new();
+                for triplet in data[3..].chunks_exact(3) {
+                    triplets.push(CountryTriplet {
+                        first_channel: triplet[0],
+                        num_channels: triplet[1],
+                        max_tx_power_dbm: triplet[2],
+                    });
+                }
+                Element::Country(Country {
+                    code: [data[0], data[1]],
+                    environment: data[2],
+                    triplets,
+                    padded: data.len() % 3 == 1,
+                })
+            }
+            EID_HT_CAP if data.len() == 26 => Element::HtCapabilities(HtCapabilities {
+                info: reader.u16()?,
+                ampdu_params: reader.take(1)?[0],
+                mcs_set: reader.take(16)?.try_into().ok()?,
+                extended: reader.u16()?,
+                txbf: u32::from_le_bytes(reader.take(4)?.try_into().ok()?),
+                asel: reader.take(1)?[0],
+            }),
+            EID_HT_OPERATION if data.len() == 22 => Element::HtOperation(HtOperation {
+                primary_channel: data[0],
+                info: data[1..6].try_into().ok()?,
+                basic_mcs_set: data[6..22].try_into().ok()?,
+            }),
+            EID_RSN => Element::Rsn(Rsn::parse(data, OUI_IEEE80211)?),
+            EID_MOBILITY_DOMAIN if data.len() == 3 => Element::MobilityDomain(MobilityDomain {
+                mdid: reader.u16()?,
+                ft_capability: data[2],
+            }),
+            EID_RM_ENABLED_CAP if data.len() == 5 => {
+                Element::RmEnabledCapabilities(data.try_into().ok()?)
+            }
+            EID_EXT_CAP => Element::ExtendedCapabilities(data.to_vec()),
+            EID_VHT_CAP if data.len() == 12 => Element::VhtCapabilities(VhtCapabilities {
+                info: u32::from_le_bytes(data[0..4].try_into().ok()?),
+                mcs_set: data[4..12].try_into().ok()?,
+            }),
+            EID_VHT_OPERATION if data.len() == 5 => Element::VhtOperation(VhtOperation {
+                channel_width: data[0],
+                center_seg0: data[1],
+                center_seg1: data[2],
+                basic_mcs_set: u16::from_le_bytes([data[3], data[4]]),
+            }),
+            EID_VENDOR_SPECIFIC if data.len() >= 3 => {
+                let oui = [data[0], data[1], data[2]];
+                if oui == OUI_MICROSOFT && data.get(3) == Some(&WPA_OUI_TYPE) {
+                    Element::Wpa(Rsn::parse(&data[4..], OUI_MICROSOFT)?)
+                } else {
+                    Element::Vendor {
+                        oui,
+                        data: data[3..].to_vec(),
+                    }
+                }
+            }
+            EID_EXTENSION if !data.is_empty() => match data[0] {
+                EID_EXT_HE_CAP => Element::HeCapabilities(decode_he_capabilities(&data[1..])?),
+                EID_EXT_HE_OPERATION if data.len() >= 7 => Element::HeOperation(HeOperation {
+                    params: data[1..4].try_into().ok()?,
+                    bss_color: data[4],
+                    basic_mcs_nss: u16::from_le_bytes([data[5], data[6]]),
+                    optional: data[7..].to_vec(),
+                }),
+                id => Element::Extension {
+                    id,
+                    data: data[1..].to_vec(),
+                },
+            },
+            _ => return None,
+        };
+        Some(element)
+    }
+
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        buf.extend_from_slice(&[self.id(), 0]);
+        match self {
+            Element::Ssid(data)
+            | Element::SupportedRates(data)
+            | Element::ExtendedSupportedRates(data)
+            | Element::ExtendedCapabilities(data) => buf.extend_from_slice(data),
+            Element::DsParameter(channel) => buf.push(*channel),
+            Element::Country(country) => {
+                buf.extend_from_slice(&country.code);
+                buf.push(country.environment);
+                for triplet in country.triplets.iter() {
+                    buf.push(triplet.first_channel);
+                    buf.push(triplet.num_channels);
+                    buf.push(triplet.max_tx_power_dbm);
+                }
+                if country.padded {
+                    buf.push(0);
+                }
+            }
+            Element::HtCapabilities(ht) => {
+                buf.extend_from_slice(&ht.info.to_le_bytes());
+                buf.push(ht.ampdu_params);
+                buf.extend_from_slice(&ht.mcs_set);
+                buf.extend_from_slice(&ht.extended.to_le_bytes());
+                buf.extend_from_slice(&ht.txbf.to_le_bytes());
+                buf.push(ht.asel);
+            }
+            Element::HtOperation(ht) => {
+                buf.push(ht.primary_channel);
+                buf.extend_from_slice(&ht.info);
+                buf.extend_from_slice(&ht.basic_mcs_set);
+            }
+            Element::Rsn(rsn) => rsn.write(buf, OUI_IEEE80211),
+            Element::MobilityDomain(md) => {
+                buf.extend_from_slice(&md.mdid.to_le_bytes());
+                buf.push(md.ft_capability);
+            }
+            Element::RmEnabledCapabilities(capabilities) => buf.extend_from_slice(capabilities),
+            Element::VhtCapabilities(vht) => {
+                buf.extend_from_slice(&vht.info.to_le_bytes());
+                buf.extend_from_slice(&vht.mcs_set);
+            }
+            Element::VhtOperation(vht) => {
+                buf.push(vht.channel_width);
+                buf.push(vht.center_seg0);
+                buf.push(vht.center_seg1);
+                buf.extend_from_slice(&vht.basic_mcs_set.to_le_bytes());
+            }
+            Element::Wpa(wpa) => {
+                buf.extend_from_slice(&OUI_MICROSOFT);
+                buf.push(WPA_OUI_TYPE);
+                wpa.write(buf, OUI_MICROSOFT);
+            }
+            Element::HeCapabilities(he) => {
+                buf.push(EID_EXT_HE_CAP);
+                buf.extend_from_slice(&he.mac);
+                buf.extend_from_slice(&he.phy);
+                buf.extend_from_slice(&he.mcs_nss);
+                buf.extend_from_slice(&he.ppe);
+            }
+            Element::HeOperation(he) => {
+                buf.push(EID_EXT_HE_OPERATION);
+                buf.extend_from_slice(&he.params);
+                buf.push(he.bss_color);
+                buf.extend_from_slice(&he.basic_mcs_nss.to_le_bytes());
+                buf.extend_from_slice(&he.optional);
+            }
+            Element::Vendor { oui, data } => {
+                buf.extend_from_slice(oui);
+                buf.extend_from_slice(data);
+            }
+            Element::Extension { id, data } => {
+                buf.push(*id);
+                buf.extend_from_slice(data);
+            }
+            Element::Unknown { data, .. } => buf.extend_from_slice(data),
+        }
+        buf[start + 1] = (buf.len() - start - 2) as u8;
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Element::Ssid(_) => EID_SSID,
+            Element::SupportedRates(_) => EID_SUPP_RATES,
+            Element::DsParameter(_) => EID_DS_PARAMS,
+            Element::Country(_) => EID_COUNTRY,
+            Element::HtCapabilities(_) => EID_HT_CAP,
+            Element::Rsn(_) => EID_RSN,
+            Element::ExtendedSupportedRates(_) => EID_EXT_SUPP_RATES,
+            Element::MobilityDomain(_) => EID_MOBILITY_DOMAIN,
+            Element::HtOperation(_) => EID_HT_OPERATION,
+            Element::RmEnabledCapabilities(_) => EID_RM_ENABLED_CAP,
+            Element::ExtendedCapabilities(_) => EID_EXT_CAP,
+            Element::VhtCapabilities(_) => EID_VHT_CAP,
+            Element::VhtOperation(_) => EID_VHT_OPERATION,
+            Element::Wpa(_) | Element::Vendor { .. } => EID_VENDOR_SPECIFIC,
+            Element::HeCapabilities(_) | Element::HeOperation(_) | Element::Extension { .. } => {
+                EID_EXTENSION
+            }
+            Element::Unknown { id, .. } => *id,
+        }
+    }
+}
+
+fn decode_he_capabilities(data: &[u8]) -> Option<HeCapabilities> {
+    let mut reader = Reader(data);
+    let mac: [u8; 6] = reader.take(6)?.try_into().ok()?;
+    let phy: [u8; 11] = reader.take(11)?.try_into().ok()?;
+    // the 80 MHz maps are always there, the 160 and 80+80 MHz ones
+    // follow the channel width set in the PHY capabilities
+    let mut mcs_len = 4;
+    if phy[0] & 0x08 != 0 {
+        mcs_len += 4;
+    }
+    if phy[0] & 0x10 != 0 {
+        mcs_len += 4;
+    }
+    let mcs_nss = reader.take(mcs_len)?.to_vec();
+    Some(HeCapabilities {
+        mac,
+        phy,
+        mcs_nss,
+        ppe: reader.0.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// beacon of a WPA2/WPA3 transition AP on channel 36 at 80 MHz, with
+    /// a mixed WPA element and some elements not decoded
+    const BEACON: &[u8] = &[
+        0x00, 0x04, b'g', b'l', b'o', b'w', // SSID
+        0x01, 0x08, 0x8c, 0x12, 0x98, 0x24, 0xb0, 0x48, 0x60, 0x6c, // rates
+        0x03, 0x01, 0x24, // DS parameter
+        0x05, 0x04, 0x00, 0x01, 0x00, 0x00, // TIM
+        0x07, 0x0a, b'D', b'E', b' ', 0x24, 0x04, 0x17, 0x34, 0x04, 0x17, 0x00, // country
+        0x2d, 0x1a, 0xef, 0x09, 0x1b, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // HT capabilities
+        0x30, 0x18, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x02,
+        0x00, 0x00, 0x0f, 0xac, 0x02, 0x00, 0x0f, 0xac, 0x08, 0x8c, 0x00, // RSN
+        0x36, 0x03, 0xab, 0xcd, 0x01, // mobility domain
+        0x3d, 0x16, 0x24, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // HT operation
+        0x46, 0x05, 0x72, 0x08, 0x01, 0x00, 0x00, // RM enabled capabilities
+        0x7f, 0x08, 0x04, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x40, // extended capabilities
+        0xbf, 0x0c, 0xb2, 0x71, 0x90, 0x33, 0xfa, 0xff, 0x00, 0x00, 0xfa, 0xff, 0x00,
+        0x00, // VHT capabilities
+        0xc0, 0x05, 0x01, 0x2a, 0x00, 0xfc, 0xff, // VHT operation
+        0xff, 0x21, 0x23, 0x01, 0x08, 0x00, 0x12, 0x00, 0x10, 0x0c, 0x20, 0x02, 0xc0, 0x0f, 0x03,
+        0x95, 0x18, 0x00, 0xcc, 0x00, 0xfa, 0xff, 0xfa, 0xff, 0xfa, 0xff, 0xfa, 0xff, 0x79, 0x1c,
+        0xc7, 0x71, 0x1c, 0xc7, 0x71, // HE capabilities
+        0xff, 0x07, 0x24, 0xf4, 0x3f, 0x00, 0x19, 0xfc, 0xff, // HE operation
+        0xdd, 0x16, 0x00, 0x50, 0xf2, 0x01, 0x01, 0x00, 0x00, 0x50, 0xf2, 0x02, 0x01, 0x00, 0x00,
+        0x50, 0xf2, 0x02, 0x01, 0x00, 0x00, 0x50, 0xf2, 0x02, // WPA
+        0xdd, 0x18, 0x00, 0x50, 0xf2, 0x02, 0x01, 0x01, 0x80, 0x00, 0x03, 0xa4, 0x00, 0x00, 0x27,
+        0xa4, 0x00, 0x00, 0x42, 0x43, 0x5e, 0x00, 0x62, 0x32, 0x2f, 0x00, // WMM
+    ];
+
+    /// beacon of an 802.11ax AP on channel 36 at 80 MHz with FT and the
+    /// WPA2/WPA3 transition mode, its elements in the order hostapd 2.10
+    /// builds them (`ieee802_11_build_ap_params`) and the TIM mac80211 adds
+    /// after the head; not captured over the air, with the elements the
+    /// other beacon lacks: transmit power envelope, spatial reuse, MU EDCA,
+    /// RSNX and MBO
+    const HOSTAPD_BEACON: &[u8] = &[
+        0x00, 0x04, b'g', b'l', b'o', b'w', // SSID
+        0x01, 0x08, 0x8c, 0x12, 0x98, 0x24, 0xb0, 0x48, 0x60, 0x6c, // rates
+        0x03, 0x01, 0x24, // DS parameter
+        0x05, 0x04, 0x00, 0x01, 0x00, 0x00, // TIM, added by mac80211
+        0x07, 0x0c, b'D', b'E', b' ', 0x24, 0x04, 0x17, 0x34, 0x04, 0x17, 0x64, 0x0b,
+        0x1e, // country
+        0x30, 0x20, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x04,
+        0x00, 0x00, 0x0f, 0xac, 0x02, 0x00, 0x0f, 0xac, 0x04, 0x00, 0x0f, 0xac, 0x08, 0x00, 0x0f,
+        0xac, 0x09, 0x8c, 0x00, // RSN
+        0x46, 0x05, 0x72, 0x00, 0x00, 0x00, 0x00, // RM enabled capabilities
+        0x36, 0x03, 0xa1, 0xb2, 0x01, // mobility domain
+        0x2d, 0x1a, 0x6f, 0x00, 0x17, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // HT capabilities
+        0x3d, 0x16, 0x24, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // HT operation
+        0x7f, 0x0a, 0x04, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00,
+        0x01, // extended capabilities
+        0xbf, 0x0c, 0xb2, 0x79, 0x80, 0x00, 0xfa, 0xff, 0x00, 0x00, 0xfa, 0xff, 0x00,
+        0x00, // VHT capabilities
+        0xc0, 0x05, 0x01, 0x2a, 0x00, 0xfc, 0xff, // VHT operation
+        0xc3, 0x04, 0x02, 0x2e, 0x2e, 0x2e, // transmit power envelope
+        0xff, 0x16, 0x23, 0x09, 0x00, 0x08, 0x12, 0x00, 0x10, 0x04, 0x20, 0x0e, 0x80, 0x0d, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xfa, 0xff, 0xfa, 0xff, // HE capabilities
+        0xff, 0x07, 0x24, 0xf4, 0x3f, 0x00, 0x2a, 0xfc, 0xff, // HE operation
+        0xff, 0x02, 0x27, 0x00, // spatial reuse parameter set
+        0xff, 0x0e, 0x26, 0x09, 0x08, 0xa4, 0x08, 0x28, 0xa4, 0x08, 0x44, 0x43, 0x08, 0x62, 0x32,
+        0x08, // MU EDCA parameter set
+        0xf4, 0x01, 0x20, // RSNX
+        0xdd, 0x18, 0x00, 0x50, 0xf2, 0x02, 0x01, 0x01, 0x80, 0x00, 0x03, 0xa4, 0x00, 0x00, 0x27,
+        0xa4, 0x00, 0x00, 0x42, 0x43, 0x5e, 0x00, 0x62, 0x32, 0x2f, 0x00, // WMM
+        0xdd, 0x07, 0x50, 0x6f, 0x9a, 0x16, 0x01, 0x01, 0x00, // MBO
+    ];
+
+    #[test]
+    fn test_parse_beacon() {
+        let elements = parse(BEACON).unwrap();
+        assert_eq!(elements.len(), 17);
+        assert_eq!(elements[0], Element::Ssid(b"glow".to_vec()));
+        assert_eq!(elements[2], Element::DsParameter(36));
+        assert_eq!(
+            elements[3],
+            Element::Unknown {
+                id: 5,
+                data: vec![0x00, 0x01, 0x00, 0x00]
+            }
+        );
+
+        match &elements[4] {
+            Element::Country(country) => {
+                assert_eq!(&country.code, b"DE");
+                assert_eq!(country.triplets.len(), 2);
+                assert_eq!(country.triplets[1].first_channel, 52);
+                assert_eq!(country.triplets[1].max_tx_power_dbm, 23);
+                assert!(country.padded);
+            }
+            element => panic!("not a country element: {:?}", element),
+        }
+
+        match &elements[6] {
+            Element::Rsn(rsn) => {
+                assert_eq!(rsn.group_cipher, Some(CipherSuite::Ccmp128));
+                assert_eq!(rsn.pairwise(), vec![CipherSuite::Ccmp128]);
+                assert_eq!(rsn.akms(), vec![AkmSuite::Psk, AkmSuite::Sae]);
+                assert!(rsn.mfp_capable());
+                assert!(!rsn.mfp_required());
+                assert_eq!(rsn.pmkids, None);
+            }
+            element => panic!("not an RSN element: {:?}", element),
+        }
+
+        assert_eq!(
+            elements[7],
+            Element::MobilityDomain(MobilityDomain {
+                mdid: 0xcdab,
+                ft_capability: 1
+            })
+        );
+        match &elements[8] {
+            Element::HtOperation(ht) => {
+                assert_eq!(ht.primary_channel, 36);
+                assert_eq!(ht.secondary_channel_offset(), 1);
+                assert!(ht.any_channel_width());
+            }
+            element => panic!("not an HT operation element: {:?}", element),
+        }
+        match &elements[10] {
+            Element::ExtendedCapabilities(capabilities) => {
+                // BSS transition
+                assert!(has_ext_capability(capabilities, 19));
+                assert!(!has_ext_capability(capabilities, 20));
+                assert!(!has_ext_capability(capabilities, 100));
+            }
+            element => panic!("not an extended capabilities element: {:?}", element),
+        }
+        assert_eq!(
+            elements[12],
+            Element::VhtOperation(VhtOperation {
+                channel_width: 1,
+                center_seg0: 42,
+                center_seg1: 0,
+                basic_mcs_set: 0xfffc,
+            })
+        );
+        match &elements[13] {
+            Element::HeCapabilities(he) => {
+                assert_eq!(he.mcs_nss.len(), 8);
+                assert_eq!(he.ppe.len(), 7);
+            }
+            element => panic!("not an HE capabilities element: {:?}", element),
+        }
+        match &elements[14] {
+            Element::HeOperation(he) => {
+                assert_eq!(he.bss_color, 0x19);
+                assert!(he.optional.is_empty());
+            }
+            element => panic!("not an HE operation element: {:?}", element),
+        }
+        match &elements[15] {
+            Element::Wpa(wpa) => {
+                assert_eq!(wpa.group_cipher, Some(CipherSuite::Tkip));
+                assert_eq!(wpa.akms(), vec![AkmSuite::Psk]);
+                assert_eq!(wpa.capabilities, None);
+            }
+            element => panic!("not a WPA element: {:?}", element),
+        }
+        match &elements[16] {
+            Element::Vendor { oui, data } => {
+                assert_eq!(*oui, OUI_MICROSOFT);
+                assert_eq!(data[0], 2);
+            }
+            element => panic!("not a vendor element: {:?}", element),
+        }
+    }
+
+    #[test]
+    fn test_parse_hostapd_beacon() {
+        let elements = parse(HOSTAPD_BEACON).unwrap();
+        assert_eq!(elements.len(), 21);
+        let ids: Vec<_> = elements.iter().map(Element::id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                0, 1, 3, 5, 7, 48, 70, 54, 45, 61, 127, 191, 192, 195, 255, 255, 255, 255, 244,
+                221, 221
+            ]
+        );
+
+        match &elements[5] {
+            Element::Rsn(rsn) => {
+                assert_eq!(
+                    rsn.akms(),
+                    vec![
+                        AkmSuite::Psk,
+                        AkmSuite::FtPsk,
+                        AkmSuite::Sae,
+                        AkmSuite::FtSae
+                    ]
+                );
+                assert!(rsn.mfp_capable());
+                assert!(!rsn.mfp_required());
+            }
+            element => panic!("not an RSN element: {:?}", element),
+        }
+        assert_eq!(
+            elements[7],
+            Element::MobilityDomain(MobilityDomain {
+                mdid: 0xb2a1,
+                ft_capability: 1
+            })
+        );
+        match &elements[10] {
+            Element::ExtendedCapabilities(capabilities) => {
+                assert!(has_ext_capability(capabilities, 19));
+                assert!(has_ext_capability(capabilities, 62));
+                assert!(has_ext_capability(capabilities, 72));
+            }
+            element => panic!("not an extended capabilities element: {:?}", element),
+        }
+        assert!(matches!(elements[11], Element::VhtCapabilities(_)));
+        // neither the transmit power envelope nor RSNX are decoded
+        assert_eq!(
+            elements[13],
+            Element::Unknown {
+                id: 195,
+                data: vec![0x02, 0x2e, 0x2e, 0x2e]
+            }
+        );
+        match &elements[14] {
+            Element::HeCapabilities(he) => {
+                // 80 MHz only, without PPE thresholds
+                assert_eq!(he.mcs_nss, vec![0xfa, 0xff, 0xfa, 0xff]);
+                assert!(he.ppe.is_empty());
+            }
+            element => panic!("not an HE capabilities element: {:?}", element),
+        }
+        match &elements[15] {
+            Element::HeOperation(he) => assert_eq!(he.bss_color, 0x2a),
+            element => panic!("not an HE operation element: {:?}", element),
+        }
+        assert_eq!(
+            elements[16],
+            Element::Extension {
+                id: 39,
+                data: vec![0x00]
+            }
+        );
+        assert!(matches!(elements[17], Element::Extension { id: 38, .. }));
+        assert_eq!(
+            elements[18],
+            Element::Unknown {
+                id: 244,
+                data: vec![0x20]
+            }
+        );
+        match &elements[20] {
+            Element::Vendor { oui, data } => {
+                assert_eq!(*oui, [0x50, 0x6f, 0x9a]);
+                assert_eq!(data[0], 0x16);
+            }
+            element => panic!("not a vendor element: {:?}", element),
+        }
+
+        assert_eq!(write(&elements), HOSTAPD_BEACON);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(write(&parse(BEACON).unwrap()), BEACON);
+
+        // WPA3 only with a PMKID and BIP-CMAC-128
+        let mut sae = vec![0x30, 0x2a, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00];
+        sae.extend_from_slice(&[0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x08]);
+        sae.extend_from_slice(&[0xc0, 0x00, 0x01, 0x00]);
+        sae.extend_from_slice(&[0x5a; 16]);
+        sae.extend_from_slice(&[0x00, 0x0f, 0xac, 0x06]);
+        let elements = parse(&sae).unwrap();
+        match &elements[0] {
+            Element::Rsn(rsn) => {
+                assert!(rsn.mfp_required());
+                assert_eq!(rsn.pmkids, Some(vec![[0x5a; 16]]));
+                assert_eq!(rsn.group_mgmt_cipher, Some(CipherSuite::BipCmac128));
+            }
+            element => panic!("not an RSN element: {:?}", element),
+        }
+        assert_eq!(write(&elements), sae);
+
+        // OWE with a vendor suite and the minimal RSN element
+        let owe = [
+            0x30, 0x16, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04,
+            0x02, 0x00, 0x00, 0x0f, 0xac, 0x12, 0x00, 0x10, 0x18, 0x01, //
+            0x30, 0x02, 0x01, 0x00,
+        ];
+        let elements = parse(&owe).unwrap();
+        match &elements[0] {
+            Element::Rsn(rsn) => {
+                assert_eq!(rsn.akms(), vec![AkmSuite::Owe, AkmSuite::Other(0x00101801)])
+            }
+            element => panic!("not an RSN element: {:?}", element),
+        }
+        match &elements[1] {
+            Element::Rsn(rsn) => {
+                assert_eq!(rsn.group_cipher, None);
+                assert_eq!(rsn.pairwise(), vec![CipherSuite::Ccmp128]);
+            }
+            element => panic!("not an RSN element: {:?}", element),
+        }
+        assert_eq!(write(&elements), owe);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(parse(&[0x00]).is_err());
+        assert!(parse(&[0x00, 0x04, b'g']).is_err());
+        assert_eq!(parse(&[]).unwrap(), vec![]);
+        // the elements before a truncated one are kept
+        assert_eq!(
+            parse_lenient(&[0x00, 0x04, b'g', b'l', b'o', b'w', 0x30, 0x14, 0x01]),
+            vec![Element::Ssid(b"glow".to_vec())]
+        );
+        assert_eq!(parse_lenient(&[0x00]), vec![]);
+
+        // malformed bodies are kept as they are
+        let malformed = [
+            0x03, 0x02, 0x01, 0x02, // DS parameter too long
+            0x30, 0x05, 0x01, 0x00, 0x00, 0x0f, 0xac, // truncated suite
+            0x2d, 0x01, 0x00, // HT capabilities too short
+            0xff, 0x02, 0x23, 0x00, // HE capabilities too short
+        ];
+        let elements = parse(&malformed).unwrap();
+        for element in elements.iter() {
+            match element {
+                Element::Unknown { .. } => (),
+                element => panic!("malformed element decoded: {:?}", element),
+            }
+        }
+        assert_eq!(write(&elements), malformed);
+    }
+}
@@ -0,0 +1,394 @@
+use super::{
+    attr::*,
+    client::NL80211Client,
+    cmd::Nl80211Cmd,
+    transport::Transport,
+    utils::{attr_string, mac_addr},
+};
+use neli::attr::Attribute;
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::err::NlError;
+use neli::genl::{Genlmsghdr, Nlattr};
+use neli::types::{Buffer, GenlBuffer};
+
+/// A wireless interface, a netdev or a wdev without one like P2P-device
+#[derive(Debug)]
+pub struct Interface {
+    /// 0 without a netdev
+    pub ifindex: u32,
+    pub name: String,
+    pub wdev: u64,
+    pub wiphy: u32,
+    pub iftype: Nl80211Iftype,
+    pub mac: [u8; 6],
+    /// SSID of the BSS joined or the one operated
+    pub ssid: Option<Vec<u8>>,
+    pub channel: Option<Channel>,
+    pub tx_power_mbm: Option<u32>,
+    pub four_addr: bool,
+}
+
+#[derive(Debug)]
+pub struct Channel {
+    pub freq: u32,
+    pub width: Nl80211ChanWidth,
+    pub center_freq1: u32,
+    /// only used with 80+80 MHz
+    pub center_freq2: u32,
+}
+
+/// Optional settings of a new virtual interface
+#[derive(Debug, Default)]
+pub struct InterfaceOptions {
+    /// MAC address instead of the one of the wiphy
+    pub mac: Option<[u8; 6]>,
+    /// 4-address frames for WDS-like bridging
+    pub four_addr: Option<bool>,
+    /// flags of a monitor interface
+    pub monitor_flags: Vec<Nl80211MntrFlags>,
+    /// mesh ID to join of a mesh point
+    pub mesh_id: Option<Vec<u8>>,
+}
+
+impl<T: Transport> NL80211Client<T> {
+    // COMMAND(dev, NULL, NULL, NL80211_CMD_GET_INTERFACE, NLM_F_DUMP, CIB_NONE, handle_dev_dump,
+    //   "List all network interfaces for wireless hardware.");
+    pub fn interfaces(&mut self) -> Result<Vec<Interface>, NlError> {
+        let responses = self.request(
+            Nl80211Cmd::CmdGetInterface,
+            Some(NlmFFlags::new(&[NlmF::Request, NlmF::Dump])),
+            None,
+        )?;
+
+        let mut interfaces = Vec::new();
+        for msg in responses.iter() {
+            interfaces.push(parse_interface(msg)?);
+        }
+        Ok(interfaces)
+    }
+
+    // COMMAND(interface, add, "<name> type <type> [mesh_id <meshid>] [4addr on|off] [flags <flag>*] [addr <mac-addr>]",
+    //   NL80211_CMD_NEW_INTERFACE, 0, CIB_PHY, handle_interface_add,
+    //   "Add a new virtual interface with the given configuration.\n"
+    //   IFACE_TYPES "\n\n"
+    //   "The flags are only used for monitor interfaces, valid flags are:\n"
+    //   VALID_FLAGS "\n\n"
+    //   "The mesh_id is used only for mesh mode.");
+    /// Create a virtual interface `name` on the wiphy of the selected device.
+    /// P2P-device interfaces have no netdev, `name` only names their wdev.
+    pub fn create_interface(
+        &mut self,
+        name: &str,
+        iftype: Nl80211Iftype,
+        options: &InterfaceOptions,
+    ) -> Result<Interface, NlError> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrIfname,
+            name,
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrIftype,
+            u16::from(iftype) as u32,
+        )?);
+        if let Some(mac) = options.mac {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrMac,
+                mac.to_vec(),
+            )?);
+        }
+        if let Some(four_addr) = options.four_addr {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::Attr4addr,
+                four_addr as u8,
+            )?);
+        }
+        if !options.monitor_flags.is_empty() {
+            attrs.push(monitor_flags_attr(&options.monitor_flags)?);
+        }
+        if let Some(mesh_id) = &options.mesh_id {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrMeshId,
+                mesh_id.clone(),
+            )?);
+        }
+
+        let responses = self.request(Nl80211Cmd::CmdNewInterface, None, Some(attrs))?;
+        match responses.first() {
+            Some(msg) => parse_interface(msg),
+            None => Err(NlError::new("no interface created")),
+        }
+    }
+
+    // COMMAND(interface, del, NULL, NL80211_CMD_DEL_INTERFACE, 0, CIB_NETDEV, handle_interface_del,
+    //   "Remove this virtual interface");
+    /// Remove the selected netdev or wdev
+    pub fn delete_interface(&mut self) -> Result<(), NlError> {
+        self.request(Nl80211Cmd::CmdDelInterface, None, None)?;
+        Ok(())
+    }
+
+    // COMMAND(set, type, "<type>",
+    //   NL80211_CMD_SET_INTERFACE, 0, CIB_NETDEV, handle_interface_type,
+    //   "Set interface type/mode.\n"
+    //   IFACE_TYPES);
+    /// Change the type of the selected netdev, which has to be down.
+    /// `monitor_flags` are only used when changing to a monitor.
+    pub fn set_interface_type(
+        &mut self,
+        iftype: Nl80211Iftype,
+        monitor_flags: &[Nl80211MntrFlags],
+    ) -> Result<(), NlError> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrIftype,
+            u16::from(iftype) as u32,
+        )?);
+        if !monitor_flags.is_empty() {
+            attrs.push(monitor_flags_attr(monitor_flags)?);
+        }
+        self.request(Nl80211Cmd::CmdSetInterface, None, Some(attrs))?;
+        Ok(())
+    }
+
+    // COMMAND(set, 4addr, "<on|off>",
+    //   NL80211_CMD_SET_INTERFACE, 0, CIB_NETDEV, handle_interface_4addr,
+    //   "Set interface 4addr (WDS) mode.");
+    pub fn set_four_addr(&mut self, enabled: bool) -> Result<(), NlError> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::Attr4addr,
+            enabled as u8,
+        )?);
+        self.request(Nl80211Cmd::CmdSetInterface, None, Some(attrs))?;
+        Ok(())
+    }
+}
+
+/// NL80211_ATTR_MNTR_FLAGS, a nested flag attribute per flag
+fn monitor_flags_attr(flags: &[Nl80211MntrFlags]) -> Result<Nlattr<Nl80211Attr, Buffer>, NlError> {
+    let mut attr = Nlattr::new(
+        None,
+        true,
+        false,
+        Nl80211Attr::AttrMntrFlags,
+        Vec::<u8>::new(),
+    )?;
+    for flag in flags.iter() {
+        attr.add_nested_attribute(&Nlattr::new(None, false, false, *flag, Vec::<u8>::new())?)?;
+    }
+    Ok(attr)
+}
+
+pub(crate) fn parse_interface(
+    msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>,
+) -> Result<Interface, NlError> {
+    let mut interface = Interface {
+        ifindex: 0,
+        name: String::new(),
+        wdev: 0,
+        wiphy: 0,
+        iftype: Nl80211Iftype::IftypeUnspecified,
+        mac: [0; 6],
+        ssid: None,
+        channel: None,
+        tx_power_mbm: None,
+        four_addr: false,
+    };
+    let mut freq = None;
+    let mut width = Nl80211ChanWidth::ChanWidth20Noht;
+    let mut center_freq1 = 0;
+    let mut center_freq2 = 0;
+
+    for attr in msg.get_attr_handle().iter() {
+        let payload = attr.payload().as_ref();
+        match &attr.nla_type {
+            Nl80211Attr::AttrIfindex => interface.ifindex = attr.get_payload_as::<u32>()?,
+            Nl80211Attr::AttrIfname => interface.name = attr_string(payload),
+            Nl80211Attr::AttrWdev => interface.wdev = attr.get_payload_as::<u64>()?,
+            Nl80211Attr::AttrWiphy => interface.wiphy = attr.get_payload_as::<u32>()?,
+            Nl80211Attr::AttrIftype => {
+                interface.iftype = Nl80211Iftype::from(attr.get_payload_as::<u32>()? as u16)
+            }
+            Nl80211Attr::AttrMac => interface.mac = mac_addr(payload)?,
+            Nl80211Attr::AttrSsid => interface.ssid = Some(payload.to_vec()),
+            Nl80211Attr::AttrWiphyFreq => freq = Some(attr.get_payload_as::<u32>()?),
+            Nl80211Attr::AttrChannelWidth => {
+                width = Nl80211ChanWidth::from(attr.get_payload_as::<u32>()? as u16)
+            }
+            Nl80211Attr::AttrCenterFreq1 => center_freq1 = attr.get_payload_as::<u32>()?,
+            Nl80211Attr::AttrCenterFreq2 => center_freq2 = attr.get_payload_as::<u32>()?,
+            Nl80211Attr::AttrWiphyTxPowerLevel => {
+                interface.tx_power_mbm = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211Attr::Attr4addr => interface.four_addr = attr.get_payload_as::<u8>()? != 0,
+            _ => (),
+        }
+    }
+
+    interface.channel = freq.map(|freq| Channel {
+        freq,
+        width,
+        center_freq1,
+        center_freq2,
+    });
+    Ok(interface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::{client, lo_ifindex, reply};
+    use crate::base::nl80211::utils::format_mac;
+
+    #[test]
+    fn test_interfaces() {
+        let mut client = client(&[include_bytes!("fixtures/interface_dump.bin")]);
+        let interfaces = client.interfaces().unwrap();
+        assert_eq!(interfaces.len(), 2);
+
+        let wlan0 = &interfaces[0];
+        assert_eq!(wlan0.ifindex, 3);
+        assert_eq!(wlan0.name, "wlan0");
+        assert_eq!(wlan0.wdev, 1);
+        assert_eq!(wlan0.iftype, Nl80211Iftype::IftypeStation);
+        assert_eq!(format_mac(&wlan0.mac), "02:00:00:00:01:00");
+        assert_eq!(wlan0.ssid.as_deref(), Some(&b"glow"[..]));
+        assert_eq!(wlan0.tx_power_mbm, Some(2000));
+        let channel = wlan0.channel.as_ref().unwrap();
+        assert_eq!(channel.freq, 5180);
+        assert_eq!(channel.width, Nl80211ChanWidth::ChanWidth80);
+        assert_eq!(channel.center_freq1, 5210);
+
+        // P2P-device has no netdev
+        let p2p = &interfaces[1];
+        assert_eq!(p2p.ifindex, 0);
+        assert_eq!(p2p.wdev, 2);
+        assert_eq!(p2p.iftype, Nl80211Iftype::IftypeP2pDevice);
+        assert!(p2p.channel.is_none());
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdGetInterface));
+        // no wiphy, netdev or wdev to filter by
+        assert_eq!(sent.len(), 20);
+    }
+
+    #[test]
+    fn test_set_netdev() {
+        let mut client = client(&[include_bytes!("fixtures/interface_dump.bin")]);
+        client.set_netdev("lo".to_string()).unwrap();
+        client.interfaces().unwrap();
+        // NL80211_ATTR_IFINDEX of lo
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[20..28], lo_ifindex());
+
+        assert!(client.set_netdev("nonexistent0".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_create_interface() {
+        let mut client = client(&[include_bytes!("fixtures/new_interface.bin")]);
+        client.set_netdev("lo".to_string()).unwrap();
+        let options = InterfaceOptions {
+            mac: Some([2, 0, 0, 0, 1, 2]),
+            four_addr: Some(false),
+            monitor_flags: vec![
+                Nl80211MntrFlags::MntrFlagControl,
+                Nl80211MntrFlags::MntrFlagOtherBss,
+            ],
+            ..Default::default()
+        };
+        let mon0 = client
+            .create_interface("mon0", Nl80211Iftype::IftypeMonitor, &options)
+            .unwrap();
+        assert_eq!(mon0.ifindex, 7);
+        assert_eq!(mon0.name, "mon0");
+        assert_eq!(mon0.iftype, Nl80211Iftype::IftypeMonitor);
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdNewInterface));
+        // NL80211_ATTR_IFNAME, NUL terminated
+        assert_eq!(
+            &sent[20..32],
+            &[9, 0, 4, 0, b'm', b'o', b'n', b'0', 0, 0, 0, 0]
+        );
+        // NL80211_ATTR_IFTYPE
+        assert_eq!(&sent[32..40], &[8, 0, 5, 0, 6, 0, 0, 0]);
+        // NL80211_ATTR_MAC
+        assert_eq!(&sent[40..50], &[10, 0, 6, 0, 2, 0, 0, 0, 1, 2]);
+        // NL80211_ATTR_4ADDR
+        assert_eq!(&sent[52..57], &[5, 0, 83, 0, 0]);
+        // nested NL80211_ATTR_MNTR_FLAGS with a flag each
+        assert_eq!(&sent[60..72], &[12, 0, 23, 0x80, 4, 0, 3, 0, 4, 0, 4, 0]);
+        // NL80211_ATTR_IFINDEX of lo, the wiphy to create it on
+        assert_eq!(sent[72..80], lo_ifindex());
+        assert_eq!(sent.len(), 80);
+    }
+
+    #[test]
+    fn test_create_interface_failed() {
+        let mut client = client(&[include_bytes!("fixtures/err_einval.bin")]);
+        let options = InterfaceOptions::default();
+        assert!(client
+            .create_interface("wlan1", Nl80211Iftype::IftypeStation, &options)
+            .is_err());
+    }
+
+    #[test]
+    fn test_delete_interface() {
+        let mut client = client(&[include_bytes!("fixtures/ack.bin")]);
+        client.set_netdev("lo".to_string()).unwrap();
+        client.delete_interface().unwrap();
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdDelInterface));
+        assert_eq!(sent[20..], lo_ifindex());
+    }
+
+    #[test]
+    fn test_set_interface() {
+        let ack = include_bytes!("fixtures/ack.bin");
+        let mut client = client(&[&reply(ack, 1), &reply(ack, 2), &reply(ack, 3)]);
+        client.set_netdev("lo".to_string()).unwrap();
+        client
+            .set_interface_type(Nl80211Iftype::IftypeMeshPoint, &[])
+            .unwrap();
+        client
+            .set_interface_type(
+                Nl80211Iftype::IftypeMonitor,
+                &[Nl80211MntrFlags::MntrFlagFcsfail],
+            )
+            .unwrap();
+        client.set_four_addr(true).unwrap();
+
+        let sent = client.transport().sent();
+        assert_eq!(sent[0][16], u8::from(Nl80211Cmd::CmdSetInterface));
+        assert_eq!(&sent[0][20..28], &[8, 0, 5, 0, 7, 0, 0, 0]);
+        assert_eq!(sent[0][28..], lo_ifindex());
+        assert_eq!(&sent[1][20..28], &[8, 0, 5, 0, 6, 0, 0, 0]);
+        assert_eq!(&sent[1][28..36], &[8, 0, 23, 0x80, 4, 0, 1, 0]);
+        // NL80211_ATTR_4ADDR
+        assert_eq!(&sent[2][20..25], &[5, 0, 83, 0, 1]);
+    }
+}
@@ -3,10 +3,34 @@ pub mod cmd;
 pub mod constant;
 
 pub mod client;
+pub mod crypto;
+pub mod eapol;
+pub mod ie;
+pub mod owe;
+pub mod sae;
+pub mod transport;
 
+mod connect;
+mod event;
+mod interface;
 mod reg;
+mod scan;
+mod station;
+mod status;
+mod supplicant;
+mod survey;
+mod wiphy;
 pub mod prelude {
+    pub use super::connect::*;
+    pub use super::event::*;
+    pub use super::interface::*;
     pub use super::reg::*;
+    pub use super::scan::*;
+    pub use super::station::*;
+    pub use super::status::*;
+    pub use super::supplicant::*;
+    pub use super::survey::*;
+    pub use super::wiphy::*;
 }
 
 pub mod utils;
@@ -0,0 +1,162 @@
+use super::crypto::Pmksa;
+use super::ie::{self, AkmSuite, CipherSuite, Element, Rsn, EID_EXT_OWE_DH_PARAM};
+use hkdf::Hkdf;
+use p256::elliptic_curve::ecdh::diffie_hellman;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use sha2::{Digest, Sha256};
+
+// References: RFC 8110, Opportunistic Wireless Encryption
+
+/// NIST P-256, the finite cyclic group every OWE AP supports
+pub const OWE_GROUP: u16 = 19;
+
+/// RSN capabilities with management frame protection required, as OWE needs
+const RSN_CAPABILITIES_MFP: u16 = 0x00c0;
+
+/// Ephemeral Diffie-Hellman key of an OWE association
+pub struct OweKey {
+    secret: SecretKey,
+    /// x-coordinate of the public key
+    public: [u8; 32],
+}
+
+impl OweKey {
+    pub fn generate() -> OweKey {
+        loop {
+            // rejects the few values not below the order of the group
+            if let Ok(key) = OweKey::from_secret(&rand::random::<[u8; 32]>()) {
+                return key;
+            }
+        }
+    }
+
+    pub fn from_secret(secret: &[u8; 32]) -> Result<OweKey, String> {
+        let secret = SecretKey::from_be_bytes(secret).map_err(|e| e.to_string())?;
+        let point = secret.public_key().to_encoded_point(false);
+        let mut public = [0; 32];
+        public.copy_from_slice(point.x().ok_or("public key at infinity")?);
+        Ok(OweKey { secret, public })
+    }
+
+    /// the Diffie-Hellman Parameter element sent in the association request
+    pub fn element(&self) -> Element {
+        let mut data = OWE_GROUP.to_le_bytes().to_vec();
+        data.extend_from_slice(&self.public);
+        Element::Extension {
+            id: EID_EXT_OWE_DH_PARAM,
+            data,
+        }
+    }
+
+    /// the RSN element of an OWE association request
+    pub fn rsn() -> Rsn {
+        Rsn {
+            version: 1,
+            group_cipher: Some(CipherSuite::Ccmp128),
+            pairwise_ciphers: Some(vec![CipherSuite::Ccmp128]),
+            akm_suites: Some(vec![AkmSuite::Owe]),
+            capabilities: Some(RSN_CAPABILITIES_MFP),
+            pmkids: None,
+            group_mgmt_cipher: None,
+        }
+    }
+
+    /// Derive the PMK from the Diffie-Hellman Parameter element of the AP,
+    /// in the IEs of the association response
+    pub fn pmk(&self, resp_ies: &[u8]) -> Result<Pmksa, String> {
+        let data = ie::parse(resp_ies)?
+            .into_iter()
+            .find_map(|element| match element {
+                Element::Extension { id, data } if id == EID_EXT_OWE_DH_PARAM => Some(data),
+                _ => None,
+            })
+            .ok_or("no OWE Diffie-Hellman Parameter element")?;
+        if data.len() < 2 || u16::from_le_bytes([data[0], data[1]]) != OWE_GROUP {
+            return Err("unsupported OWE group".to_owned());
+        }
+        if data.len() != 2 + 32 {
+            return Err("invalid OWE public key".to_owned());
+        }
+        let mut peer = [0; 32];
+        peer.copy_from_slice(&data[2..]);
+
+        // only the x-coordinate is sent, either y gives the same shared secret
+        let mut point = vec![0x02];
+        point.extend_from_slice(&peer);
+        let peer_key = PublicKey::from_sec1_bytes(&point).map_err(|e| e.to_string())?;
+        let shared = diffie_hellman(self.secret.to_nonzero_scalar(), peer_key.as_affine());
+        Ok(derive(shared.as_bytes(), &self.public, &peer))
+    }
+}
+
+/// PMK and PMKID from the shared secret `z` and the public keys of the
+/// client `c` and the AP `a`
+fn derive(z: &[u8], c: &[u8; 32], a: &[u8; 32]) -> Pmksa {
+    let mut salt = c.to_vec();
+    salt.extend_from_slice(a);
+    salt.extend_from_slice(&OWE_GROUP.to_le_bytes());
+    let mut pmk = [0; 32];
+    Hkdf::<Sha256>::new(Some(&salt), z)
+        .expand(b"OWE Key Generation", &mut pmk)
+        .expect("32 bytes are a valid length for HKDF-SHA-256");
+
+    let mut hash = Sha256::new();
+    hash.update(c);
+    hash.update(a);
+    let mut pmkid = [0; 16];
+    pmkid.copy_from_slice(&hash.finalize()[..16]);
+    Pmksa { pmk, pmkid }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::crypto::tests::unhex;
+
+    #[test]
+    fn test_pmk() {
+        let client = OweKey::from_secret(&[0x11; 32]).unwrap();
+        let ap = OweKey::from_secret(&[0x22; 32]).unwrap();
+        assert_eq!(
+            client.public.to_vec(),
+            unhex("0217e617f0b6443928278f96999e69a23a4f2c152bdf6d6cdf66e5b80282d4ed")
+        );
+
+        let mut resp_ies = Vec::new();
+        ap.element().write(&mut resp_ies);
+        assert_eq!(&resp_ies[..5], &[255, 35, 32, 19, 0]);
+        let pmksa = client.pmk(&resp_ies).unwrap();
+        assert_eq!(
+            pmksa.pmk.to_vec(),
+            unhex("6b03b935036e08247653fe6fcd7b0916ef8f0c05c733f80564771cdd96d967c2")
+        );
+        assert_eq!(
+            pmksa.pmkid.to_vec(),
+            unhex("088d4133252a88261a22f1a4d5fe80a6")
+        );
+    }
+
+    #[test]
+    fn test_pmk_invalid() {
+        let client = OweKey::generate();
+        assert!(client.pmk(&[]).is_err());
+        // group 20, P-384
+        let mut data = 20u16.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0; 48]);
+        let mut resp_ies = Vec::new();
+        Element::Extension {
+            id: EID_EXT_OWE_DH_PARAM,
+            data,
+        }
+        .write(&mut resp_ies);
+        assert_eq!(
+            client.pmk(&resp_ies),
+            Err("unsupported OWE group".to_owned())
+        );
+        // x-coordinate without point on the curve
+        let mut resp_ies = vec![255, 35, 32, 19, 0];
+        resp_ies.extend_from_slice(&[0xff; 32]);
+        assert!(client.pmk(&resp_ies).is_err());
+    }
+}
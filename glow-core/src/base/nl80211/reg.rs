@@ -2,16 +2,15 @@ use super::{
     attr::*,
     client::*,
     cmd::Nl80211Cmd,
+    transport::Transport,
     utils::{is_alpha2, is_world_regdom},
 };
 use glow_utils::binary::*;
-use neli::{
-    err::NlError, nl::NlPayload,
-};
+use neli::attr::Attribute;
+use neli::consts::nl::{NlmF, NlmFFlags};
 use neli::genl::{Genlmsghdr, Nlattr};
 use neli::types::GenlBuffer;
-use neli::consts::nl::{NlmF, NlmFFlags};
-use neli::attr::Attribute;
+use neli::{err::NlError, nl::NlPayload};
 
 #[derive(Debug)]
 pub struct RegData {
@@ -26,13 +25,13 @@ pub struct RegData {
 
 #[derive(Debug)]
 pub struct RegRule {
-    flags: u32,
-    start_freq_khz: u32,
-    end_freq_khz: u32,
-    max_bw_khz: u32,
-    max_ant_gain_mbi: u32,
-    max_eirp_mbm: u32,
-    dfs_cac_time: u32,
+    pub flags: u32,
+    pub start_freq_khz: u32,
+    pub end_freq_khz: u32,
+    pub max_bw_khz: u32,
+    pub max_ant_gain_mbi: u32,
+    pub max_eirp_mbm: u32,
+    pub dfs_cac_time: u32,
 }
 
 impl Default for RegData {
@@ -47,6 +46,21 @@ impl Default for RegData {
     }
 }
 
+impl RegData {
+    /// the rule covering `start_khz` to `end_khz` completely
+    pub fn rule(&self, start_khz: u32, end_khz: u32) -> Option<&RegRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.start_freq_khz <= start_khz && end_khz <= rule.end_freq_khz)
+    }
+}
+
+impl RegRule {
+    pub fn has_flag(&self, flag: Nl80211RegRuleFlags) -> bool {
+        self.flags & u16::from(flag) as u32 != 0
+    }
+}
+
 impl Default for RegRule {
     fn default() -> Self {
         RegRule {
@@ -61,7 +75,7 @@ impl Default for RegRule {
     }
 }
 
-impl NL80211Client {
+impl<T: Transport> NL80211Client<T> {
     // COMMAND(reg, get, NULL, NL80211_CMD_GET_REG, 0, CIB_NONE, handle_reg_get,
     //   "Print out the kernel's current regulatory domain information.");
     pub fn reg_get(&mut self) -> Result<RegData, NlError> {
@@ -70,82 +84,28 @@ impl NL80211Client {
 
     // HIDDEN(reg, dump, NULL, NL80211_CMD_GET_REG, NLM_F_DUMP, CIB_NONE, handle_reg_dump);
     fn reg_dump(&mut self) -> Result<RegData, NlError> {
-        let mut result = RegData::default();
-        let mut socket = self.send(
+        // the global regulatory domain comes first
+        Ok(self.reg_domains()?.into_iter().next().unwrap_or_default())
+    }
+
+    /// The regulatory domain `wiphy` follows, its self-managed one or
+    /// the global one
+    pub fn reg_get_wiphy(&mut self, wiphy: u32) -> Result<RegData, NlError> {
+        let mut domains = self.reg_domains()?.into_iter();
+        let global = domains.next().unwrap_or_default();
+        Ok(domains
+            .find(|reg| reg.phy_id == Some(wiphy))
+            .unwrap_or(global))
+    }
+
+    /// the global regulatory domain followed by the self-managed ones
+    fn reg_domains(&mut self) -> Result<Vec<RegData>, NlError> {
+        let responses = self.request(
             Nl80211Cmd::CmdGetReg,
-            Some(NlmFFlags::new(&[NlmF::Dump])),
+            Some(NlmFFlags::new(&[NlmF::Request, NlmF::Dump])),
             None,
         )?;
-
-        let iter = socket.iter::<Genlmsghdr<Nl80211Cmd, Nl80211Attr>>(false);
-        for response_result in iter {
-            let msg = response_result?;
-            let handle = msg.get_payload()?.get_attr_handle();
-            for attr in handle.iter() {
-                let payload = attr.payload().as_ref();
-                match &attr.nla_type {
-                    Nl80211Attr::AttrRegAlpha2 => {
-                        result.country.push(payload[0].into());
-                        result.country.push(payload[1].into());
-                    }
-                    Nl80211Attr::AttrRegRules => {
-                        let handle = attr.get_attr_handle::<NlaNested>()?;
-                        for attr in handle.iter() {
-                            let mut rule = RegRule::default();
-                            let nested = attr.get_attr_handle()?;
-                            for attr in nested.iter() {
-                                let payload = attr.payload().as_ref();
-                                match &attr.nla_type {
-                                    Nl80211RegRuleAttr::AttrRegRuleFlags => {
-                                        rule.flags = parse_u32(payload, attr.nla_network_order);
-                                    }
-                                    Nl80211RegRuleAttr::AttrFreqRangeStart => {
-                                        rule.start_freq_khz =
-                                            parse_u32(payload, attr.nla_network_order);
-                                    }
-                                    Nl80211RegRuleAttr::AttrFreqRangeEnd => {
-                                        rule.end_freq_khz =
-                                            parse_u32(payload, attr.nla_network_order);
-                                    }
-                                    Nl80211RegRuleAttr::AttrFreqRangeMaxBw => {
-                                        rule.max_bw_khz =
-                                            parse_u32(payload, attr.nla_network_order);
-                                    }
-                                    Nl80211RegRuleAttr::AttrPowerRuleMaxAntGain => {
-                                        rule.max_ant_gain_mbi =
-                                            parse_u32(payload, attr.nla_network_order);
-                                    }
-                                    Nl80211RegRuleAttr::AttrPowerRuleMaxEirp => {
-                                        rule.max_eirp_mbm =
-                                            parse_u32(payload, attr.nla_network_order);
-                                    }
-                                    Nl80211RegRuleAttr::AttrDfsCacTime => {
-                                        rule.dfs_cac_time =
-                                            parse_u32(payload, attr.nla_network_order);
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            result.rules.push(rule);
-                        }
-                    }
-                    Nl80211Attr::AttrWiphy => {
-                        glow_utils::binary::parse_some_u32(payload, attr.nla_network_order);
-                    }
-                    Nl80211Attr::AttrWiphySelfManagedReg => {
-                        result.self_managed = true;
-                    }
-                    Nl80211Attr::AttrDfsRegion => {
-                        result.dfs_domain = Some(payload[0]);
-                    }
-                    _ => (),
-                }
-            }
-
-            break;
-        }
-
-        Ok(result)
+        responses.iter().map(parse_reg).collect()
     }
 
     // COMMAND(reg, set, "<ISO/IEC 3166-1 alpha2>",
@@ -165,14 +125,138 @@ impl NL80211Client {
             NlPayload::Payload(country),
         )?;
         attrs.push(attr);
-        self.send(Nl80211Cmd::CmdGetReg, None, Some(attrs))?;
+        self.request(Nl80211Cmd::CmdReqSetReg, None, Some(attrs))?;
         Ok(())
     }
 
     // COMMAND(reg, reload, NULL, NL80211_CMD_RELOAD_REGDB, 0, CIB_NONE,
     //   handle_reg_reload, "Reload the kernel's regulatory database.");
     pub fn reg_reload(&mut self) -> Result<(), NlError> {
-        self.send(Nl80211Cmd::CmdReloadRegdb, None, None)?;
+        self.request(Nl80211Cmd::CmdReloadRegdb, None, None)?;
         Ok(())
     }
 }
+
+/// the regulatory domain of a `NL80211_CMD_GET_REG` response
+fn parse_reg(msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>) -> Result<RegData, NlError> {
+    let mut result = RegData::default();
+    let handle = msg.get_attr_handle();
+    for attr in handle.iter() {
+        let payload = attr.payload().as_ref();
+        match &attr.nla_type {
+            Nl80211Attr::AttrRegAlpha2 => {
+                result.country.push(payload[0].into());
+                result.country.push(payload[1].into());
+            }
+            Nl80211Attr::AttrRegRules => {
+                let handle = attr.get_attr_handle::<NlaNested>()?;
+                for attr in handle.iter() {
+                    let mut rule = RegRule::default();
+                    let nested = attr.get_attr_handle()?;
+                    for attr in nested.iter() {
+                        let payload = attr.payload().as_ref();
+                        match &attr.nla_type {
+                            Nl80211RegRuleAttr::AttrRegRuleFlags => {
+                                rule.flags = parse_u32(payload, attr.nla_network_order);
+                            }
+                            Nl80211RegRuleAttr::AttrFreqRangeStart => {
+                                rule.start_freq_khz = parse_u32(payload, attr.nla_network_order);
+                            }
+                            Nl80211RegRuleAttr::AttrFreqRangeEnd => {
+                                rule.end_freq_khz = parse_u32(payload, attr.nla_network_order);
+                            }
+                            Nl80211RegRuleAttr::AttrFreqRangeMaxBw => {
+                                rule.max_bw_khz = parse_u32(payload, attr.nla_network_order);
+                            }
+                            Nl80211RegRuleAttr::AttrPowerRuleMaxAntGain => {
+                                rule.max_ant_gain_mbi = parse_u32(payload, attr.nla_network_order);
+                            }
+                            Nl80211RegRuleAttr::AttrPowerRuleMaxEirp => {
+                                rule.max_eirp_mbm = parse_u32(payload, attr.nla_network_order);
+                            }
+                            Nl80211RegRuleAttr::AttrDfsCacTime => {
+                                rule.dfs_cac_time = parse_u32(payload, attr.nla_network_order);
+                            }
+                            _ => {}
+                        }
+                    }
+                    result.rules.push(rule);
+                }
+            }
+            Nl80211Attr::AttrWiphy => {
+                result.phy_id = glow_utils::binary::parse_some_u32(payload, attr.nla_network_order);
+            }
+            Nl80211Attr::AttrWiphySelfManagedReg => {
+                result.self_managed = true;
+            }
+            Nl80211Attr::AttrDfsRegion => {
+                result.dfs_domain = Some(payload[0]);
+            }
+            _ => (),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::{client, reply, FAMILY_ID};
+
+    #[test]
+    fn test_reg_get() {
+        let mut client = client(&[include_bytes!("fixtures/reg_dump.bin")]);
+        let reg = client.reg_get().unwrap();
+        assert_eq!(reg.country, "DE");
+        assert_eq!(reg.dfs_domain, Some(2));
+        assert_eq!(reg.phy_id, None);
+        assert!(!reg.self_managed);
+        assert_eq!(reg.rules.len(), 5);
+
+        let rule = &reg.rules[2];
+        assert_eq!(rule.start_freq_khz, 5250000);
+        assert_eq!(rule.end_freq_khz, 5350000);
+        assert_eq!(rule.max_bw_khz, 80000);
+        assert_eq!(rule.max_eirp_mbm, 2000);
+        assert_eq!(rule.dfs_cac_time, 60000);
+        assert_eq!(rule.flags, 1 << 3 | 1 << 4 | 1 << 11);
+
+        // a dump request of the nl80211 family
+        let sent = &client.transport().sent()[0];
+        assert_eq!(u16::from_ne_bytes([sent[4], sent[5]]), FAMILY_ID);
+        let flags = u16::from_ne_bytes([sent[6], sent[7]]);
+        assert_eq!(flags, u16::from(NlmF::Request) | u16::from(NlmF::Dump));
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdGetReg));
+    }
+
+    #[test]
+    fn test_reg_get_wiphy() {
+        let mut client = client(&[
+            include_bytes!("fixtures/reg_dump.bin"),
+            &reply(include_bytes!("fixtures/reg_dump.bin"), 2),
+        ]);
+        let reg = client.reg_get_wiphy(0).unwrap();
+        assert_eq!(reg.country, "US");
+        assert_eq!(reg.phy_id, Some(0));
+        assert!(reg.self_managed);
+        // phy1 follows the global domain
+        assert_eq!(client.reg_get_wiphy(1).unwrap().country, "DE");
+    }
+
+    #[test]
+    fn test_reg_set() {
+        let mut client = client(&[
+            include_bytes!("fixtures/ack.bin"),
+            &reply(include_bytes!("fixtures/err_einval.bin"), 2),
+        ]);
+        client.reg_set("DE").unwrap();
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdReqSetReg));
+        // NL80211_ATTR_REG_ALPHA2
+        assert_eq!(&sent[22..27], &[33, 0, b'D', b'E', 0]);
+
+        assert!(client.reg_set("DE").is_err());
+        assert!(client.reg_set("de").is_err());
+        assert_eq!(client.transport().sent().len(), 2);
+    }
+}
@@ -0,0 +1,382 @@
+use super::crypto::{hmac_sha256, kdf_sha256, Pmksa};
+use p256::elliptic_curve::ff::{Field, PrimeField};
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::elliptic_curve::subtle::Choice;
+use p256::elliptic_curve::DecompressPoint;
+use p256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar};
+
+// References: IEEE Std 802.11-2020, 12.4 Authentication using a password
+
+/// NIST P-256, the only group offered
+pub const SAE_GROUP: u16 = 19;
+
+/// Rounds of hunting and pecking, all of them are run to hide the one
+/// finding the password element
+const SAE_HUNTING_ROUNDS: u8 = 40;
+
+/// Prime of the field of P-256
+const P256_PRIME: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+];
+
+/// One SAE exchange of a station with an AP, sending a commit and
+/// confirm to the AP and checking the ones of the AP
+pub struct Sae {
+    pwe: ProjectivePoint,
+    rand: Scalar,
+    scalar: Scalar,
+    element: [u8; 64],
+    /// anti-clogging token the AP asked to send along the commit
+    token: Vec<u8>,
+    /// scalar and element of the AP once its commit is processed
+    peer: Option<(Scalar, [u8; 64])>,
+    kck: [u8; 32],
+    pmksa: Option<Pmksa>,
+    send_confirm: u16,
+}
+
+impl Sae {
+    pub fn new(password: &[u8], own: [u8; 6], peer: [u8; 6]) -> Result<Sae, String> {
+        let pwe = pwe(password, own, peer)?;
+        loop {
+            let rand = random_scalar();
+            let mask = random_scalar();
+            // the commit scalar must not be 0 or 1
+            if let Ok(sae) = Sae::with_secret(pwe, rand, mask) {
+                return Ok(sae);
+            }
+        }
+    }
+
+    /// An exchange with the given `rand` and `mask`, as both peers pick at
+    /// random for every exchange
+    pub fn from_secret(
+        password: &[u8],
+        own: [u8; 6],
+        peer: [u8; 6],
+        rand: &[u8; 32],
+        mask: &[u8; 32],
+    ) -> Result<Sae, String> {
+        let scalar = |bytes: &[u8; 32]| {
+            Option::<Scalar>::from(Scalar::from_repr((*bytes).into()))
+                .ok_or_else(|| "invalid SAE secret".to_owned())
+        };
+        Sae::with_secret(pwe(password, own, peer)?, scalar(rand)?, scalar(mask)?)
+    }
+
+    fn with_secret(pwe: ProjectivePoint, rand: Scalar, mask: Scalar) -> Result<Sae, String> {
+        let scalar = rand + mask;
+        if scalar.is_zero().into() || scalar == Scalar::one() {
+            return Err("invalid commit scalar".to_owned());
+        }
+        let element = encode(&-(pwe * mask)).ok_or("commit element at infinity")?;
+        Ok(Sae {
+            pwe,
+            rand,
+            scalar,
+            element,
+            token: Vec::new(),
+            peer: None,
+            kck: [0; 32],
+            pmksa: None,
+            send_confirm: 0,
+        })
+    }
+
+    /// Fields of the commit message after the status code
+    pub fn commit(&self) -> Vec<u8> {
+        let mut commit = SAE_GROUP.to_le_bytes().to_vec();
+        commit.extend_from_slice(&self.token);
+        commit.extend_from_slice(&self.scalar.to_repr());
+        commit.extend_from_slice(&self.element);
+        commit
+    }
+
+    /// Keep the anti-clogging token of a commit rejected with status
+    /// `AntiCloggingTokenRequired`, to send the commit again
+    pub fn anti_clogging(&mut self, body: &[u8]) -> Result<(), String> {
+        if body.len() < 2 || u16::from_le_bytes([body[0], body[1]]) != SAE_GROUP {
+            return Err("unsupported SAE group".to_owned());
+        }
+        self.token = body[2..].to_vec();
+        Ok(())
+    }
+
+    /// Derive the keys from the commit message of the AP
+    pub fn process_commit(&mut self, body: &[u8]) -> Result<(), String> {
+        if body.len() < 2 || u16::from_le_bytes([body[0], body[1]]) != SAE_GROUP {
+            return Err("unsupported SAE group".to_owned());
+        }
+        if body.len() < 2 + 32 + 64 {
+            return Err("truncated SAE commit".to_owned());
+        }
+        let mut element = [0; 64];
+        element.copy_from_slice(&body[34..98]);
+        if body[2..34] == self.scalar.to_repr()[..] && element == self.element {
+            return Err("SAE commit reflected".to_owned());
+        }
+        let scalar =
+            Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(&body[2..34])))
+                .ok_or("invalid SAE commit scalar")?;
+        if scalar.is_zero().into() || scalar == Scalar::one() {
+            return Err("invalid SAE commit scalar".to_owned());
+        }
+        let point = EncodedPoint::from_affine_coordinates(
+            FieldBytes::from_slice(&element[..32]),
+            FieldBytes::from_slice(&element[32..]),
+            false,
+        );
+        let point = Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&point))
+            .ok_or("SAE commit element not on the curve")?;
+
+        let shared = (self.pwe * scalar + ProjectivePoint::from(point)) * self.rand;
+        let k = encode(&shared).ok_or("SAE shared secret at infinity")?;
+        let keyseed = hmac_sha256(&[0; 32], &[&k[..32]]);
+        let sum = (self.scalar + scalar).to_repr();
+        let keys = kdf_sha256(&keyseed, b"SAE KCK and PMK", &sum, 512);
+
+        self.kck.copy_from_slice(&keys[..32]);
+        let mut pmksa = Pmksa {
+            pmk: [0; 32],
+            pmkid: [0; 16],
+        };
+        pmksa.pmk.copy_from_slice(&keys[32..]);
+        pmksa.pmkid.copy_from_slice(&sum[..16]);
+        self.pmksa = Some(pmksa);
+        self.peer = Some((scalar, element));
+        Ok(())
+    }
+
+    /// Fields of the confirm message after the status code, once the
+    /// commit of the AP is processed
+    pub fn confirm(&mut self) -> Result<Vec<u8>, String> {
+        let (peer_scalar, peer_element) = self.peer.ok_or("no SAE commit of the AP")?;
+        self.send_confirm = self.send_confirm.saturating_add(1);
+        let mut confirm = self.send_confirm.to_le_bytes().to_vec();
+        confirm.extend_from_slice(&hmac_sha256(
+            &self.kck,
+            &[
+                &self.send_confirm.to_le_bytes(),
+                &self.scalar.to_repr(),
+                &self.element,
+                &peer_scalar.to_repr(),
+                &peer_element,
+            ],
+        ));
+        Ok(confirm)
+    }
+
+    /// Verify the confirm message of the AP, returning the PMK
+    pub fn process_confirm(&mut self, body: &[u8]) -> Result<Pmksa, String> {
+        let (peer_scalar, peer_element) = self.peer.ok_or("no SAE commit of the AP")?;
+        if body.len() != 2 + 32 {
+            return Err("invalid length of SAE confirm".to_owned());
+        }
+        let expected = hmac_sha256(
+            &self.kck,
+            &[
+                &body[..2],
+                &peer_scalar.to_repr(),
+                &peer_element,
+                &self.scalar.to_repr(),
+                &self.element,
+            ],
+        );
+        if body[2..] != expected[..] {
+            return Err("SAE confirm mismatch".to_owned());
+        }
+        self.pmksa
+            .take()
+            .ok_or_else(|| "SAE already confirmed".to_owned())
+    }
+}
+
+/// The password element by hunting and pecking
+fn pwe(password: &[u8], a: [u8; 6], b: [u8; 6]) -> Result<ProjectivePoint, String> {
+    let mut addrs = a.max(b).to_vec();
+    addrs.extend_from_slice(&a.min(b));
+
+    let mut found = None;
+    for counter in 1..=SAE_HUNTING_ROUNDS {
+        let seed = hmac_sha256(&addrs, &[password, &[counter]]);
+        let value = kdf_sha256(&seed, b"SAE Hunting and Pecking", &P256_PRIME, 256);
+        if value[..] >= P256_PRIME[..] {
+            continue;
+        }
+        // the y-coordinate exists if the value is a quadratic residue,
+        // its parity is the one of the seed
+        let point =
+            AffinePoint::decompress(FieldBytes::from_slice(&value), Choice::from(seed[31] & 1));
+        if let Some(point) = Option::<AffinePoint>::from(point) {
+            found = found.or(Some(point));
+        }
+    }
+    found
+        .map(ProjectivePoint::from)
+        .ok_or_else(|| "no SAE password element found".to_owned())
+}
+
+fn random_scalar() -> Scalar {
+    loop {
+        let bytes = rand::random::<[u8; 32]>();
+        if let Some(scalar) = Option::<Scalar>::from(Scalar::from_repr(bytes.into())) {
+            if !bool::from(scalar.is_zero()) && scalar != Scalar::one() {
+                return scalar;
+            }
+        }
+    }
+}
+
+/// x and y of a point, none at infinity
+fn encode(point: &ProjectivePoint) -> Option<[u8; 64]> {
+    let point = point.to_affine().to_encoded_point(false);
+    let mut encoded = [0; 64];
+    encoded[..32].copy_from_slice(point.x()?);
+    encoded[32..].copy_from_slice(point.y()?);
+    Some(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::crypto::tests::unhex;
+
+    const STA: [u8; 6] = [0x4d, 0x3f, 0x2f, 0xff, 0xe3, 0x87];
+    const AP: [u8; 6] = [0xa5, 0xd8, 0xaa, 0x95, 0x8e, 0x3c];
+
+    fn exchange() -> (Sae, Sae) {
+        let sta = Sae::from_secret(b"mekmitasdigoat", STA, AP, &[0x11; 32], &[0x22; 32]).unwrap();
+        let ap = Sae::from_secret(b"mekmitasdigoat", AP, STA, &[0x33; 32], &[0x44; 32]).unwrap();
+        (sta, ap)
+    }
+
+    #[test]
+    fn test_pwe() {
+        let pwe = encode(&pwe(b"mekmitasdigoat", STA, AP).unwrap()).unwrap();
+        assert_eq!(
+            pwe.to_vec(),
+            unhex(
+                "da6eb7b06a1ac5624974f90afdd6a8e9d5722634cf987c34defc91a9874e5658\
+                 f4fefd130bd5be08fe68af3e4a290272ec065fd3671f3c25bf8ec419ddc9b822"
+            )
+        );
+    }
+
+    /// the group 19 vector of IEEE Std 802.11-2020, J.10, where the
+    /// password identifier follows the password in hunting and pecking
+    #[test]
+    fn test_annex_j() {
+        let own = [0x82, 0x7b, 0x91, 0x9d, 0xd4, 0xb9];
+        let peer = [0x1e, 0xec, 0x49, 0xea, 0x64, 0x88];
+        let mut rand = [0; 32];
+        rand.copy_from_slice(&unhex(
+            "a906f61e4d3a5d4eb2965ff34cf917dd044445c878c17ca5d5b93786da9f83cf",
+        ));
+        let mut mask = [0; 32];
+        mask.copy_from_slice(&unhex(
+            "4234b4fb17aa435c52fbfdebe64039b43478200e54ff7b6e07b69cad74153c15",
+        ));
+        let mut sae =
+            Sae::from_secret(b"mekmitasdigoatpsk4internet", own, peer, &rand, &mask).unwrap();
+        assert_eq!(
+            sae.commit(),
+            unhex(
+                "1300\
+                 eb3bab1964e4a0ab05925ddf3339519138bc65d6cdc0f813dd6fd4344eb4bfe4\
+                 4b5c21597658f4e3eddfb4b99f25b4d6540f32ff1fd5c530c60a794448610bc6\
+                 de3d92bdbbd47d935980ca6cf8988ab6630be6764c885ceb9793970f695217ee"
+            )
+        );
+
+        sae.process_commit(&unhex(
+            "1300\
+             5564f045b2ea1e566cf1dd741f70d9be35d2df5b9a5502946ee03cf8dae27e1e\
+             05b8430eb7a99e24877ce69baf3dc580e309633d6b385f83ee1c3ec3591f1a53\
+             93c06e805ddceb2fde50930dd7cfebb987c6ff9666af164eb5184d8e6662ed6a",
+        ))
+        .unwrap();
+        assert_eq!(
+            sae.kck.to_vec(),
+            unhex("599d6f1e27548be8499dceed2feccf94818ce1c79f1b4eb3d6a53228a09bf3ed")
+        );
+        // the HMAC with the KCK over both commits
+        assert_eq!(
+            sae.confirm().unwrap(),
+            unhex("010012d9d5c78c500526d36c41dbc56aedf2914cedddd7cad4a58c48f83dbde9fc77")
+        );
+        let confirm = unhex("010002871cf906898b8060ec184143be77b8c08a8019b13eb6d0aef0d8383dfac2fd");
+        let pmksa = sae.process_confirm(&confirm).unwrap();
+        assert_eq!(
+            pmksa.pmk.to_vec(),
+            unhex("7aead86fba4c3221fc437f5f14d70d854ea5d5aac1690116793081eda4d557c5")
+        );
+        assert_eq!(
+            pmksa.pmkid.to_vec(),
+            unhex("40a09b6017cebf0072843b5352aa2b4f")
+        );
+    }
+
+    #[test]
+    fn test_exchange() {
+        let (mut sta, mut ap) = exchange();
+        let commit = sta.commit();
+        assert_eq!(
+            commit,
+            unhex(
+                "1300\
+                 3333333333333333333333333333333333333333333333333333333333333333\
+                 d0961bde567d0686f39812d928e2679fb3c53bd83ec5945d60c3c0d3b746cd4d\
+                 97aabb3f951249854c74643c21a479ca8655727047895fa4cda35251ea817dbc"
+            )
+        );
+        ap.process_commit(&commit).unwrap();
+        sta.process_commit(&ap.commit()).unwrap();
+
+        let confirm = sta.confirm().unwrap();
+        assert_eq!(
+            confirm,
+            unhex("0100d16ab97467f32a9fdd2478eff6b31390008ce0c1aedcc4875d9040a564518ee2")
+        );
+        let ap_pmksa = ap.process_confirm(&confirm).unwrap();
+        let pmksa = sta.process_confirm(&ap.confirm().unwrap()).unwrap();
+        assert_eq!(
+            pmksa.pmk.to_vec(),
+            unhex("f229a5267350a770dd6b87a6a76de018fbe3a791678e3f4f5bc8a655f642b892")
+        );
+        assert_eq!(pmksa.pmkid, [0xaa; 16]);
+        assert_eq!(pmksa, ap_pmksa);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let (mut sta, mut ap) = exchange();
+        assert!(sta.confirm().is_err());
+        // reflection of the own commit
+        assert_eq!(
+            sta.process_commit(&sta.commit()),
+            Err("SAE commit reflected".to_owned())
+        );
+        // element off the curve
+        let mut commit = ap.commit();
+        commit[97] ^= 1;
+        assert!(sta.process_commit(&commit).is_err());
+
+        sta.process_commit(&ap.commit()).unwrap();
+        ap.process_commit(&sta.commit()).unwrap();
+        let mut confirm = ap.confirm().unwrap();
+        confirm[2] ^= 1;
+        assert_eq!(
+            sta.process_confirm(&confirm),
+            Err("SAE confirm mismatch".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_anti_clogging() {
+        let (mut sta, _) = exchange();
+        sta.anti_clogging(&[19, 0, 0xc0, 0xff, 0xee]).unwrap();
+        assert_eq!(&sta.commit()[..5], &[19, 0, 0xc0, 0xff, 0xee]);
+        assert!(sta.anti_clogging(&[20, 0]).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use super::{
+    attr::*,
+    client::NL80211Client,
+    cmd::Nl80211Cmd,
+    constant::NL80211_MULTICAST_GROUP_SCAN,
+    ie::{self, Element},
+    transport::Transport,
+    utils::{mac_addr, nested_list},
+};
+use neli::attr::Attribute;
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::err::NlError;
+use neli::genl::Nlattr;
+use neli::types::{Buffer, GenlBuffer};
+use std::time::{Duration, Instant};
+
+/// A BSS found by scanning
+#[derive(Debug)]
+pub struct Bss {
+    pub bssid: [u8; 6],
+    pub freq: u32,
+    /// signal strength in mBm, 100 * dBm
+    pub signal_mbm: Option<i32>,
+    /// signal strength from 0 to 100, from drivers without dBm
+    pub signal_unspec: Option<u8>,
+    /// timing synchronization function timer in microseconds
+    pub tsf: u64,
+    /// in time units of 1024 microseconds
+    pub beacon_interval: u16,
+    /// Capability Information field
+    pub capability: u16,
+    /// whether we are authenticated or associated with it
+    pub status: Option<Nl80211BssStatus>,
+    pub seen_ms_ago: u32,
+    /// information elements of the last probe response or beacon
+    pub ies: Vec<u8>,
+    /// information elements of the last beacon
+    pub beacon_ies: Vec<u8>,
+}
+
+impl Bss {
+    pub fn elements(&self) -> Result<Vec<Element>, String> {
+        ie::parse(&self.ies)
+    }
+
+    /// the SSID, also of a BSS whose last element is truncated
+    pub fn ssid(&self) -> Option<Vec<u8>> {
+        ie::parse_lenient(&self.ies)
+            .into_iter()
+            .find_map(|element| match element {
+                Element::Ssid(ssid) => Some(ssid),
+                _ => None,
+            })
+    }
+}
+
+/// How long a scan may take until its results, scanning all channels
+/// passively takes a few seconds
+const SCAN_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl<T: Transport> NL80211Client<T> {
+    // COMMAND(scan, trigger, "[freq <freq>*] [ies <hex as 00:11:..>] [meshid <meshid>] [lowpri,flush,ap-force] [randomise[=<addr>/<mask>]] [ssid <ssid>*|passive]",
+    //   NL80211_CMD_TRIGGER_SCAN, 0, CIB_NETDEV, handle_scan,
+    //   "Trigger a scan on the given frequencies with probing for the given\n"
+    //   "SSIDs (or wildcard if not given) unless passive scanning is requested.\n"
+    //   "If -u is specified print unknown data in the scan results.\n"
+    //   "Specified (vendor) IEs must be well-formed.");
+    /// Scan on the selected netdev or wdev and wait until it is done.
+    /// Probes for `ssids`, an empty SSID is the wildcard, and scans passively
+    /// without any. Empty `freqs` scans all allowed frequencies.
+    pub fn trigger_scan(
+        &mut self,
+        ssids: &[&[u8]],
+        freqs: &[u32],
+        flags: &[Nl80211ScanFlags],
+    ) -> Result<(), NlError> {
+        let mut attrs = GenlBuffer::new();
+        if !ssids.is_empty() {
+            let ssids = ssids.iter().map(|ssid| ssid.to_vec()).collect();
+            attrs.push(nested_list(Nl80211Attr::AttrScanSsids, ssids)?);
+        }
+        if !freqs.is_empty() {
+            attrs.push(nested_list(
+                Nl80211Attr::AttrScanFrequencies,
+                freqs.to_vec(),
+            )?);
+        }
+        if !flags.is_empty() {
+            let flags = flags
+                .iter()
+                .fold(0, |acc, flag| acc | u16::from(*flag) as u32);
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrScanFlags,
+                flags,
+            )?);
+        }
+
+        // join before triggering to not miss the results
+        self.subscribe(NL80211_MULTICAST_GROUP_SCAN)?;
+        let result = self.scan(attrs);
+        let left = self.unsubscribe(NL80211_MULTICAST_GROUP_SCAN);
+        // events of the group received meanwhile are of no other request
+        self.discard_events(|event| is_scan_event(event.cmd));
+        result.and(left)
+    }
+
+    fn scan(&mut self, attrs: GenlBuffer<Nl80211Attr, Buffer>) -> Result<(), NlError> {
+        let deadline = Instant::now() + SCAN_TIMEOUT;
+        let seq = self.send(Nl80211Cmd::CmdTriggerScan, None, Some(attrs))?;
+        // events arriving before the ACK are kept for recv_events
+        self.recv(seq, true)?;
+        // results of an earlier scan still queued come before it started
+        let mut started = false;
+        loop {
+            for event in self.recv_events(Some(deadline))?.iter() {
+                if !self.targets(event) {
+                    continue;
+                }
+                match event.cmd {
+                    Nl80211Cmd::CmdTriggerScan => started = true,
+                    Nl80211Cmd::CmdNewScanResults if started => return Ok(()),
+                    Nl80211Cmd::CmdScanAborted if started => {
+                        return Err(NlError::new("scan aborted"))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    // COMMAND(scan, dump, "[-u]",
+    //   NL80211_CMD_GET_SCAN, NLM_F_DUMP, CIB_NETDEV, handle_scan_dump,
+    //   "Dump the current scan results. If -u is specified, print unknown\n"
+    //   "data in scan results.");
+    pub fn scan_results(&mut self) -> Result<Vec<Bss>, NlError> {
+        let responses = self.request(
+            Nl80211Cmd::CmdGetScan,
+            Some(NlmFFlags::new(&[NlmF::Request, NlmF::Dump])),
+            None,
+        )?;
+
+        let mut results = Vec::new();
+        for msg in responses.iter() {
+            let handle = msg.get_attr_handle();
+            let attr = match handle.get_attribute(Nl80211Attr::AttrBss) {
+                Some(attr) => attr,
+                None => continue,
+            };
+
+            let mut bss = Bss {
+                bssid: [0; 6],
+                freq: 0,
+                signal_mbm: None,
+                signal_unspec: None,
+                tsf: 0,
+                beacon_interval: 0,
+                capability: 0,
+                status: None,
+                seen_ms_ago: 0,
+                ies: Vec::new(),
+                beacon_ies: Vec::new(),
+            };
+            for attr in attr.get_attr_handle::<Nl80211Bss>()?.iter() {
+                let payload = attr.payload().as_ref();
+                match &attr.nla_type {
+                    Nl80211Bss::BssBssid => bss.bssid = mac_addr(payload)?,
+                    Nl80211Bss::BssFrequency => bss.freq = attr.get_payload_as::<u32>()?,
+                    Nl80211Bss::BssSignalMbm => {
+                        bss.signal_mbm = Some(attr.get_payload_as::<i32>()?)
+                    }
+                    Nl80211Bss::BssSignalUnspec => {
+                        bss.signal_unspec = Some(attr.get_payload_as::<u8>()?)
+                    }
+                    Nl80211Bss::BssTsf => bss.tsf = attr.get_payload_as::<u64>()?,
+                    Nl80211Bss::BssBeaconInterval => {
+                        bss.beacon_interval = attr.get_payload_as::<u16>()?
+                    }
+                    Nl80211Bss::BssCapability => bss.capability = attr.get_payload_as::<u16>()?,
+                    Nl80211Bss::BssStatus => {
+                        let status = attr.get_payload_as::<u32>()? as u16;
+                        bss.status = Some(Nl80211BssStatus::from(status))
+                    }
+                    Nl80211Bss::BssSeenMsAgo => bss.seen_ms_ago = attr.get_payload_as::<u32>()?,
+                    Nl80211Bss::BssInformationElements => bss.ies = payload.to_vec(),
+                    Nl80211Bss::BssBeaconIes => bss.beacon_ies = payload.to_vec(),
+                    _ => (),
+                }
+            }
+            results.push(bss);
+        }
+        Ok(results)
+    }
+}
+
+/// whether `cmd` is one of the events of the scan multicast group
+fn is_scan_event(cmd: Nl80211Cmd) -> bool {
+    matches!(
+        cmd,
+        Nl80211Cmd::CmdTriggerScan
+            | Nl80211Cmd::CmdNewScanResults
+            | Nl80211Cmd::CmdScanAborted
+            | Nl80211Cmd::CmdStartSchedScan
+            | Nl80211Cmd::CmdSchedScanResults
+            | Nl80211Cmd::CmdSchedScanStopped
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::{client, select_netdev};
+    use crate::base::nl80211::utils::format_mac;
+
+    const SCAN_GROUP: u32 = 5;
+
+    #[test]
+    fn test_trigger_scan() {
+        let mut client = client(&[
+            include_bytes!("fixtures/ack.bin"),
+            include_bytes!("fixtures/scan_started.bin"),
+            include_bytes!("fixtures/scan_done.bin"),
+        ]);
+        client.multicast_group(NL80211_MULTICAST_GROUP_SCAN, SCAN_GROUP);
+        select_netdev(&mut client, 1);
+        client
+            .trigger_scan(
+                &[b"", b"glow"],
+                &[2412, 5180],
+                &[Nl80211ScanFlags::ScanFlagFlush],
+            )
+            .unwrap();
+        assert!(client.transport().groups().is_empty());
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdTriggerScan));
+        // nested NL80211_ATTR_SCAN_SSIDS holding the wildcard and "glow"
+        assert_eq!(&sent[20..24], &[16, 0, 45, 0x80]);
+        assert_eq!(&sent[24..28], &[4, 0, 1, 0]);
+        assert_eq!(&sent[28..36], &[8, 0, 2, 0, b'g', b'l', b'o', b'w']);
+        // NL80211_ATTR_SCAN_FREQUENCIES
+        assert_eq!(&sent[36..40], &[20, 0, 44, 0x80]);
+        assert_eq!(&sent[40..48], &[8, 0, 1, 0, 0x6c, 0x09, 0, 0]);
+        // NL80211_ATTR_SCAN_FLAGS
+        assert_eq!(&sent[56..64], &[8, 0, 158, 0, 2, 0, 0, 0]);
+        // NL80211_ATTR_IFINDEX
+        assert_eq!(&sent[64..72], &[8, 0, 3, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_scan_aborted() {
+        // group ids are unknown without resolving them
+        let mut unresolved = client(&[]);
+        assert!(unresolved.trigger_scan(&[], &[], &[]).is_err());
+        assert!(unresolved.transport().sent().is_empty());
+
+        let mut client = client(&[
+            include_bytes!("fixtures/ack.bin"),
+            include_bytes!("fixtures/scan_started.bin"),
+            include_bytes!("fixtures/scan_aborted.bin"),
+        ]);
+        client.multicast_group(NL80211_MULTICAST_GROUP_SCAN, SCAN_GROUP);
+        select_netdev(&mut client, 1);
+        let err = client.trigger_scan(&[], &[], &[]).unwrap_err();
+        assert_eq!(err.to_string(), "scan aborted");
+        assert!(client.transport().groups().is_empty());
+    }
+
+    #[test]
+    fn test_scan_stale_results() {
+        // the results of an earlier scan, queued before the ACK
+        let mut early = include_bytes!("fixtures/scan_done.bin").to_vec();
+        early.extend_from_slice(include_bytes!("fixtures/ack.bin"));
+        let mut client = client(&[
+            &early,
+            include_bytes!("fixtures/scan_aborted.bin"),
+            include_bytes!("fixtures/scan_started.bin"),
+            include_bytes!("fixtures/scan_done.bin"),
+        ]);
+        client.multicast_group(NL80211_MULTICAST_GROUP_SCAN, SCAN_GROUP);
+        select_netdev(&mut client, 1);
+        client.trigger_scan(&[], &[], &[]).unwrap();
+        assert!(client.recv_events(None).is_err());
+    }
+
+    #[test]
+    fn test_scan_results() {
+        let mut client = client(&[include_bytes!("fixtures/scan_dump.bin")]);
+        let results = client.scan_results().unwrap();
+        assert_eq!(results.len(), 2);
+
+        let bss = &results[0];
+        assert_eq!(format_mac(&bss.bssid), "02:00:00:00:02:00");
+        assert_eq!(bss.freq, 5180);
+        assert_eq!(bss.signal_mbm, Some(-4500));
+        assert_eq!(bss.tsf, 1_234_567_890);
+        assert_eq!(bss.beacon_interval, 100);
+        assert_eq!(bss.capability, 0x0011);
+        assert_eq!(bss.status, Some(Nl80211BssStatus::BssStatusAssociated));
+        assert_eq!(bss.seen_ms_ago, 120);
+        assert_eq!(bss.ssid(), Some(b"glow".to_vec()));
+
+        let bss = &results[1];
+        assert_eq!(bss.status, None);
+        assert_eq!(bss.ssid(), Some(Vec::new()));
+
+        // the SSID survives a truncated trailing element
+        let mut bss = results.into_iter().next().unwrap();
+        bss.ies.extend_from_slice(&[0xdd, 0x08, 0x00, 0x50]);
+        assert!(bss.elements().is_err());
+        assert_eq!(bss.ssid(), Some(b"glow".to_vec()));
+    }
+}
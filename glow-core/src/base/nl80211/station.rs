@@ -0,0 +1,553 @@
+use super::{
+    attr::*, client::NL80211Client, cmd::Nl80211Cmd, transport::Transport, utils::mac_addr,
+};
+use neli::attr::Attribute;
+use neli::consts::genl::NlAttrType;
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::err::NlError;
+use neli::genl::{Genlmsghdr, Nlattr};
+use neli::types::{Buffer, GenlBuffer};
+
+/// Statistics of a station, the AP we are connected to or a client of ours
+#[derive(Debug, Default)]
+pub struct Station {
+    pub mac: [u8; 6],
+    pub ifindex: u32,
+
+    pub inactive_ms: Option<u32>,
+    pub connected_secs: Option<u32>,
+
+    pub rx_bytes: Option<u64>,
+    pub tx_bytes: Option<u64>,
+    pub rx_packets: Option<u32>,
+    pub tx_packets: Option<u32>,
+    pub tx_retries: Option<u32>,
+    pub tx_failed: Option<u32>,
+    pub rx_drop_misc: Option<u64>,
+
+    pub signal_dbm: Option<i8>,
+    pub signal_avg_dbm: Option<i8>,
+    /// per antenna chain
+    pub chain_signal_dbm: Vec<i8>,
+    pub chain_signal_avg_dbm: Vec<i8>,
+    pub beacon_signal_avg_dbm: Option<i8>,
+    pub beacon_rx: Option<u64>,
+    pub beacon_loss: Option<u32>,
+
+    pub tx_bitrate: Option<RateInfo>,
+    pub rx_bitrate: Option<RateInfo>,
+    pub expected_throughput_kbps: Option<u32>,
+
+    pub bss_param: Option<BssParam>,
+    pub tid_stats: Vec<TidStats>,
+}
+
+/// Parameters of the BSS we are connected to
+#[derive(Debug, Default)]
+pub struct BssParam {
+    pub cts_protection: bool,
+    pub short_preamble: bool,
+    pub short_slot_time: bool,
+    pub dtim_period: u8,
+    pub beacon_interval: u16,
+}
+
+#[derive(Debug, Default)]
+pub struct TidStats {
+    /// traffic identifier, 16 for non-QoS traffic
+    pub tid: u16,
+    pub rx_msdu: Option<u64>,
+    pub tx_msdu: Option<u64>,
+    pub tx_msdu_retries: Option<u64>,
+    pub tx_msdu_failed: Option<u64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Modulation {
+    Legacy,
+    Ht {
+        mcs: u8,
+    },
+    Vht {
+        mcs: u8,
+        nss: u8,
+    },
+    He {
+        mcs: u8,
+        nss: u8,
+        /// guard interval of 0.8, 1.6 or 3.2 us as 0, 1 or 2
+        gi: u8,
+        dcm: bool,
+        /// resource unit of 26, 52, 106, 242, 484, 996 or 2x996 tones as 0 to 6
+        ru_alloc: Option<u8>,
+    },
+}
+
+#[derive(Debug)]
+pub struct RateInfo {
+    pub modulation: Modulation,
+    pub width: Nl80211ChanWidth,
+    /// short guard interval of HT and VHT
+    pub short_gi: bool,
+    /// as calculated by the kernel, in 100 kbit/s
+    pub bitrate: Option<u32>,
+}
+
+impl RateInfo {
+    /// bitrate in Mbit/s, from the MCS if known
+    pub fn mbps(&self) -> Option<f32> {
+        let bitrate = match self.calculate() {
+            Some(bitrate) => bitrate,
+            None => self.bitrate?,
+        };
+        Some(bitrate as f32 / 10.0)
+    }
+
+    /// bitrate in 100 kbit/s like cfg80211_calculate_bitrate()
+    pub fn calculate(&self) -> Option<u32> {
+        match self.modulation {
+            Modulation::Legacy => None,
+            Modulation::Ht { mcs } => {
+                if mcs >= 32 {
+                    return None;
+                }
+                let modulation = (mcs & 7) as u64;
+                let streams = (mcs >> 3) as u64 + 1;
+                let mut bitrate: u64 = match self.width {
+                    Nl80211ChanWidth::ChanWidth40 => 13_500_000,
+                    _ => 6_500_000,
+                };
+                bitrate *= match modulation {
+                    0..=3 => modulation + 1,
+                    4 => modulation + 2,
+                    _ => modulation + 3,
+                };
+                bitrate *= streams;
+                if self.short_gi {
+                    bitrate = (bitrate / 9) * 10;
+                }
+                Some(((bitrate + 50_000) / 100_000) as u32)
+            }
+            Modulation::Vht { mcs, nss } => {
+                let row = match self.width {
+                    Nl80211ChanWidth::ChanWidth20 | Nl80211ChanWidth::ChanWidth20Noht => 0,
+                    Nl80211ChanWidth::ChanWidth40 => 1,
+                    Nl80211ChanWidth::ChanWidth80 => 2,
+                    Nl80211ChanWidth::ChanWidth80p80 | Nl80211ChanWidth::ChanWidth160 => 3,
+                    _ => return None,
+                };
+                let mut bitrate = *VHT_BASE[row].get(mcs as usize)? as u64 * nss as u64;
+                if self.short_gi {
+                    bitrate = (bitrate / 9) * 10;
+                }
+                Some(((bitrate + 50_000) / 100_000) as u32)
+            }
+            Modulation::He {
+                mcs,
+                nss,
+                gi,
+                dcm,
+                ru_alloc,
+            } => {
+                if !(1..=8).contains(&nss) {
+                    return None;
+                }
+                let rates = match (ru_alloc, self.width) {
+                    (None, Nl80211ChanWidth::ChanWidth160)
+                    | (None, Nl80211ChanWidth::ChanWidth80p80)
+                    | (Some(6), _) => &HE_RATES_160M,
+                    (None, Nl80211ChanWidth::ChanWidth80) | (Some(5), _) => &HE_RATES_996,
+                    (None, Nl80211ChanWidth::ChanWidth40) | (Some(4), _) => &HE_RATES_484,
+                    (None, Nl80211ChanWidth::ChanWidth20) | (Some(3), _) => &HE_RATES_242,
+                    (Some(2), _) => &HE_RATES_106,
+                    (Some(1), _) => &HE_RATES_52,
+                    (Some(0), _) => &HE_RATES_26,
+                    _ => return None,
+                };
+                let mut bitrate = *rates.get(gi as usize)? as u64 * HE_SCALE
+                    / *HE_MCS_DIVISORS.get(mcs as usize)? as u64;
+                bitrate = bitrate * nss as u64 / 8;
+                if dcm {
+                    bitrate /= 2;
+                }
+                Some((bitrate / 10_000) as u32)
+            }
+        }
+    }
+}
+
+/// VHT rates of one spatial stream in bit/s, by width and MCS
+#[rustfmt::skip]
+const VHT_BASE: [[u32; 12]; 4] = [
+    [ 6_500_000,  13_000_000,  19_500_000,  26_000_000,  39_000_000,  52_000_000,
+     58_500_000,  65_000_000,  78_000_000,  86_700_000,  97_500_000, 108_300_000],
+    [13_500_000,  27_000_000,  40_500_000,  54_000_000,  81_000_000, 108_000_000,
+    121_500_000, 135_000_000, 162_000_000, 180_000_000, 202_500_000, 225_000_000],
+    [29_300_000,  58_500_000,  87_800_000, 117_000_000, 175_500_000, 234_000_000,
+    263_300_000, 292_500_000, 351_000_000, 390_000_000, 438_800_000, 487_500_000],
+    [58_500_000, 117_000_000, 175_500_000, 234_000_000, 351_000_000, 468_000_000,
+    526_500_000, 585_000_000, 702_000_000, 780_000_000, 877_500_000, 975_000_000],
+];
+
+/// HE rates of MCS 11 with 8 spatial streams in 10 bit/s by guard interval,
+/// scaled down to other MCS by `HE_SCALE / HE_MCS_DIVISORS[mcs]`
+const HE_SCALE: u64 = 6144;
+const HE_MCS_DIVISORS: [u32; 14] = [
+    102_399, 51_201, 34_134, 25_599, 17_067, 12_801, 11_377, 10_239, 8_532, 7_680, 6_828, 6_144,
+    5_690, 5_120,
+];
+const HE_RATES_160M: [u32; 3] = [960_777_777, 907_400_000, 816_666_666];
+const HE_RATES_996: [u32; 3] = [480_388_888, 453_700_000, 408_333_333];
+const HE_RATES_484: [u32; 3] = [229_411_111, 216_666_666, 195_000_000];
+const HE_RATES_242: [u32; 3] = [114_711_111, 108_333_333, 97_500_000];
+const HE_RATES_106: [u32; 3] = [40_000_000, 37_777_777, 34_000_000];
+const HE_RATES_52: [u32; 3] = [18_820_000, 17_777_777, 16_000_000];
+const HE_RATES_26: [u32; 3] = [9_411_111, 8_888_888, 8_000_000];
+
+impl<T: Transport> NL80211Client<T> {
+    // COMMAND(station, get, "<MAC address>",
+    //   NL80211_CMD_GET_STATION, 0, CIB_NETDEV, handle_station_get,
+    //   "Get information for a specific station.");
+    pub fn station_get(&mut self, mac: &[u8; 6]) -> Result<Station, NlError> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrMac,
+            mac.to_vec(),
+        )?);
+        let responses = self.request(Nl80211Cmd::CmdGetStation, None, Some(attrs))?;
+        match responses.first() {
+            Some(msg) => parse_station(msg),
+            None => Err(NlError::new("no station in the response")),
+        }
+    }
+
+    // COMMAND(station, dump, "[-v]",
+    //   NL80211_CMD_GET_STATION, NLM_F_DUMP, CIB_NETDEV, handle_station_dump,
+    //   "List all stations known, e.g. the AP on managed interfaces");
+    pub fn station_dump(&mut self) -> Result<Vec<Station>, NlError> {
+        let responses = self.request(
+            Nl80211Cmd::CmdGetStation,
+            Some(NlmFFlags::new(&[NlmF::Request, NlmF::Dump])),
+            None,
+        )?;
+        responses.iter().map(parse_station).collect()
+    }
+}
+
+fn parse_station(msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>) -> Result<Station, NlError> {
+    let mut station = Station::default();
+    let handle = msg.get_attr_handle();
+    if let Some(attr) = handle.get_attribute(Nl80211Attr::AttrMac) {
+        station.mac = mac_addr(attr.payload().as_ref())?;
+    }
+    if let Some(attr) = handle.get_attribute(Nl80211Attr::AttrIfindex) {
+        station.ifindex = attr.get_payload_as::<u32>()?;
+    }
+    let info = match handle.get_attribute(Nl80211Attr::AttrStaInfo) {
+        Some(info) => info,
+        None => return Ok(station),
+    };
+
+    let mut rx_bytes32 = None;
+    let mut tx_bytes32 = None;
+    for attr in info.get_attr_handle::<Nl80211StaInfo>()?.iter() {
+        match &attr.nla_type {
+            Nl80211StaInfo::StaInfoInactiveTime => {
+                station.inactive_ms = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoConnectedTime => {
+                station.connected_secs = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoRxBytes => rx_bytes32 = Some(attr.get_payload_as::<u32>()?),
+            Nl80211StaInfo::StaInfoTxBytes => tx_bytes32 = Some(attr.get_payload_as::<u32>()?),
+            Nl80211StaInfo::StaInfoRxBytes64 => {
+                station.rx_bytes = Some(attr.get_payload_as::<u64>()?)
+            }
+            Nl80211StaInfo::StaInfoTxBytes64 => {
+                station.tx_bytes = Some(attr.get_payload_as::<u64>()?)
+            }
+            Nl80211StaInfo::StaInfoRxPackets => {
+                station.rx_packets = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoTxPackets => {
+                station.tx_packets = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoTxRetries => {
+                station.tx_retries = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoTxFailed => {
+                station.tx_failed = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoRxDropMisc => {
+                station.rx_drop_misc = Some(attr.get_payload_as::<u64>()?)
+            }
+            Nl80211StaInfo::StaInfoSignal => {
+                station.signal_dbm = Some(attr.get_payload_as::<u8>()? as i8)
+            }
+            Nl80211StaInfo::StaInfoSignalAvg => {
+                station.signal_avg_dbm = Some(attr.get_payload_as::<u8>()? as i8)
+            }
+            Nl80211StaInfo::StaInfoChainSignal => station.chain_signal_dbm = chain_signal(attr)?,
+            Nl80211StaInfo::StaInfoChainSignalAvg => {
+                station.chain_signal_avg_dbm = chain_signal(attr)?
+            }
+            Nl80211StaInfo::StaInfoBeaconSignalAvg => {
+                station.beacon_signal_avg_dbm = Some(attr.get_payload_as::<u8>()? as i8)
+            }
+            Nl80211StaInfo::StaInfoBeaconRx => {
+                station.beacon_rx = Some(attr.get_payload_as::<u64>()?)
+            }
+            Nl80211StaInfo::StaInfoBeaconLoss => {
+                station.beacon_loss = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoTxBitrate => station.tx_bitrate = Some(parse_rate(attr)?),
+            Nl80211StaInfo::StaInfoRxBitrate => station.rx_bitrate = Some(parse_rate(attr)?),
+            Nl80211StaInfo::StaInfoExpectedThroughput => {
+                station.expected_throughput_kbps = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211StaInfo::StaInfoBssParam => station.bss_param = Some(parse_bss_param(attr)?),
+            Nl80211StaInfo::StaInfoTidStats => {
+                for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+                    station.tid_stats.push(parse_tid_stats(attr)?);
+                }
+            }
+            _ => (),
+        }
+    }
+    // the 32 bit counters wrap, use them only without the 64 bit ones
+    if station.rx_bytes.is_none() {
+        station.rx_bytes = rx_bytes32.map(u64::from);
+    }
+    if station.tx_bytes.is_none() {
+        station.tx_bytes = tx_bytes32.map(u64::from);
+    }
+    Ok(station)
+}
+
+fn chain_signal<T: NlAttrType>(attr: &Nlattr<T, Buffer>) -> Result<Vec<i8>, NlError> {
+    let mut signal = Vec::new();
+    for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+        signal.push(attr.get_payload_as::<u8>()? as i8);
+    }
+    Ok(signal)
+}
+
+fn parse_rate<T: NlAttrType>(attr: &Nlattr<T, Buffer>) -> Result<RateInfo, NlError> {
+    let mut rate = RateInfo {
+        modulation: Modulation::Legacy,
+        width: Nl80211ChanWidth::ChanWidth20,
+        short_gi: false,
+        bitrate: None,
+    };
+    let mut bitrate16 = None;
+    let mut mcs = None;
+    let mut nss = 1;
+    let mut gi = 0;
+    let mut dcm = false;
+    let mut ru_alloc = None;
+    let mut vht = false;
+    let mut he = false;
+    for attr in attr.get_attr_handle::<Nl80211RateInfo>()?.iter() {
+        match &attr.nla_type {
+            Nl80211RateInfo::RateInfoBitrate => {
+                bitrate16 = Some(attr.get_payload_as::<u16>()? as u32)
+            }
+            Nl80211RateInfo::RateInfoBitrate32 => {
+                rate.bitrate = Some(attr.get_payload_as::<u32>()?)
+            }
+            Nl80211RateInfo::RateInfoMcs => mcs = Some(attr.get_payload_as::<u8>()?),
+            Nl80211RateInfo::RateInfoVhtMcs => {
+                mcs = Some(attr.get_payload_as::<u8>()?);
+                vht = true;
+            }
+            Nl80211RateInfo::RateInfoHeMcs => {
+                mcs = Some(attr.get_payload_as::<u8>()?);
+                he = true;
+            }
+            Nl80211RateInfo::RateInfoVhtNss | Nl80211RateInfo::RateInfoHeNss => {
+                nss = attr.get_payload_as::<u8>()?
+            }
+            Nl80211RateInfo::RateInfoHeGi => gi = attr.get_payload_as::<u8>()?,
+            Nl80211RateInfo::RateInfoHeDcm => dcm = attr.get_payload_as::<u8>()? != 0,
+            Nl80211RateInfo::RateInfoHeRuAlloc => ru_alloc = Some(attr.get_payload_as::<u8>()?),
+            Nl80211RateInfo::RateInfoShortGi => rate.short_gi = true,
+            Nl80211RateInfo::RateInfo5MhzWidth => rate.width = Nl80211ChanWidth::ChanWidth5,
+            Nl80211RateInfo::RateInfo10MhzWidth => rate.width = Nl80211ChanWidth::ChanWidth10,
+            Nl80211RateInfo::RateInfo40MhzWidth => rate.width = Nl80211ChanWidth::ChanWidth40,
+            Nl80211RateInfo::RateInfo80MhzWidth => rate.width = Nl80211ChanWidth::ChanWidth80,
+            Nl80211RateInfo::RateInfo80p80MhzWidth => rate.width = Nl80211ChanWidth::ChanWidth80p80,
+            Nl80211RateInfo::RateInfo160MhzWidth => rate.width = Nl80211ChanWidth::ChanWidth160,
+            _ => (),
+        }
+    }
+    // the 16 bit bitrate is left out once it overflows
+    if rate.bitrate.is_none() {
+        rate.bitrate = bitrate16;
+    }
+    if let Some(mcs) = mcs {
+        rate.modulation = if he {
+            Modulation::He {
+                mcs,
+                nss,
+                gi,
+                dcm,
+                ru_alloc,
+            }
+        } else if vht {
+            Modulation::Vht { mcs, nss }
+        } else {
+            Modulation::Ht { mcs }
+        };
+    }
+    Ok(rate)
+}
+
+fn parse_bss_param<T: NlAttrType>(attr: &Nlattr<T, Buffer>) -> Result<BssParam, NlError> {
+    let mut param = BssParam::default();
+    for attr in attr.get_attr_handle::<Nl80211StaBssParam>()?.iter() {
+        match &attr.nla_type {
+            Nl80211StaBssParam::StaBssParamCtsProt => param.cts_protection = true,
+            Nl80211StaBssParam::StaBssParamShortPreamble => param.short_preamble = true,
+            Nl80211StaBssParam::StaBssParamShortSlotTime => param.short_slot_time = true,
+            Nl80211StaBssParam::StaBssParamDtimPeriod => {
+                param.dtim_period = attr.get_payload_as::<u8>()?
+            }
+            Nl80211StaBssParam::StaBssParamBeaconInterval => {
+                param.beacon_interval = attr.get_payload_as::<u16>()?
+            }
+            _ => (),
+        }
+    }
+    Ok(param)
+}
+
+fn parse_tid_stats(attr: &Nlattr<NlaNested, Buffer>) -> Result<TidStats, NlError> {
+    // numbered from 1, the last one is for non-QoS traffic
+    let mut stats = TidStats {
+        tid: u16::from(attr.nla_type).saturating_sub(1),
+        ..Default::default()
+    };
+    for attr in attr.get_attr_handle::<Nl80211TidStats>()?.iter() {
+        match &attr.nla_type {
+            Nl80211TidStats::TidStatsRxMsdu => stats.rx_msdu = Some(attr.get_payload_as::<u64>()?),
+            Nl80211TidStats::TidStatsTxMsdu => stats.tx_msdu = Some(attr.get_payload_as::<u64>()?),
+            Nl80211TidStats::TidStatsTxMsduRetries => {
+                stats.tx_msdu_retries = Some(attr.get_payload_as::<u64>()?)
+            }
+            Nl80211TidStats::TidStatsTxMsduFailed => {
+                stats.tx_msdu_failed = Some(attr.get_payload_as::<u64>()?)
+            }
+            _ => (),
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::client;
+
+    fn rate(modulation: Modulation, width: Nl80211ChanWidth, short_gi: bool) -> RateInfo {
+        RateInfo {
+            modulation,
+            width,
+            short_gi,
+            bitrate: None,
+        }
+    }
+
+    #[test]
+    fn test_calculate_bitrate() {
+        use Nl80211ChanWidth::*;
+
+        let ht = |mcs, width, short_gi| rate(Modulation::Ht { mcs }, width, short_gi).mbps();
+        assert_eq!(ht(7, ChanWidth20, false), Some(65.0));
+        assert_eq!(ht(7, ChanWidth20, true), Some(72.2));
+        assert_eq!(ht(15, ChanWidth40, true), Some(300.0));
+        assert_eq!(ht(32, ChanWidth40, false), None);
+
+        let vht =
+            |mcs, nss, width, short_gi| rate(Modulation::Vht { mcs, nss }, width, short_gi).mbps();
+        assert_eq!(vht(9, 2, ChanWidth80, true), Some(866.7));
+        assert_eq!(vht(0, 1, ChanWidth20, false), Some(6.5));
+        assert_eq!(vht(9, 4, ChanWidth160, true), Some(3466.7));
+        assert_eq!(vht(12, 1, ChanWidth20, false), None);
+
+        let he = |mcs, nss, gi, width, ru_alloc| {
+            let he = Modulation::He {
+                mcs,
+                nss,
+                gi,
+                dcm: false,
+                ru_alloc,
+            };
+            rate(he, width, false).mbps()
+        };
+        assert_eq!(he(11, 2, 0, ChanWidth80, None), Some(1200.9));
+        assert_eq!(he(7, 1, 0, ChanWidth20, None), Some(86.0));
+        assert_eq!(he(11, 2, 0, ChanWidth160, None), Some(2401.9));
+        // rounded down like the kernel does
+        assert_eq!(he(0, 1, 2, ChanWidth20, Some(0)), Some(0.6));
+        assert_eq!(he(0, 0, 0, ChanWidth20, None), None);
+
+        // legacy rates are the ones of the kernel
+        let mut legacy = rate(Modulation::Legacy, ChanWidth20, false);
+        assert_eq!(legacy.mbps(), None);
+        legacy.bitrate = Some(540);
+        assert_eq!(legacy.mbps(), Some(54.0));
+    }
+
+    #[test]
+    fn test_station_dump() {
+        let mut client = client(&[include_bytes!("fixtures/station_dump.bin")]);
+        let stations = client.station_dump().unwrap();
+        assert_eq!(stations.len(), 2);
+
+        let ap = &stations[0];
+        assert_eq!(ap.mac, [0x02, 0, 0, 0, 0x02, 0]);
+        assert_eq!(ap.ifindex, 3);
+        assert_eq!(ap.inactive_ms, Some(40));
+        assert_eq!(ap.connected_secs, Some(3600));
+        assert_eq!(ap.rx_bytes, Some(5_000_000_000));
+        assert_eq!(ap.tx_bytes, Some(123_456));
+        assert_eq!(ap.tx_retries, Some(7));
+        assert_eq!(ap.signal_dbm, Some(-45));
+        assert_eq!(ap.chain_signal_dbm, vec![-47, -49]);
+        assert_eq!(ap.expected_throughput_kbps, Some(600_000));
+        let tx = ap.tx_bitrate.as_ref().unwrap();
+        assert_eq!(tx.width, Nl80211ChanWidth::ChanWidth80);
+        assert_eq!(tx.mbps(), Some(1200.9));
+        let rx = ap.rx_bitrate.as_ref().unwrap();
+        assert_eq!(rx.modulation, Modulation::Vht { mcs: 9, nss: 2 });
+        assert_eq!(rx.mbps(), Some(866.7));
+        let bss = ap.bss_param.as_ref().unwrap();
+        assert!(bss.short_slot_time && !bss.cts_protection);
+        assert_eq!((bss.dtim_period, bss.beacon_interval), (2, 100));
+        assert_eq!(ap.tid_stats.len(), 2);
+        assert_eq!(ap.tid_stats[1].tid, 16);
+        assert_eq!(ap.tid_stats[0].rx_msdu, Some(42));
+
+        let legacy = &stations[1];
+        assert_eq!(legacy.signal_avg_dbm, None);
+        assert_eq!(legacy.tx_bitrate.as_ref().unwrap().mbps(), Some(54.0));
+    }
+
+    #[test]
+    fn test_station_get() {
+        let mut failing = client(&[include_bytes!("fixtures/err_einval.bin")]);
+        assert!(failing.station_get(&[0; 6]).is_err());
+
+        let mut client = client(&[include_bytes!("fixtures/station_get.bin")]);
+        let station = client.station_get(&[0x02, 0, 0, 0, 0x02, 0]).unwrap();
+        assert_eq!(station.signal_dbm, Some(-45));
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdGetStation));
+        // NL80211_ATTR_MAC
+        assert_eq!(&sent[20..30], &[10, 0, 6, 0, 0x02, 0, 0, 0, 0x02, 0]);
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt;
+
+// References: IEEE Std 802.11-2020 9.4.1.7 and 9.4.1.9
+
+/// Status code of authentication and association responses
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StatusCode {
+    /// 0
+    Success,
+    /// 1
+    Unspecified,
+    /// 10
+    CapabilitiesUnsupported,
+    /// 11
+    ReassociationNoAssociation,
+    /// 12
+    AssociationDenied,
+    /// 13
+    AuthAlgorithmUnsupported,
+    /// 14
+    UnknownAuthTransaction,
+    /// 15
+    ChallengeFailure,
+    /// 16
+    AuthTimeout,
+    /// 17
+    ApFull,
+    /// 18
+    BasicRatesUnsupported,
+    /// 30
+    RejectedTemporarily,
+    /// 31
+    RobustManagementPolicyViolation,
+    /// 37
+    RequestDeclined,
+    /// 40
+    InvalidElement,
+    /// 41
+    InvalidGroupCipher,
+    /// 42
+    InvalidPairwiseCipher,
+    /// 43
+    InvalidAkmp,
+    /// 44
+    UnsupportedRsnVersion,
+    /// 45
+    InvalidRsnCapabilities,
+    /// 46
+    CipherRejectedByPolicy,
+    /// 53
+    InvalidPmkid,
+    /// 76
+    AntiCloggingTokenRequired,
+    /// 77
+    GroupUnsupported,
+    /// 123
+    UnknownPasswordIdentifier,
+    /// 126
+    SaeHashToElement,
+    Other(u16),
+}
+
+impl StatusCode {
+    pub fn is_success(self) -> bool {
+        self == StatusCode::Success
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(code: StatusCode) -> u16 {
+        match code {
+            StatusCode::Success => 0,
+            StatusCode::Unspecified => 1,
+            StatusCode::CapabilitiesUnsupported => 10,
+            StatusCode::ReassociationNoAssociation => 11,
+            StatusCode::AssociationDenied => 12,
+            StatusCode::AuthAlgorithmUnsupported => 13,
+            StatusCode::UnknownAuthTransaction => 14,
+            StatusCode::ChallengeFailure => 15,
+            StatusCode::AuthTimeout => 16,
+            StatusCode::ApFull => 17,
+            StatusCode::BasicRatesUnsupported => 18,
+            StatusCode::RejectedTemporarily => 30,
+            StatusCode::RobustManagementPolicyViolation => 31,
+            StatusCode::RequestDeclined => 37,
+            StatusCode::InvalidElement => 40,
+            StatusCode::InvalidGroupCipher => 41,
+            StatusCode::InvalidPairwiseCipher => 42,
+            StatusCode::InvalidAkmp => 43,
+            StatusCode::UnsupportedRsnVersion => 44,
+            StatusCode::InvalidRsnCapabilities => 45,
+            StatusCode::CipherRejectedByPolicy => 46,
+            StatusCode::InvalidPmkid => 53,
+            StatusCode::AntiCloggingTokenRequired => 76,
+            StatusCode::GroupUnsupported => 77,
+            StatusCode::UnknownPasswordIdentifier => 123,
+            StatusCode::SaeHashToElement => 126,
+            StatusCode::Other(code) => code,
+        }
+    }
+}
+
+impl From<u16> for StatusCode {
+    fn from(raw: u16) -> Self {
+        match raw {
+            0 => StatusCode::Success,
+            1 => StatusCode::Unspecified,
+            10 => StatusCode::CapabilitiesUnsupported,
+            11 => StatusCode::ReassociationNoAssociation,
+            12 => StatusCode::AssociationDenied,
+            13 => StatusCode::AuthAlgorithmUnsupported,
+            14 => StatusCode::UnknownAuthTransaction,
+            15 => StatusCode::ChallengeFailure,
+            16 => StatusCode::AuthTimeout,
+            17 => StatusCode::ApFull,
+            18 => StatusCode::BasicRatesUnsupported,
+            30 => StatusCode::RejectedTemporarily,
+            31 => StatusCode::RobustManagementPolicyViolation,
+            37 => StatusCode::RequestDeclined,
+            40 => StatusCode::InvalidElement,
+            41 => StatusCode::InvalidGroupCipher,
+            42 => StatusCode::InvalidPairwiseCipher,
+            43 => StatusCode::InvalidAkmp,
+            44 => StatusCode::UnsupportedRsnVersion,
+            45 => StatusCode::InvalidRsnCapabilities,
+            46 => StatusCode::CipherRejectedByPolicy,
+            53 => StatusCode::InvalidPmkid,
+            76 => StatusCode::AntiCloggingTokenRequired,
+            77 => StatusCode::GroupUnsupported,
+            123 => StatusCode::UnknownPasswordIdentifier,
+            126 => StatusCode::SaeHashToElement,
+            code => StatusCode::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StatusCode::Success => "success",
+            StatusCode::Unspecified => "unspecified failure",
+            StatusCode::CapabilitiesUnsupported => "requested capabilities not supported",
+            StatusCode::ReassociationNoAssociation => "reassociation without association",
+            StatusCode::AssociationDenied => "association denied",
+            StatusCode::AuthAlgorithmUnsupported => "authentication algorithm not supported",
+            StatusCode::UnknownAuthTransaction => "unexpected authentication sequence number",
+            StatusCode::ChallengeFailure => "challenge failure",
+            StatusCode::AuthTimeout => "authentication timed out",
+            StatusCode::ApFull => "AP unable to handle more stations",
+            StatusCode::BasicRatesUnsupported => "basic rates not supported",
+            StatusCode::RejectedTemporarily => "association rejected temporarily, try again later",
+            StatusCode::RobustManagementPolicyViolation => {
+                "robust management frame policy violation"
+            }
+            StatusCode::RequestDeclined => "request declined",
+            StatusCode::InvalidElement => "invalid element",
+            StatusCode::InvalidGroupCipher => "invalid group cipher",
+            StatusCode::InvalidPairwiseCipher => "invalid pairwise cipher",
+            StatusCode::InvalidAkmp => "invalid AKMP",
+            StatusCode::UnsupportedRsnVersion => "unsupported RSNE version",
+            StatusCode::InvalidRsnCapabilities => "invalid RSNE capabilities",
+            StatusCode::CipherRejectedByPolicy => "cipher suite rejected by policy",
+            StatusCode::InvalidPmkid => "invalid PMKID",
+            StatusCode::AntiCloggingTokenRequired => "anti-clogging token required",
+            StatusCode::GroupUnsupported => "finite cyclic group not supported",
+            StatusCode::UnknownPasswordIdentifier => "unknown password identifier",
+            StatusCode::SaeHashToElement => "SAE hash-to-element",
+            StatusCode::Other(code) => return write!(f, "status code {}", code),
+        })
+    }
+}
+
+/// Reason code of deauthentication and disassociation frames
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ReasonCode {
+    /// 1
+    Unspecified,
+    /// 2
+    PreviousAuthNotValid,
+    /// 3
+    DeauthLeaving,
+    /// 4
+    Inactivity,
+    /// 5
+    ApBusy,
+    /// 6
+    Class2FrameFromUnauthenticated,
+    /// 7
+    Class3FrameFromUnassociated,
+    /// 8
+    DisassocLeaving,
+    /// 9
+    NotAuthenticated,
+    /// 10
+    PowerCapabilityNotValid,
+    /// 11
+    SupportedChannelsNotValid,
+    /// 13
+    InvalidElement,
+    /// 14
+    MicFailure,
+    /// 15
+    FourWayHandshakeTimeout,
+    /// 16
+    GroupKeyHandshakeTimeout,
+    /// 17
+    ElementMismatch,
+    /// 18
+    InvalidGroupCipher,
+    /// 19
+    InvalidPairwiseCipher,
+    /// 20
+    InvalidAkmp,
+    /// 21
+    UnsupportedRsnVersion,
+    /// 22
+    InvalidRsnCapabilities,
+    /// 23
+    Ieee8021xAuthFailed,
+    /// 24
+    CipherRejectedByPolicy,
+    /// 34
+    LowAck,
+    Other(u16),
+}
+
+impl From<ReasonCode> for u16 {
+    fn from(code: ReasonCode) -> u16 {
+        match code {
+            ReasonCode::Unspecified => 1,
+            ReasonCode::PreviousAuthNotValid => 2,
+            ReasonCode::DeauthLeaving => 3,
+            ReasonCode::Inactivity => 4,
+            ReasonCode::ApBusy => 5,
+            ReasonCode::Class2FrameFromUnauthenticated => 6,
+            ReasonCode::Class3FrameFromUnassociated => 7,
+            ReasonCode::DisassocLeaving => 8,
+            ReasonCode::NotAuthenticated => 9,
+            ReasonCode::PowerCapabilityNotValid => 10,
+            ReasonCode::SupportedChannelsNotValid => 11,
+            ReasonCode::InvalidElement => 13,
+            ReasonCode::MicFailure => 14,
+            ReasonCode::FourWayHandshakeTimeout => 15,
+            ReasonCode::GroupKeyHandshakeTimeout => 16,
+            ReasonCode::ElementMismatch => 17,
+            ReasonCode::InvalidGroupCipher => 18,
+            ReasonCode::InvalidPairwiseCipher => 19,
+            ReasonCode::InvalidAkmp => 20,
+            ReasonCode::UnsupportedRsnVersion => 21,
+            ReasonCode::InvalidRsnCapabilities => 22,
+            ReasonCode::Ieee8021xAuthFailed => 23,
+            ReasonCode::CipherRejectedByPolicy => 24,
+            ReasonCode::LowAck => 34,
+            ReasonCode::Other(code) => code,
+        }
+    }
+}
+
+impl From<u16> for ReasonCode {
+    fn from(raw: u16) -> Self {
+        match raw {
+            1 => ReasonCode::Unspecified,
+            2 => ReasonCode::PreviousAuthNotValid,
+            3 => ReasonCode::DeauthLeaving,
+            4 => ReasonCode::Inactivity,
+            5 => ReasonCode::ApBusy,
+            6 => ReasonCode::Class2FrameFromUnauthenticated,
+            7 => ReasonCode::Class3FrameFromUnassociated,
+            8 => ReasonCode::DisassocLeaving,
+            9 => ReasonCode::NotAuthenticated,
+            10 => ReasonCode::PowerCapabilityNotValid,
+            11 => ReasonCode::SupportedChannelsNotValid,
+            13 => ReasonCode::InvalidElement,
+            14 => ReasonCode::MicFailure,
+            15 => ReasonCode::FourWayHandshakeTimeout,
+            16 => ReasonCode::GroupKeyHandshakeTimeout,
+            17 => ReasonCode::ElementMismatch,
+            18 => ReasonCode::InvalidGroupCipher,
+            19 => ReasonCode::InvalidPairwiseCipher,
+            20 => ReasonCode::InvalidAkmp,
+            21 => ReasonCode::UnsupportedRsnVersion,
+            22 => ReasonCode::InvalidRsnCapabilities,
+            23 => ReasonCode::Ieee8021xAuthFailed,
+            24 => ReasonCode::CipherRejectedByPolicy,
+            34 => ReasonCode::LowAck,
+            code => ReasonCode::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for ReasonCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReasonCode::Unspecified => "unspecified reason",
+            ReasonCode::PreviousAuthNotValid => "previous authentication no longer valid",
+            ReasonCode::DeauthLeaving => "deauthenticated because the station is leaving",
+            ReasonCode::Inactivity => "disassociated due to inactivity",
+            ReasonCode::ApBusy => "disassociated because the AP is unable to handle all stations",
+            ReasonCode::Class2FrameFromUnauthenticated => {
+                "class 2 frame received from nonauthenticated station"
+            }
+            ReasonCode::Class3FrameFromUnassociated => {
+                "class 3 frame received from nonassociated station"
+            }
+            ReasonCode::DisassocLeaving => "disassociated because the station is leaving",
+            ReasonCode::NotAuthenticated => "association requested without authentication",
+            ReasonCode::PowerCapabilityNotValid => "power capability not acceptable",
+            ReasonCode::SupportedChannelsNotValid => "supported channels not acceptable",
+            ReasonCode::InvalidElement => "invalid element",
+            ReasonCode::MicFailure => "message integrity code failure",
+            ReasonCode::FourWayHandshakeTimeout => "4-way handshake timeout",
+            ReasonCode::GroupKeyHandshakeTimeout => "group key handshake timeout",
+            ReasonCode::ElementMismatch => {
+                "element in 4-way handshake different from (re)association"
+            }
+            ReasonCode::InvalidGroupCipher => "invalid group cipher",
+            ReasonCode::InvalidPairwiseCipher => "invalid pairwise cipher",
+            ReasonCode::InvalidAkmp => "invalid AKMP",
+            ReasonCode::UnsupportedRsnVersion => "unsupported RSNE version",
+            ReasonCode::InvalidRsnCapabilities => "invalid RSNE capabilities",
+            ReasonCode::Ieee8021xAuthFailed => "IEEE 802.1X authentication failed",
+            ReasonCode::CipherRejectedByPolicy => "cipher suite rejected by policy",
+            ReasonCode::LowAck => "disassociated because of too many lost frames",
+            ReasonCode::Other(code) => return write!(f, "reason code {}", code),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        for raw in 0..300 {
+            let status: u16 = StatusCode::from(raw).into();
+            assert_eq!(status, raw);
+            let reason: u16 = ReasonCode::from(raw).into();
+            assert_eq!(reason, raw);
+        }
+        assert!(StatusCode::from(0).is_success());
+        assert_eq!(
+            StatusCode::from(17).to_string(),
+            "AP unable to handle more stations"
+        );
+        assert_eq!(StatusCode::from(300).to_string(), "status code 300");
+        assert_eq!(ReasonCode::from(15).to_string(), "4-way handshake timeout");
+    }
+}
@@ -0,0 +1,1088 @@
+use super::{
+    attr::*,
+    client::NL80211Client,
+    cmd::Nl80211Cmd,
+    crypto::{aes_cmac, hmac_sha1_128, hmac_sha256, kdf_sha256, key_unwrap, prf_sha1, Pmksa},
+    eapol::*,
+    ie::{AkmSuite, CipherSuite, Element, Rsn, OUI_IEEE80211},
+    sae::Sae,
+    status::StatusCode,
+    transport::Transport,
+    utils::mac_addr,
+};
+use neli::attr::Attribute;
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::err::NlError;
+use neli::genl::{Genlmsghdr, Nlattr};
+use neli::types::GenlBuffer;
+use std::time::{Duration, Instant};
+
+// References: IEEE Std 802.11-2020, 12.7.6 4-way handshake
+//             IEEE Std 802.11-2020, 12.7.7 Group key handshake
+
+/// Authentication algorithm number of SAE
+const AUTH_ALGORITHM_SAE: u16 = 3;
+
+/// Length of the header of management frames
+const MGMT_HEADER_LEN: usize = 24;
+
+/// How long the 4-way handshake may take, the AP retransmitting its
+/// messages for a few seconds before giving up
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the SAE authentication may take, covering an anti-clogging
+/// token round trip
+const SAE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Keys derived from the PMK by the 4-way handshake
+struct Ptk {
+    kck: [u8; 16],
+    kek: [u8; 16],
+    tk: Vec<u8>,
+}
+
+/// A key to install with `new_key`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub cipher: CipherSuite,
+    pub index: u8,
+    pub data: Vec<u8>,
+    /// receive sequence counter to start from, least significant octet first
+    pub seq: Vec<u8>,
+    /// the AP of a pairwise key, none for group keys
+    pub peer: Option<[u8; 6]>,
+}
+
+/// What to do after an EAPOL-Key frame of the authenticator
+#[derive(Debug, Default, PartialEq)]
+pub struct Step {
+    /// EAPOL-Key frame to send back
+    pub reply: Option<Vec<u8>>,
+    /// keys to install after sending the reply
+    pub keys: Vec<Key>,
+    /// the 4-way handshake completed, the port is to be authorized
+    pub authorize: bool,
+}
+
+/// Supplicant of the 4-way and group key handshakes of one association
+/// with an RSN AP
+pub struct Supplicant {
+    pmk: [u8; 32],
+    akm: AkmSuite,
+    pairwise: CipherSuite,
+    group: CipherSuite,
+    group_mgmt: CipherSuite,
+    /// address of the AP, the authenticator
+    aa: [u8; 6],
+    /// address of the station, the supplicant
+    spa: [u8; 6],
+    /// RSN element of the association request, repeated in message 2
+    rsne: Vec<u8>,
+    /// RSN element of the AP's beacons, compared with the one of message 3
+    ap_rsn: Option<Rsn>,
+    snonce: [u8; 32],
+    anonce: Option<[u8; 32]>,
+    /// PTK of the 4-way handshake in progress
+    tptk: Option<Ptk>,
+    ptk: Option<Ptk>,
+    /// replay counter of the last frame with a valid MIC
+    replay_counter: Option<u64>,
+}
+
+impl Supplicant {
+    /// `rsn` is the RSN element of the association request, its first AKM
+    /// and pairwise cipher are the negotiated ones
+    pub fn new(pmk: [u8; 32], rsn: &Rsn, aa: [u8; 6], spa: [u8; 6]) -> Result<Supplicant, String> {
+        let akm = match rsn.akms().first() {
+            Some(akm @ AkmSuite::Psk)
+            | Some(akm @ AkmSuite::PskSha256)
+            | Some(akm @ AkmSuite::Sae)
+            | Some(akm @ AkmSuite::Owe) => *akm,
+            akm => return Err(format!("unsupported AKM {:?}", akm)),
+        };
+        let pairwise = match rsn.pairwise().first() {
+            Some(cipher @ CipherSuite::Ccmp128)
+            | Some(cipher @ CipherSuite::Gcmp128)
+            | Some(cipher @ CipherSuite::Ccmp256)
+            | Some(cipher @ CipherSuite::Gcmp256) => *cipher,
+            cipher => return Err(format!("unsupported pairwise cipher {:?}", cipher)),
+        };
+        let mut rsne = Vec::new();
+        Element::Rsn(rsn.clone()).write(&mut rsne);
+        Ok(Supplicant {
+            pmk,
+            akm,
+            pairwise,
+            group: rsn.group_cipher.unwrap_or(CipherSuite::Ccmp128),
+            group_mgmt: rsn.group_mgmt_cipher.unwrap_or(CipherSuite::BipCmac128),
+            aa,
+            spa,
+            rsne,
+            ap_rsn: None,
+            snonce: rand::random(),
+            anonce: None,
+            tptk: None,
+            ptk: None,
+            replay_counter: None,
+        })
+    }
+
+    /// Check the RSN element of message 3 against `rsn` of the AP's beacons,
+    /// detecting a downgrade of the association
+    pub fn ap_rsn(&mut self, rsn: Rsn) -> &mut Self {
+        self.ap_rsn = Some(rsn);
+        self
+    }
+
+    /// whether the 4-way handshake completed
+    pub fn established(&self) -> bool {
+        self.ptk.is_some()
+    }
+
+    /// Process an EAPOL-Key frame of the authenticator, frames failing the
+    /// checks are to be dropped
+    pub fn process(&mut self, frame: &[u8]) -> Result<Step, String> {
+        let key = EapolKey::parse(frame)?;
+        if !key.has(KEY_INFO_ACK) || key.has(KEY_INFO_REQUEST) {
+            return Err("EAPOL-Key frame not sent by the authenticator".to_owned());
+        }
+        if key.descriptor_version() != self.descriptor_version() {
+            return Err(format!(
+                "unexpected key descriptor version {}",
+                key.descriptor_version()
+            ));
+        }
+        if let Some(last) = self.replay_counter {
+            if key.replay_counter <= last {
+                return Err("replayed EAPOL-Key frame".to_owned());
+            }
+        }
+        match (key.has(KEY_INFO_PAIRWISE), key.has(KEY_INFO_MIC)) {
+            (true, false) => Ok(self.message_1(&key)),
+            (true, true) => self.message_3(frame, &key),
+            (false, true) => self.group_message_1(frame, &key),
+            (false, false) => Err("group EAPOL-Key frame without MIC".to_owned()),
+        }
+    }
+
+    fn message_1(&mut self, key: &EapolKey) -> Step {
+        let ptk = self.derive_ptk(&key.nonce);
+        let mut reply = EapolKey::reply(key, KEY_INFO_PAIRWISE | KEY_INFO_MIC);
+        reply.nonce = self.snonce;
+        reply.data = self.rsne.clone();
+        let reply = self.sign(&ptk.kck, reply);
+        // a retransmitted message 1 restarts the handshake with its ANonce
+        self.anonce = Some(key.nonce);
+        self.tptk = Some(ptk);
+        Step {
+            reply: Some(reply),
+            ..Default::default()
+        }
+    }
+
+    fn message_3(&mut self, frame: &[u8], key: &EapolKey) -> Result<Step, String> {
+        if let (None, Some(ptk)) = (&self.tptk, &self.ptk) {
+            let step = self.retransmitted_message_3(ptk, frame, key)?;
+            self.replay_counter = Some(key.replay_counter);
+            return Ok(step);
+        }
+        let ptk = self
+            .tptk
+            .as_ref()
+            .ok_or("message 3 of the 4-way handshake without message 1")?;
+        if self.anonce != Some(key.nonce) {
+            return Err("ANonce of message 3 differs from message 1".to_owned());
+        }
+        self.verify(&ptk.kck, frame, key)?;
+        if !key.has(KEY_INFO_INSTALL | KEY_INFO_SECURE | KEY_INFO_ENCRYPTED) {
+            return Err(
+                "message 3 of the 4-way handshake without install, secure or encrypted key data"
+                    .to_owned(),
+            );
+        }
+        let (elements, kdes) = parse_key_data(&key_unwrap(&ptk.kek, &key.data)?)?;
+        if let Some(ap_rsn) = &self.ap_rsn {
+            let rsn = elements.iter().find_map(|element| match element {
+                Element::Rsn(rsn) => Some(rsn),
+                _ => None,
+            });
+            if rsn != Some(ap_rsn) {
+                return Err("RSN element of message 3 differs from the beacon".to_owned());
+            }
+        }
+
+        let mut keys = vec![Key {
+            cipher: self.pairwise,
+            index: 0,
+            data: ptk.tk.clone(),
+            seq: Vec::new(),
+            peer: Some(self.aa),
+        }];
+        keys.extend(self.group_keys(key, &kdes)?);
+        let reply = EapolKey::reply(key, KEY_INFO_PAIRWISE | KEY_INFO_MIC | KEY_INFO_SECURE);
+        let reply = self.sign(&ptk.kck, reply);
+
+        self.replay_counter = Some(key.replay_counter);
+        self.ptk = self.tptk.take();
+        Ok(Step {
+            reply: Some(reply),
+            keys,
+            authorize: true,
+        })
+    }
+
+    /// message 3 sent again as message 4 got lost: message 4 is sent again,
+    /// the installed keys are not installed twice
+    fn retransmitted_message_3(
+        &self,
+        ptk: &Ptk,
+        frame: &[u8],
+        key: &EapolKey,
+    ) -> Result<Step, String> {
+        if self.anonce != Some(key.nonce) {
+            return Err("ANonce of message 3 differs from message 1".to_owned());
+        }
+        self.verify(&ptk.kck, frame, key)?;
+        let reply = EapolKey::reply(key, KEY_INFO_PAIRWISE | KEY_INFO_MIC | KEY_INFO_SECURE);
+        Ok(Step {
+            reply: Some(self.sign(&ptk.kck, reply)),
+            ..Default::default()
+        })
+    }
+
+    fn group_message_1(&mut self, frame: &[u8], key: &EapolKey) -> Result<Step, String> {
+        let ptk = self
+            .ptk
+            .as_ref()
+            .ok_or("group key handshake before the 4-way handshake")?;
+        self.verify(&ptk.kck, frame, key)?;
+        if !key.has(KEY_INFO_SECURE | KEY_INFO_ENCRYPTED) {
+            return Err(
+                "message 1 of the group key handshake without secure or encrypted key data"
+                    .to_owned(),
+            );
+        }
+        let (_, kdes) = parse_key_data(&key_unwrap(&ptk.kek, &key.data)?)?;
+        let keys = self.group_keys(key, &kdes)?;
+        let reply = self.sign(
+            &ptk.kck,
+            EapolKey::reply(key, KEY_INFO_MIC | KEY_INFO_SECURE),
+        );
+
+        self.replay_counter = Some(key.replay_counter);
+        Ok(Step {
+            reply: Some(reply),
+            keys,
+            authorize: false,
+        })
+    }
+
+    /// the GTK and the IGTK, if any, of the key data
+    fn group_keys(&self, key: &EapolKey, kdes: &[Kde]) -> Result<Vec<Key>, String> {
+        let mut keys = Vec::new();
+        let mut gtk_found = false;
+        for kde in kdes {
+            match kde {
+                Kde::Gtk {
+                    index, key: gtk, ..
+                } => {
+                    if Some(gtk.len()) != self.group.key_len() {
+                        return Err("invalid length of the GTK".to_owned());
+                    }
+                    gtk_found = true;
+                    keys.push(Key {
+                        cipher: self.group,
+                        index: *index,
+                        data: gtk.clone(),
+                        seq: key.rsc[..6].to_vec(),
+                        peer: None,
+                    });
+                }
+                Kde::Igtk {
+                    index,
+                    ipn,
+                    key: igtk,
+                } => {
+                    if Some(igtk.len()) != self.group_mgmt.key_len() || !(4..=5).contains(index) {
+                        return Err("invalid IGTK".to_owned());
+                    }
+                    keys.push(Key {
+                        cipher: self.group_mgmt,
+                        index: *index as u8,
+                        data: igtk.clone(),
+                        seq: ipn.to_vec(),
+                        peer: None,
+                    });
+                }
+                _ => {}
+            }
+        }
+        if !gtk_found {
+            return Err("no GTK in the key data".to_owned());
+        }
+        Ok(keys)
+    }
+
+    fn descriptor_version(&self) -> u16 {
+        match self.akm {
+            AkmSuite::Psk => 2,
+            AkmSuite::PskSha256 => 3,
+            // defined by the AKM
+            _ => 0,
+        }
+    }
+
+    fn derive_ptk(&self, anonce: &[u8; 32]) -> Ptk {
+        let mut data = self.aa.min(self.spa).to_vec();
+        data.extend_from_slice(&self.aa.max(self.spa));
+        data.extend_from_slice(anonce.min(&self.snonce));
+        data.extend_from_slice(anonce.max(&self.snonce));
+        let tk_len = self.pairwise.key_len().unwrap_or(16);
+        let label = b"Pairwise key expansion";
+        let ptk = match self.akm {
+            AkmSuite::Psk => prf_sha1(&self.pmk, label, &data, 32 + tk_len),
+            _ => kdf_sha256(&self.pmk, label, &data, ((32 + tk_len) * 8) as u16),
+        };
+
+        let mut kck = [0; 16];
+        kck.copy_from_slice(&ptk[..16]);
+        let mut kek = [0; 16];
+        kek.copy_from_slice(&ptk[16..32]);
+        Ptk {
+            kck,
+            kek,
+            tk: ptk[32..].to_vec(),
+        }
+    }
+
+    fn mic(&self, kck: &[u8; 16], data: &[u8]) -> [u8; 16] {
+        match self.akm {
+            AkmSuite::Psk => hmac_sha1_128(kck, data),
+            AkmSuite::Owe => {
+                let mut mic = [0; 16];
+                mic.copy_from_slice(&hmac_sha256(kck, &[data])[..16]);
+                mic
+            }
+            _ => aes_cmac(kck, data),
+        }
+    }
+
+    fn sign(&self, kck: &[u8; 16], mut reply: EapolKey) -> Vec<u8> {
+        reply.mic = self.mic(kck, &reply.write());
+        reply.write()
+    }
+
+    fn verify(&self, kck: &[u8; 16], frame: &[u8], key: &EapolKey) -> Result<(), String> {
+        if self.mic(kck, &without_mic(frame)) != key.mic {
+            return Err("invalid MIC of EAPOL-Key frame".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport> NL80211Client<T> {
+    /// Send an EAPOL frame to `dest` over the control port of the selected
+    /// netdev, unencrypted with `no_encrypt`
+    pub fn control_port_send(
+        &mut self,
+        dest: &[u8; 6],
+        frame: &[u8],
+        no_encrypt: bool,
+    ) -> Result<(), NlError> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrFrame,
+            frame.to_vec(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrMac,
+            dest.to_vec(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrControlPortEthertype,
+            ETH_P_PAE,
+        )?);
+        if no_encrypt {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrControlPortNoEncrypt,
+                Vec::<u8>::new(),
+            )?);
+        }
+        // without an ACK, a frame that failed to go out shows as the
+        // missing reply of the AP
+        self.send(
+            Nl80211Cmd::CmdControlPortFrame,
+            Some(NlmFFlags::new(&[NlmF::Request])),
+            Some(attrs),
+        )?;
+        Ok(())
+    }
+
+    /// Install `key` on the selected netdev
+    pub fn new_key(&mut self, key: &Key) -> Result<(), NlError> {
+        let mut attrs = GenlBuffer::new();
+        let mut nested = Nlattr::new(None, true, false, Nl80211Attr::AttrKey, Vec::<u8>::new())?;
+        nested.add_nested_attribute(&Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211KeyAttributes::KeyData,
+            key.data.clone(),
+        )?)?;
+        nested.add_nested_attribute(&Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211KeyAttributes::KeyCipher,
+            key.cipher.selector(OUI_IEEE80211),
+        )?)?;
+        if !key.seq.is_empty() {
+            nested.add_nested_attribute(&Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211KeyAttributes::KeySeq,
+                key.seq.clone(),
+            )?)?;
+        }
+        nested.add_nested_attribute(&Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211KeyAttributes::KeyIdx,
+            key.index,
+        )?)?;
+        let key_type = match key.peer {
+            Some(_) => Nl80211KeyType::KeytypePairwise,
+            None => Nl80211KeyType::KeytypeGroup,
+        };
+        nested.add_nested_attribute(&Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211KeyAttributes::KeyType,
+            u16::from(key_type) as u32,
+        )?)?;
+        attrs.push(nested);
+        if let Some(peer) = key.peer {
+            attrs.push(Nlattr::new(
+                None,
+                false,
+                false,
+                Nl80211Attr::AttrMac,
+                peer.to_vec(),
+            )?);
+        }
+        self.request(Nl80211Cmd::CmdNewKey, None, Some(attrs))?;
+        Ok(())
+    }
+
+    /// Authorize the port of the AP `peer`, letting frames other than EAPOL pass
+    pub fn authorize(&mut self, peer: &[u8; 6]) -> Result<(), NlError> {
+        let authorized = 1u32 << u16::from(Nl80211StaFlags::StaFlagAuthorized);
+        // struct nl80211_sta_flag_update, the mask and the flags set
+        let mut update = authorized.to_ne_bytes().to_vec();
+        update.extend_from_slice(&authorized.to_ne_bytes());
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrMac,
+            peer.to_vec(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrStaFlags2,
+            update,
+        )?);
+        self.request(Nl80211Cmd::CmdSetStation, None, Some(attrs))?;
+        Ok(())
+    }
+
+    /// Run the 4-way handshake of `supplicant` on the selected netdev after
+    /// connecting with `ConnectParams::control_port`.
+    ///
+    /// It returns once the keys are installed and the port authorized, and
+    /// fails after `HANDSHAKE_TIMEOUT` or when the connection does, as
+    /// reported on the mlme multicast group joined by `connect`.
+    pub fn handshake(&mut self, supplicant: &mut Supplicant) -> Result<(), NlError> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while !supplicant.established() {
+            let msgs = self.recv_events(Some(deadline))?;
+            if !self.answer_eapol(supplicant, msgs)? {
+                return Err(NlError::new("disconnected during the handshake"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep answering the AP after `handshake`: message 3 retransmitted as
+    /// message 4 got lost, group key handshakes and PTK rekeying.
+    ///
+    /// It returns once disconnected.
+    pub fn maintain(&mut self, supplicant: &mut Supplicant) -> Result<(), NlError> {
+        loop {
+            let msgs = self.recv_events(None)?;
+            if !self.answer_eapol(supplicant, msgs)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// answer the EAPOL-Key frames of the AP among `msgs`, installing the
+    /// keys of the handshakes they complete, false once disconnected
+    fn answer_eapol(
+        &mut self,
+        supplicant: &mut Supplicant,
+        msgs: Vec<Genlmsghdr<Nl80211Cmd, Nl80211Attr>>,
+    ) -> Result<bool, NlError> {
+        for msg in msgs {
+            if !self.targets(&msg) {
+                continue;
+            }
+            if !connected(&msg)? {
+                return Ok(false);
+            }
+            let frame = match control_port_frame(&msg, &supplicant.aa)? {
+                Some(frame) => frame,
+                None => continue,
+            };
+            let no_encrypt = !supplicant.established();
+            let step = match supplicant.process(&frame) {
+                Ok(step) => step,
+                // e.g. a forged or replayed frame
+                Err(_) => continue,
+            };
+
+            let aa = supplicant.aa;
+            if let Some(reply) = step.reply {
+                self.control_port_send(&aa, &reply, no_encrypt)?;
+            }
+            for key in step.keys.iter() {
+                self.new_key(key)?;
+            }
+            if step.authorize {
+                self.authorize(&aa)?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Authenticate the selected netdev with `bssid` on `freq` by SAE,
+    /// returning the PMK to `associate` with and run the 4-way handshake.
+    ///
+    /// The authentication frames of the AP are received from the mlme
+    /// multicast group.
+    pub fn authenticate_sae(
+        &mut self,
+        bssid: &[u8; 6],
+        freq: u32,
+        ssid: &[u8],
+        sae: &mut Sae,
+    ) -> Result<Pmksa, NlError> {
+        self.subscribe("mlme")?;
+        let result = self.sae_exchange(bssid, freq, ssid, sae);
+        let left = self.unsubscribe("mlme");
+        result.and_then(|pmksa| left.map(|_| pmksa))
+    }
+
+    fn sae_exchange(
+        &mut self,
+        bssid: &[u8; 6],
+        freq: u32,
+        ssid: &[u8],
+        sae: &mut Sae,
+    ) -> Result<Pmksa, NlError> {
+        let deadline = Instant::now() + SAE_TIMEOUT;
+        self.sae_authenticate(bssid, freq, ssid, 1, &sae.commit())?;
+        loop {
+            for msg in self.recv_events(Some(deadline))? {
+                if !self.targets(&msg) || msg.cmd != Nl80211Cmd::CmdAuthenticate {
+                    continue;
+                }
+                if msg
+                    .get_attr_handle()
+                    .get_attribute(Nl80211Attr::AttrTimedOut)
+                    .is_some()
+                {
+                    return Err(NlError::new("SAE authentication timed out"));
+                }
+                let (transaction, status, body) = match sae_frame(&msg, bssid)? {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+
+                match (transaction, status) {
+                    (1, StatusCode::Success) => {
+                        sae.process_commit(&body).map_err(NlError::new)?;
+                        let confirm = sae.confirm().map_err(NlError::new)?;
+                        self.sae_authenticate(bssid, freq, ssid, 2, &confirm)?;
+                    }
+                    (1, StatusCode::AntiCloggingTokenRequired) => {
+                        sae.anti_clogging(&body).map_err(NlError::new)?;
+                        self.sae_authenticate(bssid, freq, ssid, 1, &sae.commit())?;
+                    }
+                    (2, StatusCode::Success) => {
+                        return sae.process_confirm(&body).map_err(NlError::new);
+                    }
+                    (_, status) => {
+                        return Err(NlError::new(format!(
+                            "SAE authentication failed: {}",
+                            status
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    /// send the SAE message `transaction` of the station
+    fn sae_authenticate(
+        &mut self,
+        bssid: &[u8; 6],
+        freq: u32,
+        ssid: &[u8],
+        transaction: u16,
+        fields: &[u8],
+    ) -> Result<(), NlError> {
+        // starting with the transaction sequence number and status code
+        let mut data = transaction.to_le_bytes().to_vec();
+        data.extend_from_slice(&u16::from(StatusCode::Success).to_le_bytes());
+        data.extend_from_slice(fields);
+
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrMac,
+            bssid.to_vec(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrWiphyFreq,
+            freq,
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrSsid,
+            ssid.to_vec(),
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrAuthType,
+            u16::from(Nl80211AuthType::AuthtypeSae) as u32,
+        )?);
+        attrs.push(Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrSaeData,
+            data,
+        )?);
+        self.request(Nl80211Cmd::CmdAuthenticate, None, Some(attrs))?;
+        Ok(())
+    }
+}
+
+/// whether the association holds after `msg`, failing on a failed connect
+fn connected(msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>) -> Result<bool, NlError> {
+    let handle = msg.get_attr_handle();
+    match msg.cmd {
+        Nl80211Cmd::CmdConnect => {
+            let status = match handle.get_attribute(Nl80211Attr::AttrStatusCode) {
+                Some(attr) => StatusCode::from(attr.get_payload_as::<u16>()?),
+                None => StatusCode::Unspecified,
+            };
+            if !status.is_success() {
+                return Err(NlError::new(format!("connect failed: {}", status)));
+            }
+        }
+        Nl80211Cmd::CmdAssociate if handle.get_attribute(Nl80211Attr::AttrTimedOut).is_some() => {
+            return Err(NlError::new("association timed out"));
+        }
+        Nl80211Cmd::CmdDisconnect | Nl80211Cmd::CmdDeauthenticate => return Ok(false),
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// the EAPOL frame of `aa` in a control port message
+fn control_port_frame(
+    msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>,
+    aa: &[u8; 6],
+) -> Result<Option<Vec<u8>>, NlError> {
+    if msg.cmd != Nl80211Cmd::CmdControlPortFrame {
+        return Ok(None);
+    }
+    let handle = msg.get_attr_handle();
+    let ethertype = match handle.get_attribute(Nl80211Attr::AttrControlPortEthertype) {
+        Some(attr) => attr.get_payload_as::<u16>()?,
+        None => return Ok(None),
+    };
+    let src = match handle.get_attribute(Nl80211Attr::AttrMac) {
+        Some(attr) => mac_addr(attr.payload().as_ref())?,
+        None => return Ok(None),
+    };
+    if ethertype != ETH_P_PAE || src != *aa {
+        return Ok(None);
+    }
+    Ok(handle
+        .get_attribute(Nl80211Attr::AttrFrame)
+        .map(|attr| attr.payload().as_ref().to_vec()))
+}
+
+/// transaction sequence number, status code and SAE fields of an
+/// authentication frame of `bssid`
+fn sae_frame(
+    msg: &Genlmsghdr<Nl80211Cmd, Nl80211Attr>,
+    bssid: &[u8; 6],
+) -> Result<Option<(u16, StatusCode, Vec<u8>)>, NlError> {
+    let handle = msg.get_attr_handle();
+    let frame = match handle.get_attribute(Nl80211Attr::AttrFrame) {
+        Some(attr) => attr.payload().as_ref().to_vec(),
+        None => return Ok(None),
+    };
+    if frame.len() < MGMT_HEADER_LEN + 6 {
+        return Err(NlError::new("truncated authentication frame"));
+    }
+    // only frames sent by the AP, addr2 is the transmitter
+    let field = |offset: usize| u16::from_le_bytes([frame[offset], frame[offset + 1]]);
+    if frame[10..16] != bssid[..] || field(MGMT_HEADER_LEN) != AUTH_ALGORITHM_SAE {
+        return Ok(None);
+    }
+    Ok(Some((
+        field(MGMT_HEADER_LEN + 2),
+        StatusCode::from(field(MGMT_HEADER_LEN + 4)),
+        frame[(MGMT_HEADER_LEN + 6)..].to_vec(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::{client, datagrams, select_netdev};
+    use crate::base::nl80211::connect::ConnectParams;
+    use crate::base::nl80211::crypto::{key_wrap, psk, tests::unhex};
+
+    const AA: [u8; 6] = [2, 0, 0, 0, 1, 0];
+    const SPA: [u8; 6] = [2, 0, 0, 0, 0, 1];
+
+    /// payload of NL80211_ATTR_FRAME, the first attribute of a sent message
+    fn frame(msg: &[u8]) -> &[u8] {
+        let len = u16::from_ne_bytes([msg[20], msg[21]]) as usize;
+        assert_eq!(&msg[22..24], &[51, 0]);
+        &msg[24..(20 + len)]
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut client = client(&datagrams(include_bytes!("fixtures/handshake.bin")));
+        select_netdev(&mut client, 1);
+        let rsn = ConnectParams::psk(b"glow").rsn.unwrap();
+        let pmk = psk(b"glow-passphrase", b"glow");
+        let mut supplicant = Supplicant::new(pmk, &rsn, AA, SPA).unwrap();
+        supplicant.snonce = [0x5a; 32];
+        supplicant.ap_rsn(rsn);
+        client.handshake(&mut supplicant).unwrap();
+        assert!(supplicant.established());
+
+        let replies = include_bytes!("fixtures/handshake_replies.bin");
+        let sent = client.transport().sent();
+        assert_eq!(sent.len(), 6);
+        // messages 2 and 4 to the AP, unencrypted
+        assert_eq!(sent[0][16], u8::from(Nl80211Cmd::CmdControlPortFrame));
+        assert_eq!(frame(&sent[0]), &replies[..121]);
+        assert_eq!(frame(&sent[1]), &replies[121..220]);
+        // the MIC of message 2, HMAC-SHA-1-128 with the KCK of PRF-384 as
+        // computed by Python's hashlib
+        assert_eq!(
+            frame(&sent[0])[81..97],
+            unhex("c77e95b801648ce83828543216144a8b")[..]
+        );
+        for msg in sent[..2].iter() {
+            // NL80211_ATTR_CONTROL_PORT_ETHERTYPE, NL80211_ATTR_CONTROL_PORT_NO_ENCRYPT
+            // and NL80211_ATTR_IFINDEX
+            let tail = &msg[(msg.len() - 20)..];
+            assert_eq!(tail[..12], [6, 0, 102, 0, 0x8e, 0x88, 0, 0, 4, 0, 103, 0]);
+        }
+
+        // the PTK, nested in NL80211_ATTR_KEY
+        let ptk = &sent[2];
+        assert_eq!(ptk[16], u8::from(Nl80211Cmd::CmdNewKey));
+        assert_eq!(&ptk[20..28], &[48, 0, 80, 0x80, 20, 0, 1, 0]);
+        assert_eq!(ptk[28..44], unhex("e14b3d15a8a2b1ffa82002d7c3ccecb4")[..]);
+        // NL80211_KEY_CIPHER, CCMP-128
+        assert_eq!(&ptk[44..52], &[8, 0, 3, 0, 4, 0xac, 0x0f, 0]);
+        // NL80211_KEY_IDX and NL80211_KEY_TYPE, pairwise
+        assert_eq!(
+            &ptk[52..68],
+            &[5, 0, 2, 0, 0, 0, 0, 0, 8, 0, 7, 0, 1, 0, 0, 0]
+        );
+        // NL80211_ATTR_MAC of the AP
+        assert_eq!(&ptk[68..78], &[10, 0, 6, 0, 2, 0, 0, 0, 1, 0]);
+
+        // the GTK with its NL80211_KEY_SEQ from message 3
+        let gtk = &sent[3];
+        assert_eq!(&gtk[20..28], &[60, 0, 80, 0x80, 20, 0, 1, 0]);
+        assert_eq!(gtk[28..44], [0x47; 16]);
+        assert_eq!(&gtk[52..64], &[10, 0, 4, 0, 5, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            &gtk[64..80],
+            &[5, 0, 2, 0, 1, 0, 0, 0, 8, 0, 7, 0, 0, 0, 0, 0]
+        );
+        // the IGTK, BIP-CMAC-128 with key 4
+        let igtk = &sent[4];
+        assert_eq!(igtk[28..44], [0x49; 16]);
+        assert_eq!(&igtk[44..52], &[8, 0, 3, 0, 6, 0xac, 0x0f, 0]);
+        assert_eq!(&igtk[52..64], &[10, 0, 4, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&igtk[64..68], &[5, 0, 2, 0]);
+        assert_eq!(igtk[68], 4);
+
+        // NL80211_ATTR_STA_FLAGS2 authorizing the AP
+        let station = &sent[5];
+        assert_eq!(station[16], u8::from(Nl80211Cmd::CmdSetStation));
+        assert_eq!(&station[20..30], &[10, 0, 6, 0, 2, 0, 0, 0, 1, 0]);
+        assert_eq!(&station[32..44], &[12, 0, 67, 0, 2, 0, 0, 0, 2, 0, 0, 0]);
+
+        // message 3 retransmitted is replied to without installing the keys
+        // again, then the group key handshake, both encrypted, until the
+        // AP disconnects
+        client.maintain(&mut supplicant).unwrap();
+        let sent = client.transport().sent();
+        assert_eq!(sent.len(), 9);
+        assert_eq!(frame(&sent[6]), &replies[220..319]);
+        assert_eq!(
+            frame(&sent[6])[81..97],
+            unhex("2ef60389d105fd81642b63c3d445a6c2")[..]
+        );
+        assert_eq!(frame(&sent[7]), &replies[319..]);
+        for msg in sent[6..8].iter() {
+            let tail = &msg[(msg.len() - 16)..];
+            assert_eq!(tail[..8], [6, 0, 102, 0, 0x8e, 0x88, 0, 0]);
+        }
+        assert_eq!(sent[8][16], u8::from(Nl80211Cmd::CmdNewKey));
+        assert_eq!(sent[8][28..44], [0x48; 16]);
+        assert_eq!(&sent[8][64..68], &[5, 0, 2, 0]);
+        assert_eq!(sent[8][68], 2);
+    }
+
+    #[test]
+    fn test_supplicant_sae() {
+        let rsn = ConnectParams::sae(b"glow").rsn.unwrap();
+        let mut supplicant = Supplicant::new([0x33; 32], &rsn, AA, SPA).unwrap();
+        let anonce = [0xa0; 32];
+        let mut msg1 = EapolKey {
+            version: 2,
+            info: KEY_INFO_PAIRWISE | KEY_INFO_ACK,
+            key_len: 16,
+            replay_counter: 1,
+            nonce: anonce,
+            iv: [0; 16],
+            rsc: [0; 8],
+            mic: [0; 16],
+            data: Vec::new(),
+        };
+        // the AKM defines the MIC and KDF, not the descriptor version
+        msg1.info |= 2;
+        assert!(supplicant.process(&msg1.write()).is_err());
+        msg1.info &= !KEY_INFO_VERSION;
+
+        let step = supplicant.process(&msg1.write()).unwrap();
+        let reply = step.reply.unwrap();
+        let msg2 = EapolKey::parse(&reply).unwrap();
+        assert_eq!(msg2.info, KEY_INFO_PAIRWISE | KEY_INFO_MIC);
+        assert_eq!(msg2.nonce, supplicant.snonce);
+        assert_eq!(msg2.data, supplicant.rsne);
+        let ptk = supplicant.derive_ptk(&anonce);
+        assert_eq!(msg2.mic, aes_cmac(&ptk.kck, &without_mic(&reply)));
+
+        let mut data = vec![0xdd, 22, 0x00, 0x0f, 0xac, 1, 2, 0];
+        data.extend_from_slice(&[0x47; 16]);
+        let mut msg3 = msg1.clone();
+        msg3.info = KEY_INFO_PAIRWISE
+            | KEY_INFO_INSTALL
+            | KEY_INFO_ACK
+            | KEY_INFO_MIC
+            | KEY_INFO_SECURE
+            | KEY_INFO_ENCRYPTED;
+        msg3.replay_counter = 2;
+        msg3.data = key_wrap(&ptk.kek, &data).unwrap();
+        msg3.mic = aes_cmac(&ptk.kck, &msg3.write());
+
+        let mut forged = msg3.clone();
+        forged.mic[0] ^= 1;
+        assert_eq!(
+            supplicant.process(&forged.write()),
+            Err("invalid MIC of EAPOL-Key frame".to_owned())
+        );
+
+        let step = supplicant.process(&msg3.write()).unwrap();
+        assert!(step.authorize);
+        assert_eq!(
+            step.keys,
+            vec![
+                Key {
+                    cipher: CipherSuite::Ccmp128,
+                    index: 0,
+                    data: ptk.tk.clone(),
+                    seq: Vec::new(),
+                    peer: Some(AA),
+                },
+                Key {
+                    cipher: CipherSuite::Ccmp128,
+                    index: 2,
+                    data: vec![0x47; 16],
+                    seq: vec![0; 6],
+                    peer: None,
+                },
+            ]
+        );
+        let msg4 = EapolKey::parse(&step.reply.unwrap()).unwrap();
+        assert_eq!(
+            msg4.info,
+            KEY_INFO_PAIRWISE | KEY_INFO_MIC | KEY_INFO_SECURE
+        );
+        assert_eq!(msg4.replay_counter, 2);
+        assert!(msg4.data.is_empty());
+
+        assert_eq!(
+            supplicant.process(&msg3.write()),
+            Err("replayed EAPOL-Key frame".to_owned())
+        );
+        // retransmitted with a new replay counter, message 4 is sent again
+        // but the keys are not installed twice
+        msg3.replay_counter = 3;
+        msg3.mic = [0; 16];
+        msg3.mic = aes_cmac(&ptk.kck, &msg3.write());
+        let step = supplicant.process(&msg3.write()).unwrap();
+        assert!(step.keys.is_empty());
+        assert!(!step.authorize);
+        let msg4 = EapolKey::parse(&step.reply.unwrap()).unwrap();
+        assert_eq!(
+            msg4.info,
+            KEY_INFO_PAIRWISE | KEY_INFO_MIC | KEY_INFO_SECURE
+        );
+        assert_eq!(msg4.replay_counter, 3);
+        assert_eq!(
+            supplicant.process(&msg3.write()),
+            Err("replayed EAPOL-Key frame".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_supplicant_downgrade() {
+        let rsn = ConnectParams::psk(b"glow").rsn.unwrap();
+        let mut supplicant = Supplicant::new([0x33; 32], &rsn, AA, SPA).unwrap();
+        // the beacon offered SAE as well
+        let mut ap_rsn = rsn.clone();
+        ap_rsn.akm_suites = Some(vec![AkmSuite::Psk, AkmSuite::Sae]);
+        supplicant.ap_rsn(ap_rsn);
+
+        let mut msg1 = EapolKey {
+            version: 2,
+            info: 2 | KEY_INFO_PAIRWISE | KEY_INFO_ACK,
+            key_len: 16,
+            replay_counter: 1,
+            nonce: [0xa0; 32],
+            iv: [0; 16],
+            rsc: [0; 8],
+            mic: [0; 16],
+            data: Vec::new(),
+        };
+        supplicant.process(&msg1.write()).unwrap();
+        let ptk = supplicant.derive_ptk(&msg1.nonce);
+
+        let mut data = supplicant.rsne.clone();
+        data.extend_from_slice(&[0xdd, 22, 0x00, 0x0f, 0xac, 1, 1, 0]);
+        data.extend_from_slice(&[0x47; 16]);
+        data.extend_from_slice(&[0xdd, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        msg1.info |= KEY_INFO_INSTALL | KEY_INFO_MIC | KEY_INFO_SECURE | KEY_INFO_ENCRYPTED;
+        msg1.replay_counter = 2;
+        msg1.data = key_wrap(&ptk.kek, &data).unwrap();
+        msg1.mic = hmac_sha1_128(&ptk.kck, &msg1.write());
+        assert_eq!(
+            supplicant.process(&msg1.write()),
+            Err("RSN element of message 3 differs from the beacon".to_owned())
+        );
+        assert!(!supplicant.established());
+
+        let mut rsn = rsn;
+        rsn.akm_suites = Some(vec![AkmSuite::Ieee8021x]);
+        assert!(Supplicant::new([0x33; 32], &rsn, AA, SPA).is_err());
+    }
+
+    #[test]
+    fn test_authenticate_sae() {
+        // the AP sends the commit and confirm of the peer in
+        // the group 19 vector of IEEE Std 802.11-2020, J.10
+        const STA: [u8; 6] = [0x82, 0x7b, 0x91, 0x9d, 0xd4, 0xb9];
+        const AP: [u8; 6] = [0x1e, 0xec, 0x49, 0xea, 0x64, 0x88];
+        let mut client = client(&datagrams(include_bytes!("fixtures/sae_auth.bin")));
+        select_netdev(&mut client, 1);
+        client.multicast_group("mlme", 6);
+        let mut rand = [0; 32];
+        rand.copy_from_slice(&unhex(
+            "a906f61e4d3a5d4eb2965ff34cf917dd044445c878c17ca5d5b93786da9f83cf",
+        ));
+        let mut mask = [0; 32];
+        mask.copy_from_slice(&unhex(
+            "4234b4fb17aa435c52fbfdebe64039b43478200e54ff7b6e07b69cad74153c15",
+        ));
+        let mut sae =
+            Sae::from_secret(b"mekmitasdigoatpsk4internet", STA, AP, &rand, &mask).unwrap();
+        let pmksa = client
+            .authenticate_sae(&AP, 2412, b"glow", &mut sae)
+            .unwrap();
+        assert_eq!(
+            pmksa.pmk.to_vec(),
+            unhex("7aead86fba4c3221fc437f5f14d70d854ea5d5aac1690116793081eda4d557c5")
+        );
+        assert!(client.transport().groups().is_empty());
+
+        let sent = client.transport().sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0][16], u8::from(Nl80211Cmd::CmdAuthenticate));
+        // NL80211_ATTR_WIPHY_FREQ, NL80211_ATTR_SSID and NL80211_ATTR_AUTH_TYPE
+        assert_eq!(&sent[0][32..40], &[8, 0, 38, 0, 0x6c, 0x09, 0, 0]);
+        assert_eq!(&sent[0][40..48], &[8, 0, 52, 0, b'g', b'l', b'o', b'w']);
+        assert_eq!(&sent[0][48..56], &[8, 0, 53, 0, 4, 0, 0, 0]);
+        // NL80211_ATTR_SAE_DATA with the commit, then the confirm
+        assert_eq!(&sent[0][56..64], &[106, 0, 156, 0, 1, 0, 0, 0]);
+        assert_eq!(
+            sent[0][64..162],
+            unhex(
+                "1300\
+                 eb3bab1964e4a0ab05925ddf3339519138bc65d6cdc0f813dd6fd4344eb4bfe4\
+                 4b5c21597658f4e3eddfb4b99f25b4d6540f32ff1fd5c530c60a794448610bc6\
+                 de3d92bdbbd47d935980ca6cf8988ab6630be6764c885ceb9793970f695217ee"
+            )[..]
+        );
+        assert_eq!(&sent[1][56..64], &[42, 0, 156, 0, 2, 0, 0, 0]);
+        assert_eq!(
+            sent[1][64..98],
+            unhex("010012d9d5c78c500526d36c41dbc56aedf2914cedddd7cad4a58c48f83dbde9fc77")[..]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use super::{
+    attr::*,
+    client::NL80211Client,
+    cmd::Nl80211Cmd,
+    ie::{self, Element},
+    reg::RegData,
+    scan::Bss,
+    transport::Transport,
+    utils::freq_to_channel,
+};
+use neli::attr::Attribute;
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::err::NlError;
+
+/// Score of a channel with a strong co-channel BSS on it
+const MAX_BSS_PENALTY: f32 = 60.0;
+/// Score of a DFS channel, which needs a CAC before use and may have to be left
+const DFS_PENALTY: f32 = 20.0;
+/// Signal of a BSS which does not interfere any more
+const BSS_FLOOR_DBM: i32 = -95;
+/// Noise expected without interference
+const NOISE_FLOOR_DBM: i8 = -95;
+
+/// Survey data of a frequency, times are in milliseconds
+#[derive(Debug, Default)]
+pub struct Survey {
+    pub freq: u32,
+    pub noise_dbm: Option<i8>,
+    /// the channel currently in use
+    pub in_use: bool,
+    /// time the radio spent on the channel
+    pub time: Option<u64>,
+    /// time the channel was sensed busy
+    pub time_busy: Option<u64>,
+    /// time the extension channel was sensed busy
+    pub time_ext_busy: Option<u64>,
+    pub time_rx: Option<u64>,
+    pub time_tx: Option<u64>,
+    pub time_scan: Option<u64>,
+}
+
+impl Survey {
+    /// fraction of time the channel was busy
+    pub fn utilization(&self) -> Option<f32> {
+        match (self.time, self.time_busy) {
+            (Some(time), Some(busy)) if time > 0 => Some(busy as f32 / time as f32),
+            _ => None,
+        }
+    }
+}
+
+/// A 20 MHz channel which may be used, lower scores are better
+#[derive(Debug)]
+pub struct ChannelScore {
+    pub freq: u32,
+    pub channel: u32,
+    pub utilization: Option<f32>,
+    pub noise_dbm: Option<i8>,
+    /// BSSes on the channel or overlapping it
+    pub bss_count: usize,
+    pub dfs: bool,
+    pub max_eirp_mbm: u32,
+    pub score: f32,
+}
+
+impl<T: Transport> NL80211Client<T> {
+    // COMMAND(survey, dump, "[--radio]",
+    //   NL80211_CMD_GET_SURVEY, NLM_F_DUMP, CIB_NETDEV, handle_survey_dump,
+    //   "List all gathered channel survey data");
+    pub fn survey_dump(&mut self) -> Result<Vec<Survey>, NlError> {
+        let responses = self.request(
+            Nl80211Cmd::CmdGetSurvey,
+            Some(NlmFFlags::new(&[NlmF::Request, NlmF::Dump])),
+            None,
+        )?;
+
+        let mut surveys = Vec::new();
+        for msg in responses.iter() {
+            let handle = msg.get_attr_handle();
+            let info = match handle.get_attribute(Nl80211Attr::AttrSurveyInfo) {
+                Some(info) => info,
+                None => continue,
+            };
+            let mut survey = Survey::default();
+            for attr in info.get_attr_handle::<Nl80211SurveyInfo>()?.iter() {
+                match &attr.nla_type {
+                    Nl80211SurveyInfo::SurveyInfoFrequency => {
+                        survey.freq = attr.get_payload_as::<u32>()?
+                    }
+                    Nl80211SurveyInfo::SurveyInfoNoise => {
+                        survey.noise_dbm = Some(attr.get_payload_as::<u8>()? as i8)
+                    }
+                    Nl80211SurveyInfo::SurveyInfoInUse => survey.in_use = true,
+                    Nl80211SurveyInfo::SurveyInfoTime => {
+                        survey.time = Some(attr.get_payload_as::<u64>()?)
+                    }
+                    Nl80211SurveyInfo::SurveyInfoTimeBusy => {
+                        survey.time_busy = Some(attr.get_payload_as::<u64>()?)
+                    }
+                    Nl80211SurveyInfo::SurveyInfoTimeExtBusy => {
+                        survey.time_ext_busy = Some(attr.get_payload_as::<u64>()?)
+                    }
+                    Nl80211SurveyInfo::SurveyInfoTimeRx => {
+                        survey.time_rx = Some(attr.get_payload_as::<u64>()?)
+                    }
+                    Nl80211SurveyInfo::SurveyInfoTimeTx => {
+                        survey.time_tx = Some(attr.get_payload_as::<u64>()?)
+                    }
+                    Nl80211SurveyInfo::SurveyInfoTimeScan => {
+                        survey.time_scan = Some(attr.get_payload_as::<u64>()?)
+                    }
+                    _ => (),
+                }
+            }
+            surveys.push(survey);
+        }
+        Ok(surveys)
+    }
+}
+
+/// Rank the surveyed 20 MHz channels allowed by `reg` for running a BSS,
+/// by how busy they are, the BSSes overlapping them and their regulatory
+/// constraints, best first, `reg` being the domain of the surveying wiphy
+/// as from `reg_get_wiphy`
+pub fn rank_channels(surveys: &[Survey], bsses: &[Bss], reg: &RegData) -> Vec<ChannelScore> {
+    let spans: Vec<(u32, u32, i32)> = bsses
+        .iter()
+        .map(|bss| {
+            let (start, end) = bss_span(bss);
+            let signal = match bss.signal_mbm {
+                Some(mbm) => mbm / 100,
+                None => BSS_FLOOR_DBM,
+            };
+            (start, end, signal)
+        })
+        .collect();
+
+    let mut channels = Vec::new();
+    for survey in surveys.iter() {
+        // e.g. a survey without its frequency
+        let start = match survey.freq.checked_sub(10) {
+            Some(start) => start,
+            None => continue,
+        };
+        let end = survey.freq + 10;
+        let rule = match reg.rule(start * 1000, end * 1000) {
+            Some(rule) => rule,
+            None => continue,
+        };
+        if rule.has_flag(Nl80211RegRuleFlags::RrfNoIr) {
+            continue;
+        }
+
+        let mut score = 0.0;
+        let utilization = survey.utilization();
+        if let Some(utilization) = utilization {
+            score += utilization * 100.0;
+        }
+        if let Some(noise) = survey.noise_dbm {
+            score += (noise - NOISE_FLOOR_DBM).max(0) as f32;
+        }
+        let mut bss_count = 0;
+        for (bss_start, bss_end, signal) in spans.iter() {
+            let overlap = end.min(*bss_end) as i32 - start.max(*bss_start) as i32;
+            if overlap <= 0 {
+                continue;
+            }
+            bss_count += 1;
+            // co-channel BSSes share the medium, partially overlapping
+            // ones interfere in proportion to the overlap
+            let strength = (signal - BSS_FLOOR_DBM) as f32;
+            let fraction = overlap.min(20) as f32 / 20.0;
+            score += strength.clamp(0.0, MAX_BSS_PENALTY) * fraction;
+        }
+        let dfs = rule.has_flag(Nl80211RegRuleFlags::RrfDfs);
+        if dfs {
+            score += DFS_PENALTY;
+        }
+
+        channels.push(ChannelScore {
+            freq: survey.freq,
+            channel: freq_to_channel(survey.freq),
+            utilization,
+            noise_dbm: survey.noise_dbm,
+            bss_count,
+            dfs,
+            max_eirp_mbm: rule.max_eirp_mbm,
+            score,
+        });
+    }
+    channels.sort_by(|a, b| {
+        a.score
+            .partial_cmp(&b.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.freq.cmp(&b.freq))
+    });
+    channels
+}
+
+/// frequencies in MHz occupied by a BSS, from its HT and VHT operation
+fn bss_span(bss: &Bss) -> (u32, u32) {
+    let freq = bss.freq;
+    // DSSS channels in 2.4 GHz are 22 MHz wide
+    let half = if freq < 2500 { 11 } else { 10 };
+    let (mut start, mut end) = (freq.saturating_sub(half), freq + half);
+    for element in ie::parse_lenient(&bss.ies) {
+        match element {
+            Element::HtOperation(ht) if ht.any_channel_width() => {
+                match ht.secondary_channel_offset() {
+                    1 => end = end.max(freq + 30),
+                    3 => start = start.min(freq.saturating_sub(30)),
+                    _ => (),
+                }
+            }
+            Element::VhtOperation(vht) if vht.channel_width == 1 && freq > 5000 => {
+                let center = |seg: u8| 5000 + 5 * seg as u32;
+                let (seg0, seg1) = (vht.center_seg0, vht.center_seg1);
+                let (center, half) = if seg1 != 0 && (seg1 as i32 - seg0 as i32).abs() == 8 {
+                    (center(seg1), 80)
+                } else {
+                    (center(seg0), 40)
+                };
+                start = start.min(center - half);
+                end = end.max(center + half);
+            }
+            _ => (),
+        }
+    }
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::client;
+    use crate::base::nl80211::ie::{self, HtOperation, VhtOperation};
+    use crate::base::nl80211::reg::RegRule;
+
+    fn bss(freq: u32, signal_dbm: i32, elements: &[Element]) -> Bss {
+        Bss {
+            bssid: [0; 6],
+            freq,
+            signal_mbm: Some(signal_dbm * 100),
+            signal_unspec: None,
+            tsf: 0,
+            beacon_interval: 100,
+            capability: 0,
+            status: None,
+            seen_ms_ago: 0,
+            ies: ie::write(elements),
+            beacon_ies: Vec::new(),
+        }
+    }
+
+    fn survey(freq: u32, busy: u64, noise_dbm: i8) -> Survey {
+        Survey {
+            freq,
+            noise_dbm: Some(noise_dbm),
+            time: Some(1000),
+            time_busy: Some(busy),
+            ..Default::default()
+        }
+    }
+
+    fn rule(start_mhz: u32, end_mhz: u32, flags: u32) -> RegRule {
+        RegRule {
+            flags,
+            start_freq_khz: start_mhz * 1000,
+            end_freq_khz: end_mhz * 1000,
+            max_bw_khz: 80000,
+            max_eirp_mbm: 2000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_survey_dump() {
+        let mut client = client(&[include_bytes!("fixtures/survey_dump.bin")]);
+        let surveys = client.survey_dump().unwrap();
+        assert_eq!(surveys.len(), 3);
+        assert_eq!(surveys[0].freq, 2412);
+        assert_eq!(surveys[0].noise_dbm, Some(-92));
+        assert!(surveys[0].in_use);
+        assert_eq!(surveys[0].utilization(), Some(0.25));
+        assert_eq!(surveys[0].time_rx, Some(100));
+        assert_eq!(surveys[2].time, None);
+        assert_eq!(surveys[2].utilization(), None);
+    }
+
+    #[test]
+    fn test_rank_channels() {
+        let reg = RegData {
+            country: "DE".to_owned(),
+            rules: vec![
+                rule(2400, 2483, 0),
+                rule(5150, 5250, 0),
+                rule(5250, 5350, u16::from(Nl80211RegRuleFlags::RrfDfs) as u32),
+                rule(5470, 5725, u16::from(Nl80211RegRuleFlags::RrfNoIr) as u32),
+            ],
+            ..Default::default()
+        };
+        let surveys = [
+            survey(2412, 300, -92),
+            survey(2437, 100, -95),
+            survey(2462, 100, -95),
+            survey(5180, 100, -95),
+            survey(5200, 100, -95),
+            survey(5260, 100, -95),
+            // no initiating radiation, or out of any rule
+            survey(5500, 0, -95),
+            survey(5745, 0, -95),
+            // without its frequency
+            survey(0, 0, -95),
+        ];
+        let ht40 = Element::HtOperation(HtOperation {
+            primary_channel: 1,
+            info: [0x05, 0, 0, 0, 0],
+            basic_mcs_set: [0; 16],
+        });
+        let vht80 = Element::VhtOperation(VhtOperation {
+            channel_width: 1,
+            center_seg0: 42,
+            center_seg1: 0,
+            basic_mcs_set: 0,
+        });
+        let bsses = [
+            // 2401 to 2442 MHz with the secondary channel above
+            bss(2412, -50, &[ht40]),
+            // 5170 to 5250 MHz, co-channel to 5180 and 5200 MHz
+            bss(5180, -60, &[vht80]),
+            bss(2462, -90, &[]),
+        ];
+
+        let ranked = rank_channels(&surveys, &bsses, &reg);
+        let channels: Vec<u32> = ranked.iter().map(|c| c.channel).collect();
+        assert_eq!(channels, vec![11, 52, 6, 36, 40, 1]);
+
+        let ch11 = &ranked[0];
+        assert_eq!(ch11.bss_count, 1);
+        assert_eq!(ch11.score, 15.0);
+        let ch52 = &ranked[1];
+        assert!(ch52.dfs);
+        assert_eq!(ch52.score, 30.0);
+        // three quarters of the HT40 BSS overlap channel 6
+        assert!((ranked[2].score - (10.0 + 33.75)).abs() < 0.01);
+        let ch1 = &ranked[5];
+        assert_eq!(ch1.bss_count, 1);
+        assert!((ch1.score - (30.0 + 3.0 + 45.0)).abs() < 0.01);
+        assert_eq!(ch1.max_eirp_mbm, 2000);
+    }
+}
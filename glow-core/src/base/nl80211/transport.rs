@@ -0,0 +1,199 @@
+use neli::{err::NlError, socket::NlSocket, socket::NlSocketHandle};
+use socket2::Socket;
+use std::collections::VecDeque;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::time::Duration;
+
+/// Size of the receive buffer, large enough for any nl80211 message
+const RECV_BUFFER_SIZE: usize = 65536;
+
+/// Carries serialized netlink messages between `NL80211Client` and nl80211
+pub trait Transport {
+    /// send one netlink message
+    fn send(&mut self, msg: &[u8]) -> Result<(), NlError>;
+
+    /// receive the next chunk of netlink messages
+    fn recv(&mut self) -> Result<Vec<u8>, NlError>;
+
+    /// receive the messages multicast to `group` as well
+    fn add_membership(&mut self, group: u32) -> Result<(), NlError>;
+
+    fn drop_membership(&mut self, group: u32) -> Result<(), NlError>;
+
+    /// fail receiving after `timeout`, never with none
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), NlError>;
+}
+
+/// Generic netlink socket talking to the kernel
+pub struct NlTransport {
+    socket: NlSocket,
+    buffer: Vec<u8>,
+}
+
+impl From<NlSocketHandle> for NlTransport {
+    fn from(socket: NlSocketHandle) -> Self {
+        NlTransport {
+            socket: NlSocket::from(socket),
+            buffer: vec![0; RECV_BUFFER_SIZE],
+        }
+    }
+}
+
+impl Transport for NlTransport {
+    fn send(&mut self, msg: &[u8]) -> Result<(), NlError> {
+        self.socket.send(msg, 0)?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, NlError> {
+        let size = match self.socket.recv(&mut self.buffer[..], 0) {
+            Ok(size) => size,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                return Err(NlError::new("timed out receiving from nl80211"))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(self.buffer[..size].to_vec())
+    }
+
+    fn add_membership(&mut self, group: u32) -> Result<(), NlError> {
+        self.socket.add_mcast_membership(&[group])?;
+        Ok(())
+    }
+
+    fn drop_membership(&mut self, group: u32) -> Result<(), NlError> {
+        self.socket.drop_mcast_membership(&[group])?;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), NlError> {
+        // borrow the descriptor, the socket stays owned by `self.socket`
+        let socket = ManuallyDrop::new(unsafe { Socket::from_raw_fd(self.socket.as_raw_fd()) });
+        socket.set_read_timeout(timeout)?;
+        Ok(())
+    }
+}
+
+/// Replays recorded chunks of netlink messages in order,
+/// keeping the requests it was sent
+#[derive(Default)]
+pub struct Replay {
+    responses: VecDeque<Vec<u8>>,
+    sent: Vec<Vec<u8>>,
+    groups: Vec<u32>,
+}
+
+impl Replay {
+    pub fn new() -> Replay {
+        Replay::default()
+    }
+
+    /// receive `fixture` next, the messages of one or more chunks
+    pub fn respond(&mut self, fixture: &[u8]) -> &Replay {
+        self.responses.push_back(fixture.to_vec());
+        self
+    }
+
+    /// requests sent so far
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+
+    /// multicast groups currently joined
+    pub fn groups(&self) -> &[u32] {
+        &self.groups
+    }
+}
+
+impl Transport for Replay {
+    fn send(&mut self, msg: &[u8]) -> Result<(), NlError> {
+        self.sent.push(msg.to_vec());
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, NlError> {
+        self.responses
+            .pop_front()
+            .ok_or_else(|| NlError::new("no recorded response left"))
+    }
+
+    fn add_membership(&mut self, group: u32) -> Result<(), NlError> {
+        if !self.groups.contains(&group) {
+            self.groups.push(group);
+        }
+        Ok(())
+    }
+
+    fn drop_membership(&mut self, group: u32) -> Result<(), NlError> {
+        self.groups.retain(|g| *g != group);
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, _timeout: Option<Duration>) -> Result<(), NlError> {
+        Ok(())
+    }
+}
+
+/// Records what `T` receives, to capture fixtures for `Replay` on real hardware
+pub struct Record<T> {
+    inner: T,
+    received: Vec<Vec<u8>>,
+}
+
+impl<T: Transport> Record<T> {
+    pub fn new(inner: T) -> Record<T> {
+        Record {
+            inner,
+            received: Vec::new(),
+        }
+    }
+
+    /// chunks received so far
+    pub fn received(&self) -> &[Vec<u8>] {
+        &self.received
+    }
+}
+
+impl<T: Transport> Transport for Record<T> {
+    fn send(&mut self, msg: &[u8]) -> Result<(), NlError> {
+        self.inner.send(msg)
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, NlError> {
+        let chunk = self.inner.recv()?;
+        self.received.push(chunk.clone());
+        Ok(chunk)
+    }
+
+    fn add_membership(&mut self, group: u32) -> Result<(), NlError> {
+        self.inner.add_membership(group)
+    }
+
+    fn drop_membership(&mut self, group: u32) -> Result<(), NlError> {
+        self.inner.drop_membership(group)
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), NlError> {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neli::consts::socket::NlFamily;
+
+    #[test]
+    fn test_timeout() {
+        // nothing is sent, nothing arrives
+        let socket = NlSocketHandle::connect(NlFamily::Generic, None, &[]).unwrap();
+        let mut transport = NlTransport::from(socket);
+        transport
+            .set_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let e = transport.recv().unwrap_err();
+        assert_eq!(e.to_string(), "timed out receiving from nl80211");
+    }
+}
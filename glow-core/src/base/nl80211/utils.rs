@@ -1,5 +1,10 @@
 use glow_utils::fs::read_file;
+use super::attr::NlaNested;
+use neli::consts::genl::NlAttrType;
 use neli::err::NlError;
+use neli::genl::Nlattr;
+use neli::types::Buffer;
+use neli::Nl;
 use std::str::FromStr;
 
 pub fn phy_lookup(phy: String) -> Result<u32, NlError> {
@@ -7,6 +12,63 @@ pub fn phy_lookup(phy: String) -> Result<u32, NlError> {
     u32::from_str(str.trim()).map_err(NlError::new)
 }
 
+pub fn netdev_lookup(dev: String) -> Result<u32, NlError> {
+    let str = read_file(format!("/sys/class/net/{}/ifindex", dev)).map_err(NlError::new)?;
+    u32::from_str(str.trim()).map_err(NlError::new)
+}
+
+/// string attribute up to the terminating NUL
+pub fn attr_string(payload: &[u8]) -> String {
+    let end = payload
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(payload.len());
+    String::from_utf8_lossy(&payload[..end]).into_owned()
+}
+
+pub fn mac_addr(payload: &[u8]) -> Result<[u8; 6], NlError> {
+    let mut mac = [0; 6];
+    if payload.len() != mac.len() {
+        return Err(NlError::new("MAC address is not 6 bytes long"));
+    }
+    mac.copy_from_slice(payload);
+    Ok(mac)
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// nested attribute holding `items` as attributes numbered from 1
+pub fn nested_list<T: NlAttrType, P: Nl>(
+    nla_type: T,
+    items: Vec<P>,
+) -> Result<Nlattr<T, Buffer>, NlError> {
+    let mut attr = Nlattr::new(None, true, false, nla_type, Vec::<u8>::new())?;
+    for (i, item) in items.into_iter().enumerate() {
+        let index = NlaNested::from(i as u16 + 1);
+        attr.add_nested_attribute(&Nlattr::new(None, false, false, index, item)?)?;
+    }
+    Ok(attr)
+}
+
+/// like ieee80211_frequency_to_channel(), 0 for unknown frequencies
+pub fn freq_to_channel(freq: u32) -> u32 {
+    match freq {
+        2484 => 14,
+        2407..=2483 => (freq - 2407) / 5,
+        4910..=4980 => (freq - 4000) / 5,
+        5000..=5924 => (freq - 5000) / 5,
+        5935 => 2,
+        5950..=45000 => (freq - 5950) / 5,
+        58320..=70200 => (freq - 56160) / 2160,
+        _ => 0,
+    }
+}
+
 pub fn is_alpha2(str: &str) -> bool {
     str.len() == 2 && is_alpha_upper(str.as_bytes()[0]) && is_alpha_upper(str.as_bytes()[1])
 }
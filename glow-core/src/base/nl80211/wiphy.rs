@@ -0,0 +1,432 @@
+use super::{
+    attr::*, client::NL80211Client, cmd::Nl80211Cmd, transport::Transport, utils::attr_string,
+};
+use neli::attr::Attribute;
+use neli::consts::genl::NlAttrType;
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::err::NlError;
+use neli::genl::Nlattr;
+use neli::types::{Buffer, GenlBuffer};
+
+/// A wireless PHY and what it supports
+#[derive(Debug, Default)]
+pub struct Wiphy {
+    pub index: u32,
+    pub name: String,
+
+    pub bands: Vec<Band>,
+    /// interface types which can be created
+    pub iftypes: Vec<Nl80211Iftype>,
+    /// interface types not limited by interface combinations
+    pub software_iftypes: Vec<Nl80211Iftype>,
+    pub combinations: Vec<IfaceCombination>,
+    /// suite selectors, OUI and type, e.g. 0x000fac04 for CCMP-128
+    pub cipher_suites: Vec<u32>,
+
+    /// NL80211_FEATURE_* bits
+    pub features: u32,
+    /// bitmap of NL80211_EXT_FEATURE_* indices
+    pub ext_features: Vec<u8>,
+    pub max_scan_ssids: u8,
+}
+
+impl Wiphy {
+    pub fn has_feature(&self, feature: u32) -> bool {
+        self.features & feature == feature
+    }
+
+    pub fn has_ext_feature(&self, index: u16) -> bool {
+        match self.ext_features.get(index as usize / 8) {
+            Some(byte) => byte & (1 << (index % 8)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn band(&self, band: Nl80211Bandc) -> Option<&Band> {
+        self.bands.iter().find(|b| b.band == band)
+    }
+}
+
+#[derive(Debug)]
+pub struct Band {
+    pub band: Nl80211Bandc,
+    pub frequencies: Vec<Frequency>,
+    pub bitrates: Vec<Bitrate>,
+    pub ht: Option<HtCapabilities>,
+    pub vht: Option<VhtCapabilities>,
+    /// HE capabilities, each for a set of interface types
+    pub he: Vec<HeCapabilities>,
+}
+
+#[derive(Debug, Default)]
+pub struct Frequency {
+    pub mhz: u32,
+    pub disabled: bool,
+    /// initiating radiation, like beaconing or probing, is not allowed
+    pub no_ir: bool,
+    /// radar detection is required
+    pub radar: bool,
+    pub max_tx_power_mbm: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Bitrate {
+    /// in units of 100 kbit/s
+    pub rate: u32,
+    /// short preamble is supported in the 2.4 GHz band
+    pub short_preamble: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct HtCapabilities {
+    /// HT Capability Information field
+    pub capa: u16,
+    pub mcs_set: Vec<u8>,
+    pub ampdu_factor: u8,
+    pub ampdu_density: u8,
+}
+
+#[derive(Debug, Default)]
+pub struct VhtCapabilities {
+    /// VHT Capabilities Information field
+    pub capa: u32,
+    pub mcs_set: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct HeCapabilities {
+    pub iftypes: Vec<Nl80211Iftype>,
+    pub mac: Vec<u8>,
+    pub phy: Vec<u8>,
+    pub mcs_set: Vec<u8>,
+    pub ppe: Vec<u8>,
+}
+
+/// Interfaces which can be used at the same time
+#[derive(Debug, Default)]
+pub struct IfaceCombination {
+    pub limits: Vec<IfaceLimit>,
+    pub max_interfaces: u32,
+    /// different channels in use at the same time
+    pub num_channels: u32,
+    /// beacon intervals of all interfaces must match
+    pub beacon_int_match: bool,
+    /// bitmap of channel widths radar detection is supported on
+    pub radar_detect_widths: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct IfaceLimit {
+    pub max: u32,
+    pub iftypes: Vec<Nl80211Iftype>,
+}
+
+impl<T: Transport> NL80211Client<T> {
+    // COMMAND(list, NULL, NULL, NL80211_CMD_GET_WIPHY, NLM_F_DUMP, CIB_NONE, handle_info,
+    //   "List all wireless devices and their capabilities.");
+    pub fn wiphy_list(&mut self) -> Result<Vec<Wiphy>, NlError> {
+        // a wiphy with all its channels does not fit in one message,
+        // let the kernel split it and merge the parts here
+        let mut attrs = GenlBuffer::new();
+        let attr = Nlattr::new(
+            None,
+            false,
+            false,
+            Nl80211Attr::AttrSplitWiphyDump,
+            Vec::<u8>::new(),
+        )?;
+        attrs.push(attr);
+        let responses = self.request(
+            Nl80211Cmd::CmdGetWiphy,
+            Some(NlmFFlags::new(&[NlmF::Request, NlmF::Dump])),
+            Some(attrs),
+        )?;
+
+        let mut wiphys: Vec<Wiphy> = Vec::new();
+        for msg in responses.iter() {
+            let handle = msg.get_attr_handle();
+            let index = match handle.get_attribute(Nl80211Attr::AttrWiphy) {
+                Some(attr) => attr.get_payload_as::<u32>()?,
+                None => continue,
+            };
+            let position = match wiphys.iter().position(|w| w.index == index) {
+                Some(position) => position,
+                None => {
+                    wiphys.push(Wiphy {
+                        index,
+                        ..Default::default()
+                    });
+                    wiphys.len() - 1
+                }
+            };
+            let wiphy = &mut wiphys[position];
+
+            for attr in handle.iter() {
+                let payload = attr.payload().as_ref();
+                match &attr.nla_type {
+                    Nl80211Attr::AttrWiphyName => {
+                        wiphy.name = attr_string(payload);
+                    }
+                    Nl80211Attr::AttrWiphyBands => {
+                        for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+                            let id = Nl80211Bandc::from(u16::from(attr.nla_type));
+                            let position = match wiphy.bands.iter().position(|b| b.band == id) {
+                                Some(position) => position,
+                                None => {
+                                    wiphy.bands.push(Band {
+                                        band: id,
+                                        frequencies: Vec::new(),
+                                        bitrates: Vec::new(),
+                                        ht: None,
+                                        vht: None,
+                                        he: Vec::new(),
+                                    });
+                                    wiphy.bands.len() - 1
+                                }
+                            };
+                            parse_band(&mut wiphy.bands[position], attr)?;
+                        }
+                    }
+                    Nl80211Attr::AttrSupportedIftypes => {
+                        wiphy.iftypes = iftypes(attr)?;
+                    }
+                    Nl80211Attr::AttrSoftwareIftypes => {
+                        wiphy.software_iftypes = iftypes(attr)?;
+                    }
+                    Nl80211Attr::AttrInterfaceCombinations => {
+                        for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+                            wiphy.combinations.push(parse_combination(attr)?);
+                        }
+                    }
+                    Nl80211Attr::AttrCipherSuites => {
+                        wiphy.cipher_suites = payload
+                            .chunks_exact(4)
+                            .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                            .collect();
+                    }
+                    Nl80211Attr::AttrFeatureFlags => {
+                        wiphy.features = attr.get_payload_as::<u32>()?;
+                    }
+                    Nl80211Attr::AttrExtFeatures => {
+                        wiphy.ext_features = payload.to_vec();
+                    }
+                    Nl80211Attr::AttrMaxNumScanSsids => {
+                        wiphy.max_scan_ssids = attr.get_payload_as::<u8>()?;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(wiphys)
+    }
+}
+
+/// merge one part of a band into `band`
+fn parse_band<T: NlAttrType>(band: &mut Band, attr: &Nlattr<T, Buffer>) -> Result<(), NlError> {
+    for attr in attr.get_attr_handle::<Nl80211BandAttr>()?.iter() {
+        let payload = attr.payload().as_ref();
+        match &attr.nla_type {
+            Nl80211BandAttr::BandAttrFreqs => {
+                for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+                    band.frequencies.push(parse_frequency(attr)?);
+                }
+            }
+            Nl80211BandAttr::BandAttrRates => {
+                for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+                    let mut bitrate = Bitrate::default();
+                    for attr in attr.get_attr_handle::<Nl80211BitrateAttr>()?.iter() {
+                        match &attr.nla_type {
+                            Nl80211BitrateAttr::BitrateAttrRate => {
+                                bitrate.rate = attr.get_payload_as::<u32>()?;
+                            }
+                            Nl80211BitrateAttr::BitrateAttr2ghzShortpreamble => {
+                                bitrate.short_preamble = true;
+                            }
+                            _ => (),
+                        }
+                    }
+                    band.bitrates.push(bitrate);
+                }
+            }
+            Nl80211BandAttr::BandAttrHtCapa => {
+                band.ht.get_or_insert_with(Default::default).capa = attr.get_payload_as::<u16>()?;
+            }
+            Nl80211BandAttr::BandAttrHtMcsSet => {
+                band.ht.get_or_insert_with(Default::default).mcs_set = payload.to_vec();
+            }
+            Nl80211BandAttr::BandAttrHtAmpduFactor => {
+                band.ht.get_or_insert_with(Default::default).ampdu_factor =
+                    attr.get_payload_as::<u8>()?;
+            }
+            Nl80211BandAttr::BandAttrHtAmpduDensity => {
+                band.ht.get_or_insert_with(Default::default).ampdu_density =
+                    attr.get_payload_as::<u8>()?;
+            }
+            Nl80211BandAttr::BandAttrVhtCapa => {
+                band.vht.get_or_insert_with(Default::default).capa =
+                    attr.get_payload_as::<u32>()?;
+            }
+            Nl80211BandAttr::BandAttrVhtMcsSet => {
+                band.vht.get_or_insert_with(Default::default).mcs_set = payload.to_vec();
+            }
+            Nl80211BandAttr::BandAttrIftypeData => {
+                for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+                    let mut he = HeCapabilities::default();
+                    for attr in attr.get_attr_handle::<Nl80211BandIftypeAttr>()?.iter() {
+                        let payload = attr.payload().as_ref();
+                        match &attr.nla_type {
+                            Nl80211BandIftypeAttr::BandIftypeAttrIftypes => {
+                                he.iftypes = iftypes(attr)?;
+                            }
+                            Nl80211BandIftypeAttr::BandIftypeAttrHeCapMac => {
+                                he.mac = payload.to_vec();
+                            }
+                            Nl80211BandIftypeAttr::BandIftypeAttrHeCapPhy => {
+                                he.phy = payload.to_vec();
+                            }
+                            Nl80211BandIftypeAttr::BandIftypeAttrHeCapMcsSet => {
+                                he.mcs_set = payload.to_vec();
+                            }
+                            Nl80211BandIftypeAttr::BandIftypeAttrHeCapPpe => {
+                                he.ppe = payload.to_vec();
+                            }
+                            _ => (),
+                        }
+                    }
+                    band.he.push(he);
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn parse_frequency<T: NlAttrType>(attr: &Nlattr<T, Buffer>) -> Result<Frequency, NlError> {
+    let mut frequency = Frequency::default();
+    for attr in attr.get_attr_handle::<Nl80211FrequencyAttr>()?.iter() {
+        match &attr.nla_type {
+            Nl80211FrequencyAttr::FrequencyAttrFreq => {
+                frequency.mhz = attr.get_payload_as::<u32>()?;
+            }
+            Nl80211FrequencyAttr::FrequencyAttrDisabled => frequency.disabled = true,
+            Nl80211FrequencyAttr::FrequencyAttrNoIr => frequency.no_ir = true,
+            Nl80211FrequencyAttr::FrequencyAttrRadar => frequency.radar = true,
+            Nl80211FrequencyAttr::FrequencyAttrMaxTxPower => {
+                frequency.max_tx_power_mbm = attr.get_payload_as::<u32>()?;
+            }
+            _ => (),
+        }
+    }
+    Ok(frequency)
+}
+
+fn parse_combination<T: NlAttrType>(attr: &Nlattr<T, Buffer>) -> Result<IfaceCombination, NlError> {
+    let mut combination = IfaceCombination::default();
+    for attr in attr.get_attr_handle::<Nl80211IfCombinationAttrs>()?.iter() {
+        match &attr.nla_type {
+            Nl80211IfCombinationAttrs::IfaceCombLimits => {
+                for attr in attr.get_attr_handle::<NlaNested>()?.iter() {
+                    let mut limit = IfaceLimit::default();
+                    for attr in attr.get_attr_handle::<Nl80211IfaceLimitAttrs>()?.iter() {
+                        match &attr.nla_type {
+                            Nl80211IfaceLimitAttrs::IfaceLimitMax => {
+                                limit.max = attr.get_payload_as::<u32>()?;
+                            }
+                            Nl80211IfaceLimitAttrs::IfaceLimitTypes => {
+                                limit.iftypes = iftypes(attr)?;
+                            }
+                            _ => (),
+                        }
+                    }
+                    combination.limits.push(limit);
+                }
+            }
+            Nl80211IfCombinationAttrs::IfaceCombMaxnum => {
+                combination.max_interfaces = attr.get_payload_as::<u32>()?;
+            }
+            Nl80211IfCombinationAttrs::IfaceCombNumChannels => {
+                combination.num_channels = attr.get_payload_as::<u32>()?;
+            }
+            Nl80211IfCombinationAttrs::IfaceCombStaApBiMatch => {
+                combination.beacon_int_match = true;
+            }
+            Nl80211IfCombinationAttrs::IfaceCombRadarDetectWidths => {
+                combination.radar_detect_widths = attr.get_payload_as::<u32>()?;
+            }
+            _ => (),
+        }
+    }
+    Ok(combination)
+}
+
+/// interface types nested as flag attributes
+fn iftypes<T: NlAttrType>(attr: &Nlattr<T, Buffer>) -> Result<Vec<Nl80211Iftype>, NlError> {
+    Ok(attr
+        .get_attr_handle::<Nl80211Iftype>()?
+        .iter()
+        .map(|attr| attr.nla_type)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::nl80211::client::tests::client;
+
+    #[test]
+    fn test_wiphy_list() {
+        let mut client = client(&[include_bytes!("fixtures/wiphy_dump.bin")]);
+        let wiphys = client.wiphy_list().unwrap();
+        assert_eq!(wiphys.len(), 2);
+
+        let phy0 = &wiphys[0];
+        assert_eq!(phy0.name, "phy0");
+        assert_eq!(phy0.max_scan_ssids, 20);
+        assert_eq!(phy0.cipher_suites[3], 0x000fac04);
+        assert_eq!(phy0.iftypes.len(), 5);
+        assert_eq!(phy0.software_iftypes, vec![Nl80211Iftype::IftypeMonitor]);
+        assert!(phy0.has_feature(0x40000));
+        assert!(phy0.has_ext_feature(2));
+        assert!(!phy0.has_ext_feature(1));
+        assert!(phy0.has_ext_feature(16));
+        assert!(!phy0.has_ext_feature(100));
+
+        // the 2.4 GHz band came in two parts
+        assert_eq!(phy0.bands.len(), 2);
+        let band = phy0.band(Nl80211Bandc::Band2ghz).unwrap();
+        let mhz: Vec<u32> = band.frequencies.iter().map(|f| f.mhz).collect();
+        assert_eq!(mhz, vec![2412, 2417, 2472, 2484]);
+        assert!(band.frequencies[3].disabled);
+        assert_eq!(band.frequencies[0].max_tx_power_mbm, 2000);
+        assert_eq!(band.bitrates.len(), 3);
+        assert!(band.bitrates[1].short_preamble);
+        let ht = band.ht.as_ref().unwrap();
+        assert_eq!(ht.capa, 0x19ef);
+        assert_eq!((ht.ampdu_factor, ht.ampdu_density), (3, 6));
+        assert!(band.vht.is_none());
+
+        let band = phy0.band(Nl80211Bandc::Band5ghz).unwrap();
+        assert!(band.frequencies[0].no_ir);
+        assert!(band.frequencies[1].radar);
+        assert_eq!(band.vht.as_ref().unwrap().capa, 0x339071b2);
+        assert_eq!(band.he.len(), 1);
+        assert_eq!(band.he[0].iftypes, vec![Nl80211Iftype::IftypeStation]);
+        assert_eq!(band.he[0].mac.len(), 6);
+
+        let combination = &phy0.combinations[0];
+        assert_eq!(combination.limits.len(), 2);
+        assert_eq!(combination.limits[1].iftypes.len(), 3);
+        assert_eq!(combination.max_interfaces, 2);
+        assert_eq!(combination.num_channels, 1);
+        assert!(combination.beacon_int_match);
+
+        assert_eq!(wiphys[1].name, "phy1");
+        assert!(wiphys[1].bands.is_empty());
+
+        let sent = &client.transport().sent()[0];
+        assert_eq!(sent[16], u8::from(Nl80211Cmd::CmdGetWiphy));
+        // NL80211_ATTR_SPLIT_WIPHY_DUMP
+        assert_eq!(&sent[20..24], &[4, 0, 174, 0]);
+    }
+}
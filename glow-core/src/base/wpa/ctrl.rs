@@ -0,0 +1,749 @@
+use super::{network, ConnectionState, NetworkValue};
+use crate::base::nl80211::prelude::{ConnectParams, Connector, ReasonCode, StatusCode};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// References: wpa_supplicant/ctrl_iface.c, the commands and their replies
+//             src/common/wpa_ctrl.h, the CTRL-EVENT-* messages
+
+/// Directory of the control sockets of wpa_supplicant, one per interface
+pub const CTRL_DIR: &str = "/var/run/wpa_supplicant";
+
+/// Size of the receive buffer, long enough for SCAN_RESULTS
+const REPLY_LEN: usize = 65536;
+
+/// Timeout of requests, as wpa_cli
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Counter of the local sockets of this process
+static LOCAL_SOCKETS: AtomicUsize = AtomicUsize::new(0);
+
+/// A BSS of SCAN_RESULTS
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanResult {
+    pub bssid: [u8; 6],
+    pub freq: u32,
+    /// in dBm
+    pub signal: i32,
+    /// e.g. WPA2-PSK-CCMP and ESS
+    pub flags: Vec<String>,
+    /// escaped by wpa_supplicant if not printable
+    pub ssid: String,
+}
+
+/// Reply of STATUS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub wpa_state: ConnectionState,
+    pub bssid: Option<[u8; 6]>,
+    pub freq: Option<u32>,
+    pub ssid: Option<String>,
+    /// network of the connection
+    pub id: Option<u32>,
+    pub key_mgmt: Option<String>,
+    pub address: Option<[u8; 6]>,
+    pub ip_address: Option<IpAddr>,
+}
+
+/// An unsolicited message of wpa_supplicant to attached clients
+#[derive(Debug, Clone, PartialEq)]
+pub enum WpaEvent {
+    Connected {
+        bssid: Option<[u8; 6]>,
+        id: Option<u32>,
+    },
+    Disconnected {
+        bssid: Option<[u8; 6]>,
+        reason: ReasonCode,
+        /// by us, not by the AP
+        locally_generated: bool,
+    },
+    /// only sent by the Android builds of wpa_supplicant
+    StateChange {
+        id: Option<u32>,
+        state: ConnectionState,
+        bssid: Option<[u8; 6]>,
+        ssid: Option<String>,
+    },
+    ScanStarted,
+    /// the results can be fetched with `scan_results`
+    ScanResults,
+    ScanFailed,
+    NetworkNotFound,
+    AssocReject {
+        bssid: Option<[u8; 6]>,
+        status: StatusCode,
+    },
+    /// the network is skipped for a while after failures, e.g. a wrong key
+    SsidTempDisabled {
+        id: Option<u32>,
+        reason: Option<String>,
+    },
+    Terminating,
+    /// other messages, without their priority
+    Other(String),
+}
+
+impl WpaEvent {
+    /// Parse a message of wpa_supplicant, starting with its priority like
+    /// `<3>CTRL-EVENT-SCAN-STARTED`
+    pub fn parse(msg: &str) -> WpaEvent {
+        let msg = msg.trim_end();
+        let msg = match (msg.starts_with('<'), msg.find('>')) {
+            (true, Some(end)) => &msg[(end + 1)..],
+            _ => msg,
+        };
+        let (name, rest) = match msg.find(' ') {
+            Some(space) => (&msg[..space], &msg[(space + 1)..]),
+            None => (msg, ""),
+        };
+        let args = Args::new(rest);
+        match name {
+            // - Connection to 02:00:00:00:01:00 completed [id=0 id_str=]
+            "CTRL-EVENT-CONNECTED" => WpaEvent::Connected {
+                bssid: rest.split(' ').find_map(parse_mac),
+                id: rest
+                    .find("[id=")
+                    .and_then(|start| Args::new(&rest[(start + 1)..]).num("id")),
+            },
+            "CTRL-EVENT-DISCONNECTED" => WpaEvent::Disconnected {
+                bssid: args.get("bssid").and_then(parse_mac),
+                reason: ReasonCode::from(args.num("reason").unwrap_or(0)),
+                locally_generated: args.get("locally_generated") == Some("1"),
+            },
+            "CTRL-EVENT-STATE-CHANGE" => {
+                match args.num("state").and_then(ConnectionState::from_number) {
+                    Some(state) => WpaEvent::StateChange {
+                        id: args
+                            .num("id")
+                            .filter(|id: &i64| *id >= 0)
+                            .map(|id| id as u32),
+                        state,
+                        bssid: args.get("BSSID").and_then(parse_mac),
+                        // the SSID comes last and may contain spaces
+                        ssid: rest
+                            .find(" SSID=")
+                            .map(|start| rest[(start + 6)..].to_owned()),
+                    },
+                    None => WpaEvent::Other(msg.to_owned()),
+                }
+            }
+            "CTRL-EVENT-SCAN-STARTED" => WpaEvent::ScanStarted,
+            "CTRL-EVENT-SCAN-RESULTS" => WpaEvent::ScanResults,
+            "CTRL-EVENT-SCAN-FAILED" => WpaEvent::ScanFailed,
+            "CTRL-EVENT-NETWORK-NOT-FOUND" => WpaEvent::NetworkNotFound,
+            "CTRL-EVENT-ASSOC-REJECT" => WpaEvent::AssocReject {
+                bssid: args.get("bssid").and_then(parse_mac),
+                status: StatusCode::from(args.num("status_code").unwrap_or(1)),
+            },
+            "CTRL-EVENT-SSID-TEMP-DISABLED" => WpaEvent::SsidTempDisabled {
+                id: args.num("id"),
+                reason: args.get("reason").map(str::to_owned),
+            },
+            "CTRL-EVENT-TERMINATING" => WpaEvent::Terminating,
+            _ => WpaEvent::Other(msg.to_owned()),
+        }
+    }
+}
+
+/// `name=value` arguments of an event, values may be quoted
+struct Args<'a>(Vec<(&'a str, &'a str)>);
+
+impl<'a> Args<'a> {
+    fn new(s: &'a str) -> Args<'a> {
+        let mut args = Vec::new();
+        let mut rest = s.trim_start();
+        while let Some(eq) = rest.find('=') {
+            let name = &rest[..eq];
+            let value = &rest[(eq + 1)..];
+            let (value, next) = if let Some(quoted) = value.strip_prefix('"') {
+                match quoted.find('"') {
+                    Some(end) => (&quoted[..end], &quoted[(end + 1)..]),
+                    None => (quoted, ""),
+                }
+            } else {
+                match value.find(' ') {
+                    Some(end) => (&value[..end], &value[end..]),
+                    None => (value, ""),
+                }
+            };
+            args.push((name, value));
+            rest = next.trim_start();
+        }
+        Args(args)
+    }
+
+    fn get(&self, name: &str) -> Option<&'a str> {
+        self.0.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+    }
+
+    fn num<N: std::str::FromStr>(&self, name: &str) -> Option<N> {
+        self.get(name).and_then(|value| value.parse().ok())
+    }
+}
+
+/// A client of the control interface of wpa_supplicant, over the UNIX
+/// datagram socket of one of its interfaces
+pub struct WpaCtrl {
+    socket: UnixDatagram,
+    /// bound for the replies, removed on drop
+    local: PathBuf,
+    /// events received while waiting for replies
+    events: VecDeque<WpaEvent>,
+    /// see `state`
+    state: ConnectionState,
+    /// network added by `connect`, replaced by the next one
+    network: Option<u32>,
+}
+
+impl WpaCtrl {
+    /// Open the control socket of the interface `ifname` in `CTRL_DIR`
+    pub fn open(ifname: &str) -> io::Result<WpaCtrl> {
+        WpaCtrl::open_path(Path::new(CTRL_DIR).join(ifname))
+    }
+
+    pub fn open_path<P: AsRef<Path>>(path: P) -> io::Result<WpaCtrl> {
+        let local = std::env::temp_dir().join(format!(
+            "glow_wpa_ctrl_{}-{}",
+            std::process::id(),
+            LOCAL_SOCKETS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_file(&local);
+        let socket = UnixDatagram::bind(&local)?;
+        let ctrl = WpaCtrl {
+            socket,
+            local,
+            events: VecDeque::new(),
+            state: ConnectionState::Disconnected,
+            network: None,
+        };
+        ctrl.socket.connect(path)?;
+        ctrl.socket.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        Ok(ctrl)
+    }
+
+    /// Time out requests and `recv_event` after `timeout`, never with none
+    pub fn timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Send `cmd` and wait for its reply, queueing the events received
+    /// meanwhile for `recv_event`
+    pub fn request(&mut self, cmd: &str) -> io::Result<String> {
+        self.drain()?;
+        self.socket.send(cmd.as_bytes())?;
+        loop {
+            let msg = self.recv()?;
+            if msg.starts_with('<') {
+                let event = self.apply(WpaEvent::parse(&msg));
+                self.events.push_back(event);
+                continue;
+            }
+            return Ok(msg);
+        }
+    }
+
+    /// drop the late replies of timed out requests, which would be taken for
+    /// the reply of the next one, queueing the events among them
+    fn drain(&mut self) -> io::Result<()> {
+        self.socket.set_nonblocking(true)?;
+        let drained = loop {
+            match self.recv() {
+                Ok(msg) if msg.starts_with('<') => {
+                    let event = self.apply(WpaEvent::parse(&msg));
+                    self.events.push_back(event);
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.socket.set_nonblocking(false)?;
+        drained
+    }
+
+    /// Send `cmd`, expecting OK
+    fn command(&mut self, cmd: &str, name: &str) -> io::Result<()> {
+        let reply = self.request(cmd)?;
+        if reply.trim_end() != "OK" {
+            return Err(failed(name, &reply));
+        }
+        Ok(())
+    }
+
+    /// Receive the events of wpa_supplicant, see `recv_event`
+    pub fn attach(&mut self) -> io::Result<()> {
+        self.command("ATTACH", "ATTACH")
+    }
+
+    pub fn detach(&mut self) -> io::Result<()> {
+        self.command("DETACH", "DETACH")
+    }
+
+    /// Add a disabled network, returning its id
+    pub fn add_network(&mut self) -> io::Result<u32> {
+        let reply = self.request("ADD_NETWORK")?;
+        reply
+            .trim_end()
+            .parse()
+            .map_err(|_| failed("ADD_NETWORK", &reply))
+    }
+
+    /// Set the variable `name` of the network `id`, strings are quoted or
+    /// hex encoded in `value`
+    pub fn set_network(&mut self, id: u32, name: &str, value: &str) -> io::Result<()> {
+        // keep the value, possibly a passphrase, out of the error
+        self.command(
+            &format!("SET_NETWORK {} {} {}", id, name, value),
+            &format!("SET_NETWORK {} {}", id, name),
+        )
+    }
+
+    /// Connect to the network `id`, disabling the others
+    pub fn select_network(&mut self, id: u32) -> io::Result<()> {
+        self.command(&format!("SELECT_NETWORK {}", id), "SELECT_NETWORK")
+    }
+
+    pub fn remove_network(&mut self, id: u32) -> io::Result<()> {
+        self.command(&format!("REMOVE_NETWORK {}", id), "REMOVE_NETWORK")
+    }
+
+    /// Disconnect and stay disconnected until a network is selected
+    pub fn disconnect(&mut self) -> io::Result<()> {
+        self.command("DISCONNECT", "DISCONNECT")
+    }
+
+    /// Request a scan, reported by `WpaEvent::ScanResults`
+    pub fn scan(&mut self) -> io::Result<()> {
+        self.command("SCAN", "SCAN")
+    }
+
+    pub fn scan_results(&mut self) -> io::Result<Vec<ScanResult>> {
+        let reply = self.request("SCAN_RESULTS")?;
+        let mut results = Vec::new();
+        // after the header, bssid / frequency / signal level / flags / ssid
+        for line in reply.lines().skip(1) {
+            let fields: Vec<&str> = line.splitn(5, '\t').collect();
+            let result = match fields[..] {
+                [bssid, freq, signal, flags, ssid] => parse_mac(bssid).and_then(|bssid| {
+                    Some(ScanResult {
+                        bssid,
+                        freq: freq.parse().ok()?,
+                        signal: signal.parse().ok()?,
+                        flags: flags
+                            .split(['[', ']'])
+                            .filter(|flag| !flag.is_empty())
+                            .map(str::to_owned)
+                            .collect(),
+                        ssid: ssid.to_owned(),
+                    })
+                }),
+                _ => None,
+            };
+            results.push(result.ok_or_else(|| failed("SCAN_RESULTS", line))?);
+        }
+        Ok(results)
+    }
+
+    /// The state of the connection, updating `state`
+    pub fn status(&mut self) -> io::Result<Status> {
+        let reply = self.request("STATUS")?;
+        let lines: Vec<(&str, &str)> = reply
+            .lines()
+            .filter_map(|line| {
+                let eq = line.find('=')?;
+                Some((&line[..eq], &line[(eq + 1)..]))
+            })
+            .collect();
+        let get = |name: &str| {
+            lines
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| *value)
+        };
+        let wpa_state = get("wpa_state")
+            .and_then(|state| state.parse().ok())
+            .ok_or_else(|| failed("STATUS", &reply))?;
+        self.state = wpa_state;
+        Ok(Status {
+            wpa_state,
+            bssid: get("bssid").and_then(parse_mac),
+            freq: get("freq").and_then(|freq| freq.parse().ok()),
+            ssid: get("ssid").map(str::to_owned),
+            id: get("id").and_then(|id| id.parse().ok()),
+            key_mgmt: get("key_mgmt").map(str::to_owned),
+            address: get("address").and_then(parse_mac),
+            ip_address: get("ip_address").and_then(|ip| ip.parse().ok()),
+        })
+    }
+
+    /// Wait for the next event once attached
+    pub fn recv_event(&mut self) -> io::Result<WpaEvent> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(event);
+        }
+        loop {
+            let msg = self.recv()?;
+            // a late reply of a timed out request
+            if !msg.starts_with('<') {
+                continue;
+            }
+            return Ok(self.apply(WpaEvent::parse(&msg)));
+        }
+    }
+
+    /// The state of the connection as of the last STATUS, followed by the
+    /// events received since: CONNECTED completes it, DISCONNECTED and
+    /// ASSOC-REJECT end it. Only Android builds report the states in between
+    /// with STATE-CHANGE, `status` refreshes it otherwise.
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    fn recv(&self) -> io::Result<String> {
+        let mut buf = vec![0; REPLY_LEN];
+        let len = self.socket.recv(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+    }
+
+    /// advance `state` by `event`
+    fn apply(&mut self, event: WpaEvent) -> WpaEvent {
+        match &event {
+            WpaEvent::StateChange { state, .. } => self.state = *state,
+            WpaEvent::Connected { .. } => self.state = ConnectionState::Completed,
+            WpaEvent::Disconnected { .. } | WpaEvent::AssocReject { .. } => {
+                self.state = ConnectionState::Disconnected
+            }
+            _ => {}
+        }
+        event
+    }
+}
+
+impl Drop for WpaCtrl {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.local);
+    }
+}
+
+impl Connector for WpaCtrl {
+    type Error = io::Error;
+
+    /// Add the network of `params` and select it, replacing the one of the
+    /// previous `connect`; its IEs are left to wpa_supplicant
+    fn connect(&mut self, params: &ConnectParams) -> io::Result<()> {
+        let variables =
+            network(params).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if let Some(id) = self.network.take() {
+            self.remove_network(id)?;
+        }
+        let id = self.add_network()?;
+        self.network = Some(id);
+        for (name, value) in variables.iter() {
+            let value = match value {
+                NetworkValue::Str(s) => format!("\"{}\"", s),
+                NetworkValue::Raw(s) => s.clone(),
+                NetworkValue::Bytes(bytes) => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+                NetworkValue::Int(n) => n.to_string(),
+            };
+            self.set_network(id, name, &value)?;
+        }
+        self.select_network(id)
+    }
+
+    /// Disconnect and remove the network of `connect`, wpa_supplicant
+    /// always deauthenticates as leaving
+    fn disconnect(&mut self, _reason: ReasonCode) -> io::Result<()> {
+        WpaCtrl::disconnect(self)?;
+        if let Some(id) = self.network.take() {
+            self.remove_network(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// a MAC address like 02:00:00:00:01:00
+fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut octets = s.split(':');
+    for octet in mac.iter_mut() {
+        *octet = u8::from_str_radix(octets.next()?, 16).ok()?;
+    }
+    match octets.next() {
+        Some(_) => None,
+        None => Some(mac),
+    }
+}
+
+fn failed(cmd: &str, reply: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("{} failed: {}", cmd, reply.trim_end()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::{self, JoinHandle};
+
+    /// A fake wpa_supplicant replying to each command received with the
+    /// messages of `script` in turn, returning the commands
+    fn server(name: &str, script: Vec<Vec<&'static str>>) -> (PathBuf, JoinHandle<Vec<String>>) {
+        let path = std::env::temp_dir().join(format!("glow-wpa-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let handle = thread::spawn(move || {
+            let mut commands = Vec::new();
+            let mut buf = [0; 4096];
+            for replies in script {
+                let (len, src) = socket.recv_from(&mut buf).unwrap();
+                commands.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+                for reply in replies {
+                    let dest = src.as_pathname().unwrap();
+                    socket.send_to(reply.as_bytes(), dest).unwrap();
+                }
+            }
+            commands
+        });
+        (path, handle)
+    }
+
+    #[test]
+    fn test_parse_event() {
+        assert_eq!(
+            WpaEvent::parse(
+                "<3>CTRL-EVENT-CONNECTED - Connection to 02:00:00:00:01:00 completed [id=0 id_str=]"
+            ),
+            WpaEvent::Connected {
+                bssid: Some([2, 0, 0, 0, 1, 0]),
+                id: Some(0),
+            }
+        );
+        assert_eq!(
+            WpaEvent::parse(
+                "<3>CTRL-EVENT-DISCONNECTED bssid=02:00:00:00:01:00 reason=3 locally_generated=1"
+            ),
+            WpaEvent::Disconnected {
+                bssid: Some([2, 0, 0, 0, 1, 0]),
+                reason: ReasonCode::DeauthLeaving,
+                locally_generated: true,
+            }
+        );
+        assert_eq!(
+            WpaEvent::parse(
+                "<3>CTRL-EVENT-STATE-CHANGE id=-1 state=3 BSSID=00:00:00:00:00:00 SSID=glow net"
+            ),
+            WpaEvent::StateChange {
+                id: None,
+                state: ConnectionState::Scanning,
+                bssid: Some([0; 6]),
+                ssid: Some("glow net".to_owned()),
+            }
+        );
+        assert_eq!(
+            WpaEvent::parse(
+                "<3>CTRL-EVENT-SSID-TEMP-DISABLED id=1 ssid=\"glow net\" auth_failures=1 duration=10 reason=WRONG_KEY"
+            ),
+            WpaEvent::SsidTempDisabled {
+                id: Some(1),
+                reason: Some("WRONG_KEY".to_owned()),
+            }
+        );
+        assert_eq!(
+            WpaEvent::parse("<3>CTRL-EVENT-ASSOC-REJECT bssid=02:00:00:00:01:00 status_code=17"),
+            WpaEvent::AssocReject {
+                bssid: Some([2, 0, 0, 0, 1, 0]),
+                status: StatusCode::from(17),
+            }
+        );
+        assert_eq!(
+            WpaEvent::parse("<3>CTRL-EVENT-SCAN-RESULTS "),
+            WpaEvent::ScanResults
+        );
+        assert_eq!(
+            WpaEvent::parse("<2>Trying to associate with 02:00:00:00:01:00"),
+            WpaEvent::Other("Trying to associate with 02:00:00:00:01:00".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_requests() {
+        let (path, handle) = server(
+            "requests",
+            vec![
+                vec!["OK\n", "<3>CTRL-EVENT-SCAN-STARTED "],
+                vec![
+                    "<3>CTRL-EVENT-SCAN-RESULTS ",
+                    "bssid / frequency / signal level / flags / ssid\n\
+                     02:00:00:00:01:00\t2412\t-42\t[WPA2-PSK-CCMP][ESS]\tglow\n\
+                     02:00:00:00:02:00\t5180\t-71\t[ESS]\t\n",
+                ],
+                vec![
+                    "bssid=02:00:00:00:01:00\nfreq=2412\nssid=glow\nid=0\nmode=station\n\
+                     key_mgmt=WPA2-PSK\nwpa_state=COMPLETED\nip_address=192.168.1.2\n\
+                     address=02:00:00:00:00:01\n",
+                ],
+                vec!["FAIL\n"],
+            ],
+        );
+        let mut ctrl = WpaCtrl::open_path(&path).unwrap();
+        ctrl.scan().unwrap();
+        let results = ctrl.scan_results().unwrap();
+        assert_eq!(
+            results,
+            vec![
+                ScanResult {
+                    bssid: [2, 0, 0, 0, 1, 0],
+                    freq: 2412,
+                    signal: -42,
+                    flags: vec!["WPA2-PSK-CCMP".to_owned(), "ESS".to_owned()],
+                    ssid: "glow".to_owned(),
+                },
+                ScanResult {
+                    bssid: [2, 0, 0, 0, 2, 0],
+                    freq: 5180,
+                    signal: -71,
+                    flags: vec!["ESS".to_owned()],
+                    ssid: String::new(),
+                },
+            ]
+        );
+        // both events arrived with the replies
+        assert_eq!(ctrl.recv_event().unwrap(), WpaEvent::ScanStarted);
+        assert_eq!(ctrl.recv_event().unwrap(), WpaEvent::ScanResults);
+
+        let status = ctrl.status().unwrap();
+        assert_eq!(status.wpa_state, ConnectionState::Completed);
+        assert_eq!(status.bssid, Some([2, 0, 0, 0, 1, 0]));
+        assert_eq!(status.ssid, Some("glow".to_owned()));
+        assert_eq!(status.id, Some(0));
+        assert_eq!(status.ip_address, Some("192.168.1.2".parse().unwrap()));
+        assert_eq!(ctrl.state(), ConnectionState::Completed);
+
+        let err = ctrl.set_network(0, "psk", "\"secret\"").unwrap_err();
+        assert_eq!(err.to_string(), "SET_NETWORK 0 psk failed: FAIL");
+
+        assert_eq!(
+            handle.join().unwrap(),
+            vec![
+                "SCAN",
+                "SCAN_RESULTS",
+                "STATUS",
+                "SET_NETWORK 0 psk \"secret\""
+            ]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_late_reply() {
+        let path = std::env::temp_dir().join(format!("glow-wpa-{}-late", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let mut ctrl = WpaCtrl::open_path(&path).unwrap();
+        ctrl.timeout(Some(Duration::from_millis(50))).unwrap();
+
+        // the reply to SCAN comes after it timed out, before STATUS
+        let mut buf = [0; 64];
+        assert!(ctrl.scan().is_err());
+        let (_, src) = socket.recv_from(&mut buf).unwrap();
+        let src = src.as_pathname().unwrap().to_owned();
+        socket.send_to(b"OK\n", &src).unwrap();
+        socket
+            .send_to(b"<3>CTRL-EVENT-SCAN-STARTED ", &src)
+            .unwrap();
+        let handle = thread::spawn(move || {
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..len], b"ADD_NETWORK");
+            socket.send_to(b"1\n", &src).unwrap();
+        });
+        assert_eq!(ctrl.add_network().unwrap(), 1);
+        assert_eq!(ctrl.recv_event().unwrap(), WpaEvent::ScanStarted);
+        handle.join().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_connect() {
+        let ok = || vec!["OK\n"];
+        let (path, handle) = server(
+            "connect",
+            vec![
+                ok(),
+                vec!["0\n"],
+                ok(),
+                ok(),
+                ok(),
+                ok(),
+                ok(),
+                ok(),
+                ok(),
+                ok(),
+                vec![
+                    "OK\n",
+                    "<3>CTRL-EVENT-STATE-CHANGE id=0 state=7 BSSID=02:00:00:00:01:00 SSID=glow",
+                    "<3>CTRL-EVENT-ASSOC-REJECT bssid=02:00:00:00:01:00 status_code=17",
+                    "<3>CTRL-EVENT-CONNECTED - Connection to 02:00:00:00:01:00 completed [id=0 id_str=]",
+                ],
+                ok(),
+                ok(),
+            ],
+        );
+        let mut ctrl = WpaCtrl::open_path(&path).unwrap();
+        ctrl.attach().unwrap();
+
+        let mut params = ConnectParams::psk(b"glow");
+        params.bssid = Some([2, 0, 0, 0, 1, 0]);
+        assert!(Connector::connect(&mut ctrl, &params).is_err());
+        params.passphrase = Some("glow-passphrase".to_owned());
+        Connector::connect(&mut ctrl, &params).unwrap();
+
+        assert_eq!(ctrl.state(), ConnectionState::Disconnected);
+        match ctrl.recv_event().unwrap() {
+            WpaEvent::StateChange { state, .. } => {
+                assert_eq!(state, ConnectionState::FourWayHandshake)
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!(ctrl.state(), ConnectionState::FourWayHandshake);
+        // without STATE-CHANGE, as outside of Android
+        assert!(matches!(
+            ctrl.recv_event().unwrap(),
+            WpaEvent::AssocReject { .. }
+        ));
+        assert_eq!(ctrl.state(), ConnectionState::Disconnected);
+        assert!(matches!(
+            ctrl.recv_event().unwrap(),
+            WpaEvent::Connected { id: Some(0), .. }
+        ));
+        assert_eq!(ctrl.state(), ConnectionState::Completed);
+        Connector::disconnect(&mut ctrl, ReasonCode::DeauthLeaving).unwrap();
+
+        assert_eq!(
+            handle.join().unwrap(),
+            vec![
+                "ATTACH",
+                "ADD_NETWORK",
+                "SET_NETWORK 0 ssid 676c6f77",
+                "SET_NETWORK 0 bssid 02:00:00:00:01:00",
+                "SET_NETWORK 0 proto RSN",
+                "SET_NETWORK 0 key_mgmt WPA-PSK",
+                "SET_NETWORK 0 psk \"glow-passphrase\"",
+                "SET_NETWORK 0 pairwise CCMP",
+                "SET_NETWORK 0 group CCMP",
+                "SET_NETWORK 0 ieee80211w 1",
+                "SELECT_NETWORK 0",
+                "DISCONNECT",
+                "REMOVE_NETWORK 0",
+            ]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use crate::base::nl80211::attr::Nl80211Mfp;
+use crate::base::nl80211::ie::{AkmSuite, CipherSuite};
+use crate::base::nl80211::prelude::ConnectParams;
+use crate::base::nl80211::utils::format_mac;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod ctrl;
+
+/// State of the connection of a station, as `wpa_state` of wpa_supplicant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Disconnected,
+    /// the netdev is down or rfkilled
+    InterfaceDisabled,
+    /// no enabled network to connect to
+    Inactive,
+    Scanning,
+    Authenticating,
+    Associating,
+    Associated,
+    FourWayHandshake,
+    GroupHandshake,
+    Completed,
+}
+
+impl ConnectionState {
+    /// the state of its number in `enum wpa_states`, as sent by
+    /// CTRL-EVENT-STATE-CHANGE
+    pub fn from_number(state: u8) -> Option<ConnectionState> {
+        let state = match state {
+            0 => ConnectionState::Disconnected,
+            1 => ConnectionState::InterfaceDisabled,
+            2 => ConnectionState::Inactive,
+            3 => ConnectionState::Scanning,
+            4 => ConnectionState::Authenticating,
+            5 => ConnectionState::Associating,
+            6 => ConnectionState::Associated,
+            7 => ConnectionState::FourWayHandshake,
+            8 => ConnectionState::GroupHandshake,
+            9 => ConnectionState::Completed,
+            _ => return None,
+        };
+        Some(state)
+    }
+
+    /// whether data frames can pass
+    pub fn is_connected(&self) -> bool {
+        *self == ConnectionState::Completed
+    }
+}
+
+impl FromStr for ConnectionState {
+    type Err = String;
+
+    /// the upper case names of STATUS and the lower case ones of the D-Bus API
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let state = match s.to_ascii_uppercase().as_str() {
+            "DISCONNECTED" => ConnectionState::Disconnected,
+            "INTERFACE_DISABLED" => ConnectionState::InterfaceDisabled,
+            "INACTIVE" => ConnectionState::Inactive,
+            "SCANNING" => ConnectionState::Scanning,
+            "AUTHENTICATING" => ConnectionState::Authenticating,
+            "ASSOCIATING" => ConnectionState::Associating,
+            "ASSOCIATED" => ConnectionState::Associated,
+            "4WAY_HANDSHAKE" => ConnectionState::FourWayHandshake,
+            "GROUP_HANDSHAKE" => ConnectionState::GroupHandshake,
+            "COMPLETED" => ConnectionState::Completed,
+            _ => return Err(format!("unknown connection state {}", s)),
+        };
+        Ok(state)
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ConnectionState::Disconnected => "DISCONNECTED",
+            ConnectionState::InterfaceDisabled => "INTERFACE_DISABLED",
+            ConnectionState::Inactive => "INACTIVE",
+            ConnectionState::Scanning => "SCANNING",
+            ConnectionState::Authenticating => "AUTHENTICATING",
+            ConnectionState::Associating => "ASSOCIATING",
+            ConnectionState::Associated => "ASSOCIATED",
+            ConnectionState::FourWayHandshake => "4WAY_HANDSHAKE",
+            ConnectionState::GroupHandshake => "GROUP_HANDSHAKE",
+            ConnectionState::Completed => "COMPLETED",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A value of a variable of a network block of wpa_supplicant
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkValue {
+    /// a string to quote, e.g. a passphrase
+    Str(String),
+    /// a keyword or address taken as is, e.g. WPA-PSK
+    Raw(String),
+    Bytes(Vec<u8>),
+    Int(u32),
+}
+
+/// The variables of the network block of `params`, shared by the backends
+/// driving wpa_supplicant
+pub fn network(params: &ConnectParams) -> Result<Vec<(&'static str, NetworkValue)>, String> {
+    let mut variables = vec![("ssid", NetworkValue::Bytes(params.ssid.clone()))];
+    if let Some(bssid) = params.bssid {
+        variables.push(("bssid", NetworkValue::Raw(format_mac(&bssid))));
+    }
+    if let Some(freq) = params.freq {
+        variables.push(("freq_list", NetworkValue::Raw(freq.to_string())));
+    }
+
+    let rsn = match &params.rsn {
+        Some(rsn) => rsn,
+        None => {
+            variables.push(("key_mgmt", NetworkValue::Raw("NONE".to_owned())));
+            return Ok(variables);
+        }
+    };
+    let (key_mgmt, password) = match rsn.akms().first() {
+        Some(AkmSuite::Psk) => ("WPA-PSK", Some("psk")),
+        Some(AkmSuite::PskSha256) => ("WPA-PSK-SHA256", Some("psk")),
+        Some(AkmSuite::Sae) => ("SAE", Some("sae_password")),
+        Some(AkmSuite::Owe) => ("OWE", None),
+        akm => return Err(format!("unsupported AKM {:?} for wpa_supplicant", akm)),
+    };
+    variables.push(("proto", NetworkValue::Raw("RSN".to_owned())));
+    variables.push(("key_mgmt", NetworkValue::Raw(key_mgmt.to_owned())));
+    if let Some(name) = password {
+        let passphrase = params
+            .passphrase
+            .as_ref()
+            .ok_or_else(|| format!("{} network without passphrase", key_mgmt))?;
+        variables.push((name, NetworkValue::Str(passphrase.clone())));
+    }
+    let pairwise = rsn
+        .pairwise()
+        .into_iter()
+        .map(cipher_name)
+        .collect::<Result<Vec<_>, String>>()?;
+    variables.push(("pairwise", NetworkValue::Raw(pairwise.join(" "))));
+    if let Some(group) = rsn.group_cipher {
+        variables.push(("group", NetworkValue::Raw(cipher_name(group)?.to_owned())));
+    }
+    let ieee80211w = match params.mfp {
+        Nl80211Mfp::MfpOptional => 1,
+        Nl80211Mfp::MfpRequired => 2,
+        _ => 0,
+    };
+    variables.push(("ieee80211w", NetworkValue::Int(ieee80211w)));
+    Ok(variables)
+}
+
+fn cipher_name(cipher: CipherSuite) -> Result<&'static str, String> {
+    match cipher {
+        CipherSuite::Ccmp128 => Ok("CCMP"),
+        CipherSuite::Gcmp128 => Ok("GCMP"),
+        CipherSuite::Ccmp256 => Ok("CCMP-256"),
+        CipherSuite::Gcmp256 => Ok("GCMP-256"),
+        CipherSuite::Tkip => Ok("TKIP"),
+        cipher => Err(format!(
+            "unsupported cipher {:?} for wpa_supplicant",
+            cipher
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_state() {
+        assert_eq!("COMPLETED".parse(), Ok(ConnectionState::Completed));
+        assert_eq!(
+            "4way_handshake".parse(),
+            Ok(ConnectionState::FourWayHandshake)
+        );
+        assert!("ROAMING".parse::<ConnectionState>().is_err());
+        assert_eq!(
+            ConnectionState::from_number(9),
+            Some(ConnectionState::Completed)
+        );
+        assert_eq!(ConnectionState::from_number(10), None);
+        assert_eq!(
+            ConnectionState::GroupHandshake.to_string(),
+            "GROUP_HANDSHAKE"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use glow_utils::u8_merge;
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::consts::rtnl::{Ifa, IfaF, IfaFFlags, RtAddrFamily, Rtm};
+use neli::consts::socket::NlFamily;
+use neli::err::NlError;
+use neli::nl::{NlPayload, Nlmsghdr};
+use neli::rtnl::Ifaddrmsg;
+use neli::socket::NlSocketHandle;
+use neli::types::RtBuffer;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// an address assigned to a local link
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct HostAddr {
+    pub addr: IpAddr,
+    pub prefix_len: u8,
+    /// index of the link
+    pub index: u32,
+}
+
+/// list usable addresses of all links, blocking on a route netlink
+/// socket so that it can be called inside and outside of a runtime
+pub fn host_addrs() -> Result<Vec<HostAddr>, NlError> {
+    let mut socket = NlSocketHandle::connect(NlFamily::Route, None, &[])?;
+    let request = Ifaddrmsg {
+        ifa_family: RtAddrFamily::Unspecified,
+        ifa_prefixlen: 0,
+        ifa_flags: IfaFFlags::empty(),
+        ifa_scope: 0,
+        ifa_index: 0,
+        rtattrs: RtBuffer::new(),
+    };
+    socket.send(Nlmsghdr::new(
+        None,
+        Rtm::Getaddr,
+        NlmFFlags::new(&[NlmF::Request, NlmF::Dump]),
+        None,
+        None,
+        NlPayload::Payload(request),
+    ))?;
+
+    let mut addrs = Vec::new();
+    for msg in socket.iter::<Ifaddrmsg>(false) {
+        let msg = msg?;
+        let msg = match msg.nl_payload {
+            NlPayload::Payload(msg) => msg,
+            NlPayload::Err(e) => return Err(NlError::from(e)),
+            _ => continue,
+        };
+        // such addresses can not be used yet
+        if msg.ifa_flags.contains(&IfaF::Dadfailed) || msg.ifa_flags.contains(&IfaF::Tentative) {
+            continue;
+        }
+        for attr in msg.rtattrs.iter() {
+            if attr.rta_type != Ifa::Address {
+                continue;
+            }
+            if let Some(addr) = ip_from_bytes(attr.rta_payload.as_ref()) {
+                addrs.push(HostAddr {
+                    addr,
+                    prefix_len: msg.ifa_prefixlen,
+                    index: msg.ifa_index as u32,
+                });
+            }
+        }
+    }
+    Ok(addrs)
+}
+
+/// address from its 4 or 16 network order octets
+pub(crate) fn ip_from_bytes(addr: &[u8]) -> Option<IpAddr> {
+    match addr.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(
+            addr[0], addr[1], addr[2], addr[3],
+        ))),
+        16 => Some(IpAddr::V6(Ipv6Addr::new(
+            u8_merge!(addr[0], addr[1]),
+            u8_merge!(addr[2], addr[3]),
+            u8_merge!(addr[4], addr[5]),
+            u8_merge!(addr[6], addr[7]),
+            u8_merge!(addr[8], addr[9]),
+            u8_merge!(addr[10], addr[11]),
+            u8_merge!(addr[12], addr[13]),
+            u8_merge!(addr[14], addr[15]),
+        ))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_host_addrs_in_runtime() {
+        let addrs = host_addrs().unwrap();
+        assert!(addrs
+            .iter()
+            .any(|a| a.addr == IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    }
+}
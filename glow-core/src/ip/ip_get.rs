@@ -1,12 +1,14 @@
+use super::addr::ip_from_bytes;
+use crate::base::dns::sync::DNSClient;
 use futures::stream::TryStreamExt;
-use glow_utils::u8_merge;
 use rtnetlink::packet::nlas::address::Nla;
 use rtnetlink::packet::AddressMessage;
 use rtnetlink::{new_connection, Error};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
+/// list addresses of all links,
+/// annotate them with PTR names when `resolver` is provided
 #[tokio::main]
-pub async fn ip_get() -> Result<(), Error> {
+pub async fn ip_get(resolver: Option<&DNSClient>) -> Result<(), Error> {
     let (connection, handle, _) = new_connection().unwrap();
     tokio::spawn(connection);
 
@@ -23,28 +25,7 @@ pub async fn ip_get() -> Result<(), Error> {
 
             for nla in msg.nlas.iter() {
                 if let Some(addr) = match nla {
-                    Nla::Address(addr) => {
-                        if addr.len() == 4 {
-                            // IPv4
-                            Some(IpAddr::V4(Ipv4Addr::new(
-                                addr[0], addr[1], addr[2], addr[3],
-                            )))
-                        } else if addr.len() == 16 {
-                            // IPv6
-                            Some(IpAddr::V6(Ipv6Addr::new(
-                                u8_merge!(addr[0], addr[1]),
-                                u8_merge!(addr[2], addr[3]),
-                                u8_merge!(addr[4], addr[5]),
-                                u8_merge!(addr[6], addr[7]),
-                                u8_merge!(addr[8], addr[9]),
-                                u8_merge!(addr[10], addr[11]),
-                                u8_merge!(addr[12], addr[13]),
-                                u8_merge!(addr[14], addr[15]),
-                            )))
-                        } else {
-                            None
-                        }
-                    }
+                    Nla::Address(addr) => ip_from_bytes(addr),
                     Nla::Label(l) => {
                         println!("{}", l);
                         None
@@ -55,7 +36,12 @@ pub async fn ip_get() -> Result<(), Error> {
                     }
                 } {
                     addr_count += 1;
-                    println!("{}", addr.to_string());
+                    match resolver.and_then(|r| r.reverse(addr).ok()) {
+                        Some(names) if !names.is_empty() => {
+                            println!("{} ({})", addr, names.join(", "))
+                        }
+                        _ => println!("{}", addr),
+                    }
                 }
             }
         }
@@ -1,2 +1,4 @@
+mod addr;
 mod ip_get;
+pub use addr::*;
 pub use ip_get::*;
@@ -22,6 +22,7 @@ pub fn write_file<P: AsRef<Path>>(file_path: P, content: &str) -> Result<(), Err
     let mut file = fs::OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(file_path.as_ref())
         .map_err(|e| Error::FileOpen(e))?;
     file.write_all(content.as_bytes()).map_err(|e| Error::FileWrite(e))?;
@@ -26,3 +26,27 @@ impl Singleton {
         let _ = remove_file(&self.pid_file);
     }
 }
+
+impl Drop for Singleton {
+    /// the pid file is also removed when `main` returns early with an error
+    fn drop(&mut self) {
+        self.exit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop() {
+        let path = std::env::temp_dir().join(format!("glow-{}.lock", std::process::id()));
+        let path = path.to_str().unwrap();
+        {
+            let _singleton = Singleton::init(path).unwrap();
+            assert!(Singleton::init(path).is_err());
+        }
+        assert!(!Path::new(path).exists());
+        Singleton::init(path).unwrap();
+    }
+}
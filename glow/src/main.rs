@@ -1,8 +1,126 @@
 use clap::{App, Arg, SubCommand};
 use glow_common::Result;
+use glow_core::base::dns::blocklist::{BlockAction, Blocklist};
+use glow_core::base::dns::dns64::Dns64;
+use glow_core::base::dns::querylog::QueryLog;
+use glow_core::base::dns::server::DNSServer;
+use glow_core::base::dns::sync::DNSClient;
+use glow_core::base::dns::zone::Zone;
+use glow_core::base::nl80211::client::NL80211Client;
+use glow_core::base::nl80211::prelude::{
+    rank_channels, ConnectParams, Connector, ReasonCode, StatusCode,
+};
+use glow_core::base::nl80211::utils::format_mac;
+use glow_core::base::wpa::ctrl::{WpaCtrl, WpaEvent};
 use glow_utils::Singleton;
 use nix::unistd::{geteuid, ROOT};
+use std::fmt::Display;
+use std::io;
 use std::process::exit;
+use std::time::{Duration, Instant};
+use wpa_dbus::{Change, WpaDbus};
+
+mod wpa_dbus;
+
+/// Time to wait for wpa_supplicant to connect, from start to end
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connect by either backend of wpa_supplicant, `wait` until connected,
+/// and remove the network again if that fails, which wpa_supplicant
+/// would keep trying otherwise
+fn connect<C, F>(connector: &mut C, params: &ConnectParams, wait: F) -> Result<()>
+where
+    C: Connector,
+    C::Error: Display,
+    F: FnOnce(&mut C) -> Result<()>,
+{
+    connector.connect(params).map_err(|e| e.to_string())?;
+    let result = wait(connector);
+    if result.is_err() {
+        if let Err(e) = connector.disconnect(ReasonCode::DeauthLeaving) {
+            eprintln!("failed to remove the network: {}", e);
+        }
+    }
+    result
+}
+
+/// wait for the control socket to report the connection until `deadline`
+fn wait_ctrl(ctrl: &mut WpaCtrl, deadline: Instant) -> Result<()> {
+    while !ctrl.state().is_connected() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err("connecting timed out".to_owned());
+        }
+        ctrl.timeout(Some(left)).map_err(|e| e.to_string())?;
+        let event = match ctrl.recv_event() {
+            Ok(event) => event,
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                return Err("connecting timed out".to_owned())
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        match event {
+            WpaEvent::StateChange { state, .. } => println!("{}", state),
+            WpaEvent::SsidTempDisabled { reason, .. } => {
+                return Err(format!("connecting failed: {}", reason.unwrap_or_default()))
+            }
+            WpaEvent::AssocReject { status, .. } => {
+                return Err(format!("association rejected: {}", status))
+            }
+            WpaEvent::NetworkNotFound => return Err("network not found".to_owned()),
+            WpaEvent::Disconnected {
+                reason,
+                locally_generated,
+                ..
+            } => disconnected(reason, locally_generated)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// wait for the D-Bus properties to report the connection until `deadline`
+fn wait_dbus(supplicant: &mut WpaDbus, deadline: Instant) -> Result<()> {
+    while !supplicant.state().is_connected() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let change = match supplicant.wait_change(left).map_err(|e| e.to_string())? {
+            Some(change) => change,
+            None => return Err("connecting timed out".to_owned()),
+        };
+        match change {
+            Change::State(state) => println!("{}", state),
+            Change::AuthStatusCode(code) if code != 0 => {
+                return Err(format!(
+                    "authentication failed: {}",
+                    StatusCode::from(code as u16)
+                ))
+            }
+            Change::AssocStatusCode(code) if code != 0 => {
+                return Err(format!(
+                    "association rejected: {}",
+                    StatusCode::from(code as u16)
+                ))
+            }
+            // negated when locally generated, 0 before any
+            Change::DisconnectReason(reason) if reason != 0 => {
+                disconnected(ReasonCode::from(reason.unsigned_abs() as u16), reason < 0)?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// the failure of a disconnect while connecting, wpa_supplicant leaves the
+/// network it was connected to before with reason 3 on its own
+fn disconnected(reason: ReasonCode, locally_generated: bool) -> Result<()> {
+    if locally_generated && reason == ReasonCode::DeauthLeaving {
+        return Ok(());
+    }
+    Err(format!("disconnected: {}", reason))
+}
 
 fn main() -> Result<()> {
     // Use geteuid here to follow setuid user
@@ -20,18 +138,353 @@ fn main() -> Result<()> {
                 .help("Path of configuration files")
                 .default_value("/var/lib/glow"),
         )
-        .subcommand(SubCommand::with_name("ip").help("IP configs"))
+        .subcommand(
+            SubCommand::with_name("ip")
+                .help("IP configs")
+                .arg(
+                    Arg::with_name("resolve")
+                        .long("resolve")
+                        .help("Annotate addresses with their PTR names"),
+                )
+                .arg(
+                    Arg::with_name("upstream")
+                        .long("upstream")
+                        .takes_value(true)
+                        .help("DNS server used by --resolve, defaults to /etc/resolv.conf"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dns")
+                .help("Run the DNS forwarder")
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:53")
+                        .help("Address to serve DNS on"),
+                )
+                .arg(
+                    Arg::with_name("upstream")
+                        .long("upstream")
+                        .takes_value(true)
+                        .help("DNS server to forward to, defaults to /etc/resolv.conf"),
+                )
+                .arg(
+                    Arg::with_name("zone")
+                        .long("zone")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(
+                            "Zone file to serve authoritatively, names are relative to its $ORIGIN",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("blocklist")
+                        .long("blocklist")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Hosts-format or domain list of names to block"),
+                )
+                .arg(
+                    Arg::with_name("block-mode")
+                        .long("block-mode")
+                        .takes_value(true)
+                        .default_value("nxdomain")
+                        .help("Answer for blocked names: nxdomain, null or <ipv4>[,<ipv6>]"),
+                )
+                .arg(
+                    Arg::with_name("dns64")
+                        .long("dns64")
+                        .takes_value(true)
+                        .min_values(0)
+                        .help(
+                            "Synthesize AAAA records with this NAT64 prefix, default 64:ff9b::/96",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("dns64-exclude")
+                        .long("dns64-exclude")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Network or domain excluded from DNS64 synthesis"),
+                )
+                .arg(
+                    Arg::with_name("querylog")
+                        .long("querylog")
+                        .takes_value(true)
+                        .help("Write JSON query log to this file, - for stdout"),
+                )
+                .arg(
+                    Arg::with_name("querylog-size")
+                        .long("querylog-size")
+                        .takes_value(true)
+                        .default_value("10485760")
+                        .help("Rotate the query log file after this many bytes"),
+                )
+                .arg(
+                    Arg::with_name("metrics")
+                        .long("metrics")
+                        .takes_value(true)
+                        .help("Serve Prometheus metrics on this address, e.g. 127.0.0.1:9153"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("wifi")
+                .help("Wireless devices")
+                .subcommand(
+                    SubCommand::with_name("scan")
+                        .help("Scan for networks and list them by signal strength")
+                        .arg(
+                            Arg::with_name("dev")
+                                .required(true)
+                                .help("Wireless interface to scan on, e.g. wlan0"),
+                        )
+                        .arg(
+                            Arg::with_name("freq")
+                                .long("freq")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .help("Frequency in MHz to scan, all allowed ones by default"),
+                        )
+                        .arg(
+                            Arg::with_name("passive")
+                                .long("passive")
+                                .help("Listen for beacons without sending probe requests"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("channels")
+                        .help("Rank the allowed channels by utilization and nearby networks")
+                        .arg(
+                            Arg::with_name("dev")
+                                .required(true)
+                                .help("Wireless interface to survey on, e.g. wlan0"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("connect")
+                        .help("Connect to a network through wpa_supplicant")
+                        .arg(
+                            Arg::with_name("dev")
+                                .required(true)
+                                .help("Wireless interface to connect, e.g. wlan0"),
+                        )
+                        .arg(
+                            Arg::with_name("ssid")
+                                .required(true)
+                                .help("SSID of the network"),
+                        )
+                        .arg(
+                            Arg::with_name("passphrase")
+                                .long("passphrase")
+                                .takes_value(true)
+                                .help("Passphrase of a WPA2/WPA3-Personal network"),
+                        )
+                        .arg(
+                            Arg::with_name("sae")
+                                .long("sae")
+                                .requires("passphrase")
+                                .help("Authenticate by SAE of WPA3-Personal instead of PSK"),
+                        )
+                        .arg(
+                            Arg::with_name("backend")
+                                .long("backend")
+                                .takes_value(true)
+                                .possible_values(&["dbus", "ctrl"])
+                                .default_value("dbus")
+                                .help("Drive wpa_supplicant over D-Bus or its control socket"),
+                        ),
+                ),
+        )
         .get_matches();
 
     let lib_path = matches.value_of("lib").unwrap();
 
-    let singleton = Singleton::init(&format!("{}/glow.lock", lib_path.to_owned()))?;
+    // removes the lock file when dropped
+    let _singleton = Singleton::init(&format!("{}/glow.lock", lib_path.to_owned()))
+        .map_err(|e| e.to_string())?;
 
-    if let Some(_) = matches.subcommand_matches("ip") {
+    if let Some(matches) = matches.subcommand_matches("ip") {
         // TODO: ip subcommand
-        glow_core::ip::ip_get().unwrap();
+        let resolver = if matches.is_present("resolve") {
+            let mut client = DNSClient::system("0.0.0.0:0").map_err(|e| e.to_string())?;
+            if let Some(upstream) = matches.value_of("upstream") {
+                client.upstream(upstream);
+            }
+            Some(client)
+        } else {
+            None
+        };
+        glow_core::ip::ip_get(resolver.as_ref()).unwrap();
+    }
+    if let Some(matches) = matches.subcommand_matches("dns") {
+        let mut client = DNSClient::system("0.0.0.0:0").map_err(|e| e.to_string())?;
+        if let Some(upstream) = matches.value_of("upstream") {
+            client.upstream(upstream);
+        }
+
+        let mut server = DNSServer::new(matches.value_of("listen").unwrap(), client);
+        for path in matches.values_of("zone").into_iter().flatten() {
+            let zone = Zone::load(path, ".").map_err(|e| format!("{}: {}", path, e))?;
+            server.zone(zone);
+        }
+        if let Some(paths) = matches.values_of("blocklist") {
+            let paths: Vec<&str> = paths.collect();
+            let list = Blocklist::load(&paths).map_err(|e| e.to_string())?;
+            let action: BlockAction = matches.value_of("block-mode").unwrap().parse()?;
+            server.blocklist(list, action);
+        }
+        if matches.is_present("dns64") {
+            let mut dns64: Dns64 = match matches.value_of("dns64") {
+                Some(prefix) => prefix.parse()?,
+                None => Dns64::default(),
+            };
+            for exclude in matches.values_of("dns64-exclude").into_iter().flatten() {
+                if exclude.contains('/') || exclude.parse::<std::net::IpAddr>().is_ok() {
+                    dns64.exclude(exclude)?;
+                } else {
+                    dns64.exclude_name(exclude);
+                }
+            }
+            server.dns64(dns64);
+        }
+        match matches.value_of("querylog") {
+            Some("-") => {
+                server.querylog(QueryLog::stdout());
+            }
+            Some(path) => {
+                let size = matches
+                    .value_of("querylog-size")
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| "invalid --querylog-size".to_owned())?;
+                server.querylog(QueryLog::file(path, size, 5).map_err(|e| e.to_string())?);
+            }
+            None => {}
+        }
+        if let Some(addr) = matches.value_of("metrics") {
+            server.metrics().serve(addr).map_err(|e| e.to_string())?;
+        }
+        server.serve().map_err(|e| e.to_string())?;
+    }
+    if let Some(matches) = matches.subcommand_matches("wifi") {
+        if let Some(matches) = matches.subcommand_matches("scan") {
+            let mut client = NL80211Client::new().map_err(|e| e.to_string())?;
+            client
+                .set_netdev(matches.value_of("dev").unwrap().to_owned())
+                .map_err(|e| e.to_string())?;
+            let mut freqs = Vec::new();
+            for freq in matches.values_of("freq").into_iter().flatten() {
+                freqs.push(
+                    freq.parse()
+                        .map_err(|_| format!("invalid --freq {}", freq))?,
+                );
+            }
+            let ssids: &[&[u8]] = if matches.is_present("passive") {
+                &[]
+            } else {
+                &[b""]
+            };
+            client
+                .trigger_scan(ssids, &freqs, &[])
+                .map_err(|e| e.to_string())?;
+
+            let mut results = client.scan_results().map_err(|e| e.to_string())?;
+            results.sort_by_key(|bss| std::cmp::Reverse(bss.signal_mbm));
+            for bss in results.iter() {
+                let signal = match bss.signal_mbm {
+                    Some(mbm) => format!("{:.2} dBm", mbm as f32 / 100.0),
+                    None => "-".to_owned(),
+                };
+                let ssid = match bss.ssid() {
+                    Some(ssid) => String::from_utf8_lossy(&ssid).into_owned(),
+                    None => String::new(),
+                };
+                println!(
+                    "{}  {:>4} MHz  {:>10}  {}",
+                    format_mac(&bss.bssid),
+                    bss.freq,
+                    signal,
+                    ssid
+                );
+            }
+        }
+        if let Some(matches) = matches.subcommand_matches("channels") {
+            let mut client = NL80211Client::new().map_err(|e| e.to_string())?;
+            client
+                .set_netdev(matches.value_of("dev").unwrap().to_owned())
+                .map_err(|e| e.to_string())?;
+            // scanning also fills the survey of every channel visited
+            client
+                .trigger_scan(&[b""], &[], &[])
+                .map_err(|e| e.to_string())?;
+            let bsses = client.scan_results().map_err(|e| e.to_string())?;
+            let surveys = client.survey_dump().map_err(|e| e.to_string())?;
+            // a self-managed wiphy follows its own regulatory domain
+            let dev = matches.value_of("dev").unwrap();
+            let wiphy = client
+                .interfaces()
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .find(|interface| interface.name == dev)
+                .map(|interface| interface.wiphy)
+                .ok_or_else(|| format!("{} is no wireless interface", dev))?;
+            let reg = client.reg_get_wiphy(wiphy).map_err(|e| e.to_string())?;
+
+            println!("channel  freq      busy  noise     bss  eirp     score");
+            for channel in rank_channels(&surveys, &bsses, &reg) {
+                let busy = match channel.utilization {
+                    Some(utilization) => format!("{:.0}%", utilization * 100.0),
+                    None => "-".to_owned(),
+                };
+                let noise = match channel.noise_dbm {
+                    Some(noise) => format!("{} dBm", noise),
+                    None => "-".to_owned(),
+                };
+                println!(
+                    "{:>7}  {:>4} MHz  {:>4}  {:>7}  {:>3}  {:>2} dBm  {:>5.1}{}",
+                    channel.channel,
+                    channel.freq,
+                    busy,
+                    noise,
+                    channel.bss_count,
+                    channel.max_eirp_mbm / 100,
+                    channel.score,
+                    if channel.dfs { "  DFS" } else { "" }
+                );
+            }
+        }
+        if let Some(matches) = matches.subcommand_matches("connect") {
+            let dev = matches.value_of("dev").unwrap();
+            let ssid = matches.value_of("ssid").unwrap().as_bytes();
+            let passphrase = matches.value_of("passphrase");
+            let mut params = match (passphrase, matches.is_present("sae")) {
+                (None, _) => ConnectParams::new(ssid),
+                (Some(_), false) => ConnectParams::psk(ssid),
+                (Some(_), true) => ConnectParams::sae(ssid),
+            };
+            params.passphrase = passphrase.map(str::to_owned);
+
+            if matches.value_of("backend") == Some("ctrl") {
+                let mut ctrl = WpaCtrl::open(dev).map_err(|e| e.to_string())?;
+                ctrl.attach().map_err(|e| e.to_string())?;
+                let deadline = Instant::now() + CONNECT_TIMEOUT;
+                connect(&mut ctrl, &params, |ctrl| wait_ctrl(ctrl, deadline))?;
+            } else {
+                let mut supplicant = WpaDbus::system(dev).map_err(|e| e.to_string())?;
+                let deadline = Instant::now() + CONNECT_TIMEOUT;
+                // fresh results let wpa_supplicant pick the BSS right away
+                supplicant.scan().map_err(|e| e.to_string())?;
+                connect(&mut supplicant, &params, |supplicant| {
+                    wait_dbus(supplicant, deadline)
+                })?;
+            }
+        }
     }
-    singleton.exit();
 
     Ok(())
 }
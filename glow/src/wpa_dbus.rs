@@ -0,0 +1,455 @@
+use dbus::arg::{AppendAll, ReadAll, RefArg, Variant};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use dbus::{Error, Message, Path};
+use glow_core::base::nl80211::prelude::{ConnectParams, Connector, ReasonCode};
+use glow_core::base::wpa::{network, ConnectionState, NetworkValue};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// References: wpa_supplicant/dbus/dbus_new.h, the fi.w1.wpa_supplicant1 API
+
+/// a{sv} arguments of methods and signals
+type PropMap = HashMap<String, Variant<Box<dyn RefArg>>>;
+
+/// Bus name, object path and interface of wpa_supplicant
+pub const SERVICE: &str = "fi.w1.wpa_supplicant1";
+const PATH: &str = "/fi/w1/wpa_supplicant1";
+const INTERFACE: &str = "fi.w1.wpa_supplicant1.Interface";
+
+/// Error of GetInterface for interfaces wpa_supplicant does not manage
+const INTERFACE_UNKNOWN: &str = "fi.w1.wpa_supplicant1.InterfaceUnknown";
+
+/// Timeout of method calls and scans
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A change of the interface reported by PropertiesChanged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    State(ConnectionState),
+    /// reason code of the last disconnect, negated if locally generated
+    DisconnectReason(i32),
+    /// status code of the last authentication
+    AuthStatusCode(i32),
+    /// status code of the last association
+    AssocStatusCode(i32),
+}
+
+/// A backend driving an interface of wpa_supplicant over D-Bus
+pub struct WpaDbus {
+    conn: Connection,
+    /// object of the interface
+    interface: Path<'static>,
+    /// network added by `connect`, replaced by the next one
+    network: Option<Path<'static>>,
+    /// changes of the PropertiesChanged signals not waited for yet
+    changes: Arc<Mutex<VecDeque<Change>>>,
+    /// results of the ScanDone signals
+    scans: Arc<Mutex<VecDeque<bool>>>,
+    state: ConnectionState,
+}
+
+impl WpaDbus {
+    /// Drive the interface `ifname` through the system bus
+    pub fn system(ifname: &str) -> Result<WpaDbus, Error> {
+        WpaDbus::new(Connection::new_system()?, ifname)
+    }
+
+    /// Drive the interface `ifname` through `conn`, letting wpa_supplicant
+    /// manage it if it does not yet
+    pub fn new(conn: Connection, ifname: &str) -> Result<WpaDbus, Error> {
+        let supplicant = conn.with_proxy(SERVICE, PATH, TIMEOUT);
+        let interface = match supplicant.method_call(SERVICE, "GetInterface", (ifname,)) {
+            Ok((interface,)) => interface,
+            Err(e) if e.name() == Some(INTERFACE_UNKNOWN) => {
+                let mut args = PropMap::new();
+                args.insert("Ifname".to_owned(), Variant(Box::new(ifname.to_owned())));
+                let (interface,): (Path<'static>,) =
+                    supplicant.method_call(SERVICE, "CreateInterface", (args,))?;
+                interface
+            }
+            Err(e) => return Err(e),
+        };
+
+        let proxy = conn.with_proxy(SERVICE, interface.clone(), TIMEOUT);
+        let changes = Arc::new(Mutex::new(VecDeque::new()));
+        let queue = changes.clone();
+        proxy.match_signal(
+            move |signal: PropertiesPropertiesChanged, _: &Connection, _: &Message| {
+                if signal.interface_name != INTERFACE {
+                    return true;
+                }
+                let properties = &signal.changed_properties;
+                let code = |name: &str| {
+                    let value = properties.get(name)?.0.as_i64()?;
+                    Some(value as i32)
+                };
+                // the outcome of the attempt before the state it leads to
+                let mut queue = queue.lock().unwrap();
+                queue.extend(code("AuthStatusCode").map(Change::AuthStatusCode));
+                queue.extend(code("AssocStatusCode").map(Change::AssocStatusCode));
+                queue.extend(code("DisconnectReason").map(Change::DisconnectReason));
+                let state = properties
+                    .get("State")
+                    .and_then(|state| state.0.as_str())
+                    .and_then(|state| state.parse().ok());
+                queue.extend(state.map(Change::State));
+                true
+            },
+        )?;
+        let scans = Arc::new(Mutex::new(VecDeque::new()));
+        let queue = scans.clone();
+        let mut rule = MatchRule::new_signal(INTERFACE, "ScanDone");
+        rule.path = Some(interface.clone());
+        rule.sender = Some(SERVICE.into());
+        conn.add_match(rule, move |(success,): (bool,), _, _| {
+            queue.lock().unwrap().push_back(success);
+            true
+        })?;
+
+        let state: String = proxy.get(INTERFACE, "State")?;
+        let state = state.parse().map_err(|e: String| Error::new_failed(&e))?;
+        Ok(WpaDbus {
+            conn,
+            interface,
+            network: None,
+            changes,
+            scans,
+            state,
+        })
+    }
+
+    /// Scan for networks, waiting for the results
+    pub fn scan(&mut self) -> Result<(), Error> {
+        self.scans.lock().unwrap().clear();
+        let mut args = PropMap::new();
+        args.insert("Type".to_owned(), Variant(Box::new("active".to_owned())));
+        self.call::<(), _>("Scan", (args,))?;
+
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(success) = self.scans.lock().unwrap().pop_front() {
+                return match success {
+                    true => Ok(()),
+                    false => Err(Error::new_failed("scan failed")),
+                };
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::new_failed("scan timed out"));
+            }
+            self.conn.process(deadline - now)?;
+        }
+    }
+
+    /// Wait up to `timeout` for the next change of the connection
+    pub fn wait_change(&mut self, timeout: Duration) -> Result<Option<Change>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(change) = self.changes.lock().unwrap().pop_front() {
+                if let Change::State(state) = change {
+                    self.state = state;
+                }
+                return Ok(Some(change));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            self.conn.process(deadline - now)?;
+        }
+    }
+
+    /// the state of the connection as of the last `wait_change`
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    fn call<R: ReadAll, A: AppendAll>(&self, method: &str, args: A) -> Result<R, Error> {
+        self.conn
+            .with_proxy(SERVICE, self.interface.clone(), TIMEOUT)
+            .method_call(INTERFACE, method, args)
+    }
+}
+
+impl Connector for WpaDbus {
+    type Error = Error;
+
+    /// Add the network of `params` and select it, replacing the one of the
+    /// previous `connect`; its IEs are left to wpa_supplicant
+    fn connect(&mut self, params: &ConnectParams) -> Result<(), Error> {
+        let variables = network(params)
+            .map_err(|e| Error::new_custom("org.freedesktop.DBus.Error.InvalidArgs", &e))?;
+        if let Some(network) = self.network.take() {
+            self.call::<(), _>("RemoveNetwork", (network,))?;
+        }
+
+        // strings are quoted by wpa_supplicant but for keywords like key_mgmt
+        let mut args = PropMap::new();
+        for (name, value) in variables {
+            let value: Box<dyn RefArg> = match value {
+                NetworkValue::Str(s) | NetworkValue::Raw(s) => Box::new(s),
+                NetworkValue::Bytes(bytes) => Box::new(bytes),
+                NetworkValue::Int(n) => Box::new(n),
+            };
+            args.insert(name.to_owned(), Variant(value));
+        }
+        let (network,): (Path<'static>,) = self.call("AddNetwork", (args,))?;
+        self.network = Some(network.clone());
+        self.call("SelectNetwork", (network,))
+    }
+
+    /// Disconnect and remove the network of `connect`, wpa_supplicant
+    /// always deauthenticates as leaving
+    fn disconnect(&mut self, _reason: ReasonCode) -> Result<(), Error> {
+        self.call::<(), _>("Disconnect", ())?;
+        if let Some(network) = self.network.take() {
+            self.call::<(), _>("RemoveNetwork", (network,))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::channel::{Channel, MatchingReceiver, Sender};
+    use dbus::message::SignalArgs;
+    use std::io::{BufRead, BufReader, ErrorKind};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    const INTERFACE_PATH: &str = "/fi/w1/wpa_supplicant1/Interfaces/0";
+    const NETWORK_PATH: &str = "/fi/w1/wpa_supplicant1/Interfaces/0/Networks/0";
+
+    /// A private bus of its own dbus-daemon, killed on drop
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        /// none without dbus-daemon installed
+        fn start() -> Option<Bus> {
+            let mut daemon = match Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+            {
+                Ok(daemon) => daemon,
+                Err(e) if e.kind() == ErrorKind::NotFound => return None,
+                Err(e) => panic!("starting dbus-daemon failed: {}", e),
+            };
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.as_mut().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Some(Bus {
+                daemon,
+                address: address.trim().to_owned(),
+            })
+        }
+
+        fn connect(&self) -> Connection {
+            let mut channel = Channel::open_private(&self.address).unwrap();
+            channel.register().unwrap();
+            Connection::from(channel)
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// `name=value` of the variables of a{sv} arguments, sorted
+    fn variables(args: &PropMap) -> String {
+        let mut variables: Vec<String> = args
+            .iter()
+            .map(|(name, value)| {
+                let value = if let Some(s) = value.0.as_str() {
+                    s.to_owned()
+                } else if let Some(n) = value.0.as_u64() {
+                    n.to_string()
+                } else {
+                    let bytes = value.0.as_iter().unwrap().filter_map(|b| b.as_u64());
+                    bytes.map(|b| format!("{:02x}", b)).collect()
+                };
+                format!("{}={}", name, value)
+            })
+            .collect();
+        variables.sort();
+        variables.join(" ")
+    }
+
+    fn state_changed(conn: &Connection, state: &str) {
+        let mut changed = PropMap::new();
+        changed.insert("State".to_owned(), Variant(Box::new(state.to_owned())));
+        properties_changed(conn, changed);
+    }
+
+    fn properties_changed(conn: &Connection, changed: PropMap) {
+        let signal = PropertiesPropertiesChanged {
+            interface_name: INTERFACE.to_owned(),
+            changed_properties: changed,
+            invalidated_properties: Vec::new(),
+        };
+        let path = Path::from(INTERFACE_PATH);
+        conn.send(signal.to_emit_message(&path)).unwrap();
+    }
+
+    /// A mock wpa_supplicant on `bus` managing no interface yet, returning
+    /// the methods called on it in order
+    fn mock(bus: &Bus, stop: Arc<AtomicBool>) -> thread::JoinHandle<Vec<String>> {
+        let conn = bus.connect();
+        conn.request_name(SERVICE, false, true, true).unwrap();
+        thread::spawn(move || {
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let log = calls.clone();
+            conn.start_receive(
+                MatchRule::new_method_call(),
+                Box::new(move |msg: Message, conn: &Connection| {
+                    let member = msg.member().unwrap().to_string();
+                    let reply = match member.as_str() {
+                        "GetInterface" => {
+                            let ifname: &str = msg.read1().unwrap();
+                            log.lock().unwrap().push(format!("GetInterface {}", ifname));
+                            let text = "wpa_supplicant knows nothing about this interface.";
+                            msg.error(
+                                &INTERFACE_UNKNOWN.into(),
+                                &std::ffi::CString::new(text).unwrap(),
+                            )
+                        }
+                        "CreateInterface" | "AddNetwork" | "Scan" => {
+                            let args: PropMap = msg.read1().unwrap();
+                            log.lock()
+                                .unwrap()
+                                .push(format!("{} {}", member, variables(&args)));
+                            match member.as_str() {
+                                "CreateInterface" => {
+                                    msg.method_return().append1(Path::from(INTERFACE_PATH))
+                                }
+                                "AddNetwork" => {
+                                    msg.method_return().append1(Path::from(NETWORK_PATH))
+                                }
+                                _ => msg.method_return(),
+                            }
+                        }
+                        "Get" => {
+                            let (interface, name): (String, String) = msg.read2().unwrap();
+                            log.lock()
+                                .unwrap()
+                                .push(format!("Get {}.{}", interface, name));
+                            msg.method_return()
+                                .append1(Variant("disconnected".to_owned()))
+                        }
+                        _ => {
+                            let path: Option<Path> = msg.read1().ok();
+                            let path = path.map(|path| format!(" {}", path)).unwrap_or_default();
+                            log.lock().unwrap().push(format!("{}{}", member, path));
+                            msg.method_return()
+                        }
+                    };
+                    conn.send(reply).unwrap();
+
+                    match member.as_str() {
+                        "Scan" => {
+                            let signal =
+                                Message::new_signal(INTERFACE_PATH, INTERFACE, "ScanDone").unwrap();
+                            conn.send(signal.append1(true)).unwrap();
+                        }
+                        "SelectNetwork" => {
+                            // leaving the network connected before
+                            let mut changed = PropMap::new();
+                            changed.insert("DisconnectReason".to_owned(), Variant(Box::new(-3)));
+                            changed.insert(
+                                "State".to_owned(),
+                                Variant(Box::new("disconnected".to_owned())),
+                            );
+                            properties_changed(conn, changed);
+                            state_changed(conn, "associating");
+                            let mut changed = PropMap::new();
+                            changed.insert("AssocStatusCode".to_owned(), Variant(Box::new(0)));
+                            properties_changed(conn, changed);
+                            state_changed(conn, "4way_handshake");
+                            state_changed(conn, "completed");
+                        }
+                        "Disconnect" => state_changed(conn, "disconnected"),
+                        _ => {}
+                    }
+                    true
+                }),
+            );
+            while !stop.load(Ordering::Relaxed) {
+                conn.process(Duration::from_millis(50)).unwrap();
+            }
+            let calls = calls.lock().unwrap().clone();
+            calls
+        })
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon, run with --ignored where it is installed"]
+    fn test_wpa_dbus() {
+        let bus = Bus::start().expect("dbus-daemon is not installed");
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = mock(&bus, stop.clone());
+
+        let mut supplicant = WpaDbus::new(bus.connect(), "wlan0").unwrap();
+        assert_eq!(supplicant.state(), ConnectionState::Disconnected);
+        supplicant.scan().unwrap();
+
+        let mut params = ConnectParams::sae(b"glow");
+        assert!(Connector::connect(&mut supplicant, &params).is_err());
+        params.passphrase = Some("glow-passphrase".to_owned());
+        Connector::connect(&mut supplicant, &params).unwrap();
+
+        let mut changes = Vec::new();
+        while let Some(change) = supplicant.wait_change(TIMEOUT).unwrap() {
+            changes.push(change);
+            if supplicant.state().is_connected() {
+                break;
+            }
+        }
+        assert_eq!(
+            changes,
+            vec![
+                Change::DisconnectReason(-3),
+                Change::State(ConnectionState::Disconnected),
+                Change::State(ConnectionState::Associating),
+                Change::AssocStatusCode(0),
+                Change::State(ConnectionState::FourWayHandshake),
+                Change::State(ConnectionState::Completed),
+            ]
+        );
+        assert_eq!(supplicant.state(), ConnectionState::Completed);
+
+        Connector::disconnect(&mut supplicant, ReasonCode::DeauthLeaving).unwrap();
+        assert_eq!(
+            supplicant.wait_change(TIMEOUT).unwrap(),
+            Some(Change::State(ConnectionState::Disconnected))
+        );
+
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!(
+            handle.join().unwrap(),
+            vec![
+                "GetInterface wlan0".to_owned(),
+                "CreateInterface Ifname=wlan0".to_owned(),
+                format!("Get {}.State", INTERFACE),
+                "Scan Type=active".to_owned(),
+                "AddNetwork group=CCMP ieee80211w=2 key_mgmt=SAE pairwise=CCMP proto=RSN \
+                 sae_password=glow-passphrase ssid=676c6f77"
+                    .to_owned(),
+                format!("SelectNetwork {}", NETWORK_PATH),
+                "Disconnect".to_owned(),
+                format!("RemoveNetwork {}", NETWORK_PATH),
+            ]
+        );
+    }
+}